@@ -1,208 +1,3202 @@
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
+use arc_swap::{ArcSwap, Guard};
 use audio_gate::NoiseGate;
 use cpal::traits::DeviceTrait;
-use cpal::{BufferSize, Device, Stream, StreamConfig};
+use cpal::{
+    BufferSize, Device, FromSample, Sample, SampleFormat, SizedSample, Stream, StreamConfig,
+    StreamError,
+};
 use ringbuf::{
-    traits::{Consumer, Producer, Split},
-    HeapRb,
+    traits::{Consumer, Observer, Producer, Split},
+    HeapCons, HeapProd, HeapRb,
+};
+
+use crate::beam::{BeamCalibration, Beamformer, Steering};
+use crate::bus::{Bus, BusMix, Tap, CUE_OUTPUT};
+use crate::denoise::{self, Denoiser};
+use crate::device;
+use crate::dsp::{
+    db_to_gain, gain_to_db, mix, Agc, Alert, AlertTone, Biquad, Butterworth, CallSim, Compressor,
+    Echo, Expander, FlushToZero, GateMode, HumFilter, Limiter, LoudnessMeter, PsolaShifter,
+    Resampler, Reverb, ReverbKind, Robot, RobotMode, Saturator, SidechainGain, SidechainMode,
+    SignalGenerator, Smoother, Source, TelephoneSim, TonalDetector, VoiceChanger, VoicePreset,
 };
+use crate::eq::{EqBand, EqProfile, Equalizer, CHAIN_BANDS};
+use crate::events::{Event, EventReceiver, EventSender, StreamRole};
+use crate::loopguard::{LoopGuard, ProbeTone};
+use crate::meter::{triple_buffer, MeterFrame, TripleReader, TripleWriter, SCOPE_LEN};
+use crate::player::{FilePlayer, FileSlot};
+use crate::room::RoomCheck;
+#[cfg(feature = "scripting")]
+use crate::script::{ScriptSlot, ScriptStage};
+
+/// The privacy voice's (pitch, formant) shift in semitones. The pitch
+/// goes a few semitones down and the formants a little up, so the speaker
+/// is hard to place but the voice stays the same size: no child or giant,
+/// and no move into another gender range.
+pub const PRIVACY_SHIFTS: (f32, f32) = (-3.0, 1.0);
+
+/// Default harmony: a major third up, as loud as the voice.
+pub const HARMONY_SEMITONES: f32 = 4.0;
+pub const HARMONY_MIX: f32 = 0.5;
+
+/// High-pass corner: removes rumble, plosives and AC hum.
+pub const HIGHPASS_HZ: f32 = 100.0;
+/// Low-pass corner: removes hiss above the voice range.
+pub const LOWPASS_HZ: f32 = 8000.0;
+/// Butterworth order of both filters: 24 dB/oct.
+pub const FILTER_ORDER: u32 = 4;
+/// Hum notch: overtones notched above the mains frequency by default.
+pub const HUM_OVERTONES: u32 = 2;
+/// AGC target speech level (dBFS RMS) and the most it may boost or cut.
+pub const AGC_TARGET_DB: f32 = -18.0;
+pub const AGC_MAX_GAIN_DB: f32 = 12.0;
+/// Compressor (threshold dB, ratio, attack ms, release ms, makeup dB): a
+/// gentle voice setting that evens out loud and quiet speech.
+pub const COMP_DEFAULTS: (f32, f32, f32, f32, f32) = (-24.0, 3.0, 5.0, 120.0, 6.0);
+/// Reverb defaults: a short decay and a touch of wet signal, enough to sing
+/// into without washing out the monitor mix.
+pub const REVERB_DECAY_S: f32 = 1.2;
+/// Saturation drive, tone and output trim (dB): audible warmth on speech
+/// peaks, no change in brightness.
+pub const SATURATION_DRIVE_DB: f32 = 6.0;
+pub const SATURATION_TONE_DB: f32 = 0.0;
+pub const SATURATION_TRIM_DB: f32 = 0.0;
+pub const REVERB_MIX: f32 = 0.2;
+/// Echo defaults: a slapback, a couple of audible repeats under the voice.
+pub const ECHO_TIME_MS: f32 = 120.0;
+pub const ECHO_FEEDBACK: f32 = 0.3;
+pub const ECHO_MIX: f32 = 0.3;
+/// Robot oscillator default: low enough to sound mechanical, high enough
+/// that the vocoder's words stay clear.
+pub const ROBOT_PITCH_HZ: f32 = 100.0;
+/// Default limiter ceiling: a little headroom for lossy encoders downstream.
+pub const LIMITER_CEILING_DB: f32 = -1.0;
+/// Outputs the GUI opens at most: OUT and OUT 2.
+pub const MAX_OUTPUTS: usize = 2;
+/// Longest alignment delay on an output: speakers 34 m away.
+pub const MAX_OUTPUT_DELAY_MS: f32 = 100.0;
+/// Clock drift (see `OutputRing`): how long the streams get to settle
+/// before an output ring's fill is held, how long the fill is averaged
+/// over, and the controller's gains, per second of error. Critically
+/// damped at about two seconds; a fill 2 ms off target saturates it.
+const DRIFT_SETTLE_S: f32 = 2.0;
+const DRIFT_SMOOTH_S: f32 = 0.25;
+const DRIFT_KP: f64 = 1.0;
+const DRIFT_KI: f64 = 0.25;
+
+/// Atomic f32 stored as bit-cast u32 for lock-free access in callbacks.
+pub struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    pub fn new(val: f32) -> Self {
+        Self(AtomicU32::new(val.to_bits()))
+    }
+
+    pub fn load(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn store(&self, val: f32) {
+        self.0.store(val.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Every setting the GUI controls, as one value. The GUI publishes a new
+/// one for each change (see `AudioParams::publish`) and the callbacks load
+/// one per block, so a block never runs on half of a change.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Controls {
+    pub volume: f32,
+    /// Blend of the whole chain with the unprocessed input: 1 is fully
+    /// processed. Sidechain ducking, volume and the limiter still apply.
+    pub dry_wet: f32,
+    pub noise_gate_enabled: bool,
+    pub noise_gate_threshold: f32,
+    /// Music mode: while sustained tonal content is detected the gate
+    /// holds longer and opens `gate_music_db` lower.
+    pub gate_music: bool,
+    pub gate_music_db: f32,
+    /// Once open, the gate passes audio for at least this long, so a
+    /// marginal signal can't make it flutter.
+    pub gate_min_open_ms: f32,
+    /// As an expander the stage turns audio under the threshold down by
+    /// `expander_ratio`, at most `expander_range_db`.
+    pub gate_mode: GateMode,
+    pub expander_ratio: f32,
+    pub expander_range_db: f32,
+    /// Set by an external trigger: the gate held open (`Some(true)`) or
+    /// the mic shut (`Some(false)`), whether the gate is on or not.
+    pub gate_forced: Option<bool>,
+    /// RNNoise denoiser ahead of the gate; only runs at `denoise::SAMPLE_RATE`.
+    pub denoise: bool,
+    /// Automatic gain control after the gate: target RMS and gain limit.
+    pub agc_enabled: bool,
+    pub agc_target_db: f32,
+    pub agc_max_gain_db: f32,
+    /// Compressor after the AGC.
+    pub comp_enabled: bool,
+    pub comp_threshold: f32,
+    pub comp_ratio: f32,
+    pub comp_attack_ms: f32,
+    pub comp_release_ms: f32,
+    pub comp_makeup_db: f32,
+    /// Oversampled tube-style saturation after the compressor.
+    pub saturation_enabled: bool,
+    pub saturation_drive_db: f32,
+    pub saturation_tone_db: f32,
+    pub saturation_trim_db: f32,
+    /// Feedback delay: time in ms, feedback and wet/dry mix.
+    pub echo_enabled: bool,
+    pub echo_time_ms: f32,
+    pub echo_feedback: f32,
+    pub echo_mix: f32,
+    /// Reverb on the monitor mix: kind, RT60 in seconds and wet/dry mix.
+    pub reverb_enabled: bool,
+    pub reverb_kind: ReverbKind,
+    pub reverb_decay_s: f32,
+    pub reverb_mix: f32,
+    /// Wet/dry of each stage, indexed by `Stage as usize`: 1 is fully
+    /// processed, 0 passes what the stage got. Only `Stage::blendable`
+    /// stages use it.
+    pub stage_mix: [f32; Stage::ALL.len()],
+    /// Brickwall limiter after the volume, the last stage before the outputs.
+    pub limiter_enabled: bool,
+    pub limiter_ceiling: f32,
+    pub highpass_enabled: bool,
+    pub lowpass_enabled: bool,
+    /// Butterworth order of both filters, one of `Butterworth::ORDERS`.
+    pub filter_order: u32,
+    /// Mains hum notch: 0 (off), 50 or 60 Hz, plus this many overtones.
+    pub hum_mains_hz: u32,
+    pub hum_overtones: u32,
+    /// Mic EQ after the filters; bands start as `eq::CHAIN_BANDS`.
+    pub eq_enabled: bool,
+    pub eq_bands: [EqBand; CHAIN_BANDS.len()],
+    /// Voice changer: pitch and formant shifts in semitones, each on its own.
+    pub voice_enabled: bool,
+    pub voice_pitch_semitones: f32,
+    pub voice_formant_semitones: f32,
+    /// Robot voice: mode and oscillator pitch in Hz.
+    pub robot_enabled: bool,
+    pub robot_mode: RobotMode,
+    pub robot_pitch_hz: f32,
+    /// Disguise the voice by `PRIVACY_SHIFTS`.
+    pub privacy_voice: bool,
+    /// Pitch-shifted copy of the voice to sing against: interval in
+    /// semitones (±12) and how much of it replaces the dry voice.
+    pub harmony_enabled: bool,
+    pub harmony_semitones: f32,
+    pub harmony_mix: f32,
+    pub call_sim: CallSim,
+    /// Anything but `Mic` replaces the captured signal.
+    pub source: Source,
+    pub sidechain_mode: SidechainMode,
+    /// Sidechain level (dBFS) above which the sidechain counts as active.
+    pub sidechain_threshold: f32,
+    #[cfg(feature = "scripting")]
+    pub script_enabled: bool,
+    /// What the outputs do once the input is lost.
+    pub input_loss: InputLoss,
+    /// Streams keep running and metering but the outputs are hard-muted,
+    /// so going live skips the device open.
+    pub standby: bool,
+    /// BEAM mode: where the null points (see `beam::Steering`).
+    pub beam_steering: Steering,
+    /// STEREO only: process the mid and pass the sides (see
+    /// `InputChain::process_stereo`).
+    pub mid_side: bool,
+    pub effect_chain: EffectChain,
+    /// Level of alert cues in the outputs; 0 turns them off.
+    pub alert_volume: f32,
+    /// Alignment delay of each output, in the order they were opened, so
+    /// outputs heard together (speakers and headphones) line up.
+    pub output_delay_ms: [f32; MAX_OUTPUTS],
+    /// A CUE bus, played on OUT 2 in place of MAIN (see `bus`). SPLIT
+    /// sends its raw pairs everywhere regardless.
+    pub cue: Option<BusMix>,
+}
+
+/// What the engine runs with until the GUI publishes its own.
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            dry_wet: 1.0,
+            noise_gate_enabled: false,
+            noise_gate_threshold: GATE_THRESHOLD_DB,
+            gate_music: false,
+            gate_music_db: 10.0,
+            gate_min_open_ms: GATE_MIN_OPEN_MS,
+            gate_mode: GateMode::Gate,
+            gate_forced: None,
+            expander_ratio: EXPANDER_RATIO,
+            expander_range_db: EXPANDER_RANGE_DB,
+            denoise: false,
+            agc_enabled: false,
+            agc_target_db: AGC_TARGET_DB,
+            agc_max_gain_db: AGC_MAX_GAIN_DB,
+            comp_enabled: false,
+            comp_threshold: COMP_DEFAULTS.0,
+            comp_ratio: COMP_DEFAULTS.1,
+            comp_attack_ms: COMP_DEFAULTS.2,
+            comp_release_ms: COMP_DEFAULTS.3,
+            comp_makeup_db: COMP_DEFAULTS.4,
+            saturation_enabled: false,
+            saturation_drive_db: SATURATION_DRIVE_DB,
+            saturation_tone_db: SATURATION_TONE_DB,
+            saturation_trim_db: SATURATION_TRIM_DB,
+            echo_enabled: false,
+            echo_time_ms: ECHO_TIME_MS,
+            echo_feedback: ECHO_FEEDBACK,
+            echo_mix: ECHO_MIX,
+            reverb_enabled: false,
+            reverb_kind: ReverbKind::Room,
+            reverb_decay_s: REVERB_DECAY_S,
+            reverb_mix: REVERB_MIX,
+            stage_mix: [1.0; Stage::ALL.len()],
+            limiter_enabled: true,
+            limiter_ceiling: LIMITER_CEILING_DB,
+            highpass_enabled: false,
+            lowpass_enabled: false,
+            filter_order: FILTER_ORDER,
+            hum_mains_hz: 0,
+            hum_overtones: HUM_OVERTONES,
+            eq_enabled: false,
+            eq_bands: CHAIN_BANDS,
+            voice_enabled: false,
+            voice_pitch_semitones: VoicePreset::Deep.shifts().0,
+            voice_formant_semitones: VoicePreset::Deep.shifts().1,
+            robot_enabled: false,
+            robot_mode: RobotMode::Vocoder,
+            robot_pitch_hz: ROBOT_PITCH_HZ,
+            privacy_voice: false,
+            harmony_enabled: false,
+            harmony_semitones: HARMONY_SEMITONES,
+            harmony_mix: HARMONY_MIX,
+            call_sim: CallSim::Off,
+            source: Source::Mic,
+            sidechain_mode: SidechainMode::Duck,
+            sidechain_threshold: -40.0,
+            #[cfg(feature = "scripting")]
+            script_enabled: false,
+            input_loss: InputLoss::Silence,
+            standby: false,
+            beam_steering: Steering::OFF,
+            mid_side: false,
+            effect_chain: EffectChain::default(),
+            alert_volume: 0.0,
+            output_delay_ms: [0.0; MAX_OUTPUTS],
+            cue: None,
+        }
+    }
+}
+
+/// A published `Controls`, tagged with the generation it went out as.
+#[derive(Debug)]
+pub struct Snapshot {
+    pub generation: u64,
+    pub controls: Controls,
+}
+
+/// Snapshots `publish` holds on to after replacing them. A callback may
+/// still be reading one; if the GUI let go first, the callback would be
+/// the one to free it. The GUI publishes at most once a frame, so a few
+/// outlast any callback.
+const RETIRED_SNAPSHOTS: usize = 4;
+
+/// Shared between the GUI/main thread and the audio callbacks: the
+/// published controls, plus what the callbacks report back.
+pub struct AudioParams {
+    controls: ArcSwap<Snapshot>,
+    retired: Mutex<VecDeque<Arc<Snapshot>>>,
+    /// Music mode currently sees a held note.
+    pub tonal: AtomicBool,
+    /// Peak envelope of the sidechain input in dBFS, written by its callback.
+    pub sidechain_level: AtomicF32,
+    /// Clip for `Source::File`, loaded by `player::spawn_loader`.
+    pub file: Arc<FileSlot>,
+    #[cfg(feature = "scripting")]
+    pub script: Arc<ScriptSlot>,
+    /// Last generation the input callback has picked up.
+    pub acked_generation: AtomicU64,
+    /// Set by a stream's error callback when the device changed format
+    /// under it (e.g. a headset switching to mono hands-free mode) and the
+    /// streams have to be rebuilt.
+    pub stream_invalidated: AtomicBool,
+    /// Bumped by every input callback, so the outputs can tell it stalled.
+    pub input_heartbeat: AtomicU32,
+    /// The input device failed or stopped calling back. Cleared again if a
+    /// stalled input resumes.
+    pub input_lost: AtomicBool,
+    /// Set to ramp every output down to silence before the engine is dropped.
+    pub fading_out: AtomicBool,
+    /// Number of outputs that have finished fading out.
+    pub faded_outputs: AtomicUsize,
+    /// Outputs the engine opened: how many `faded_out` waits for.
+    pub open_outputs: AtomicUsize,
+    /// Start-up check for an output that feeds back into the input.
+    pub loop_guard: LoopGuard,
+    /// Listens to the raw input to grade the room.
+    pub room: RoomCheck,
+    /// BEAM mode's "point at noise" calibration.
+    pub beam: BeamCalibration,
+    /// `Stage` bits of stages that panicked and are bypassed from then on.
+    pub faulted_stages: AtomicU32,
+    /// Bumped each time an output flushes and re-primes after an xrun burst.
+    pub xrun_recoveries: AtomicU32,
+    /// Output callbacks that ran out of audio, and input blocks an output's
+    /// ring had no room for. Either is a dropout.
+    pub underruns: AtomicU32,
+    pub overruns: AtomicU32,
+    /// Highest sample peak in dBFS since the session started or the GUI
+    /// reset it.
+    pub peak_hold: AtomicF32,
+    /// Clipping events this session; a run of clipped blocks counts once.
+    pub clip_count: AtomicU32,
+    /// Samples this session where the voice was through the gate (above
+    /// the gate threshold, whether or not the gate is on).
+    pub speaking_samples: AtomicU64,
+    /// Audio per input callback in ms, the longest seen; 0 until the first.
+    pub input_period_ms: AtomicF32,
+    /// OUT's share of the latency in ms: what it buffers before playing
+    /// plus its own callback period. 0 until it first runs.
+    pub output_latency_ms: AtomicF32,
+    /// Latest alert: trigger count in the high bits, `Alert` in the low 4.
+    alert: AtomicU32,
+}
+
+impl AudioParams {
+    /// Everything at its default, at the given volume.
+    pub fn new(volume: f32) -> Self {
+        let controls = Controls {
+            volume,
+            ..Controls::default()
+        };
+        Self {
+            controls: ArcSwap::from_pointee(Snapshot {
+                generation: 0,
+                controls,
+            }),
+            retired: Mutex::new(VecDeque::with_capacity(RETIRED_SNAPSHOTS + 1)),
+            tonal: AtomicBool::new(false),
+            sidechain_level: AtomicF32::new(gain_to_db(0.0)),
+            file: Arc::new(FileSlot::default()),
+            #[cfg(feature = "scripting")]
+            script: Arc::new(ScriptSlot::default()),
+            acked_generation: AtomicU64::new(0),
+            stream_invalidated: AtomicBool::new(false),
+            fading_out: AtomicBool::new(false),
+            faded_outputs: AtomicUsize::new(0),
+            open_outputs: AtomicUsize::new(0),
+            loop_guard: LoopGuard::default(),
+            room: RoomCheck::default(),
+            beam: BeamCalibration::default(),
+            faulted_stages: AtomicU32::new(0),
+            input_heartbeat: AtomicU32::new(0),
+            input_lost: AtomicBool::new(false),
+            xrun_recoveries: AtomicU32::new(0),
+            underruns: AtomicU32::new(0),
+            overruns: AtomicU32::new(0),
+            peak_hold: AtomicF32::new(gain_to_db(0.0)),
+            clip_count: AtomicU32::new(0),
+            speaking_samples: AtomicU64::new(0),
+            input_period_ms: AtomicF32::new(0.0),
+            output_latency_ms: AtomicF32::new(0.0),
+            alert: AtomicU32::new(0),
+        }
+    }
+
+    /// Input to OUT in ms, from the periods the streams actually run at;
+    /// `None` until both have.
+    pub fn latency_ms(&self) -> Option<f32> {
+        let (input, output) = (self.input_period_ms.load(), self.output_latency_ms.load());
+        (input > 0.0 && output > 0.0).then_some(input + output)
+    }
+
+    /// Start ramping all outputs to silence; poll `faded_out` before
+    /// dropping the engine.
+    pub fn fade_out(&self) {
+        self.fading_out.store(true, Ordering::Relaxed);
+    }
+
+    pub fn faded_out(&self) -> bool {
+        self.faded_outputs.load(Ordering::Relaxed) >= self.open_outputs.load(Ordering::Relaxed)
+    }
+
+    /// The latest published controls. Lock-free; hold it for one block at
+    /// most.
+    pub fn controls(&self) -> Guard<Arc<Snapshot>> {
+        self.controls.load()
+    }
+
+    /// Replace the controls in one store, as the next generation. GUI
+    /// thread only.
+    pub fn publish(&self, controls: Controls) -> u64 {
+        let generation = self.controls.load().generation + 1;
+        let old = self.controls.swap(Arc::new(Snapshot {
+            generation,
+            controls,
+        }));
+        let mut retired = self.retired.lock().unwrap();
+        retired.push_back(old);
+        if retired.len() > RETIRED_SNAPSHOTS {
+            retired.pop_front();
+        }
+        generation
+    }
+
+    /// Queue a cue for every output. A newer alert replaces one still playing.
+    pub fn trigger_alert(&self, alert: Alert) {
+        let _ = self
+            .alert
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                Some(((v >> 4).wrapping_add(1) << 4) | alert as u32)
+            });
+    }
+}
+
+/// Largest internal processing block, in samples.
+const MAX_BLOCK_SIZE: usize = 64;
+
+/// This many underrunning output callbacks within `XRUN_WINDOW_MS` is
+/// treated as a glitch to recover from rather than a one-off.
+const XRUN_BURST: u32 = 3;
+const XRUN_WINDOW_MS: u64 = 1000;
+
+/// An input that hasn't called back for this long counts as lost.
+const INPUT_STALL_MS: u64 = 1000;
+/// Time between repeats of the input-lost cue.
+const INPUT_LOST_ALERT_SECS: u64 = 3;
+/// Cue level when alerts are otherwise turned off.
+const INPUT_LOST_ALERT_LEVEL: f32 = 0.5;
+
+/// Minimum time between two clipping alerts.
+const CLIP_ALERT_COOLDOWN_SECS: f32 = 2.0;
+
+/// How quickly smoothed parameters follow the GUI: the time constant of
+/// their glide.
+pub const PARAM_SMOOTH_MS: f32 = 20.0;
+
+/// Gate open threshold until the GUI sets one.
+const GATE_THRESHOLD_DB: f32 = -36.0;
+/// Gate hold time: bridges syllable gaps, or a note's decay in music mode.
+const GATE_HOLD_MS: f32 = 150.0;
+const GATE_MUSIC_HOLD_MS: f32 = 600.0;
+/// Default minimum open time, and the fade back to the gate's own output
+/// once it has passed.
+pub const GATE_MIN_OPEN_MS: f32 = 50.0;
+const GATE_MIN_OPEN_FADE_MS: f32 = 20.0;
+/// Expander ratio and range until the GUI sets them.
+pub const EXPANDER_RATIO: f32 = 2.0;
+pub const EXPANDER_RANGE_DB: f32 = 20.0;
+
+/// Processing stages that are contained on their own: a panic in one
+/// bypasses just that stage instead of taking down the stream thread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Source,
+    Filters,
+    Hum,
+    Eq,
+    Denoise,
+    Gate,
+    Agc,
+    Compressor,
+    Saturation,
+    Voice,
+    Robot,
+    Privacy,
+    Harmony,
+    CallSim,
+    Script,
+    Echo,
+    Reverb,
+    Sidechain,
+    Limiter,
+    HeadphoneEq,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 20] = [
+        Stage::Source,
+        Stage::Filters,
+        Stage::Hum,
+        Stage::Eq,
+        Stage::Denoise,
+        Stage::Gate,
+        Stage::Agc,
+        Stage::Compressor,
+        Stage::Saturation,
+        Stage::Voice,
+        Stage::Robot,
+        Stage::Privacy,
+        Stage::Harmony,
+        Stage::CallSim,
+        Stage::Script,
+        Stage::Echo,
+        Stage::Reverb,
+        Stage::Sidechain,
+        Stage::Limiter,
+        Stage::HeadphoneEq,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Stage::Source => "SRC",
+            Stage::Filters => "HP/LP",
+            Stage::Hum => "HUM",
+            Stage::Eq => "EQ",
+            Stage::Denoise => "DENOISE",
+            Stage::Gate => "GATE",
+            Stage::Agc => "AGC",
+            Stage::Compressor => "COMP",
+            Stage::Saturation => "SATURATE",
+            Stage::Voice => "VOICE",
+            Stage::Robot => "ROBOT",
+            Stage::Privacy => "PRIVACY",
+            Stage::Harmony => "HARMONY",
+            Stage::CallSim => "CALL SIM",
+            Stage::Script => "SCRIPT",
+            Stage::Echo => "ECHO",
+            Stage::Reverb => "REVERB",
+            Stage::Sidechain => "SIDECHAIN",
+            Stage::Limiter => "LIMIT",
+            Stage::HeadphoneEq => "HEADPHONE EQ",
+        }
+    }
+
+    /// Whether the stage takes a wet/dry mix. The source replaces the input
+    /// rather than processing it, denoise and the pitch shifters delay it
+    /// (so a blend would comb-filter), echo, reverb and harmony have a mix of
+    /// their own, and a blended limiter would no longer hold its ceiling.
+    pub fn blendable(self) -> bool {
+        !matches!(
+            self,
+            Stage::Source
+                | Stage::Denoise
+                | Stage::Voice
+                | Stage::Privacy
+                | Stage::Harmony
+                | Stage::Echo
+                | Stage::Reverb
+                | Stage::Limiter
+        )
+    }
+
+    /// This stage's bit in `AudioParams::faulted_stages`.
+    pub fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// Order of the stages between the source and the sidechain/limiter tail.
+/// The source always comes first, ducking and the limiter always last (so
+/// the ceiling holds whatever the chain does), and the headphone EQ runs
+/// per output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EffectChain([Stage; 16]);
+
+impl Default for EffectChain {
+    fn default() -> Self {
+        Self([
+            Stage::Filters,
+            Stage::Hum,
+            Stage::Eq,
+            Stage::Denoise,
+            Stage::Gate,
+            Stage::Agc,
+            Stage::Compressor,
+            Stage::Saturation,
+            Stage::Voice,
+            Stage::Robot,
+            Stage::Privacy,
+            Stage::Harmony,
+            Stage::CallSim,
+            Stage::Script,
+            Stage::Echo,
+            Stage::Reverb,
+        ])
+    }
+}
+
+impl EffectChain {
+    pub fn stages(&self) -> &[Stage] {
+        &self.0
+    }
+
+    /// Where `stage` runs, if it's part of the chain.
+    pub fn position(&self, stage: Stage) -> Option<usize> {
+        self.0.iter().position(|&s| s == stage)
+    }
+
+    /// Take the stage at `from` out and put it back in at `to`.
+    pub fn move_stage(&mut self, from: usize, to: usize) {
+        if from < to {
+            self.0[from..=to].rotate_left(1);
+        } else {
+            self.0[to..=from].rotate_right(1);
+        }
+    }
+}
+
+/// Run one stage over `block` (at most `MAX_BLOCK_SIZE` samples), then
+/// blend the result with what the stage got by its `stage_mix`. If it
+/// panics, the block is put back the way the stage got it and the stage is
+/// marked faulted, after which it is skipped. Its state can't be trusted
+/// after an unwind, so it stays bypassed until the engine is rebuilt.
+fn contained(
+    stage: Stage,
+    params: &AudioParams,
+    controls: &Controls,
+    block: &mut [f32],
+    run: impl FnOnce(&mut [f32]),
+) {
+    let faults = &params.faulted_stages;
+    if faults.load(Ordering::Relaxed) & stage.bit() != 0 {
+        return;
+    }
+    let mut dry = [0.0f32; MAX_BLOCK_SIZE];
+    let dry = &mut dry[..block.len()];
+    dry.copy_from_slice(block);
+    if catch_unwind(AssertUnwindSafe(|| run(block))).is_err() {
+        block.copy_from_slice(dry);
+        faults.fetch_or(stage.bit(), Ordering::Relaxed);
+        return;
+    }
+    let wet = controls.stage_mix[stage as usize].clamp(0.0, 1.0);
+    if stage.blendable() && wet < 1.0 {
+        mix(block, dry, wet);
+    }
+}
+
+/// What the chain's stages tell the meters about the current block.
+#[derive(Default)]
+struct StageReport {
+    /// See `MeterFrame::gated`.
+    gated: bool,
+    /// Music mode currently sees a held note.
+    tonal: bool,
+    agc_db: f32,
+    reduction_db: f32,
+    limited_db: f32,
+}
+
+/// The parameters around the chain that glide to a new setting instead of
+/// jumping: gains per sample, the ceiling per block. Stages smooth their
+/// own.
+struct Smoothed {
+    volume: Smoother,
+    /// The volume again, for STEREO's sides while M/S is on.
+    side_volume: Smoother,
+    dry_wet: Smoother,
+    limiter_ceiling: Smoother,
+}
+
+impl Smoothed {
+    fn new(sample_rate: f32) -> Self {
+        let smoother = || Smoother::new(sample_rate, PARAM_SMOOTH_MS);
+        Self {
+            volume: smoother(),
+            side_volume: smoother(),
+            dry_wet: smoother(),
+            limiter_ceiling: smoother(),
+        }
+    }
+}
+
+/// DSP state for the input callback, run one fixed-size block at a time.
+struct InputChain {
+    params: Arc<AudioParams>,
+    smoothed: Smoothed,
+    generator: SignalGenerator,
+    player: FilePlayer,
+    /// The reorderable stages, kept in `EffectChain` order.
+    effects: Vec<Box<dyn Effect>>,
+    /// Only present when a sidechain input is open.
+    sidechain: Option<SidechainGain>,
+    limiter: Limiter,
+    /// STEREO's sides while M/S is on, held back by the limiter's
+    /// lookahead so they stay in line with the mid.
+    side_delay: Vec<f32>,
+    side_pos: usize,
+    /// The last block at each `Tap`, kept while a CUE bus takes them.
+    taps: [[f32; MAX_BLOCK_SIZE]; Tap::ALL.len()],
+    loudness: LoudnessMeter,
+    /// Samples until another clipping alert may fire.
+    clip_cooldown: usize,
+    clip_cooldown_len: usize,
+    /// The previous block clipped.
+    was_clipping: bool,
+    /// Output peak envelope and its per-sample decay.
+    peak_env: f32,
+    peak_decay: f32,
+    /// Rolling window of output samples for the scope.
+    scope: [f32; SCOPE_LEN],
+    scope_pos: usize,
+    /// `None` for the right-hand chain in STEREO, which the left one meters.
+    meter: Option<TripleWriter<MeterFrame>>,
+    /// Clipping and the gate for the GUI, `None` alongside `meter`; and
+    /// whether the gate was shut, to send only when it changes.
+    events: Option<EventSender>,
+    was_gated: bool,
+}
+
+impl InputChain {
+    fn new(
+        sr: f32,
+        gate_thresh: f32,
+        sidechain: bool,
+        params: Arc<AudioParams>,
+        meter: Option<TripleWriter<MeterFrame>>,
+        events: Option<EventSender>,
+    ) -> Self {
+        Self {
+            effects: effects(sr, gate_thresh, &params),
+            player: FilePlayer::new(Arc::clone(&params.file)),
+            params,
+            smoothed: Smoothed::new(sr),
+            generator: SignalGenerator::new(sr),
+            sidechain: sidechain.then(|| SidechainGain::new(sr)),
+            limiter: Limiter::new(sr, LIMITER_CEILING_DB),
+            side_delay: vec![0.0; Limiter::new(sr, LIMITER_CEILING_DB).latency()],
+            side_pos: 0,
+            taps: [[0.0; MAX_BLOCK_SIZE]; Tap::ALL.len()],
+            loudness: LoudnessMeter::new(sr),
+            clip_cooldown: 0,
+            clip_cooldown_len: (CLIP_ALERT_COOLDOWN_SECS * sr) as usize,
+            was_clipping: false,
+            peak_env: 0.0,
+            // ~300 ms time constant, slow enough to read by eye
+            peak_decay: (-1.0 / (0.3 * sr)).exp(),
+            scope: [0.0; SCOPE_LEN],
+            scope_pos: 0,
+            meter,
+            events,
+            was_gated: false,
+        }
+    }
+
+    fn process(&mut self, c: &Controls, block: &mut [f32]) {
+        self.fill_source(c, block);
+        let report = self.effects(c, block);
+        self.report(c, &[&*block], &report);
+    }
+
+    /// STEREO: this chain runs the left channel and `right` the right one,
+    /// each with its own filter and dynamics state. A test source plays in
+    /// both; the meters and alerts cover both.
+    ///
+    /// With M/S on, this chain runs the mid (L+R)/2 instead and the sides
+    /// (L-R)/2 only get the volume, before both are turned back into L/R.
+    fn process_stereo(
+        &mut self,
+        c: &Controls,
+        right: &mut InputChain,
+        left_block: &mut [f32],
+        right_block: &mut [f32],
+    ) {
+        let mid_side = c.mid_side;
+        if mid_side {
+            for (l, r) in left_block.iter_mut().zip(right_block.iter_mut()) {
+                (*l, *r) = (0.5 * (*l + *r), 0.5 * (*l - *r));
+            }
+        }
+        if self.fill_source(c, left_block) {
+            if mid_side {
+                right_block.fill(0.0);
+            } else {
+                right_block.copy_from_slice(left_block);
+            }
+        }
+        let report = self.effects(c, left_block);
+        if mid_side {
+            let delayed = c.limiter_enabled;
+            for (m, s) in left_block.iter_mut().zip(right_block.iter_mut()) {
+                let mut side = *s * self.smoothed.side_volume.next(c.volume);
+                if delayed {
+                    std::mem::swap(&mut side, &mut self.side_delay[self.side_pos]);
+                    self.side_pos = (self.side_pos + 1) % self.side_delay.len();
+                }
+                (*m, *s) = (*m + side, *m - side);
+            }
+        } else {
+            right.effects(c, right_block);
+        }
+        self.report(c, &[&*left_block, &*right_block], &report);
+    }
+
+    /// Replace the input with the chosen test source, if it isn't the mic.
+    /// Returns whether it did.
+    fn fill_source(&mut self, c: &Controls, block: &mut [f32]) -> bool {
+        let params = &self.params;
+        let source = c.source;
+        if source == Source::Mic {
+            return false;
+        }
+        contained(Stage::Source, params, c, block, |block| match source {
+            Source::File => self.player.fill(block),
+            _ => self.generator.fill(source, block),
+        });
+        true
+    }
+
+    /// Everything between the source and the meters: the reorderable
+    /// chain, the global dry/wet, the sidechain, volume and the limiter.
+    fn effects(&mut self, c: &Controls, block: &mut [f32]) -> StageReport {
+        // Each stage runs contained; see `contained`.
+        // The reorderable middle of the chain, then the global dry/wet
+        let mut dry = [0.0f32; MAX_BLOCK_SIZE];
+        let dry = &mut dry[..block.len()];
+        dry.copy_from_slice(block);
+        let mut report = StageReport::default();
+        let params = &self.params;
+        let order = &c.effect_chain;
+        if !self.effects.is_sorted_by_key(|e| order.position(e.stage())) {
+            self.effects
+                .sort_unstable_by_key(|e| order.position(e.stage()));
+        }
+        for effect in &mut self.effects {
+            if effect.prepare(c, block, &mut report) {
+                let stage = effect.stage();
+                contained(stage, params, c, block, |block| {
+                    effect.process(c, block, &mut report)
+                });
+            }
+        }
+        let wet = c.dry_wet.clamp(0.0, 1.0);
+        if !self.smoothed.dry_wet.settled(wet) {
+            for (s, &d) in block.iter_mut().zip(dry.iter()) {
+                *s = d + (*s - d) * self.smoothed.dry_wet.next(wet);
+            }
+        } else if wet < 1.0 {
+            mix(block, dry, wet);
+        }
+
+        // Sidechain duck/unmute
+        if let Some(sidechain) = &mut self.sidechain {
+            let keyed = params.sidechain_level.load() > c.sidechain_threshold;
+            let mode = c.sidechain_mode;
+            contained(Stage::Sidechain, params, c, block, |block| {
+                sidechain.process(block, keyed, mode);
+            });
+        }
+
+        if c.cue.is_some() {
+            self.taps[Tap::Dry as usize][..block.len()].copy_from_slice(dry);
+            self.taps[Tap::Voice as usize][..block.len()].copy_from_slice(block);
+        }
+
+        // Volume, then the limiter
+        self.smoothed.volume.apply(block, c.volume);
+        let ceiling = self
+            .smoothed
+            .limiter_ceiling
+            .block(c.limiter_ceiling, block.len());
+        if c.limiter_enabled {
+            contained(Stage::Limiter, params, c, block, |block| {
+                self.limiter.set_ceiling(ceiling);
+                for s in block.iter_mut() {
+                    *s = self.limiter.process(*s);
+                }
+                report.limited_db = self.limiter.reduction_db();
+            });
+        }
+        report
+    }
+
+    /// What the last block left at each tap, `len` long.
+    fn taps(&self, len: usize) -> [&[f32]; Tap::ALL.len()] {
+        self.taps.each_ref().map(|tap| &tap[..len])
+    }
+
+    /// STEREO: average `right`'s taps into this chain's, so a CUE bus
+    /// hears both mics.
+    fn fold_taps(&mut self, right: &InputChain, len: usize) {
+        for (tap, other) in self.taps.iter_mut().zip(&right.taps) {
+            for (s, &o) in tap[..len].iter_mut().zip(other) {
+                *s = 0.5 * (*s + o);
+            }
+        }
+    }
+
+    /// Meter what goes out, one block per channel, and raise the alerts.
+    fn report(&mut self, c: &Controls, channels: &[&[f32]], report: &StageReport) {
+        let params = &self.params;
+        let len = channels[0].len();
+        params.tonal.store(report.tonal, Ordering::Relaxed);
+        let peak = |b: &[f32]| b.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        let block_peak = channels.iter().map(|b| peak(b)).fold(0.0, f32::max);
+        if block_peak > db_to_gain(c.noise_gate_threshold) {
+            params
+                .speaking_samples
+                .fetch_add(len as u64, Ordering::Relaxed);
+        }
+
+        let clipped = block_peak >= 1.0;
+        // Loudness and the scope follow the mid signal.
+        let width = channels.len() as f32;
+        for i in 0..len {
+            let s = channels.iter().map(|b| b[i]).sum::<f32>() / width;
+            self.loudness.process(s);
+            self.scope[self.scope_pos] = s;
+            self.scope_pos = (self.scope_pos + 1) % SCOPE_LEN;
+        }
+        self.peak_env = block_peak.max(self.peak_env * self.peak_decay.powi(len as i32));
+        let block_peak_db = gain_to_db(block_peak);
+        if block_peak_db > params.peak_hold.load() {
+            params.peak_hold.store(block_peak_db);
+        }
+        if let Some(meter) = &mut self.meter {
+            let frame = meter.back();
+            frame.peak_db = gain_to_db(self.peak_env);
+            frame.short_term_lufs = self.loudness.short_term_lufs();
+            frame.gated = report.gated;
+            frame.agc_gain_db = report.agc_db;
+            frame.gain_reduction_db = report.reduction_db;
+            frame.limiter_reduction_db = report.limited_db;
+            let (older, newer) = self.scope.split_at(self.scope_pos);
+            frame.scope[..newer.len()].copy_from_slice(newer);
+            frame.scope[newer.len()..].copy_from_slice(older);
+            meter.publish();
+        }
+
+        if clipped && !self.was_clipping {
+            params.clip_count.fetch_add(1, Ordering::Relaxed);
+            if let Some(events) = &mut self.events {
+                events.send(Event::Clip(block_peak_db));
+            }
+        }
+        if report.gated != self.was_gated {
+            self.was_gated = report.gated;
+            if let Some(events) = &mut self.events {
+                events.send(Event::Gate(!report.gated));
+            }
+        }
+        self.was_clipping = clipped;
+        self.clip_cooldown = self.clip_cooldown.saturating_sub(len);
+        if clipped && self.clip_cooldown == 0 {
+            params.trigger_alert(Alert::Clip);
+            self.clip_cooldown = self.clip_cooldown_len;
+        }
+    }
+}
+
+/// One stage of the reorderable chain: its DSP state and how it follows
+/// the controls. `InputChain` keeps them boxed, in `EffectChain` order.
+trait Effect: Send {
+    fn stage(&self) -> Stage;
+
+    /// Keep up with the controls, once a block whether the stage is on or
+    /// not, outside `contained`. Returns whether it runs this block.
+    fn prepare(&mut self, c: &Controls, block: &mut [f32], report: &mut StageReport) -> bool;
+
+    /// Run over `block`, inside `contained`.
+    fn process(&mut self, c: &Controls, block: &mut [f32], report: &mut StageReport);
+}
+
+/// Every stage of the chain, in the default order.
+#[cfg_attr(not(feature = "scripting"), allow(unused_variables))]
+fn effects(sr: f32, gate_thresh: f32, params: &AudioParams) -> Vec<Box<dyn Effect>> {
+    vec![
+        Box::new(FilterStage {
+            highpass: Butterworth::highpass(sr, HIGHPASS_HZ, FILTER_ORDER as usize),
+            lowpass: Butterworth::lowpass(sr, LOWPASS_HZ, FILTER_ORDER as usize),
+        }),
+        Box::new(HumStage {
+            hum: HumFilter::new(sr),
+        }),
+        Box::new(EqStage {
+            sample_rate: sr,
+            tuned: CHAIN_BANDS,
+            filters: CHAIN_BANDS.map(|b| b.biquad(sr)),
+        }),
+        Box::new(DenoiseStage {
+            denoiser: (sr == denoise::SAMPLE_RATE as f32).then(Denoiser::new),
+            switched: SwitchedOn::default(),
+        }),
+        Box::new(GateStage::new(sr, gate_thresh)),
+        Box::new(AgcStage {
+            agc: Agc::new(sr, AGC_TARGET_DB, AGC_MAX_GAIN_DB),
+            settings: (AGC_TARGET_DB, AGC_MAX_GAIN_DB),
+        }),
+        Box::new(CompressorStage::new(sr)),
+        Box::new(SaturationStage::new(sr)),
+        Box::new(VoiceStage::new(sr)),
+        Box::new(RobotStage {
+            robot: Robot::new(sr, RobotMode::Vocoder, ROBOT_PITCH_HZ),
+        }),
+        Box::new(PrivacyStage::new(sr)),
+        Box::new(HarmonyStage::new(sr)),
+        // Call simulation (codec band-limit + compression + bitrate grit)
+        Box::new(CallSimStage {
+            sim: TelephoneSim::new(sr),
+            mode: CallSim::Off,
+        }),
+        #[cfg(feature = "scripting")]
+        Box::new(ScriptStage::new(Arc::clone(&params.script), sr)),
+        Box::new(EchoStage {
+            echo: Echo::new(sr, ECHO_TIME_MS, ECHO_FEEDBACK, ECHO_MIX),
+            switched: SwitchedOn::default(),
+        }),
+        Box::new(ReverbStage {
+            reverb: Reverb::new(sr, ReverbKind::Room, REVERB_DECAY_S, REVERB_MIX),
+            switched: SwitchedOn::default(),
+        }),
+    ]
+}
+
+/// For stages that drop whatever they still had buffered when they're
+/// switched back on: whether this block is the first since.
+#[derive(Default)]
+struct SwitchedOn {
+    was_on: bool,
+    fresh: bool,
+}
+
+impl SwitchedOn {
+    /// Note whether the stage is `on` this block, and pass it on.
+    fn update(&mut self, on: bool) -> bool {
+        self.fresh = on && !self.was_on;
+        self.was_on = on;
+        on
+    }
+}
+
+/// High-pass (remove rumble) → low-pass (remove hiss).
+struct FilterStage {
+    highpass: Butterworth,
+    lowpass: Butterworth,
+}
+
+impl Effect for FilterStage {
+    fn stage(&self) -> Stage {
+        Stage::Filters
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        c.highpass_enabled || c.lowpass_enabled
+    }
+
+    fn process(&mut self, c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        let order = c.filter_order as usize;
+        self.highpass.set_order(order);
+        self.lowpass.set_order(order);
+        if c.highpass_enabled {
+            self.highpass.process_block(block);
+        }
+        if c.lowpass_enabled {
+            self.lowpass.process_block(block);
+        }
+    }
+}
+
+/// Mains hum notches, retuned in place when the setting changes.
+struct HumStage {
+    hum: HumFilter,
+}
+
+impl Effect for HumStage {
+    fn stage(&self) -> Stage {
+        Stage::Hum
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        c.hum_mains_hz != 0
+    }
+
+    fn process(&mut self, c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        self.hum.set(c.hum_mains_hz, c.hum_overtones as usize);
+        self.hum.process_block(block);
+    }
+}
+
+/// Mic EQ, retuned in place when a band moves.
+struct EqStage {
+    sample_rate: f32,
+    /// Bands as last tuned, and their filters.
+    tuned: [EqBand; CHAIN_BANDS.len()],
+    filters: [Biquad; CHAIN_BANDS.len()],
+}
+
+impl Effect for EqStage {
+    fn stage(&self) -> Stage {
+        Stage::Eq
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        c.eq_enabled
+    }
+
+    fn process(&mut self, c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        for ((tuned, filter), shared) in self
+            .tuned
+            .iter_mut()
+            .zip(self.filters.iter_mut())
+            .zip(c.eq_bands.iter())
+        {
+            let band = EqBand {
+                freq: shared.freq.min(self.sample_rate * 0.45),
+                gain_db: shared.gain_db,
+                q: shared.q,
+                ..*tuned
+            };
+            if band != *tuned {
+                *tuned = band;
+                filter.retune(band.biquad(self.sample_rate));
+            }
+        }
+        for filter in &mut self.filters {
+            filter.process_block(block);
+        }
+    }
+}
+
+/// Denoiser, which drops stale buffered audio when re-enabled.
+struct DenoiseStage {
+    /// Only present at `denoise::SAMPLE_RATE`.
+    denoiser: Option<Denoiser>,
+    switched: SwitchedOn,
+}
+
+impl Effect for DenoiseStage {
+    fn stage(&self) -> Stage {
+        Stage::Denoise
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        self.switched.update(c.denoise && self.denoiser.is_some())
+    }
+
+    fn process(&mut self, _c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        if let Some(denoiser) = &mut self.denoiser {
+            if self.switched.fresh {
+                denoiser.reset();
+            }
+            denoiser.process(block);
+        }
+    }
+}
+
+/// Noise gate (batch process) or downward expander. "Gated" means it's
+/// taking at least 40 dB off a signal that is actually there.
+struct GateStage {
+    sample_rate: f32,
+    threshold: Smoother,
+    /// This block's smoothed threshold.
+    threshold_db: f32,
+    /// The mic's level under an external trigger: 0 while forced shut.
+    trigger: Smoother,
+    gate: NoiseGate,
+    /// (open threshold, hold ms) the gate was last set to.
+    settings: (f32, f32),
+    /// Minimum open time: whether the gate passed the last block, samples
+    /// left in the current minimum, and how much of the ungated signal is
+    /// mixed back in (1 while the minimum runs, fading to 0 after).
+    open: bool,
+    min_left: usize,
+    override_mix: f32,
+    expander: Expander,
+    tonal: TonalDetector,
+}
+
+impl GateStage {
+    fn new(sr: f32, gate_thresh: f32) -> Self {
+        let smoother = || Smoother::new(sr, PARAM_SMOOTH_MS);
+        Self {
+            sample_rate: sr,
+            threshold: smoother(),
+            threshold_db: gate_thresh,
+            trigger: smoother(),
+            // Noise gate (audio-gate crate v0.2)
+            gate: NoiseGate::new(
+                gate_thresh,
+                gate_thresh - 10.0,
+                sr,
+                1,    // mono
+                80.0, // release rate ms
+                1.0,  // attack rate ms (near-instant open)
+                GATE_HOLD_MS,
+            ),
+            settings: (gate_thresh, GATE_HOLD_MS),
+            open: false,
+            min_left: 0,
+            override_mix: 0.0,
+            expander: Expander::new(sr),
+            tonal: TonalDetector::new(sr),
+        }
+    }
+}
+
+impl Effect for GateStage {
+    fn stage(&self) -> Stage {
+        Stage::Gate
+    }
+
+    /// An external trigger fades the mic out while it's forced shut, and
+    /// skips the gate while it's forced open.
+    fn prepare(&mut self, c: &Controls, block: &mut [f32], report: &mut StageReport) -> bool {
+        self.threshold_db = self.threshold.block(c.noise_gate_threshold, block.len());
+        let level = if c.gate_forced == Some(false) {
+            0.0
+        } else {
+            1.0
+        };
+        if level == 0.0 || !self.trigger.settled(1.0) {
+            self.trigger.apply(block, level);
+        }
+        if let Some(open) = c.gate_forced {
+            report.gated = !open;
+            return false;
+        }
+        c.noise_gate_enabled
+    }
+
+    fn process(&mut self, c: &Controls, block: &mut [f32], report: &mut StageReport) {
+        let peak = |b: &[f32]| b.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        if c.gate_music {
+            self.tonal.process(block);
+            report.tonal = self.tonal.is_tonal();
+        }
+        // Retune the gate when the threshold or music state changed
+        let (thresh, hold) = if report.tonal {
+            (self.threshold_db - c.gate_music_db, GATE_MUSIC_HOLD_MS)
+        } else {
+            (self.threshold_db, GATE_HOLD_MS)
+        };
+        if c.gate_mode == GateMode::Expander {
+            self.expander
+                .set(thresh, c.expander_ratio, c.expander_range_db);
+            for s in block.iter_mut() {
+                *s = self.expander.process(*s);
+            }
+            report.gated = self.expander.reduction_db() >= 40.0;
+            return;
+        }
+        let (cached_thresh, cached_hold) = self.settings;
+        if (thresh - cached_thresh).abs() > 0.1 || hold != cached_hold {
+            self.settings = (thresh, hold);
+            self.gate.update(thresh, thresh - 10.0, 80.0, 1.0, hold);
+        }
+        let mut dry = [0.0f32; MAX_BLOCK_SIZE];
+        let dry = &mut dry[..block.len()];
+        dry.copy_from_slice(block);
+        let before = peak(block);
+        self.gate.process_frame(block);
+        let after = peak(block);
+
+        // Minimum open time: from the block the gate opens in, keep
+        // passing the dry signal, then hand back to the gate gently.
+        let open = before > 0.0 && after >= before * 0.5;
+        if open && !self.open {
+            let min_open = c.gate_min_open_ms.max(0.0);
+            self.min_left = (min_open * 0.001 * self.sample_rate) as usize;
+        }
+        self.open = open;
+        let fade_step = 1000.0 / (GATE_MIN_OPEN_FADE_MS * self.sample_rate);
+        for (s, &d) in block.iter_mut().zip(dry.iter()) {
+            if self.min_left > 0 {
+                self.min_left -= 1;
+                self.override_mix = 1.0;
+            } else {
+                self.override_mix = (self.override_mix - fade_step).max(0.0);
+            }
+            *s += (d - *s) * self.override_mix;
+        }
+        report.gated = before > 0.0 && peak(block) <= before * 0.01;
+    }
+}
+
+/// AGC; the gain carries over when settings move or it's re-enabled.
+struct AgcStage {
+    agc: Agc,
+    /// (target, max gain) the AGC was last set to.
+    settings: (f32, f32),
+}
+
+impl Effect for AgcStage {
+    fn stage(&self) -> Stage {
+        Stage::Agc
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        c.agc_enabled
+    }
+
+    fn process(&mut self, c: &Controls, block: &mut [f32], report: &mut StageReport) {
+        let settings = (c.agc_target_db, c.agc_max_gain_db);
+        if settings != self.settings {
+            self.settings = settings;
+            self.agc.set(settings.0, settings.1);
+        }
+        for s in block.iter_mut() {
+            *s = self.agc.process(*s);
+        }
+        report.agc_db = self.agc.gain_db();
+    }
+}
+
+/// Compressor, retuned when a setting moves; the envelope carries on.
+struct CompressorStage {
+    comp: Compressor,
+    /// `COMP_DEFAULTS`-shaped settings the compressor was last set to.
+    settings: (f32, f32, f32, f32, f32),
+    threshold: Smoother,
+    makeup: Smoother,
+    /// This block's smoothed threshold and makeup.
+    levels: (f32, f32),
+}
+
+impl CompressorStage {
+    fn new(sr: f32) -> Self {
+        let (threshold, ratio, attack, release, makeup) = COMP_DEFAULTS;
+        let mut comp = Compressor::new(sr);
+        comp.set(threshold, ratio, attack, release, makeup);
+        Self {
+            comp,
+            settings: COMP_DEFAULTS,
+            threshold: Smoother::new(sr, PARAM_SMOOTH_MS),
+            makeup: Smoother::new(sr, PARAM_SMOOTH_MS),
+            levels: (threshold, makeup),
+        }
+    }
+}
+
+impl Effect for CompressorStage {
+    fn stage(&self) -> Stage {
+        Stage::Compressor
+    }
+
+    fn prepare(&mut self, c: &Controls, block: &mut [f32], _report: &mut StageReport) -> bool {
+        self.levels = (
+            self.threshold.block(c.comp_threshold, block.len()),
+            self.makeup.block(c.comp_makeup_db, block.len()),
+        );
+        c.comp_enabled
+    }
+
+    fn process(&mut self, c: &Controls, block: &mut [f32], report: &mut StageReport) {
+        let (threshold, makeup) = self.levels;
+        let settings = (
+            threshold,
+            c.comp_ratio,
+            c.comp_attack_ms,
+            c.comp_release_ms,
+            makeup,
+        );
+        if settings != self.settings {
+            self.settings = settings;
+            let (threshold, ratio, attack, release, makeup) = settings;
+            self.comp.set(threshold, ratio, attack, release, makeup);
+        }
+        for s in block.iter_mut() {
+            *s = self.comp.process(*s);
+        }
+        report.reduction_db = self.comp.reduction_db();
+    }
+}
+
+/// Tube-style saturation, retuned when drive, tone or trim moves.
+struct SaturationStage {
+    saturator: Saturator,
+    /// (drive, tone, trim) the saturator was last set to.
+    settings: (f32, f32, f32),
+}
+
+impl SaturationStage {
+    fn new(sr: f32) -> Self {
+        let settings = (SATURATION_DRIVE_DB, SATURATION_TONE_DB, SATURATION_TRIM_DB);
+        let mut saturator = Saturator::new(sr);
+        saturator.set(settings.0, settings.1, settings.2);
+        Self {
+            saturator,
+            settings,
+        }
+    }
+}
+
+impl Effect for SaturationStage {
+    fn stage(&self) -> Stage {
+        Stage::Saturation
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        c.saturation_enabled
+    }
+
+    fn process(&mut self, c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        let settings = (
+            c.saturation_drive_db,
+            c.saturation_tone_db,
+            c.saturation_trim_db,
+        );
+        if settings != self.settings {
+            self.settings = settings;
+            self.saturator.set(settings.0, settings.1, settings.2);
+        }
+        for s in block.iter_mut() {
+            *s = self.saturator.process(*s);
+        }
+    }
+}
+
+/// Voice changer, retuned when a shift moves; a fresh start drops
+/// whatever was still buffered.
+struct VoiceStage {
+    voice: VoiceChanger,
+    /// (pitch, formant) the voice changer was last set to.
+    settings: (f32, f32),
+    switched: SwitchedOn,
+}
+
+impl VoiceStage {
+    fn new(sr: f32) -> Self {
+        let settings = VoicePreset::Deep.shifts();
+        let mut voice = VoiceChanger::new(sr);
+        voice.set(settings.0, settings.1);
+        Self {
+            voice,
+            settings,
+            switched: SwitchedOn::default(),
+        }
+    }
+}
+
+impl Effect for VoiceStage {
+    fn stage(&self) -> Stage {
+        Stage::Voice
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        let settings = (
+            c.voice_pitch_semitones.clamp(-12.0, 12.0),
+            c.voice_formant_semitones.clamp(-6.0, 6.0),
+        );
+        if settings != self.settings {
+            self.settings = settings;
+            self.voice.set(settings.0, settings.1);
+        }
+        self.switched.update(c.voice_enabled)
+    }
+
+    fn process(&mut self, _c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        if self.switched.fresh {
+            self.voice.reset();
+        }
+        for s in block.iter_mut() {
+            *s = self.voice.process(*s);
+        }
+    }
+}
+
+/// Robot voice.
+struct RobotStage {
+    robot: Robot,
+}
+
+impl Effect for RobotStage {
+    fn stage(&self) -> Stage {
+        Stage::Robot
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        c.robot_enabled
+    }
+
+    fn process(&mut self, c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        self.robot.set(c.robot_mode, c.robot_pitch_hz);
+        for s in block.iter_mut() {
+            *s = self.robot.process(*s);
+        }
+    }
+}
+
+/// Privacy voice: a fixed voice change, dropping stale buffered audio
+/// when re-enabled.
+struct PrivacyStage {
+    privacy: VoiceChanger,
+    switched: SwitchedOn,
+}
+
+impl PrivacyStage {
+    fn new(sr: f32) -> Self {
+        let mut privacy = VoiceChanger::new(sr);
+        privacy.set(PRIVACY_SHIFTS.0, PRIVACY_SHIFTS.1);
+        Self {
+            privacy,
+            switched: SwitchedOn::default(),
+        }
+    }
+}
+
+impl Effect for PrivacyStage {
+    fn stage(&self) -> Stage {
+        Stage::Privacy
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        self.switched.update(c.privacy_voice)
+    }
+
+    fn process(&mut self, _c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        if self.switched.fresh {
+            self.privacy.reset();
+        }
+        for s in block.iter_mut() {
+            *s = self.privacy.process(*s);
+        }
+    }
+}
+
+/// Harmony: a shifted copy blended with the voice; a fresh start drops
+/// whatever was still buffered.
+struct HarmonyStage {
+    shifter: PsolaShifter,
+    semitones: f32,
+    switched: SwitchedOn,
+}
+
+impl HarmonyStage {
+    fn new(sr: f32) -> Self {
+        let mut shifter = PsolaShifter::new(sr);
+        shifter.set_semitones(HARMONY_SEMITONES);
+        Self {
+            shifter,
+            semitones: HARMONY_SEMITONES,
+            switched: SwitchedOn::default(),
+        }
+    }
+}
+
+impl Effect for HarmonyStage {
+    fn stage(&self) -> Stage {
+        Stage::Harmony
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        let semitones = c.harmony_semitones.clamp(-12.0, 12.0);
+        if semitones != self.semitones {
+            self.semitones = semitones;
+            self.shifter.set_semitones(semitones);
+        }
+        self.switched.update(c.harmony_enabled)
+    }
+
+    fn process(&mut self, c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        if self.switched.fresh {
+            self.shifter.reset();
+        }
+        let mix = c.harmony_mix.clamp(0.0, 1.0);
+        for s in block.iter_mut() {
+            *s += (self.shifter.process(*s) - *s) * mix;
+        }
+    }
+}
+
+struct CallSimStage {
+    sim: TelephoneSim,
+    mode: CallSim,
+}
+
+impl Effect for CallSimStage {
+    fn stage(&self) -> Stage {
+        Stage::CallSim
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        if c.call_sim != self.mode {
+            self.mode = c.call_sim;
+            self.sim.set_mode(c.call_sim);
+        }
+        self.mode != CallSim::Off
+    }
+
+    fn process(&mut self, _c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        for s in block.iter_mut() {
+            *s = self.sim.process(*s);
+        }
+    }
+}
+
+/// Scripted stage (experimental).
+#[cfg(feature = "scripting")]
+impl Effect for ScriptStage {
+    fn stage(&self) -> Stage {
+        Stage::Script
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        c.script_enabled
+    }
 
-/// Atomic f32 stored as bit-cast u32 for lock-free access in callbacks.
-pub struct AtomicF32(AtomicU32);
+    fn process(&mut self, _c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        ScriptStage::process(self, block);
+    }
+}
 
-impl AtomicF32 {
-    pub fn new(val: f32) -> Self {
-        Self(AtomicU32::new(val.to_bits()))
+/// Echo; a fresh start drops the old repeats.
+struct EchoStage {
+    echo: Echo,
+    switched: SwitchedOn,
+}
+
+impl Effect for EchoStage {
+    fn stage(&self) -> Stage {
+        Stage::Echo
     }
 
-    pub fn load(&self) -> f32 {
-        f32::from_bits(self.0.load(Ordering::Relaxed))
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        self.switched.update(c.echo_enabled)
     }
 
-    pub fn store(&self, val: f32) {
-        self.0.store(val.to_bits(), Ordering::Relaxed);
+    fn process(&mut self, c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        self.echo.set(c.echo_time_ms, c.echo_feedback, c.echo_mix);
+        if self.switched.fresh {
+            self.echo.reset();
+        }
+        for s in block.iter_mut() {
+            *s = self.echo.process(*s);
+        }
     }
 }
 
-/// Shared parameters between GUI/main thread and audio callback.
-pub struct AudioParams {
-    pub volume: AtomicF32,
-    pub noise_gate_enabled: AtomicBool,
-    pub noise_gate_threshold: AtomicF32,
-    pub highpass_enabled: AtomicBool,
-    pub lowpass_enabled: AtomicBool,
+/// Reverb; a fresh start drops the old tail.
+struct ReverbStage {
+    reverb: Reverb,
+    switched: SwitchedOn,
+}
+
+impl Effect for ReverbStage {
+    fn stage(&self) -> Stage {
+        Stage::Reverb
+    }
+
+    fn prepare(&mut self, c: &Controls, _block: &mut [f32], _report: &mut StageReport) -> bool {
+        self.switched.update(c.reverb_enabled)
+    }
+
+    fn process(&mut self, c: &Controls, block: &mut [f32], _report: &mut StageReport) {
+        self.reverb
+            .set(c.reverb_kind, c.reverb_decay_s, c.reverb_mix);
+        if self.switched.fresh {
+            self.reverb.reset();
+        }
+        for s in block.iter_mut() {
+            *s = self.reverb.process(*s);
+        }
+    }
+}
+
+/// How the input's channels reach the outputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelMode {
+    /// Mixed to mono and run through the processing chain.
+    Mix,
+    /// Channels 1 and 2 each run through their own copy of the chain and
+    /// stay left and right, for stereo mics and interfaces.
+    Stereo,
+    /// Two mics of the same model on channels 1 and 2, beamformed to mono
+    /// with a null on a noise source (see `beam`), then processed.
+    Beam,
+    /// Diagnostic: input channel 1 to the left ear and 2 to the right,
+    /// unprocessed, for checking stereo mics and cable wiring.
+    Split,
+}
+
+impl ChannelMode {
+    pub const ALL: [ChannelMode; 4] = [
+        ChannelMode::Mix,
+        ChannelMode::Stereo,
+        ChannelMode::Beam,
+        ChannelMode::Split,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ChannelMode::Mix => "MIX",
+            ChannelMode::Stereo => "STEREO",
+            ChannelMode::Beam => "BEAM",
+            ChannelMode::Split => "SPLIT",
+        }
+    }
+
+    /// Samples per frame in the output rings.
+    fn frame_len(self) -> usize {
+        match self {
+            ChannelMode::Mix | ChannelMode::Beam => 1,
+            ChannelMode::Stereo | ChannelMode::Split => 2,
+        }
+    }
+}
+
+/// What happens once the input device fails or stops delivering audio.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputLoss {
+    /// Fade out and tear the engine down.
+    Stop,
+    /// Keep the outputs open, playing silence.
+    Silence,
+    /// Keep the outputs open and repeat a cue until the input is back.
+    Alert,
+}
+
+impl InputLoss {
+    pub const ALL: [InputLoss; 3] = [InputLoss::Stop, InputLoss::Silence, InputLoss::Alert];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            InputLoss::Stop => "STOP",
+            InputLoss::Silence => "SILENCE",
+            InputLoss::Alert => "ALERT",
+        }
+    }
+}
+
+/// An input device and the channel count to open it with.
+pub struct InputTarget<'a> {
+    pub device: &'a Device,
+    pub channels: u16,
+    /// Ignored for the sidechain, which only reads a level.
+    pub mode: ChannelMode,
+}
+
+/// One device the processed signal is sent to.
+pub struct OutputTarget<'a> {
+    pub device: &'a Device,
+    pub channels: u16,
+    /// `None` lets the driver pick its own period (Bluetooth and HDMI sinks
+    /// often refuse small fixed sizes).
+    pub buffer_size: Option<u32>,
+    /// The rate the device is opened at. One that differs from the
+    /// session's gets the session resampled to it.
+    pub sample_rate: u32,
+    /// Correction for this device (e.g. its headphones), applied in its
+    /// own callback only.
+    pub eq: Option<&'a EqProfile>,
 }
 
 pub struct AudioEngine {
     pub input_stream: Stream,
-    pub output_stream: Stream,
+    /// Level-only input keying the sidechain stage, if one was chosen.
+    pub sidechain_stream: Option<Stream>,
+    /// Primary output first, then any secondary outputs.
+    pub output_streams: Vec<Stream>,
+    /// Channel counts the streams were opened with.
+    pub in_channels: u16,
+    pub out_channels: Vec<u16>,
+    /// Streams the driver wouldn't open at the fixed buffer size asked
+    /// for, opened at its default size instead.
+    pub default_buffer: Vec<StreamRole>,
+    meter: TripleReader<MeterFrame>,
+    events: EventReceiver,
     _params: Arc<AudioParams>,
 }
 
 impl AudioEngine {
     pub fn build(
-        input_device: &Device,
-        output_device: &Device,
+        input: InputTarget,
+        outputs: &[OutputTarget],
+        sidechain: Option<InputTarget>,
         sample_rate: u32,
         buffer_size: u32,
-        in_channels: u16,
-        out_channels: u16,
         volume: f32,
+        fade_ms: u32,
     ) -> Result<(Self, Arc<AudioParams>)> {
+        let in_channels = input.channels;
+        let mode = input.mode;
+        let frame_len = mode.frame_len();
         let in_config = StreamConfig {
             channels: in_channels,
             sample_rate,
             buffer_size: BufferSize::Fixed(buffer_size),
         };
 
-        let out_config = StreamConfig {
-            channels: out_channels,
-            sample_rate,
-            buffer_size: BufferSize::Fixed(buffer_size),
-        };
-
-        let ring_capacity = (buffer_size as usize) * 4;
-        let ring = HeapRb::<f32>::new(ring_capacity);
-        let (mut producer, mut consumer) = ring.split();
+        let params = Arc::new(AudioParams::new(volume));
+        let mut events = EventReceiver::default();
+        // Each output gets its own ring so a slow or large-period device
+        // (e.g. Bluetooth) can't add latency or underruns to the others.
+        // Rings hold frames at the output's own rate: one at another rate
+        // has its frames resampled on the way in.
+        let mut producers = Vec::with_capacity(outputs.len());
+        let mut output_streams = Vec::with_capacity(outputs.len());
+        let mut default_buffer = Vec::new();
+        for (slot, target) in outputs.iter().enumerate() {
+            let out_rate = target.sample_rate;
+            // At least a period of the input and of the output, whichever
+            // is longer, so the ring bridges the two sizes.
+            let prime = match target.buffer_size {
+                Some(size) => size.max(buffer_size) as usize,
+                // Unknown period: start 20 ms deep.
+                None => (buffer_size as usize).max(out_rate as usize / 50),
+            };
+            // Room for 200 ms either way: a driver can call back with more
+            // than was asked for, and the output then primes deeper.
+            let ring_capacity = (prime * 4).max(out_rate as usize / 5);
+            let ring = HeapRb::<f32>::new(ring_capacity * frame_len);
+            let (mut producer, consumer) = ring.split();
+            for _ in 0..prime * frame_len {
+                let _ = producer.try_push(0.0f32);
+            }
 
-        for _ in 0..buffer_size {
-            let _ = producer.try_push(0.0f32);
+            let out_config = StreamConfig {
+                channels: target.channels,
+                sample_rate: out_rate,
+                buffer_size: target
+                    .buffer_size
+                    .map_or(BufferSize::Default, BufferSize::Fixed),
+            };
+            let (stream, fell_back) = build_output_stream(
+                target.device,
+                &out_config,
+                OutputFeed {
+                    consumer,
+                    prime,
+                    mode,
+                    slot,
+                },
+                target.eq,
+                fade_ms,
+                Arc::clone(&params),
+                events.sender(),
+            )?;
+            output_streams.push(stream);
+            if fell_back {
+                default_buffer.push(StreamRole::Output(slot));
+            }
+            let resampler = if out_rate == sample_rate {
+                Resampler::drift(frame_len)
+            } else {
+                Resampler::new(sample_rate, out_rate, frame_len)
+            };
+            producers.push(OutputRing::new(producer, frame_len, resampler, sample_rate));
         }
+        params
+            .open_outputs
+            .store(output_streams.len(), Ordering::Relaxed);
 
-        let default_gate_thresh: f32 = -36.0;
-
-        let params = Arc::new(AudioParams {
-            volume: AtomicF32::new(volume),
-            noise_gate_enabled: AtomicBool::new(false),
-            noise_gate_threshold: AtomicF32::new(default_gate_thresh),
-            highpass_enabled: AtomicBool::new(false),
-            lowpass_enabled: AtomicBool::new(false),
-        });
         let params_in = Arc::clone(&params);
+        let params_err = Arc::clone(&params);
+        let mut input_errors = events.sender();
 
-        let sr = sample_rate as f32;
-        let dt = 1.0 / sr;
+        let sidechain_stream = sidechain
+            .map(|target| {
+                build_sidechain_stream(&target, sample_rate, Arc::clone(&params), events.sender())
+            })
+            .transpose()?;
 
-        // High-pass filter state (100 Hz — remove rumble, plosives, AC hum)
-        let mut hp_prev_input: f32 = 0.0;
-        let mut hp_prev_output: f32 = 0.0;
-        let rc_hp = 1.0 / (2.0 * std::f32::consts::PI * 100.0);
-        let alpha_hp = rc_hp / (rc_hp + dt);
-
-        // Low-pass filter state (8 kHz — remove hiss above voice range)
-        let mut lp_prev_output: f32 = 0.0;
-        let rc_lp = 1.0 / (2.0 * std::f32::consts::PI * 8000.0);
-        let alpha_lp = dt / (rc_lp + dt);
-
-        // Noise gate (audio-gate crate v0.2)
-        let mut gate = NoiseGate::new(
-            default_gate_thresh,
-            default_gate_thresh - 10.0,
-            sr,
-            1,      // mono
-            80.0,   // release rate ms
-            1.0,    // attack rate ms (near-instant open)
-            150.0,  // hold time ms (bridge syllable gaps)
+        // cpal opens a stream at exactly the configured rate or fails to
+        // build it, so this is the rate the chain will actually run at.
+        let (meter_writer, meter) = triple_buffer();
+        let mut chain = InputChain::new(
+            in_config.sample_rate as f32,
+            GATE_THRESHOLD_DB,
+            sidechain_stream.is_some(),
+            Arc::clone(&params),
+            Some(meter_writer),
+            Some(events.sender()),
         );
-        let mut gate_thresh_cached = default_gate_thresh;
+        let mut right_chain = (mode == ChannelMode::Stereo).then(|| {
+            InputChain::new(
+                in_config.sample_rate as f32,
+                GATE_THRESHOLD_DB,
+                sidechain_stream.is_some(),
+                Arc::clone(&params),
+                None,
+                None,
+            )
+        });
 
-        // Pre-allocated buffer for batch noise gate processing
-        let mut mono_buf: Vec<f32> = Vec::with_capacity(buffer_size as usize * 2);
+        // Adaptation buffer: driver chunks of any size are collected into
+        // fixed blocks, so every stage sees the same block length and DSP
+        // cost per callback is predictable. Capped at the requested buffer
+        // size so small buffers don't pick up extra latency.
+        let mut block = vec![0.0f32; (buffer_size as usize).min(MAX_BLOCK_SIZE)];
+        // STEREO's right channel, and the block's frames interleaved again.
+        let mut right_block = vec![0.0f32; block.len()];
+        let mut pairs = vec![0.0f32; 2 * block.len()];
+        let mut filled = 0usize;
+        // The CUE bus runs mono; STEREO's rings get it as pairs.
+        let mut cue_bus = Bus::new(in_config.sample_rate as f32);
+        let mut cue_block = vec![0.0f32; block.len()];
+        let mut cue_pairs = vec![0.0f32; 2 * block.len()];
+        let mut beamformer = Beamformer::new(in_config.sample_rate as f32);
+        let ms_per_frame = 1000.0 / in_config.sample_rate as f32;
 
         // ──────────────────────────────────────────────────────────────
         // Input callback
         //
-        // Signal chain:
-        //   1. Mix to mono
-        //   2. High-pass 100 Hz (remove rumble/plosives)
-        //   3. Low-pass 8 kHz (remove hiss above voice range)
-        //   4. Noise gate (silence between words)
-        //   5. Volume + push to ring buffer
+        // Signal chain (per block, see `InputChain::process`):
+        //   1. Mix to mono (into the adaptation buffer; STEREO keeps channels
+        //      1 and 2 apart and runs each through its own chain, BEAM
+        //      subtracts the steered mic 2 from mic 1)
+        //   2. Test source, replacing the mic (if one is picked)
+        //   3. The reorderable stages, in `Controls::effect_chain` order
+        //      (filters through reverb by default; see
+        //      `EffectChain::default`), each skipped while it's off
+        //   4. Global dry/wet
+        //   5. Sidechain duck/unmute (if a sidechain input is open)
+        //   6. Volume + brickwall limiter (1 ms lookahead)
+        //   7. Meters and loudness, then push to ring buffer
+        //
+        // The source, every stage in 3, the sidechain and the limiter each
+        // run contained: one that panics is bypassed and reported through
+        // `faulted_stages`, and the stream keeps going.
+        //
+        // In SPLIT mode none of this runs: raw L/R pairs go straight out.
         // ──────────────────────────────────────────────────────────────
-        let input_stream = input_device.build_input_stream(
+        let (input_stream, fell_back) = open_input_stream(
+            input.device,
             &in_config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+            device::input_format(input.device, in_channels, sample_rate),
+            move |data: &[f32]| {
                 let ch = in_channels as usize;
-                let vol = params_in.volume.load();
-                let hp_on = params_in.highpass_enabled.load(Ordering::Relaxed);
-                let lp_on = params_in.lowpass_enabled.load(Ordering::Relaxed);
-                let gate_on = params_in.noise_gate_enabled.load(Ordering::Relaxed);
-                let gate_thresh = params_in.noise_gate_threshold.load();
-
-                // Update noise gate if threshold changed
-                if gate_on && (gate_thresh - gate_thresh_cached).abs() > 0.1 {
-                    gate_thresh_cached = gate_thresh;
-                    gate.update(
-                        gate_thresh,
-                        gate_thresh - 10.0,
-                        80.0,
-                        1.0,
-                        150.0,
-                    );
-                }
-
-                // Mix to mono → high-pass → low-pass → into mono_buf
-                mono_buf.clear();
-                for frame in data.chunks_exact(ch) {
-                    let mut sample: f32 = frame.iter().sum::<f32>() / ch as f32;
-
-                    // High-pass (remove rumble)
-                    if hp_on {
-                        let out = alpha_hp * (hp_prev_output + sample - hp_prev_input);
-                        hp_prev_input = sample;
-                        hp_prev_output = out;
-                        sample = out;
-                    }
+                // One snapshot for the whole callback, so every block in it
+                // runs on the same controls.
+                let snapshot = params_in.controls();
+                let controls = &snapshot.controls;
+                params_in.input_heartbeat.fetch_add(1, Ordering::Relaxed);
+                let period_ms = (data.len() / ch) as f32 * ms_per_frame;
+                if period_ms > params_in.input_period_ms.load() {
+                    params_in.input_period_ms.store(period_ms);
+                }
+                params_in.loop_guard.capture(data, ch);
+                params_in.beam.capture(data, ch);
+                params_in.room.capture(data, ch);
 
-                    // Low-pass (remove hiss)
-                    if lp_on {
-                        lp_prev_output += alpha_lp * (sample - lp_prev_output);
-                        sample = lp_prev_output;
+                if mode == ChannelMode::Split {
+                    // Raw L/R pairs, pushed whole so the rings stay aligned.
+                    for frame in data.chunks_exact(ch) {
+                        let left = frame[0];
+                        let pair = [left, frame.get(1).copied().unwrap_or(left)];
+                        for ring in producers.iter_mut() {
+                            if ring.push(&pair) {
+                                params_in.overruns.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
                     }
-
-                    mono_buf.push(sample);
+                    params_in
+                        .acked_generation
+                        .store(snapshot.generation, Ordering::Release);
+                    return;
                 }
 
-                // Noise gate (batch process)
-                if gate_on {
-                    gate.process_frame(&mut mono_buf);
-                }
+                let steering = controls.beam_steering;
+                for frame in data.chunks_exact(ch) {
+                    let mic2 = frame.get(1).copied();
+                    match mode {
+                        ChannelMode::Stereo => {
+                            block[filled] = frame[0];
+                            right_block[filled] = mic2.unwrap_or(frame[0]);
+                        }
+                        ChannelMode::Beam => {
+                            block[filled] = match mic2 {
+                                Some(mic2) => beamformer.process(frame[0], mic2, steering),
+                                None => frame[0],
+                            };
+                        }
+                        _ => block[filled] = frame.iter().sum::<f32>() / ch as f32,
+                    }
+                    filled += 1;
+                    if filled < block.len() {
+                        continue;
+                    }
+                    filled = 0;
 
-                // Volume + push to ring buffer
-                for &s in &mono_buf {
-                    let _ = producer.try_push(s * vol);
+                    match &mut right_chain {
+                        Some(right_chain) => {
+                            chain.process_stereo(
+                                controls,
+                                right_chain,
+                                &mut block,
+                                &mut right_block,
+                            );
+                            for (pair, (&l, &r)) in pairs
+                                .chunks_exact_mut(2)
+                                .zip(block.iter().zip(&right_block))
+                            {
+                                pair.copy_from_slice(&[l, r]);
+                            }
+                            let cued = if let Some(mix) = &controls.cue {
+                                // Under M/S the right chain doesn't run; the mid is both mics.
+                                if !controls.mid_side {
+                                    chain.fold_taps(right_chain, block.len());
+                                }
+                                cue_bus.process(mix, chain.taps(block.len()), &mut cue_block);
+                                for (pair, &s) in cue_pairs.chunks_exact_mut(2).zip(&cue_block) {
+                                    pair.fill(s);
+                                }
+                                true
+                            } else {
+                                false
+                            };
+                            for (slot, ring) in producers.iter_mut().enumerate() {
+                                let frames = if cued && slot == CUE_OUTPUT {
+                                    &cue_pairs
+                                } else {
+                                    &pairs
+                                };
+                                if ring.push(frames) {
+                                    params_in.overruns.fetch_add(1, Ordering::Relaxed);
+                                }
+                            }
+                        }
+                        None => {
+                            chain.process(controls, &mut block);
+                            let cued = if let Some(mix) = &controls.cue {
+                                cue_bus.process(mix, chain.taps(block.len()), &mut cue_block);
+                                true
+                            } else {
+                                false
+                            };
+                            for (slot, ring) in producers.iter_mut().enumerate() {
+                                let frames = if cued && slot == CUE_OUTPUT {
+                                    &cue_block
+                                } else {
+                                    &block
+                                };
+                                if ring.push(frames) {
+                                    params_in.overruns.fetch_add(1, Ordering::Relaxed);
+                                }
+                            }
+                        }
+                    }
                 }
-            },
-            |err| eprintln!("input error: {err}"),
-            None,
-        )?;
 
-        let output_stream = output_device.build_output_stream(
-            &out_config,
-            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                let ch = out_channels as usize;
-                for frame in data.chunks_exact_mut(ch) {
-                    let sample = consumer.try_pop().unwrap_or(0.0);
-                    for s in frame.iter_mut() {
-                        *s = sample;
+                params_in
+                    .acked_generation
+                    .store(snapshot.generation, Ordering::Release);
+            },
+            move |err| {
+                match err {
+                    StreamError::StreamInvalidated => {
+                        params_err.stream_invalidated.store(true, Ordering::Relaxed);
+                    }
+                    StreamError::DeviceNotAvailable => {
+                        params_err.input_lost.store(true, Ordering::Relaxed);
                     }
+                    _ => {}
                 }
+                input_errors.send(Event::StreamError(StreamRole::Input, err));
             },
-            |err| eprintln!("output error: {err}"),
-            None,
         )?;
+        if fell_back {
+            default_buffer.insert(0, StreamRole::Input);
+        }
 
         let params_handle = Arc::clone(&params);
         Ok((
             Self {
                 input_stream,
-                output_stream,
+                sidechain_stream,
+                output_streams,
+                in_channels,
+                out_channels: outputs.iter().map(|t| t.channels).collect(),
+                default_buffer,
+                meter,
+                events,
                 _params: params,
             },
             params_handle,
         ))
     }
+
+    /// Latest meter frame from the input callback.
+    pub fn meter(&mut self) -> &MeterFrame {
+        self.meter.read()
+    }
+
+    /// What the callbacks reported since the last call.
+    pub fn events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.events.drain()
+    }
+}
+
+/// Level meter on a second input: tracks its peak envelope into
+/// `sidechain_level` for the chain's sidechain stage. No audio is kept.
+fn build_sidechain_stream(
+    target: &InputTarget,
+    sample_rate: u32,
+    params: Arc<AudioParams>,
+    mut errors: EventSender,
+) -> Result<Stream> {
+    let config = StreamConfig {
+        channels: target.channels,
+        sample_rate,
+        buffer_size: BufferSize::Default,
+    };
+    let ch = target.channels as usize;
+    // ~100 ms release so the level doesn't flicker between syllables.
+    let release = (-1.0 / (0.1 * sample_rate as f32)).exp();
+    let mut env = 0.0f32;
+    let (stream, _) = open_input_stream(
+        target.device,
+        &config,
+        device::input_format(target.device, target.channels, sample_rate),
+        move |data: &[f32]| {
+            for frame in data.chunks_exact(ch) {
+                let peak = frame.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+                env = if peak > env { peak } else { env * release };
+            }
+            params.sidechain_level.store(gain_to_db(env));
+        },
+        move |err| errors.send(Event::StreamError(StreamRole::Sidechain, err)),
+    )?;
+    Ok(stream)
+}
+
+/// Most frames a period is converted for when the device's sample format
+/// isn't f32: the largest buffer the device offers, capped here since
+/// some drivers report huge ranges. The rest of a longer period is
+/// dropped rather than grown into on the audio thread.
+const MAX_PERIOD_FRAMES: u32 = 16384;
+
+/// Samples to preallocate for converting periods of a stream opened with
+/// `config` on a device whose largest buffer is `offered_max` frames.
+fn period_capacity(config: &StreamConfig, offered_max: Option<u32>) -> usize {
+    let fixed = match config.buffer_size {
+        BufferSize::Fixed(frames) => frames,
+        BufferSize::Default => 0,
+    };
+    let frames = offered_max
+        .unwrap_or(MAX_PERIOD_FRAMES)
+        .max(fixed)
+        .min(MAX_PERIOD_FRAMES);
+    frames as usize * config.channels as usize
+}
+
+/// `build_input_stream` in the device's own sample `format`, with `data`
+/// handed f32 samples whatever it is, under `FlushToZero`. Also says
+/// whether the stream fell back to the driver's default buffer size; see
+/// `with_buffer_fallback`.
+fn open_input_stream<D, E>(
+    device: &Device,
+    config: &StreamConfig,
+    format: SampleFormat,
+    data: D,
+    error: E,
+) -> Result<(Stream, bool)>
+where
+    D: FnMut(&[f32]) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let offered_max = device::input_max_period(device);
+    let (data, error) = (SharedCallback::new(data), SharedCallback::new(error));
+    with_buffer_fallback(config, |config| {
+        let mut data = data.handle();
+        let mut data = move |samples: &[f32]| {
+            let _ftz = FlushToZero::new();
+            if let Some(data) = data.get() {
+                data(samples);
+            }
+        };
+        let mut error = error.handle();
+        let error = move |err| {
+            if let Some(error) = error.get() {
+                error(err);
+            }
+        };
+        let capacity = period_capacity(config, offered_max);
+        Ok(match format {
+            SampleFormat::I16 => input_as::<i16, _, _>(device, config, capacity, data, error)?,
+            SampleFormat::U16 => input_as::<u16, _, _>(device, config, capacity, data, error)?,
+            SampleFormat::I32 => input_as::<i32, _, _>(device, config, capacity, data, error)?,
+            _ => device.build_input_stream(
+                config,
+                move |samples: &[f32], _: &cpal::InputCallbackInfo| data(samples),
+                error,
+                None,
+            )?,
+        })
+    })
+}
+
+fn input_as<T, D, E>(
+    device: &Device,
+    config: &StreamConfig,
+    capacity: usize,
+    mut data: D,
+    error: E,
+) -> Result<Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+    D: FnMut(&[f32]) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let mut converted = Vec::with_capacity(capacity);
+    Ok(device.build_input_stream(
+        config,
+        move |samples: &[T], _: &cpal::InputCallbackInfo| {
+            let kept = &samples[..samples.len().min(capacity)];
+            converted.clear();
+            converted.extend(kept.iter().map(|&s| f32::from_sample(s)));
+            data(&converted);
+        },
+        error,
+        None,
+    )?)
+}
+
+/// `build_output_stream` in the device's own sample `format`; `data`
+/// fills f32 samples whatever it is, under `FlushToZero`. Also says
+/// whether the stream fell back to the driver's default buffer size.
+fn open_output_stream<D, E>(
+    device: &Device,
+    config: &StreamConfig,
+    format: SampleFormat,
+    data: D,
+    error: E,
+) -> Result<(Stream, bool)>
+where
+    D: FnMut(&mut [f32]) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let offered_max = device::output_max_period(device);
+    let (data, error) = (SharedCallback::new(data), SharedCallback::new(error));
+    with_buffer_fallback(config, |config| {
+        let mut data = data.handle();
+        let mut data = move |samples: &mut [f32]| {
+            let _ftz = FlushToZero::new();
+            match data.get() {
+                Some(data) => data(samples),
+                None => samples.fill(0.0),
+            }
+        };
+        let mut error = error.handle();
+        let error = move |err| {
+            if let Some(error) = error.get() {
+                error(err);
+            }
+        };
+        let capacity = period_capacity(config, offered_max);
+        Ok(match format {
+            SampleFormat::I16 => output_as::<i16, _, _>(device, config, capacity, data, error)?,
+            SampleFormat::U16 => output_as::<u16, _, _>(device, config, capacity, data, error)?,
+            SampleFormat::I32 => output_as::<i32, _, _>(device, config, capacity, data, error)?,
+            _ => device.build_output_stream(
+                config,
+                move |samples: &mut [f32], _: &cpal::OutputCallbackInfo| data(samples),
+                error,
+                None,
+            )?,
+        })
+    })
+}
+
+fn output_as<T, D, E>(
+    device: &Device,
+    config: &StreamConfig,
+    capacity: usize,
+    mut data: D,
+    error: E,
+) -> Result<Stream>
+where
+    T: SizedSample + FromSample<f32>,
+    D: FnMut(&mut [f32]) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let mut unconverted = vec![0.0; capacity];
+    Ok(device.build_output_stream(
+        config,
+        move |samples: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let (played, dropped) = samples.split_at_mut(samples.len().min(capacity));
+            let unconverted = &mut unconverted[..played.len()];
+            data(unconverted);
+            for (out, &s) in played.iter_mut().zip(&*unconverted) {
+                *out = T::from_sample(s);
+            }
+            dropped.fill(T::EQUILIBRIUM);
+        },
+        error,
+        None,
+    )?)
+}
+
+/// Open with `config`, and if the driver refuses its fixed buffer size
+/// (some only take their own), again at the driver's default. Returns
+/// the first error if both fail, and whether the fallback was needed.
+fn with_buffer_fallback<T>(
+    config: &StreamConfig,
+    mut open: impl FnMut(&StreamConfig) -> Result<T>,
+) -> Result<(T, bool)> {
+    match open(config) {
+        Ok(opened) => Ok((opened, false)),
+        Err(e) if matches!(config.buffer_size, BufferSize::Fixed(_)) => {
+            let default = StreamConfig {
+                buffer_size: BufferSize::Default,
+                ..config.clone()
+            };
+            open(&default).map(|opened| (opened, true)).map_err(|_| e)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// A stream callback that can be offered to more than one
+/// `build_*_stream` attempt: cpal drops the callback of one that fails,
+/// which here drops only that attempt's handle and hands the callback
+/// back for the next.
+struct SharedCallback<F>(Arc<Mutex<Option<F>>>);
+
+impl<F> SharedCallback<F> {
+    fn new(callback: F) -> Self {
+        Self(Arc::new(Mutex::new(Some(callback))))
+    }
+
+    fn handle(&self) -> CallbackHandle<F> {
+        CallbackHandle {
+            shared: Arc::clone(&self.0),
+            callback: None,
+        }
+    }
+}
+
+/// One attempt's hold on a `SharedCallback`. The first call takes the
+/// callback out, locking once; later calls don't lock at all. Dropping
+/// the handle puts it back.
+struct CallbackHandle<F> {
+    shared: Arc<Mutex<Option<F>>>,
+    callback: Option<F>,
+}
+
+impl<F> CallbackHandle<F> {
+    /// The callback, or `None` while another attempt still holds it. Never
+    /// waits for the lock, as it's called from the audio thread.
+    fn get(&mut self) -> Option<&mut F> {
+        if self.callback.is_none() {
+            if let Ok(mut shared) = self.shared.try_lock() {
+                self.callback = shared.take();
+            }
+        }
+        self.callback.as_mut()
+    }
+}
+
+impl<F> Drop for CallbackHandle<F> {
+    fn drop(&mut self) {
+        if let (Some(callback), Ok(mut shared)) = (self.callback.take(), self.shared.lock()) {
+            *shared = Some(callback);
+        }
+    }
+}
+
+/// Input side of one output's ring.
+///
+/// Input and output run on separate clocks, so the output plays a little
+/// faster or slower than the input fills its ring, and without help it
+/// would end in an underrun or an overflow after a few minutes. Once the
+/// streams have settled, the ring's fill is held where it settled: a PI
+/// controller on the smoothed fill sets the resampler's speed, faster
+/// when the ring runs full and slower when it runs low.
+struct OutputRing {
+    producer: HeapProd<f32>,
+    frame_len: usize,
+    /// To the output's rate, nudged to its clock.
+    resampler: Resampler,
+    sample_rate: f32,
+    /// Input frames left before the target is taken.
+    settle_left: usize,
+    /// Smoothed fill and the fill to hold, in output frames.
+    fill: f32,
+    target: Option<f32>,
+    /// The controller's integral term: the speed change the clocks need.
+    drift: f64,
+}
+
+impl OutputRing {
+    fn new(
+        producer: HeapProd<f32>,
+        frame_len: usize,
+        resampler: Resampler,
+        sample_rate: u32,
+    ) -> Self {
+        let fill = (producer.occupied_len() / frame_len) as f32;
+        Self {
+            producer,
+            frame_len,
+            resampler,
+            sample_rate: sample_rate as f32,
+            settle_left: (DRIFT_SETTLE_S * sample_rate as f32) as usize,
+            fill,
+            target: None,
+            drift: 0.0,
+        }
+    }
+
+    /// Push whole frames; what doesn't fit is dropped a frame at a time,
+    /// so the ring stays aligned. Returns whether any was dropped.
+    fn push(&mut self, frames: &[f32]) -> bool {
+        let producer = &mut self.producer;
+        let mut overran = false;
+        for frame in frames.chunks_exact(self.frame_len) {
+            self.resampler.process(frame, |out| {
+                if producer.vacant_len() >= out.len() {
+                    producer.push_slice(out);
+                } else {
+                    overran = true;
+                }
+            });
+        }
+
+        let pushed = frames.len() / self.frame_len;
+        let fill = (producer.occupied_len() / self.frame_len) as f32;
+        let share = pushed as f32 / (DRIFT_SMOOTH_S * self.sample_rate);
+        self.fill += (fill - self.fill) * share.min(1.0);
+        let Some(target) = self.target else {
+            self.settle_left = self.settle_left.saturating_sub(pushed);
+            if self.settle_left == 0 {
+                self.target = Some(self.fill);
+            }
+            return overran;
+        };
+        // Seconds of audio too many in the ring.
+        let error = f64::from((self.fill - target) / self.sample_rate);
+        let max = Resampler::MAX_SPEED_CHANGE;
+        let dt = pushed as f64 / f64::from(self.sample_rate);
+        self.drift = (self.drift + DRIFT_KI * error * dt).clamp(-max, max);
+        self.resampler
+            .set_speed(1.0 + DRIFT_KP * error + self.drift);
+        overran
+    }
+}
+
+/// The ring an output plays from, in frames of `mode.frame_len()` samples.
+struct OutputFeed {
+    consumer: HeapCons<f32>,
+    /// Frames to buffer before playing (again, after an xrun).
+    prime: usize,
+    mode: ChannelMode,
+    /// Which output this is, for `Controls::output_delay_ms`.
+    slot: usize,
+}
+
+/// An output's alignment delay: frames of `width` samples, written and
+/// read one at a time.
+struct OutputDelay {
+    line: Vec<f32>,
+    width: usize,
+    pos: usize,
+    /// The line holds only silence.
+    silent: bool,
+}
+
+impl OutputDelay {
+    fn new(sample_rate: u32, width: usize) -> Self {
+        let frames = (MAX_OUTPUT_DELAY_MS * 0.001 * sample_rate as f32) as usize + 1;
+        Self {
+            line: vec![0.0; frames * width],
+            width,
+            pos: 0,
+            silent: true,
+        }
+    }
+
+    /// Swap `frame` for the one `delay` frames back.
+    fn process(&mut self, frame: &mut [f32], delay: usize) {
+        let len = self.line.len() / self.width;
+        let write = self.pos * self.width;
+        self.line[write..write + self.width].copy_from_slice(frame);
+        let read = (self.pos + len - delay.min(len - 1)) % len * self.width;
+        frame.copy_from_slice(&self.line[read..read + self.width]);
+        self.pos = (self.pos + 1) % len;
+        self.silent = false;
+    }
+
+    /// Forget what's in the line, so it doesn't play after a mute.
+    fn clear(&mut self) {
+        if !self.silent {
+            self.line.fill(0.0);
+            self.silent = true;
+        }
+    }
+}
+
+/// How an output's frames from the ring land on the device's channels,
+/// settled once per stream so the callback never branches on it per frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Fan {
+    /// Ring frames are already device frames: MIX or BEAM on one channel,
+    /// STEREO or SPLIT on two.
+    Direct,
+    /// MIX or BEAM on several channels: the one sample fills them all.
+    Duplicate,
+    /// STEREO or SPLIT on more than two channels: left and right first,
+    /// the rest silent so a miswired pair is obvious.
+    Pad,
+    /// SPLIT on a single channel: only the left side plays.
+    LeftOnly,
+    /// STEREO on a single channel: left and right mixed back to mono.
+    Fold,
+}
+
+impl Fan {
+    fn new(mode: ChannelMode, channels: usize) -> Self {
+        match (mode, channels) {
+            (ChannelMode::Mix | ChannelMode::Beam, 1)
+            | (ChannelMode::Stereo | ChannelMode::Split, 2) => Fan::Direct,
+            (ChannelMode::Mix | ChannelMode::Beam, _) => Fan::Duplicate,
+            (ChannelMode::Stereo, 1) => Fan::Fold,
+            (ChannelMode::Split, 1) => Fan::LeftOnly,
+            (ChannelMode::Stereo | ChannelMode::Split, _) => Fan::Pad,
+        }
+    }
+
+    /// Samples per frame while packed at the front of the callback's slice.
+    fn width(self, channels: usize) -> usize {
+        match self {
+            Fan::Direct => channels,
+            Fan::Duplicate | Fan::LeftOnly | Fan::Fold => 1,
+            Fan::Pad => 2,
+        }
+    }
+
+    /// Fill `packed` from the ring, straight into the device's slice.
+    /// Returns how many samples arrived; the rest is left as it was.
+    fn pop(self, consumer: &mut HeapCons<f32>, packed: &mut [f32]) -> usize {
+        if !matches!(self, Fan::LeftOnly | Fan::Fold) {
+            return consumer.pop_slice(packed);
+        }
+        let one = |left: f32, right: f32| {
+            if self == Fan::Fold {
+                0.5 * (left + right)
+            } else {
+                left
+            }
+        };
+        // Pairs pop into the unfilled tail and are folded down to one
+        // sample each, halving what's left each round.
+        let mut filled = 0;
+        while filled < packed.len() {
+            let rest = &mut packed[filled..];
+            let got = if rest.len() >= 2 {
+                let pairs = rest.len() / 2;
+                let got = consumer.pop_slice(&mut rest[..2 * pairs]) / 2;
+                for i in 0..got {
+                    rest[i] = one(rest[2 * i], rest[2 * i + 1]);
+                }
+                if got < pairs {
+                    return filled + got;
+                }
+                got
+            } else {
+                let mut pair = [0.0; 2];
+                if consumer.pop_slice(&mut pair) < 2 {
+                    return filled;
+                }
+                rest[0] = one(pair[0], pair[1]);
+                1
+            };
+            filled += got;
+        }
+        filled
+    }
+
+    /// Spread the packed frames at the front of `data` over every channel,
+    /// last frame first so none is overwritten before it has moved.
+    fn spread(self, data: &mut [f32], channels: usize) {
+        let frames = data.len() / channels;
+        match self {
+            Fan::Direct | Fan::LeftOnly | Fan::Fold => {}
+            Fan::Duplicate => {
+                for f in (0..frames).rev() {
+                    let s = data[f];
+                    data[f * channels..(f + 1) * channels].fill(s);
+                }
+            }
+            Fan::Pad => {
+                for f in (0..frames).rev() {
+                    let (left, right) = (data[2 * f], data[2 * f + 1]);
+                    let frame = &mut data[f * channels..(f + 1) * channels];
+                    frame.fill(0.0);
+                    frame[0] = left;
+                    frame[1] = right;
+                }
+            }
+        }
+    }
+}
+
+/// Mono ring buffer → every channel of one output device.
+///
+/// `prime` is the fill level the ring started at; if the device stalls and
+/// the backlog grows past a few periods, it is trimmed back to that level so
+/// this output doesn't stay permanently late. A burst of underruns (a USB
+/// bus hiccup, typically) flushes the ring and holds silence until it has
+/// refilled to `prime`, so the output doesn't keep limping along dry.
+///
+/// Output ramps up over `fade_ms` on start and back down once
+/// `fading_out` is set, so the DAC never sees a hard edge at either end.
+/// Alert cues are mixed in here rather than in the chain, so they stay
+/// clean and reach every output.
+fn build_output_stream(
+    device: &Device,
+    config: &StreamConfig,
+    feed: OutputFeed,
+    eq: Option<&EqProfile>,
+    fade_ms: u32,
+    params: Arc<AudioParams>,
+    mut errors: EventSender,
+) -> Result<(Stream, bool)> {
+    let OutputFeed {
+        mut consumer,
+        mut prime,
+        mode,
+        slot,
+    } = feed;
+    let frame_len = mode.frame_len();
+    let ch = config.channels as usize;
+    let fan = Fan::new(mode, ch);
+    let width = fan.width(ch);
+    let sample_rate = config.sample_rate as u64;
+    let fade_step = 1000.0 / (fade_ms.max(1) as f32 * config.sample_rate as f32);
+    let mut gain = 0.0f32;
+    let mut faded = false;
+    // Underrun tracking, counted in output frames.
+    let mut clock = 0u64;
+    let mut window_start = 0u64;
+    let mut xruns = 0u32;
+    let mut repriming = false;
+    // Input watch: heartbeat last seen, when, whether this output called
+    // it stalled, and when the input-lost cue may repeat.
+    let mut last_heartbeat = 0u32;
+    let mut heartbeat_at = 0u64;
+    let mut stalled = false;
+    let mut next_lost_cue = 0u64;
+    let mut alert_tone = AlertTone::new(config.sample_rate as f32);
+    let mut probe = ProbeTone::new();
+    // One per side so SPLIT keeps left and right filter state apart.
+    let mut eq = eq.map(|profile| {
+        let sr = config.sample_rate as f32;
+        [Equalizer::new(sr, profile), Equalizer::new(sr, profile)]
+    });
+    let mut delay = OutputDelay::new(config.sample_rate, width);
+    let frames_per_ms = config.sample_rate as f32 * 0.001;
+    let mut last_alert = 0u32;
+    let params_cb = Arc::clone(&params);
+    let ring_frames = consumer.capacity().get() / frame_len;
+    open_output_stream(
+        device,
+        config,
+        device::output_format(device, config.channels, config.sample_rate),
+        move |data: &mut [f32]| {
+            let frames = data.len() / ch;
+            // Prime deep enough to cover a whole period of this output and
+            // of the input, whatever the drivers were asked for.
+            let input_period = (params_cb.input_period_ms.load() * frames_per_ms).ceil() as usize;
+            prime = prime.max(frames.max(input_period).min(ring_frames / 4));
+            if slot == 0 {
+                params_cb
+                    .output_latency_ms
+                    .store((prime + frames) as f32 / frames_per_ms);
+            }
+            let fill = consumer.occupied_len() / frame_len;
+            if repriming && fill >= prime {
+                repriming = false;
+            }
+            if fill > frames + prime * 3 {
+                consumer.skip((fill - frames - prime) * frame_len);
+            }
+
+            let snapshot = params_cb.controls();
+            let controls = &snapshot.controls;
+            let fading_out = params_cb.fading_out.load(Ordering::Relaxed);
+            let alert = params_cb.alert.load(Ordering::Relaxed);
+            if alert != last_alert {
+                last_alert = alert;
+                if let Some(kind) = Alert::from_u32(alert & 0xF) {
+                    alert_tone.start(kind);
+                }
+            }
+            let alert_volume = controls.alert_volume;
+            let probing = params_cb.loop_guard.probing();
+
+            // A stalled input is lost; one that resumes is back.
+            let heartbeat = params_cb.input_heartbeat.load(Ordering::Relaxed);
+            if heartbeat != last_heartbeat {
+                last_heartbeat = heartbeat;
+                heartbeat_at = clock;
+                if stalled {
+                    stalled = false;
+                    params_cb.input_lost.store(false, Ordering::Relaxed);
+                }
+            } else if !stalled && clock - heartbeat_at > sample_rate * INPUT_STALL_MS / 1000 {
+                stalled = true;
+                params_cb.input_lost.store(true, Ordering::Relaxed);
+            }
+            // Nothing in the ring is worth playing once the input is gone:
+            // hold silence (plus the cue, if that's the policy) and re-prime
+            // if it comes back. Standby mutes the same way, so going live
+            // starts from fresh audio with a fade-in.
+            let input_lost = params_cb.input_lost.load(Ordering::Relaxed);
+            let muted = input_lost || controls.standby;
+            let mut cue_level = 0.0;
+            if muted {
+                consumer.clear();
+                repriming = true;
+                gain = 0.0;
+            }
+            if input_lost {
+                if controls.input_loss == InputLoss::Alert {
+                    cue_level = if alert_volume > 0.0 {
+                        alert_volume
+                    } else {
+                        INPUT_LOST_ALERT_LEVEL
+                    };
+                    if clock >= next_lost_cue {
+                        alert_tone.start(Alert::InputLost);
+                        next_lost_cue = clock + sample_rate * INPUT_LOST_ALERT_SECS;
+                    }
+                }
+            } else {
+                next_lost_cue = 0;
+            }
+
+            // The ring's frames are popped to the front of `data`,
+            // processed there, then spread over the device's channels.
+            let packed = &mut data[..frames * width];
+            let mut dry = false;
+            if muted {
+                delay.clear();
+                for frame in packed.chunks_exact_mut(width) {
+                    frame.fill(alert_tone.next_sample() * cue_level);
+                }
+            } else if repriming {
+                delay.clear();
+                packed.fill(0.0);
+            } else {
+                let delay_frames = controls
+                    .output_delay_ms
+                    .get(slot)
+                    .map_or(0, |ms| (ms * frames_per_ms) as usize);
+                let got = fan.pop(&mut consumer, packed);
+                if got < packed.len() {
+                    packed[got..].fill(0.0);
+                    dry = true;
+                }
+                for frame in packed.chunks_exact_mut(width) {
+                    gain = if fading_out {
+                        (gain - fade_step).max(0.0)
+                    } else {
+                        (gain + fade_step).min(1.0)
+                    };
+                    if delay_frames > 0 {
+                        delay.process(frame, delay_frames);
+                    }
+                    if let Some([eq_left, eq_right]) = &mut eq {
+                        contained(Stage::HeadphoneEq, &params_cb, controls, frame, |frame| {
+                            frame[0] = eq_left.process(frame[0]);
+                            if let Some(right) = frame.get_mut(1) {
+                                *right = eq_right.process(*right);
+                            }
+                        });
+                    }
+                    let alert = alert_tone.next_sample() * alert_volume;
+                    for s in frame.iter_mut() {
+                        *s = (*s + alert) * gain;
+                    }
+                }
+            }
+            // The loopback probe plays even muted, under everything else.
+            if probing {
+                for frame in packed.chunks_exact_mut(width) {
+                    let p = probe.next_sample();
+                    frame.iter_mut().for_each(|s| *s += p);
+                }
+            }
+            fan.spread(&mut data[..frames * ch], ch);
+
+            clock += frames as u64;
+            if clock - window_start > sample_rate * XRUN_WINDOW_MS / 1000 {
+                window_start = clock;
+                xruns = 0;
+            }
+            if dry && !fading_out {
+                params_cb.underruns.fetch_add(1, Ordering::Relaxed);
+                xruns += 1;
+                if xruns >= XRUN_BURST {
+                    consumer.clear();
+                    gain = 0.0;
+                    repriming = true;
+                    xruns = 0;
+                    params_cb.xrun_recoveries.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            if fading_out && gain == 0.0 && !faded {
+                faded = true;
+                params_cb.faded_outputs.fetch_add(1, Ordering::Relaxed);
+            }
+        },
+        move |err| {
+            if matches!(err, StreamError::StreamInvalidated) {
+                params.stream_invalidated.store(true, Ordering::Relaxed);
+            }
+            errors.send(Event::StreamError(StreamRole::Output(slot), err));
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn panicking_stage_is_bypassed_with_block_intact() {
+        let params = AudioParams::new(1.0);
+        let controls = Controls::default();
+        let mut block = [0.5f32; 8];
+        contained(Stage::Eq, &params, &controls, &mut block, |block| {
+            block[0] = 9.0;
+            panic!("stage bug");
+        });
+        assert_eq!(block, [0.5; 8]);
+        assert_eq!(
+            params.faulted_stages.load(Ordering::Relaxed),
+            Stage::Eq.bit()
+        );
+
+        let mut ran = false;
+        contained(Stage::Eq, &params, &controls, &mut block, |_| ran = true);
+        assert!(!ran, "faulted stage ran again");
+
+        contained(Stage::Gate, &params, &controls, &mut block, |block| {
+            block.fill(0.0)
+        });
+        assert_eq!(block, [0.0; 8]);
+    }
+
+    #[test]
+    fn output_frames_fan_out_in_place() {
+        // Three frames in the ring, four slots asked for: the last is dry.
+        let play = |mode: ChannelMode, channels: usize| {
+            let (mut producer, mut consumer) = HeapRb::<f32>::new(16).split();
+            let frames: &[f32] = match mode {
+                ChannelMode::Mix | ChannelMode::Beam => &[1.0, 2.0, 3.0],
+                ChannelMode::Stereo => &[1.0, 3.0, 2.0, 4.0, 3.0, 5.0],
+                ChannelMode::Split => &[1.0, -1.0, 2.0, -2.0, 3.0, -3.0],
+            };
+            producer.push_slice(frames);
+            let fan = Fan::new(mode, channels);
+            let mut data = vec![9.0; 4 * channels];
+            let packed = &mut data[..4 * fan.width(channels)];
+            let got = fan.pop(&mut consumer, packed);
+            packed[got..].fill(0.0);
+            fan.spread(&mut data, channels);
+            (fan, data)
+        };
+        assert_eq!(
+            play(ChannelMode::Mix, 1),
+            (Fan::Direct, vec![1.0, 2.0, 3.0, 0.0])
+        );
+        assert_eq!(
+            play(ChannelMode::Mix, 2),
+            (Fan::Duplicate, vec![1.0, 1.0, 2.0, 2.0, 3.0, 3.0, 0.0, 0.0])
+        );
+        assert_eq!(
+            play(ChannelMode::Split, 2),
+            (Fan::Direct, vec![1.0, -1.0, 2.0, -2.0, 3.0, -3.0, 0.0, 0.0])
+        );
+        let (fan, data) = play(ChannelMode::Split, 3);
+        assert_eq!(fan, Fan::Pad);
+        assert_eq!(
+            data,
+            [1.0, -1.0, 0.0, 2.0, -2.0, 0.0, 3.0, -3.0, 0.0, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            play(ChannelMode::Split, 1),
+            (Fan::LeftOnly, vec![1.0, 2.0, 3.0, 0.0])
+        );
+        assert_eq!(
+            play(ChannelMode::Stereo, 1),
+            (Fan::Fold, vec![2.0, 3.0, 4.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn output_ring_follows_a_drifting_clock() {
+        // An output 500 ppm fast: 24 extra frames a second at 48 kHz,
+        // through a 256-frame cushion in well under a minute if unchecked.
+        let (sr, prime, block, period) = (48000usize, 256usize, 64usize, 256usize);
+        let (mut producer, mut consumer) = HeapRb::<f32>::new(prime * 4).split();
+        for _ in 0..prime {
+            let _ = producer.try_push(0.0);
+        }
+        let mut ring = OutputRing::new(producer, 1, Resampler::drift(1), sr as u32);
+        let (mut owed, mut lowest, mut highest) = (0.0f64, usize::MAX, 0);
+        for second in 0..40 {
+            for _ in 0..sr / block {
+                assert!(!ring.push(&[0.25; 64]), "overrun {second} s in");
+                owed += block as f64 * 1.0005;
+                while owed >= period as f64 {
+                    owed -= period as f64;
+                    let fill = consumer.occupied_len();
+                    assert!(fill >= period, "underrun {second} s in");
+                    consumer.skip(period);
+                    if second >= 20 {
+                        (lowest, highest) = (lowest.min(fill), highest.max(fill));
+                    }
+                }
+            }
+        }
+        // Held where it settled, within a block of the start.
+        let settled = ring.target.unwrap() as usize;
+        assert!(
+            lowest + block >= settled && highest <= settled + period + block,
+            "{lowest}..{highest} around {settled}"
+        );
+        assert!((ring.drift + 0.0005).abs() < 0.0001, "{}", ring.drift);
+    }
+
+    #[test]
+    fn output_delay_holds_frames_back() {
+        let mut delay = OutputDelay::new(1000, 2);
+        let mut out = Vec::new();
+        for n in 1..=5 {
+            let mut frame = [n as f32, -n as f32];
+            delay.process(&mut frame, 2);
+            out.push(frame);
+        }
+        assert_eq!(
+            out,
+            [
+                [0.0, 0.0],
+                [0.0, 0.0],
+                [1.0, -1.0],
+                [2.0, -2.0],
+                [3.0, -3.0]
+            ]
+        );
+
+        // A mute forgets what was held; longer than the line plays the oldest.
+        delay.clear();
+        let mut frame = [9.0, 9.0];
+        delay.process(&mut frame, 1000);
+        assert_eq!(frame, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn effect_chain_reorders_and_publishes() {
+        let params = AudioParams::new(1.0);
+        let mut chain = params.controls().controls.effect_chain;
+        assert_eq!(chain, EffectChain::default());
+
+        // Gate to the front, then the EQ to the back.
+        chain.move_stage(4, 0);
+        chain.move_stage(3, 11);
+        assert_eq!(
+            &chain.stages()[..4],
+            [Stage::Gate, Stage::Filters, Stage::Hum, Stage::Denoise]
+        );
+        assert_eq!(chain.stages()[11], Stage::Eq);
+
+        // Each publish is the next generation, seen whole.
+        let controls = Controls {
+            effect_chain: chain,
+            ..Controls::default()
+        };
+        assert_eq!(params.publish(controls.clone()), 1);
+        assert_eq!(params.publish(controls.clone()), 2);
+        let snapshot = params.controls();
+        assert_eq!((snapshot.generation, &snapshot.controls), (2, &controls));
+
+        // The input chain's effects follow the new order from the next block.
+        let params = Arc::new(params);
+        let mut input = InputChain::new(
+            48000.0,
+            GATE_THRESHOLD_DB,
+            false,
+            Arc::clone(&params),
+            None,
+            None,
+        );
+        input.process(&controls, &mut [0.0; 64]);
+        let running: Vec<Stage> = input.effects.iter().map(|e| e.stage()).collect();
+        let expected: Vec<Stage> = chain
+            .stages()
+            .iter()
+            .copied()
+            .filter(|&s| cfg!(feature = "scripting") || s != Stage::Script)
+            .collect();
+        assert_eq!(running, expected);
+    }
+
+    #[test]
+    fn stage_mix_blends_wet_with_dry() {
+        let params = AudioParams::new(1.0);
+        let mut controls = Controls::default();
+        controls.stage_mix[Stage::Gate as usize] = 0.25;
+        controls.stage_mix[Stage::Limiter as usize] = 0.25;
+
+        let mut block = [0.8f32; 8];
+        contained(Stage::Gate, &params, &controls, &mut block, |block| {
+            block.fill(0.0)
+        });
+        assert_eq!(block, [0.6; 8]);
+
+        // A limiter with dry signal mixed back in would not hold its ceiling.
+        contained(Stage::Limiter, &params, &controls, &mut block, |block| {
+            block.fill(0.1)
+        });
+        assert_eq!(block, [0.1; 8]);
+    }
+
+    #[test]
+    fn trigger_holds_the_gate_open_or_shuts_the_mic() {
+        let params = Arc::new(AudioParams::new(1.0));
+        let mut chain = InputChain::new(
+            48000.0,
+            GATE_THRESHOLD_DB,
+            false,
+            Arc::clone(&params),
+            None,
+            None,
+        );
+        // A -40 dBFS tone under a -20 dB expander, 20 dB down at most.
+        let mut controls = Controls {
+            noise_gate_enabled: true,
+            noise_gate_threshold: -20.0,
+            gate_mode: GateMode::Expander,
+            ..Controls::default()
+        };
+        let mut run = |controls: &Controls| {
+            let mut peak = 0.0f32;
+            for n in 0..200 {
+                let mut block: Vec<f32> = (0..MAX_BLOCK_SIZE)
+                    .map(|i| {
+                        0.01 * (2.0 * PI * 440.0 * (n * MAX_BLOCK_SIZE + i) as f32 / 48000.0).sin()
+                    })
+                    .collect();
+                chain.process(controls, &mut block);
+                peak = block.iter().fold(0.0, |m, x| m.max(x.abs()));
+            }
+            peak
+        };
+        assert!(run(&controls) < 0.0015, "expanded");
+        controls.gate_forced = Some(true);
+        assert!(run(&controls) > 0.005, "forced open");
+        controls.gate_forced = Some(false);
+        controls.noise_gate_enabled = false;
+        assert_eq!(run(&controls), 0.0, "forced shut, gate off or not");
+    }
+
+    #[test]
+    fn stereo_chains_keep_left_and_right_apart() {
+        let params = Arc::new(AudioParams::new(1.0));
+        let mut controls = Controls::default();
+        let (writer, mut meter) = triple_buffer();
+        let new_chain = |meter| {
+            InputChain::new(
+                48000.0,
+                GATE_THRESHOLD_DB,
+                false,
+                Arc::clone(&params),
+                meter,
+                None,
+            )
+        };
+        let (mut left, mut right) = (new_chain(Some(writer)), new_chain(None));
+
+        // A tone on the left only: the right stays silent, the meter hears it.
+        for n in 0..100 {
+            let mut l: Vec<f32> = (0..MAX_BLOCK_SIZE)
+                .map(|i| {
+                    0.25 * (2.0 * PI * 1000.0 * (n * MAX_BLOCK_SIZE + i) as f32 / 48000.0).sin()
+                })
+                .collect();
+            let mut r = vec![0.0; MAX_BLOCK_SIZE];
+            left.process_stereo(&controls, &mut right, &mut l, &mut r);
+            assert!(r.iter().all(|&s| s == 0.0), "block {n}: {r:?}");
+        }
+        assert!(meter.read().peak_db > -20.0, "{}", meter.read().peak_db);
+
+        // M/S: a tone in the sides only comes back untouched, 1 ms late
+        // like the mid (the limiter's lookahead), with the chain's gate
+        // shut on the silent mid.
+        controls.mid_side = true;
+        controls.noise_gate_enabled = true;
+        let side = |i: usize| 0.25 * (2.0 * PI * 1000.0 * i as f32 / 48000.0).sin();
+        let mut heard = Vec::new();
+        for n in 0..20 {
+            let mut l: Vec<f32> = (0..MAX_BLOCK_SIZE)
+                .map(|i| side(n * MAX_BLOCK_SIZE + i))
+                .collect();
+            let mut r: Vec<f32> = l.iter().map(|s| -s).collect();
+            left.process_stereo(&controls, &mut right, &mut l, &mut r);
+            heard.extend(l.iter().zip(&r).map(|(l, r)| (*l, *r)));
+        }
+        for (i, &(l, r)) in heard.iter().enumerate().skip(100) {
+            assert!(
+                (l - side(i - 48)).abs() < 1e-6 && (r + side(i - 48)).abs() < 1e-6,
+                "{i}: {l} {r}"
+            );
+        }
+        controls.mid_side = false;
+
+        // A test source plays in both.
+        controls.source = Source::Tone;
+        let (mut l, mut r) = (vec![0.0; MAX_BLOCK_SIZE], vec![0.0; MAX_BLOCK_SIZE]);
+        left.process_stereo(&controls, &mut right, &mut l, &mut r);
+        assert!(r.iter().any(|&s| s != 0.0));
+    }
+
+    #[test]
+    fn refused_fixed_buffer_retries_at_default_with_the_same_callback() {
+        let config = StreamConfig {
+            channels: 1,
+            sample_rate: 48000,
+            buffer_size: BufferSize::Fixed(64),
+        };
+        let mut calls = 0;
+        let callback = SharedCallback::new(move || {
+            calls += 1;
+            calls
+        });
+        let mut tried = Vec::new();
+        let (mut handle, fell_back) = with_buffer_fallback(&config, |config| {
+            tried.push(config.buffer_size);
+            // Like cpal, a failed attempt drops its callback, here after
+            // calling it once.
+            let mut handle = callback.handle();
+            match config.buffer_size {
+                BufferSize::Fixed(_) => {
+                    assert_eq!(handle.get().map(|f| f()), Some(1));
+                    let mut rival = callback.handle();
+                    assert!(rival.get().is_none(), "held by the attempt still open");
+                    Err(anyhow::anyhow!("buffer size not supported"))
+                }
+                BufferSize::Default => Ok(handle),
+            }
+        })
+        .unwrap();
+        assert!(fell_back);
+        assert_eq!(tried, [BufferSize::Fixed(64), BufferSize::Default]);
+        assert_eq!(
+            handle.get().map(|f| f()),
+            Some(2),
+            "the failed attempt handed it back"
+        );
+        assert_eq!(handle.get().map(|f| f()), Some(3));
+
+        let (_, fell_back) = with_buffer_fallback(&config, |_| Ok(())).unwrap();
+        assert!(!fell_back);
+        let default = StreamConfig {
+            buffer_size: BufferSize::Default,
+            ..config
+        };
+        let mut attempts = 0;
+        let failed = with_buffer_fallback(&default, |_| -> Result<()> {
+            attempts += 1;
+            Err(anyhow::anyhow!("no device"))
+        });
+        assert_eq!(
+            (failed.unwrap_err().to_string(), attempts),
+            ("no device".to_string(), 1)
+        );
+    }
 }
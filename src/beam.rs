@@ -0,0 +1,215 @@
+//! Dual-mic beamforming (MODE: BEAM), for two mics of the same model with
+//! a steady noise source (a fan, a PC, the street) off to one side.
+//!
+//! Delay-and-subtract: mic 2, delayed and scaled so the noise in it lines
+//! up with the noise in mic 1, is taken away from mic 1. That puts a null
+//! on the noise's direction while a voice from elsewhere, which reaches
+//! the mics with a different delay, comes through (tilted towards the
+//! highs, as with any differential pair).
+//!
+//! "Point at noise" calibration: with only the noise playing, the input
+//! callback keeps both mics for a moment and the GUI finds the lag and
+//! gain that best map mic 2 onto mic 1.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{bail, Result};
+
+/// How long calibration listens to the noise.
+pub const CALIBRATE_MS: u64 = 2000;
+/// Enough capture for `CALIBRATE_MS` at the highest rate offered.
+const CAPTURE_LEN: usize = 96_000 * CALIBRATE_MS as usize / 1000;
+/// Furthest the noise can arrive apart: sound travels half a metre in 1.5 ms.
+const MAX_LAG_MS: f32 = 1.5;
+/// Correlation below which the two mics don't hear the same noise clearly
+/// enough to null it.
+const MIN_CORRELATION: f32 = 0.5;
+/// Quieter than this (RMS) and there is no noise to aim at.
+const MIN_RMS: f32 = 1e-4;
+
+/// Where the null points: noise in mic 1 is `gain` times mic 2's, `lag`
+/// samples later (earlier when negative).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Steering {
+    pub lag: i32,
+    pub gain: f32,
+}
+
+impl Steering {
+    /// Mic 1 alone, until calibrated.
+    pub const OFF: Steering = Steering { lag: 0, gain: 0.0 };
+}
+
+/// Shared between the GUI and the input callback.
+pub struct BeamCalibration {
+    /// Set while calibration listens.
+    listening: AtomicBool,
+    /// Mics 1 and 2 heard while listening. The input callback only
+    /// `try_lock`s it and never grows it past its capacity.
+    capture: Mutex<Vec<[f32; 2]>>,
+}
+
+impl Default for BeamCalibration {
+    fn default() -> Self {
+        Self {
+            listening: AtomicBool::new(false),
+            capture: Mutex::new(Vec::with_capacity(CAPTURE_LEN)),
+        }
+    }
+}
+
+impl BeamCalibration {
+    pub fn start(&self) {
+        self.capture.lock().unwrap().clear();
+        self.listening.store(true, Ordering::Relaxed);
+    }
+
+    pub fn listening(&self) -> bool {
+        self.listening.load(Ordering::Relaxed)
+    }
+
+    /// Input side: keep the first two channels of `data`.
+    pub fn capture(&self, data: &[f32], channels: usize) {
+        if !self.listening() || channels < 2 {
+            return;
+        }
+        if let Ok(mut capture) = self.capture.try_lock() {
+            let room = capture.capacity() - capture.len();
+            capture.extend(data.chunks_exact(channels).take(room).map(|f| [f[0], f[1]]));
+        }
+    }
+
+    /// Stop listening and aim the null at what was heard.
+    pub fn finish(&self, sample_rate: u32) -> Result<Steering> {
+        self.listening.store(false, Ordering::Relaxed);
+        let max_lag = (MAX_LAG_MS * 0.001 * sample_rate as f32) as usize;
+        aim(&self.capture.lock().unwrap(), max_lag)
+    }
+}
+
+/// The lag within `max_lag` at which mic 2 best matches mic 1, and the
+/// gain that matches it.
+fn aim(captured: &[[f32; 2]], max_lag: usize) -> Result<Steering> {
+    if captured.len() < 4 * max_lag + 1 {
+        bail!("nothing heard; is the input running on two channels?");
+    }
+    // Mic 1 away from the ends, so every lag has a full window of mic 2.
+    let window = max_lag..captured.len() - max_lag;
+    let energy = |range: std::ops::Range<usize>, ch: usize| -> f32 {
+        captured[range].iter().map(|f| f[ch] * f[ch]).sum()
+    };
+    let e1 = energy(window.clone(), 0);
+    if (e1 / window.len() as f32).sqrt() < MIN_RMS {
+        bail!("too quiet: make sure the noise is on while calibrating");
+    }
+    let (mut best, mut best_corr) = (Steering::OFF, 0.0f32);
+    for lag in -(max_lag as i32)..=max_lag as i32 {
+        let shifted = (window.start as i32 - lag) as usize..(window.end as i32 - lag) as usize;
+        let cross: f32 = captured[window.clone()]
+            .iter()
+            .zip(&captured[shifted.clone()])
+            .map(|(a, b)| a[0] * b[1])
+            .sum();
+        let e2 = energy(shifted, 1);
+        let corr = cross / (e1 * e2).sqrt().max(f32::MIN_POSITIVE);
+        if corr.abs() > best_corr.abs() {
+            best = Steering {
+                lag,
+                gain: cross / e2,
+            };
+            best_corr = corr;
+        }
+    }
+    if best_corr.abs() < MIN_CORRELATION {
+        bail!(
+            "the mics don't hear the same noise (correlation {:.2}); move them closer together",
+            best_corr.abs()
+        );
+    }
+    Ok(best)
+}
+
+/// Audio side: mic 1 minus the steered mic 2.
+pub struct Beamformer {
+    /// Recent samples of mics 1 and 2.
+    history: Vec<[f32; 2]>,
+    pos: usize,
+}
+
+impl Beamformer {
+    pub fn new(sample_rate: f32) -> Self {
+        let max_lag = (MAX_LAG_MS * 0.001 * sample_rate) as usize;
+        Self {
+            history: vec![[0.0; 2]; max_lag + 1],
+            pos: 0,
+        }
+    }
+
+    /// A negative lag delays mic 1 instead, by at most `MAX_LAG_MS`.
+    pub fn process(&mut self, mic1: f32, mic2: f32, steering: Steering) -> f32 {
+        let len = self.history.len();
+        self.history[self.pos] = [mic1, mic2];
+        let ago = |n: i32, ch: usize| {
+            let n = (n.max(0) as usize).min(len - 1);
+            self.history[(self.pos + len - n) % len][ch]
+        };
+        let y = ago(-steering.lag, 0) - steering.gain * ago(steering.lag, 1);
+        self.pos = (self.pos + 1) % len;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calibrates_a_null_onto_the_noise() {
+        let mut rng = 0x9e37_79b9_7f4a_7c15u64;
+        let mut noise = move || {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            (rng >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        };
+        let sr = 48000.0;
+        // The noise reaches mic 2 first, and mic 1 20 samples later at 0.8.
+        let fan: Vec<f32> = (0..sr as usize).map(|_| noise() * 0.2).collect();
+        let heard: Vec<[f32; 2]> = (0..fan.len())
+            .map(|i| [i.checked_sub(20).map_or(0.0, |j| fan[j] * 0.8), fan[i]])
+            .collect();
+        let steering = aim(&heard, 72).unwrap();
+        assert_eq!(steering.lag, 20);
+        assert!((steering.gain - 0.8).abs() < 0.01, "{steering:?}");
+
+        let rms = |x: &[f32]| (x.iter().map(|v| v * v).sum::<f32>() / x.len() as f32).sqrt();
+        let mut beam = Beamformer::new(sr);
+        let out: Vec<f32> = heard
+            .iter()
+            .map(|&[a, b]| beam.process(a, b, steering))
+            .collect();
+        assert!(rms(&out[100..]) < 1e-3 * rms(&fan), "{}", rms(&out[100..]));
+
+        // A voice from the other side (mic 1 first) still comes through.
+        let voice: Vec<f32> = (0..4800)
+            .map(|i| 0.2 * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / sr).sin())
+            .collect();
+        let mut beam = Beamformer::new(sr);
+        let out: Vec<f32> = (0..voice.len())
+            .map(|i| {
+                beam.process(
+                    voice[i],
+                    i.checked_sub(20).map_or(0.0, |j| voice[j]),
+                    steering,
+                )
+            })
+            .collect();
+        assert!(rms(&out[100..]) > 0.5 * rms(&voice), "{}", rms(&out[100..]));
+
+        // Unrelated noise in each mic can't be aimed at.
+        let apart: Vec<[f32; 2]> = (0..fan.len()).map(|_| [noise(), noise()]).collect();
+        assert!(aim(&apart, 72).is_err());
+        assert!(aim(&[[0.0; 2]; 48000], 72).is_err(), "silence");
+    }
+}
@@ -0,0 +1,163 @@
+//! Mix buses: what the outputs play, built from sends off the chain.
+//!
+//! MAIN is the chain's own output, at VOLUME and through the limiter, and
+//! plays everywhere a CUE bus isn't routed. CUE is a second mix for a
+//! guest or producer on OUT 2: a send level from each tap, then its own
+//! EQ, volume and limiter. The chain runs once whatever the buses; a bus
+//! only mixes what it taps.
+
+use crate::audio::{LIMITER_CEILING_DB, PARAM_SMOOTH_MS};
+use crate::dsp::{Biquad, Limiter, Smoother};
+use crate::eq::{EqBand, CHAIN_BANDS};
+
+/// The output a CUE bus plays on: OUT 2.
+pub const CUE_OUTPUT: usize = 1;
+
+/// Points along the chain a bus can take signal from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tap {
+    /// The processed voice, before VOLUME and the limiter.
+    Voice,
+    /// The source as it came in, before any stage. Not delayed to line
+    /// up with `Voice`, so blending the two can sound phasey.
+    Dry,
+}
+
+impl Tap {
+    pub const ALL: [Tap; 2] = [Tap::Voice, Tap::Dry];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Tap::Voice => "VOICE",
+            Tap::Dry => "DRY",
+        }
+    }
+}
+
+/// A bus's settings, published with the controls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BusMix {
+    /// Send level of each tap, by `Tap as usize`; 0 leaves it out.
+    pub sends: [f32; Tap::ALL.len()],
+    pub volume: f32,
+    /// `None` leaves the bus unequalized.
+    pub eq: Option<[EqBand; CHAIN_BANDS.len()]>,
+    /// Ceiling of the bus's own limiter, in dBFS.
+    pub ceiling_db: f32,
+}
+
+/// The processed voice alone, as MAIN has it before VOLUME.
+impl Default for BusMix {
+    fn default() -> Self {
+        Self {
+            sends: [1.0, 0.0],
+            volume: 1.0,
+            eq: None,
+            ceiling_db: LIMITER_CEILING_DB,
+        }
+    }
+}
+
+/// One bus running at one sample rate.
+pub struct Bus {
+    sample_rate: f32,
+    sends: [Smoother; Tap::ALL.len()],
+    volume: Smoother,
+    eq_tuned: [EqBand; CHAIN_BANDS.len()],
+    eq: [Biquad; CHAIN_BANDS.len()],
+    ceiling: Smoother,
+    limiter: Limiter,
+}
+
+impl Bus {
+    pub fn new(sample_rate: f32) -> Self {
+        let smoother = || Smoother::new(sample_rate, PARAM_SMOOTH_MS);
+        Self {
+            sample_rate,
+            sends: [smoother(), smoother()],
+            volume: smoother(),
+            eq_tuned: CHAIN_BANDS,
+            eq: CHAIN_BANDS.map(|band| band.biquad(sample_rate)),
+            ceiling: smoother(),
+            limiter: Limiter::new(sample_rate, LIMITER_CEILING_DB),
+        }
+    }
+
+    /// Mix `taps` (a block each, by `Tap as usize`) into `out`.
+    pub fn process(&mut self, mix: &BusMix, taps: [&[f32]; Tap::ALL.len()], out: &mut [f32]) {
+        out.fill(0.0);
+        for ((send, &level), tap) in self.sends.iter_mut().zip(&mix.sends).zip(taps) {
+            if level == 0.0 && send.settled(level) {
+                continue;
+            }
+            for (o, &t) in out.iter_mut().zip(tap) {
+                *o += t * send.next(level);
+            }
+        }
+        if let Some(bands) = &mix.eq {
+            for ((tuned, filter), shared) in self.eq_tuned.iter_mut().zip(&mut self.eq).zip(bands) {
+                let band = EqBand {
+                    freq: shared.freq.min(self.sample_rate * 0.45),
+                    ..*shared
+                };
+                if band != *tuned {
+                    *tuned = band;
+                    filter.retune(band.biquad(self.sample_rate));
+                }
+                filter.process_block(out);
+            }
+        }
+        self.volume.apply(out, mix.volume);
+        self.limiter
+            .set_ceiling(self.ceiling.block(mix.ceiling_db, out.len()));
+        for s in out.iter_mut() {
+            *s = self.limiter.process(*s);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::db_to_gain;
+
+    #[test]
+    fn cue_mixes_its_sends_at_its_own_volume() {
+        let mut bus = Bus::new(48000.0);
+        let (voice, dry) = ([0.1f32; 64], [0.2f32; 64]);
+        let mut out = [0.0f32; 64];
+        let mix = BusMix {
+            sends: [1.0, 0.5],
+            volume: 0.5,
+            ..BusMix::default()
+        };
+        // Past the limiter's lookahead and the first glide.
+        for _ in 0..200 {
+            bus.process(&mix, [&voice, &dry], &mut out);
+        }
+        assert!(out.iter().all(|&s| (s - 0.1).abs() < 1e-4), "{out:?}");
+
+        let silent = BusMix {
+            sends: [0.0, 0.0],
+            ..mix.clone()
+        };
+        for _ in 0..200 {
+            bus.process(&silent, [&voice, &dry], &mut out);
+        }
+        assert!(out.iter().all(|&s| s.abs() < 1e-4), "{out:?}");
+
+        // Loud sends are held under the bus's own ceiling.
+        let loud = BusMix {
+            sends: [10.0, 10.0],
+            volume: 1.0,
+            eq: Some(CHAIN_BANDS),
+            ceiling_db: -6.0,
+        };
+        for _ in 0..200 {
+            bus.process(&loud, [&voice, &dry], &mut out);
+        }
+        let ceiling = db_to_gain(-6.0);
+        assert!(out.iter().all(|&s| s.abs() <= ceiling + 1e-4), "{out:?}");
+        assert!(out.iter().all(|&s| s > 0.8 * ceiling), "{out:?}");
+    }
+}
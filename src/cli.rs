@@ -0,0 +1,107 @@
+//! Command-line options that start the GUI in a given state, and the
+//! reverse: the current GUI state as a command line (COPY CLI).
+
+use anyhow::{bail, Context, Result};
+
+/// Everything but `profile` overrides what the profile would pick.
+#[derive(Default)]
+pub struct LaunchOptions {
+    pub profile: Option<String>,
+    /// Device names; an exact match wins, otherwise a case-insensitive
+    /// fragment is enough.
+    pub input: Option<String>,
+    pub output: Option<String>,
+    pub aux_output: Option<String>,
+    pub rate: Option<u32>,
+    pub buffer: Option<u32>,
+    /// OUT's buffer size, when it differs from the input's.
+    pub buffer_out: Option<u32>,
+    /// Percent.
+    pub volume: Option<u32>,
+    /// Gate threshold in dBFS; turns the gate on.
+    pub gate: Option<f32>,
+}
+
+impl LaunchOptions {
+    /// `--name <value>` or `--name=<value>`. Unknown arguments are ignored.
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.iter().cloned();
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                continue;
+            };
+            let (flag, value) = match flag.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (flag.to_string(), None),
+            };
+            if !matches!(
+                flag.as_str(),
+                "profile"
+                    | "input"
+                    | "output"
+                    | "output2"
+                    | "rate"
+                    | "buffer"
+                    | "buffer-out"
+                    | "volume"
+                    | "gate"
+            ) {
+                continue;
+            }
+            let value = match value.or_else(|| args.next()) {
+                Some(v) if !v.trim().is_empty() => v.trim().to_string(),
+                _ => bail!("--{flag} needs a value"),
+            };
+            let number = |v: &str| v.parse::<u32>().with_context(|| format!("--{flag} {v}"));
+            match flag.as_str() {
+                "profile" => options.profile = Some(value),
+                "input" => options.input = Some(value),
+                "output" => options.output = Some(value),
+                "output2" => options.aux_output = Some(value),
+                "rate" => options.rate = Some(number(&value)?),
+                "buffer" => options.buffer = Some(number(&value)?),
+                "buffer-out" => options.buffer_out = Some(number(&value)?),
+                "volume" => options.volume = Some(number(&value)?.min(100)),
+                "gate" => {
+                    let db = value
+                        .parse::<f32>()
+                        .with_context(|| format!("--gate {value}"))?;
+                    options.gate = Some(db.clamp(-60.0, -10.0));
+                }
+                _ => unreachable!(),
+            }
+        }
+        Ok(options)
+    }
+
+    /// The `vibetone ...` command that reproduces these options.
+    pub fn to_command(&self) -> String {
+        let mut cmd = String::from("vibetone");
+        let mut push = |flag: &str, value: Option<String>| {
+            if let Some(value) = value {
+                cmd.push_str(&format!(" --{flag} {}", shell_quote(&value)));
+            }
+        };
+        push("profile", self.profile.clone());
+        push("input", self.input.clone());
+        push("output", self.output.clone());
+        push("output2", self.aux_output.clone());
+        push("rate", self.rate.map(|r| r.to_string()));
+        push("buffer", self.buffer.map(|b| b.to_string()));
+        push("buffer-out", self.buffer_out.map(|b| b.to_string()));
+        push("volume", self.volume.map(|v| v.to_string()));
+        push("gate", self.gate.map(|g| g.to_string()));
+        cmd
+    }
+}
+
+/// Single-quote `s` for a POSIX shell unless it's plainly safe.
+fn shell_quote(s: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:@%+=,".contains(c);
+    if !s.is_empty() && s.chars().all(safe) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
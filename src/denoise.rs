@@ -0,0 +1,51 @@
+//! RNNoise speech denoiser (nnnoiseless). Removes steady and clicky
+//! background noise (fans, keyboards) while keeping word tails that the
+//! gate would cut.
+
+use nnnoiseless::DenoiseState;
+
+/// The model only works at this rate.
+pub const SAMPLE_RATE: u32 = 48_000;
+
+const FRAME: usize = DenoiseState::FRAME_SIZE;
+/// nnnoiseless works on 16-bit sample values held in floats.
+const SCALE: f32 = 32768.0;
+
+/// Runs the model on any block size by collecting whole 10 ms frames,
+/// which delays the signal by one frame.
+pub struct Denoiser {
+    state: Box<DenoiseState<'static>>,
+    input: [f32; FRAME],
+    output: [f32; FRAME],
+    pos: usize,
+}
+
+impl Denoiser {
+    pub fn new() -> Self {
+        Self {
+            state: DenoiseState::new(),
+            input: [0.0; FRAME],
+            output: [0.0; FRAME],
+            pos: 0,
+        }
+    }
+
+    /// Drop buffered audio, so re-enabling doesn't replay a stale frame.
+    pub fn reset(&mut self) {
+        self.input = [0.0; FRAME];
+        self.output = [0.0; FRAME];
+        self.pos = 0;
+    }
+
+    pub fn process(&mut self, block: &mut [f32]) {
+        for s in block.iter_mut() {
+            self.input[self.pos] = *s * SCALE;
+            *s = self.output[self.pos] / SCALE;
+            self.pos += 1;
+            if self.pos == FRAME {
+                self.pos = 0;
+                self.state.process_frame(&mut self.output, &self.input);
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use cpal::traits::{DeviceTrait, HostTrait};
-use cpal::{Device, Host, SupportedBufferSize, SupportedStreamConfigRange};
+use cpal::{Device, Host, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
 
 fn device_name(dev: &Device) -> String {
     dev.description()
@@ -8,6 +8,86 @@ fn device_name(dev: &Device) -> String {
         .unwrap_or_else(|_| "???".into())
 }
 
+/// Devices that are almost never what a user wants to monitor through:
+/// OS mappers, loopback captures and null sinks.
+pub const DEFAULT_BLOCKLIST: &[&str] = &[
+    "Microsoft Sound Mapper",
+    "Primary Sound Capture Driver",
+    "Primary Sound Driver",
+    "Stereo Mix",
+    "What U Hear",
+    "Monitor of",
+    "null",
+];
+
+/// Whether `name` contains any of the blocklist fragments (case-insensitive).
+pub fn is_blocked(name: &str, blocklist: &[String]) -> bool {
+    let name = name.to_lowercase();
+    blocklist
+        .iter()
+        .map(|p| p.trim().to_lowercase())
+        .any(|p| !p.is_empty() && name.contains(&p))
+}
+
+/// The physical device an endpoint name belongs to, so an interface's
+/// input and output pair up: "Microphone (USB Audio)" and
+/// "Speakers (USB Audio)" both give "usb audio". Names without a
+/// parenthesised part are their own key.
+pub fn hardware_key(name: &str) -> String {
+    let key = match (name.rfind('('), name.rfind(')')) {
+        (Some(open), Some(close)) if open < close => &name[open + 1..close],
+        _ => name,
+    };
+    key.trim().to_lowercase()
+}
+
+/// Name fragments (lowercase) that mark virtual or loopback devices.
+const VIRTUAL_HINTS: &[&str] = &[
+    "virtual",
+    "loopback",
+    "cable",
+    "voicemeeter",
+    "blackhole",
+    "soundflower",
+    "vb-audio",
+];
+/// Name fragments (lowercase) that mark Bluetooth devices, which add
+/// 100+ ms of latency on output and drop to hands-free quality as input.
+const BLUETOOTH_HINTS: &[&str] = &["bluetooth", "airpods", "hands-free", "handsfree", "buds"];
+
+pub fn is_bluetooth(name: &str) -> bool {
+    let name = name.to_lowercase();
+    BLUETOOTH_HINTS.iter().any(|h| name.contains(h))
+}
+
+/// How sensible `input` + `output` is as a first-run monitoring pair;
+/// higher is better. Used instead of just taking the first of each list.
+pub fn pair_score(input: &str, output: &str) -> i32 {
+    let (input, output) = (input.to_lowercase(), output.to_lowercase());
+    let any = |name: &str, hints: &[&str]| hints.iter().any(|h| name.contains(h));
+    let mut score = 0;
+    if input.contains("microphone") || input.contains("mic") {
+        score += 3;
+    }
+    if output.contains("headphone") || output.contains("headset") {
+        score += 2;
+    }
+    if is_bluetooth(&output) {
+        score -= 4;
+    }
+    if is_bluetooth(&input) {
+        score -= 2;
+    }
+    if any(&input, VIRTUAL_HINTS) || any(&output, VIRTUAL_HINTS) {
+        score -= 5;
+    }
+    // A headset or interface used for both is usually the intended pair.
+    if hardware_key(&input) == hardware_key(&output) {
+        score += 1;
+    }
+    score
+}
+
 pub fn host() -> Host {
     cpal::default_host()
 }
@@ -43,95 +123,282 @@ pub fn negotiate_config(
     Ok((in_cfg.channels(), out_cfg.channels()))
 }
 
-/// Return the subset of `candidates` that both devices support as buffer sizes.
-/// Falls back to full candidate list if device reports Unknown.
-pub fn supported_buffer_sizes(
-    input: &Device,
-    output: &Device,
-    candidates: &[u32],
-) -> Vec<u32> {
-    let range = |configs: Result<Vec<SupportedStreamConfigRange>, _>| -> Option<(u32, u32)> {
-        let configs = configs.ok()?;
-        let mut global_min = u32::MAX;
-        let mut global_max = 0u32;
-        for cfg in configs {
-            match cfg.buffer_size() {
-                SupportedBufferSize::Range { min, max } => {
-                    global_min = global_min.min(*min);
-                    global_max = global_max.max(*max);
-                }
-                SupportedBufferSize::Unknown => return None,
-            }
-        }
-        if global_max > 0 { Some((global_min, global_max)) } else { None }
-    };
-
-    let in_range = range(input.supported_input_configs().map(|i| i.collect()));
-    let out_range = range(output.supported_output_configs().map(|i| i.collect()));
-
-    match (in_range, out_range) {
-        (Some((in_min, in_max)), Some((out_min, out_max))) => {
-            let lo = in_min.max(out_min);
-            let hi = in_max.min(out_max);
-            candidates.iter().copied().filter(|&s| s >= lo && s <= hi).collect()
-        }
-        (Some((min, max)), None) | (None, Some((min, max))) => {
-            candidates.iter().copied().filter(|&s| s >= min && s <= max).collect()
-        }
-        (None, None) => candidates.to_vec(),
-    }
+/// Default channel count of an input opened on its own (e.g. a sidechain).
+pub fn input_channels(input: &Device) -> Result<u16> {
+    Ok(input.default_input_config()?.channels())
 }
 
-/// Check whether the given buffer size and sample rate are supported by both devices.
+/// Check whether each device takes its buffer size and the input takes
+/// the sample rate. Outputs that don't take the rate are resampled (see
+/// `output_rate`).
 pub fn validate_config(
     input: &Device,
     output: &Device,
-    buffer_size: u32,
+    in_buffer: u32,
+    out_buffer: u32,
     sample_rate: u32,
 ) -> Result<(), String> {
-    if supported_buffer_sizes(input, output, &[buffer_size]).is_empty() {
+    if !supports_buffer(input.supported_input_configs(), in_buffer) {
+        return Err(format!(
+            "Buffer size {in_buffer} not supported by the input"
+        ));
+    }
+    if !supports_buffer(output.supported_output_configs(), out_buffer) {
         return Err(format!(
-            "Buffer size {buffer_size} not supported by selected devices"
+            "Buffer size {out_buffer} not supported by the output (set its own under OUT)"
         ));
     }
-    if supported_sample_rates(input, output, &[sample_rate]).is_empty() {
+    if !supports_rate(input.supported_input_configs(), sample_rate) {
         return Err(format!(
-            "Sample rate {sample_rate} Hz not supported by selected devices"
+            "Sample rate {sample_rate} Hz not supported by the input"
         ));
     }
     Ok(())
 }
 
-/// Return the subset of `candidates` that both devices support as sample rates.
-pub fn supported_sample_rates(
-    input: &Device,
-    output: &Device,
-    candidates: &[u32],
-) -> Vec<u32> {
-    let ranges = |configs: Result<Vec<SupportedStreamConfigRange>, _>| -> Option<Vec<(u32, u32)>> {
-        let configs = configs.ok()?;
-        Some(
-            configs
-                .into_iter()
-                .map(|c| (c.min_sample_rate(), c.max_sample_rate()))
-                .collect(),
-        )
-    };
+/// The rate to open `output` at for a session at `sample_rate`: that rate
+/// if the device takes it, otherwise its own default, which the session
+/// is then resampled to.
+pub fn output_rate(output: &Device, sample_rate: u32) -> u32 {
+    if supports_rate(output.supported_output_configs(), sample_rate) {
+        return sample_rate;
+    }
+    output
+        .default_output_config()
+        .map_or(sample_rate, |c| c.sample_rate())
+}
 
-    let in_ranges = ranges(input.supported_input_configs().map(|i| i.collect()));
-    let out_ranges = ranges(output.supported_output_configs().map(|i| i.collect()));
+/// The rate the OS mixer runs `input` at in shared mode (WASAPI's mix
+/// format, the PipeWire or PulseAudio graph rate), if it says.
+pub fn input_mixer_rate(input: &Device) -> Option<u32> {
+    input.default_input_config().ok().map(|c| c.sample_rate())
+}
 
-    let rate_in_ranges = |rate: u32, rs: &[(u32, u32)]| -> bool {
-        rs.iter().any(|&(lo, hi)| rate >= lo && rate <= hi)
-    };
+/// The rate the OS mixer runs `output` at, if it says.
+pub fn output_mixer_rate(output: &Device) -> Option<u32> {
+    output.default_output_config().ok().map(|c| c.sample_rate())
+}
 
-    candidates
-        .iter()
-        .copied()
-        .filter(|&rate| {
-            let in_ok = in_ranges.as_ref().is_none_or(|r| rate_in_ranges(rate, r));
-            let out_ok = out_ranges.as_ref().is_none_or(|r| rate_in_ranges(rate, r));
-            in_ok && out_ok
+/// The session rate that keeps the OS from resampling behind our back:
+/// the input's mixer rate, else the output's, if it's one `offered`. The
+/// input comes first because the chain runs at its rate; an output left
+/// at another rate is resampled in the open (see `output_rate`).
+pub fn mixer_session_rate(input: Option<u32>, output: Option<u32>, offered: &[u32]) -> Option<u32> {
+    [input, output]
+        .into_iter()
+        .flatten()
+        .find(|rate| offered.contains(rate))
+}
+
+/// Sample formats streams can be opened in, best first. The callbacks
+/// convert to and from f32 at the edge.
+pub const SAMPLE_FORMATS: [SampleFormat; 4] = [
+    SampleFormat::F32,
+    SampleFormat::I32,
+    SampleFormat::I16,
+    SampleFormat::U16,
+];
+
+/// The format to open `input` in at `channels` and `rate`.
+pub fn input_format(input: &Device, channels: u16, rate: u32) -> SampleFormat {
+    pick_format(input.supported_input_configs(), channels, rate)
+}
+
+/// The format to open `output` in at `channels` and `rate`.
+pub fn output_format(output: &Device, channels: u16, rate: u32) -> SampleFormat {
+    pick_format(output.supported_output_configs(), channels, rate)
+}
+
+/// The longest period, in frames, `input` says it can deliver.
+pub fn input_max_period(input: &Device) -> Option<u32> {
+    max_period(input.supported_input_configs())
+}
+
+/// The longest period, in frames, `output` says it can ask for.
+pub fn output_max_period(output: &Device) -> Option<u32> {
+    max_period(output.supported_output_configs())
+}
+
+/// The best of `SAMPLE_FORMATS` among `configs` that fit `channels` and
+/// `rate`; f32 if none do or the device won't say, so opening fails with
+/// the driver's own error.
+fn pick_format<E>(
+    configs: Result<impl Iterator<Item = SupportedStreamConfigRange>, E>,
+    channels: u16,
+    rate: u32,
+) -> SampleFormat {
+    let offered: Vec<SampleFormat> = configs.map_or(Vec::new(), |configs| {
+        configs
+            .filter(|c| c.channels() == channels)
+            .filter(|c| (c.min_sample_rate()..=c.max_sample_rate()).contains(&rate))
+            .map(|c| c.sample_format())
+            .collect()
+    });
+    SAMPLE_FORMATS
+        .into_iter()
+        .find(|f| offered.contains(f))
+        .unwrap_or(SampleFormat::F32)
+}
+
+/// Whether any of `configs` covers `rate`; true if the device won't say.
+fn supports_rate<E>(
+    configs: Result<impl Iterator<Item = SupportedStreamConfigRange>, E>,
+    rate: u32,
+) -> bool {
+    configs.map_or(true, |mut configs| {
+        configs.any(|c| (c.min_sample_rate()..=c.max_sample_rate()).contains(&rate))
+    })
+}
+
+/// The largest buffer any of `configs` allows; `None` if none say.
+fn max_period<E>(
+    configs: Result<impl Iterator<Item = SupportedStreamConfigRange>, E>,
+) -> Option<u32> {
+    configs
+        .ok()?
+        .filter_map(|c| match *c.buffer_size() {
+            SupportedBufferSize::Range { max, .. } => Some(max),
+            SupportedBufferSize::Unknown => None,
         })
-        .collect()
+        .max()
+}
+
+/// Whether any of `configs` takes a fixed buffer of `size` frames; true
+/// if the device won't say.
+fn supports_buffer<E>(
+    configs: Result<impl Iterator<Item = SupportedStreamConfigRange>, E>,
+    size: u32,
+) -> bool {
+    let ranges: Option<Vec<(u32, u32)>> = configs.ok().and_then(|configs| {
+        configs
+            .map(|c| match *c.buffer_size() {
+                SupportedBufferSize::Range { min, max } => Some((min, max)),
+                SupportedBufferSize::Unknown => None,
+            })
+            .collect()
+    });
+    match ranges {
+        Some(ranges) if !ranges.is_empty() => {
+            ranges.iter().any(|&(min, max)| (min..=max).contains(&size))
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pair_score_prefers_wired_mic_and_headphones() {
+        let best = pair_score("Microphone (USB Audio)", "Headphones (USB Audio)");
+        assert!(best > pair_score("Line In (Realtek)", "Headphones (USB Audio)"));
+        assert!(best > pair_score("Microphone (USB Audio)", "Headphones (AirPods Pro)"));
+        assert!(
+            best > pair_score(
+                "Microphone (USB Audio)",
+                "CABLE Input (VB-Audio Virtual Cable)"
+            )
+        );
+        assert!(pair_score("Mic (Virtual)", "Speakers") < pair_score("Line In", "Speakers"));
+    }
+
+    #[test]
+    fn session_rate_follows_the_input_mixer_then_the_output() {
+        let offered = [44100, 48000, 96000];
+        assert_eq!(
+            mixer_session_rate(Some(44100), Some(48000), &offered),
+            Some(44100)
+        );
+        assert_eq!(
+            mixer_session_rate(Some(32000), Some(48000), &offered),
+            Some(48000)
+        );
+        assert_eq!(mixer_session_rate(None, Some(96000), &offered), Some(96000));
+        assert_eq!(mixer_session_rate(Some(22050), None, &offered), None);
+        assert_eq!(mixer_session_rate(None, None, &offered), None);
+    }
+
+    #[test]
+    fn buffer_sizes_are_checked_per_device() {
+        let config = |buffer_size| {
+            SupportedStreamConfigRange::new(2, 44100, 48000, buffer_size, SampleFormat::F32)
+        };
+        let dac = || {
+            Ok::<_, ()>(
+                vec![config(SupportedBufferSize::Range {
+                    min: 256,
+                    max: 2048,
+                })]
+                .into_iter(),
+            )
+        };
+        assert!(!supports_buffer(dac(), 64));
+        assert!(supports_buffer(dac(), 256));
+        let mixed = vec![
+            config(SupportedBufferSize::Range {
+                min: 256,
+                max: 2048,
+            }),
+            config(SupportedBufferSize::Unknown),
+        ];
+        assert!(
+            supports_buffer(Ok::<_, ()>(mixed.into_iter()), 64),
+            "one config won't say"
+        );
+        assert!(supports_buffer(Err::<std::vec::IntoIter<_>, _>(()), 64));
+        assert_eq!(max_period(dac()), Some(2048));
+        assert_eq!(
+            max_period(Ok::<_, ()>(
+                vec![config(SupportedBufferSize::Unknown)].into_iter()
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn picks_the_best_format_the_device_offers() {
+        let config = |channels, max_rate, format| {
+            SupportedStreamConfigRange::new(
+                channels,
+                44100,
+                max_rate,
+                SupportedBufferSize::Unknown,
+                format,
+            )
+        };
+        let pick = |configs: Vec<SupportedStreamConfigRange>| {
+            pick_format(Ok::<_, ()>(configs.into_iter()), 2, 48000)
+        };
+        // A WASAPI shared-mode mic: 16-bit only.
+        assert_eq!(
+            pick(vec![config(2, 48000, SampleFormat::I16)]),
+            SampleFormat::I16
+        );
+        assert_eq!(
+            pick(vec![
+                config(2, 48000, SampleFormat::U16),
+                config(2, 96000, SampleFormat::I32)
+            ]),
+            SampleFormat::I32
+        );
+        // Float only counts where it fits the channels and rate.
+        assert_eq!(
+            pick(vec![
+                config(1, 48000, SampleFormat::F32),
+                config(2, 44100, SampleFormat::F32),
+                config(2, 48000, SampleFormat::I16)
+            ]),
+            SampleFormat::I16
+        );
+        assert_eq!(
+            pick(vec![config(2, 48000, SampleFormat::U8)]),
+            SampleFormat::F32,
+            "unsupported: let the driver say"
+        );
+        assert_eq!(
+            pick_format(Err::<std::iter::Empty<_>, _>(()), 2, 48000),
+            SampleFormat::F32
+        );
+    }
 }
@@ -0,0 +1,2956 @@
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+/// Butterworth high- or low-pass: a cascade of biquads, maximally flat in
+/// the passband and exactly -3 dB at `cutoff` whatever the order. Each
+/// order adds 6 dB/oct of slope.
+pub struct Butterworth {
+    sample_rate: f32,
+    cutoff: f32,
+    high: bool,
+    order: usize,
+    sections: [Biquad; Self::MAX_ORDER / 2],
+}
+
+impl Butterworth {
+    /// Even orders only; odd ones would need a first-order section.
+    pub const ORDERS: [usize; 4] = [2, 4, 6, 8];
+    pub const MAX_ORDER: usize = 8;
+
+    pub fn highpass(sample_rate: f32, cutoff: f32, order: usize) -> Self {
+        Self::new(sample_rate, cutoff, true, order)
+    }
+
+    pub fn lowpass(sample_rate: f32, cutoff: f32, order: usize) -> Self {
+        Self::new(sample_rate, cutoff, false, order)
+    }
+
+    fn new(sample_rate: f32, cutoff: f32, high: bool, order: usize) -> Self {
+        let mut filter = Self {
+            sample_rate,
+            cutoff,
+            high,
+            order: 0,
+            sections: std::array::from_fn(|_| Biquad::lowpass(sample_rate, cutoff, FRAC_1_SQRT_2)),
+        };
+        filter.set_order(order);
+        filter
+    }
+
+    /// Change the slope in place. Sections that were already running keep
+    /// their state, so this is click-free while audio flows.
+    pub fn set_order(&mut self, order: usize) {
+        let order = order.clamp(2, Self::MAX_ORDER) & !1;
+        if order == self.order {
+            return;
+        }
+        for k in 0..order / 2 {
+            // Pole pair k of the Butterworth prototype.
+            let angle = PI * (2 * k + 1) as f32 / (2 * order) as f32;
+            let q = 1.0 / (2.0 * angle.cos());
+            let tuned = if self.high {
+                Biquad::highpass(self.sample_rate, self.cutoff, q)
+            } else {
+                Biquad::lowpass(self.sample_rate, self.cutoff, q)
+            };
+            if k < self.order / 2 {
+                self.sections[k].retune(tuned);
+            } else {
+                self.sections[k] = tuned;
+            }
+        }
+        self.order = order;
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.sections[..self.order / 2]
+            .iter_mut()
+            .fold(x, |y, section| section.process(y))
+    }
+
+    /// `process` over a block, one section at a time.
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for section in &mut self.sections[..self.order / 2] {
+            section.process_block(block);
+        }
+    }
+}
+
+/// Delay-line pitch shifter.
+///
+/// Two read taps sweep through a short window at a rate set by the pitch
+/// ratio and crossfade so one of them is always away from the wrap point.
+/// Formants move together with the pitch, but the latency is only half the
+/// window and it works at any callback size.
+pub struct PitchShifter {
+    buf: Vec<f32>,
+    mask: usize,
+    write: usize,
+    phase: f32,
+    window: f32,
+    ratio: f32,
+}
+
+impl PitchShifter {
+    pub fn new(sample_rate: f32, window_ms: f32) -> Self {
+        let window = (sample_rate * window_ms / 1000.0).max(16.0);
+        let len = (window as usize + 2).next_power_of_two();
+        Self {
+            buf: vec![0.0; len],
+            mask: len - 1,
+            write: 0,
+            phase: 0.0,
+            window,
+            ratio: 1.0,
+        }
+    }
+
+    pub fn set_semitones(&mut self, semitones: f32) {
+        self.ratio = 2f32.powf(semitones / 12.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.buf.fill(0.0);
+        self.phase = 0.0;
+    }
+
+    /// Read `delay` samples behind the newest written sample (linear interpolation).
+    fn tap(&self, delay: f32) -> f32 {
+        let whole = delay as usize;
+        let frac = delay - whole as f32;
+        let a = self.buf[self.write.wrapping_sub(whole) & self.mask];
+        let b = self.buf[self.write.wrapping_sub(whole + 1) & self.mask];
+        a + (b - a) * frac
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.buf[self.write] = x;
+
+        let phase_b = (self.phase + 0.5).fract();
+        // sin² and cos² of the same angle: the two gains always sum to 1.
+        let gain_a = (PI * self.phase).sin().powi(2);
+        let y = self.tap(self.phase * self.window) * gain_a
+            + self.tap(phase_b * self.window) * (1.0 - gain_a);
+
+        self.phase = (self.phase + (1.0 - self.ratio) / self.window).rem_euclid(1.0);
+        self.write = (self.write + 1) & self.mask;
+        y
+    }
+}
+
+/// Pitch-synchronous overlap-add (TD-PSOLA) shifter, for harmonies.
+///
+/// The voice's period is tracked by autocorrelation. Grains two periods
+/// long are cut one period apart and laid back down `ratio` times as
+/// often, so the pitch moves cleanly over intervals up to an octave where
+/// `PitchShifter`'s fixed window would comb. Works at any callback size,
+/// with a latency of two of the longest periods it tracks (~29 ms).
+pub struct PsolaShifter {
+    ratio: f32,
+    input: Vec<f32>,
+    output: Vec<f32>,
+    mask: usize,
+    /// Samples taken in so far.
+    n: usize,
+    latency: usize,
+    /// Tracked period in samples; `default_period` when unvoiced.
+    period: f32,
+    default_period: f32,
+    /// Newest analysis mark and the centre of the next grain out.
+    mark: f32,
+    next_grain: f32,
+    /// Input averaged down by `DECIMATE` for the pitch tracker, written
+    /// twice so the history is always one contiguous slice.
+    decimated: Vec<f32>,
+    dec_pos: usize,
+    dec_sum: f32,
+    lags: std::ops::RangeInclusive<usize>,
+    until_detect: usize,
+}
+
+impl PsolaShifter {
+    const MIN_HZ: f32 = 70.0;
+    const MAX_HZ: f32 = 1000.0;
+    /// Period used for unvoiced input (breath, consonants).
+    const DEFAULT_HZ: f32 = 200.0;
+    const DECIMATE: usize = 4;
+    const DETECT_EVERY: usize = 256;
+    /// Normalised autocorrelation below this is unvoiced.
+    const VOICED: f32 = 0.5;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let max_period = sample_rate / Self::MIN_HZ;
+        let latency = (2.0 * max_period).ceil() as usize + 2;
+        let len = (2 * latency + max_period as usize * 2).next_power_of_two();
+        let min_lag = ((sample_rate / Self::MAX_HZ) as usize / Self::DECIMATE).max(2);
+        let max_lag = (max_period as usize).div_ceil(Self::DECIMATE);
+        Self {
+            ratio: 1.0,
+            input: vec![0.0; len],
+            output: vec![0.0; len],
+            mask: len - 1,
+            n: 0,
+            latency,
+            period: sample_rate / Self::DEFAULT_HZ,
+            default_period: sample_rate / Self::DEFAULT_HZ,
+            mark: 0.0,
+            next_grain: 0.0,
+            // Two of the longest periods, plus room for the lag itself.
+            decimated: vec![0.0; 2 * 3 * max_lag],
+            dec_pos: 0,
+            dec_sum: 0.0,
+            lags: min_lag..=max_lag,
+            until_detect: Self::DETECT_EVERY,
+        }
+    }
+
+    pub fn set_semitones(&mut self, semitones: f32) {
+        self.ratio = 2f32.powf(semitones / 12.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.input.fill(0.0);
+        self.output.fill(0.0);
+        self.decimated.fill(0.0);
+        self.n = 0;
+        self.period = self.default_period;
+        self.mark = 0.0;
+        self.next_grain = 0.0;
+        self.dec_pos = 0;
+        self.dec_sum = 0.0;
+        self.until_detect = Self::DETECT_EVERY;
+    }
+
+    /// Strongest normalised autocorrelation lag of the decimated history,
+    /// taking the shortest lag near the top so it doesn't lock an octave low.
+    fn detect(&mut self) {
+        let len = self.decimated.len() / 2;
+        let history = &self.decimated[self.dec_pos..self.dec_pos + len];
+        let (min_lag, max_lag) = (*self.lags.start(), *self.lags.end());
+        let window = len - max_lag;
+        let dot = |a: &[f32], b: &[f32]| -> f32 { a.iter().zip(b).map(|(x, y)| x * y).sum() };
+        let head = &history[..window];
+        let energy0 = dot(head, head);
+        let mut corr = [0.0f32; 3];
+        let mut best: Option<(f32, f32)> = None;
+        let mut peak = 0.0f32;
+        for lag in min_lag..=max_lag {
+            let lagged = &history[lag..lag + window];
+            let normalised = dot(head, lagged) / (energy0 * dot(lagged, lagged)).sqrt().max(1e-9);
+            corr = [corr[1], corr[2], normalised];
+            peak = peak.max(normalised);
+            // corr[1] is a local maximum at lag - 1: refine it.
+            let [a, b, c] = corr;
+            if lag >= min_lag + 2 && b >= a && b >= c && best.is_none_or(|(_, v)| b > v * 1.15) {
+                let denom = a - 2.0 * b + c;
+                let shift = if denom.abs() > 1e-9 {
+                    0.5 * (a - c) / denom
+                } else {
+                    0.0
+                };
+                best = Some(((lag - 1) as f32 + shift, b));
+            }
+        }
+        self.period = match best {
+            Some((lag, v)) if v >= Self::VOICED && v >= 0.85 * peak => lag * Self::DECIMATE as f32,
+            _ => self.default_period,
+        };
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let n = self.n;
+        self.input[n & self.mask] = x;
+        self.n += 1;
+
+        self.dec_sum += x;
+        if self.n.is_multiple_of(Self::DECIMATE) {
+            let len = self.decimated.len() / 2;
+            let v = self.dec_sum / Self::DECIMATE as f32;
+            self.decimated[self.dec_pos] = v;
+            self.decimated[self.dec_pos + len] = v;
+            self.dec_pos = (self.dec_pos + 1) % len;
+            self.dec_sum = 0.0;
+        }
+        self.until_detect -= 1;
+        if self.until_detect == 0 {
+            self.until_detect = Self::DETECT_EVERY;
+            self.detect();
+        }
+
+        let period = self.period;
+        // Analysis marks one period apart, the newest with a whole grain in.
+        while self.mark + 2.0 * period <= n as f32 {
+            self.mark += period;
+        }
+        let Some(read) = n.checked_sub(self.latency) else {
+            return 0.0;
+        };
+
+        // Lay down every grain that starts by the sample about to go out.
+        let half = period.round() as usize;
+        let scale = 1.0 / self.ratio.max(1.0);
+        while self.next_grain - period <= read as f32 {
+            let back = ((self.mark - self.next_grain) / period).round().max(0.0);
+            let source = (self.mark - back * period).round() as usize;
+            let centre = self.next_grain.round() as usize;
+            for j in 0..=2 * half {
+                let out = (centre + j).wrapping_sub(half);
+                if out < read {
+                    continue;
+                }
+                let w = 0.5 + 0.5 * (PI * (j as f32 - half as f32) / half as f32).cos();
+                let sample = self.input[(source + j).wrapping_sub(half) & self.mask];
+                self.output[out & self.mask] += sample * w * scale;
+            }
+            self.next_grain += period / self.ratio;
+        }
+
+        let y = self.output[read & self.mask];
+        self.output[read & self.mask] = 0.0;
+        y
+    }
+}
+
+/// Character voices for the voice changer, as (pitch, formant) shifts in
+/// semitones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoicePreset {
+    Deep,
+    Chipmunk,
+    Monster,
+}
+
+impl VoicePreset {
+    pub const ALL: [VoicePreset; 3] = [
+        VoicePreset::Deep,
+        VoicePreset::Chipmunk,
+        VoicePreset::Monster,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VoicePreset::Deep => "DEEP",
+            VoicePreset::Chipmunk => "CHIPMUNK",
+            VoicePreset::Monster => "MONSTER",
+        }
+    }
+
+    pub fn shifts(self) -> (f32, f32) {
+        match self {
+            VoicePreset::Deep => (-5.0, -3.0),
+            VoicePreset::Chipmunk => (9.0, 5.0),
+            VoicePreset::Monster => (-12.0, -6.0),
+        }
+    }
+}
+
+/// Voice changer with pitch and formants (the vocal tract's resonances,
+/// what makes a voice sound big or small) shifted independently.
+///
+/// `PsolaShifter` moves the pitch and keeps the formants; `PitchShifter`
+/// then moves both by the formant shift. So the PSOLA stage takes the
+/// pitch shift minus the formant shift, and the two add up to the pitch
+/// asked for.
+pub struct VoiceChanger {
+    pitch: PsolaShifter,
+    formant: PitchShifter,
+}
+
+impl VoiceChanger {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            pitch: PsolaShifter::new(sample_rate),
+            formant: PitchShifter::new(sample_rate, 30.0),
+        }
+    }
+
+    pub fn set(&mut self, pitch_semitones: f32, formant_semitones: f32) {
+        self.pitch
+            .set_semitones(pitch_semitones - formant_semitones);
+        self.formant.set_semitones(formant_semitones);
+    }
+
+    pub fn reset(&mut self) {
+        self.pitch.reset();
+        self.formant.reset();
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.formant.process(self.pitch.process(x))
+    }
+}
+
+/// Robot voice: a ring modulator's metallic wobble, or a channel vocoder
+/// that speaks through a steady buzz.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RobotMode {
+    Ring,
+    Vocoder,
+}
+
+impl RobotMode {
+    pub const ALL: [RobotMode; 2] = [RobotMode::Ring, RobotMode::Vocoder];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RobotMode::Ring => "RING",
+            RobotMode::Vocoder => "VOCODER",
+        }
+    }
+}
+
+/// One vocoder band: the voice's level in it, imposed on the buzz's.
+struct VocoderBand {
+    voice: Biquad,
+    buzz: Biquad,
+    voice_env: f32,
+    buzz_env: f32,
+}
+
+/// Robot voices driven by an internal oscillator at `pitch_hz`.
+///
+/// The ring modulator multiplies the voice by a sine, which moves every
+/// partial up and down by the pitch. The vocoder splits the voice into
+/// bands, follows each band's level and plays a sawtooth through the same
+/// bands at those levels, so the words come out on one note. Each band of
+/// the buzz is levelled on its own first, so the saw's falling spectrum
+/// doesn't dull the speech.
+pub struct Robot {
+    sample_rate: f32,
+    mode: RobotMode,
+    /// Oscillator phase in cycles and its step per sample.
+    phase: f32,
+    step: f32,
+    bands: Vec<VocoderBand>,
+    /// Per-sample coefficient of the band envelope followers.
+    env: f32,
+}
+
+impl Robot {
+    const BANDS: usize = 16;
+    const LOW_HZ: f32 = 100.0;
+    const HIGH_HZ: f32 = 7000.0;
+    const ENV_MS: f32 = 10.0;
+
+    pub fn new(sample_rate: f32, mode: RobotMode, pitch_hz: f32) -> Self {
+        let high = Self::HIGH_HZ.min(0.4 * sample_rate);
+        let ratio = (high / Self::LOW_HZ).powf(1.0 / (Self::BANDS - 1) as f32);
+        // Neighbouring bands cross about 3 dB down.
+        let q = ratio.sqrt() / (ratio - 1.0);
+        let bands = (0..Self::BANDS)
+            .map(|i| {
+                let freq = Self::LOW_HZ * ratio.powi(i as i32);
+                VocoderBand {
+                    voice: Biquad::bandpass(sample_rate, freq, q),
+                    buzz: Biquad::bandpass(sample_rate, freq, q),
+                    voice_env: 0.0,
+                    buzz_env: 0.0,
+                }
+            })
+            .collect();
+        let mut robot = Self {
+            sample_rate,
+            mode,
+            phase: 0.0,
+            step: 0.0,
+            bands,
+            env: 1.0 - (-1.0 / (Self::ENV_MS * 0.001 * sample_rate)).exp(),
+        };
+        robot.set(mode, pitch_hz);
+        robot
+    }
+
+    pub fn set(&mut self, mode: RobotMode, pitch_hz: f32) {
+        self.mode = mode;
+        self.step = pitch_hz.clamp(20.0, 1000.0) / self.sample_rate;
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let phase = self.phase;
+        self.phase = (self.phase + self.step).fract();
+        match self.mode {
+            RobotMode::Ring => x * (2.0 * PI * phase).sin(),
+            RobotMode::Vocoder => {
+                // Sawtooth with a polyBLEP at the wrap, which keeps the
+                // upper bands free of aliasing.
+                let mut buzz = 2.0 * phase - 1.0;
+                let t = self.step;
+                if phase < t {
+                    let p = phase / t;
+                    buzz -= p + p - p * p - 1.0;
+                } else if phase > 1.0 - t {
+                    let p = (phase - 1.0) / t;
+                    buzz -= p * p + p + p + 1.0;
+                }
+                let env = self.env;
+                self.bands
+                    .iter_mut()
+                    .map(|band| {
+                        let voice = band.voice.process(x);
+                        let carrier = band.buzz.process(buzz);
+                        band.voice_env += (voice.abs() - band.voice_env) * env;
+                        band.buzz_env += (carrier.abs() - band.buzz_env) * env;
+                        carrier * band.voice_env / band.buzz_env.max(1e-4)
+                    })
+                    .sum::<f32>()
+                    // Neighbouring bands overlap, so each partial of the
+                    // voice is heard about twice.
+                    * 0.5
+            }
+        }
+    }
+}
+
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-9).log10()
+}
+
+/// Multiply `block` by `gain`.
+pub fn scale(block: &mut [f32], gain: f32) {
+    for s in block {
+        *s *= gain;
+    }
+}
+
+/// Blend `wet` of `block` with `dry`, which is as long.
+pub fn mix(block: &mut [f32], dry: &[f32], wet: f32) {
+    for (s, &d) in block.iter_mut().zip(dry) {
+        *s = d + (*s - d) * wet;
+    }
+}
+
+/// Zero for values too small to hear. Recursive state decaying towards
+/// silence goes denormal otherwise, and denormals are many times slower
+/// on most CPUs. Backs up `FlushToZero` where the CPU has no such mode.
+#[inline]
+pub fn flush_denormal(x: f32) -> f32 {
+    if x.abs() < 1e-20 {
+        0.0
+    } else {
+        x
+    }
+}
+
+/// Puts the calling thread's FPU in flush-to-zero mode until dropped, so
+/// denormals become zero in hardware: FTZ and DAZ on x86_64, FZ on
+/// aarch64, nothing elsewhere. Held for the length of each audio callback.
+pub struct FlushToZero {
+    #[cfg_attr(
+        not(any(target_arch = "x86_64", target_arch = "aarch64")),
+        allow(dead_code)
+    )]
+    saved: u64,
+}
+
+impl FlushToZero {
+    pub fn new() -> Self {
+        let saved = fp_mode::read();
+        fp_mode::write(saved | fp_mode::FLUSH);
+        Self { saved }
+    }
+}
+
+impl Default for FlushToZero {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for FlushToZero {
+    fn drop(&mut self) {
+        fp_mode::write(self.saved);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod fp_mode {
+    use std::arch::asm;
+
+    /// MXCSR flush-to-zero (bit 15) and denormals-are-zero (bit 6).
+    pub const FLUSH: u64 = 0x8040;
+
+    pub fn read() -> u64 {
+        let mut csr = 0u32;
+        unsafe { asm!("stmxcsr [{}]", in(reg) &mut csr, options(nostack, preserves_flags)) };
+        csr as u64
+    }
+
+    pub fn write(mode: u64) {
+        let csr = mode as u32;
+        unsafe { asm!("ldmxcsr [{}]", in(reg) &csr, options(nostack, preserves_flags)) };
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod fp_mode {
+    use std::arch::asm;
+
+    /// FPCR flush-to-zero (bit 24).
+    pub const FLUSH: u64 = 1 << 24;
+
+    pub fn read() -> u64 {
+        let fpcr: u64;
+        unsafe { asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack, preserves_flags)) };
+        fpcr
+    }
+
+    pub fn write(mode: u64) {
+        unsafe { asm!("msr fpcr, {}", in(reg) mode, options(nomem, nostack, preserves_flags)) };
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod fp_mode {
+    pub const FLUSH: u64 = 0;
+
+    pub fn read() -> u64 {
+        0
+    }
+
+    pub fn write(_mode: u64) {}
+}
+
+/// One-pole glide from a parameter's last value to its latest, so a
+/// setting moved in the GUI doesn't step the signal (zipper noise). The
+/// first value is taken as it is.
+pub struct Smoother {
+    coef: f32,
+    value: Option<f32>,
+}
+
+impl Smoother {
+    pub fn new(sample_rate: f32, time_ms: f32) -> Self {
+        Self {
+            coef: 1.0 - (-1.0 / (time_ms.max(0.01) * 0.001 * sample_rate)).exp(),
+            value: None,
+        }
+    }
+
+    /// One sample closer to `target`.
+    pub fn next(&mut self, target: f32) -> f32 {
+        self.step(target, self.coef)
+    }
+
+    /// Whether the glide has arrived at `target`.
+    pub fn settled(&self, target: f32) -> bool {
+        self.value == Some(target)
+    }
+
+    /// Multiply `block` by the gain gliding to `target`.
+    pub fn apply(&mut self, block: &mut [f32], target: f32) {
+        if self.settled(target) {
+            scale(block, target);
+        } else {
+            for s in block.iter_mut() {
+                *s *= self.next(target);
+            }
+        }
+    }
+
+    /// `len` samples closer to `target` at once, for parameters read once
+    /// a block.
+    pub fn block(&mut self, target: f32, len: usize) -> f32 {
+        self.step(target, 1.0 - (1.0 - self.coef).powi(len as i32))
+    }
+
+    fn step(&mut self, target: f32, coef: f32) -> f32 {
+        let value = self.value.map_or(target, |v| {
+            let v = v + (target - v) * coef;
+            // Land on the target exactly, so settings cached downstream
+            // stop changing.
+            if (target - v).abs() < 1e-4 {
+                target
+            } else {
+                v
+            }
+        });
+        self.value = Some(value);
+        value
+    }
+}
+
+/// Second-order IIR section (RBJ cookbook coefficients, transposed direct form II).
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    fn from_coeffs(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn lowpass(sample_rate: f32, freq: f32, q: f32) -> Self {
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::from_coeffs(
+            (1.0 - cos) / 2.0,
+            1.0 - cos,
+            (1.0 - cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    pub fn highpass(sample_rate: f32, freq: f32, q: f32) -> Self {
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::from_coeffs(
+            (1.0 + cos) / 2.0,
+            -(1.0 + cos),
+            (1.0 + cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    /// Bell boost/cut of `gain_db` at `freq`.
+    pub fn peaking(sample_rate: f32, freq: f32, gain_db: f32, q: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::from_coeffs(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        )
+    }
+
+    /// Shelf of `gain_db` below `freq`.
+    pub fn low_shelf(sample_rate: f32, freq: f32, gain_db: f32, q: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let k = 2.0 * a.sqrt() * sin / (2.0 * q);
+        Self::from_coeffs(
+            a * ((a + 1.0) - (a - 1.0) * cos + k),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - k),
+            (a + 1.0) + (a - 1.0) * cos + k,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - k,
+        )
+    }
+
+    /// Shelf of `gain_db` above `freq`.
+    pub fn high_shelf(sample_rate: f32, freq: f32, gain_db: f32, q: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let k = 2.0 * a.sqrt() * sin / (2.0 * q);
+        Self::from_coeffs(
+            a * ((a + 1.0) + (a - 1.0) * cos + k),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - k),
+            (a + 1.0) - (a - 1.0) * cos + k,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - k,
+        )
+    }
+
+    /// RBJ band-pass with 0 dB at `freq`, bandwidth `freq / q`.
+    pub fn bandpass(sample_rate: f32, freq: f32, q: f32) -> Self {
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::from_coeffs(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    /// RBJ notch: full cut at `freq`, bandwidth `freq / q`.
+    pub fn notch(sample_rate: f32, freq: f32, q: f32) -> Self {
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::from_coeffs(1.0, -2.0 * cos, 1.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    /// Take `to`'s coefficients but keep this filter's state, so a running
+    /// filter can be retuned without a click.
+    pub fn retune(&mut self, to: Biquad) {
+        *self = Self {
+            z1: self.z1,
+            z2: self.z2,
+            ..to
+        };
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = flush_denormal(self.b1 * x - self.a1 * y + self.z2);
+        self.z2 = flush_denormal(self.b2 * x - self.a2 * y);
+        y
+    }
+
+    /// `process` over a block, with the state kept in registers and
+    /// flushed of denormals once at the end.
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        let (b0, b1, b2, a1, a2) = (self.b0, self.b1, self.b2, self.a1, self.a2);
+        let (mut z1, mut z2) = (self.z1, self.z2);
+        for s in block.iter_mut() {
+            let x = *s;
+            let y = b0 * x + z1;
+            z1 = b1 * x - a1 * y + z2;
+            z2 = b2 * x - a2 * y;
+            *s = y;
+        }
+        self.z1 = flush_denormal(z1);
+        self.z2 = flush_denormal(z2);
+    }
+}
+
+/// Feed-forward peak compressor with a smoothed envelope.
+pub struct Compressor {
+    sample_rate: f32,
+    threshold_db: f32,
+    ratio: f32,
+    attack: f32,
+    release: f32,
+    makeup: f32,
+    env: f32,
+}
+
+impl Compressor {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut c = Self {
+            sample_rate,
+            threshold_db: 0.0,
+            ratio: 1.0,
+            attack: 0.0,
+            release: 0.0,
+            makeup: 1.0,
+            env: 0.0,
+        };
+        c.set(-20.0, 3.0, 5.0, 80.0, 0.0);
+        c
+    }
+
+    pub fn set(
+        &mut self,
+        threshold_db: f32,
+        ratio: f32,
+        attack_ms: f32,
+        release_ms: f32,
+        makeup_db: f32,
+    ) {
+        let coef = |ms: f32| (-1.0 / (ms.max(0.01) * 0.001 * self.sample_rate)).exp();
+        self.threshold_db = threshold_db;
+        self.ratio = ratio.max(1.0);
+        self.attack = coef(attack_ms);
+        self.release = coef(release_ms);
+        self.makeup = db_to_gain(makeup_db);
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let level = x.abs();
+        let coef = if level > self.env {
+            self.attack
+        } else {
+            self.release
+        };
+        self.env = flush_denormal(coef * self.env + (1.0 - coef) * level);
+
+        let over = gain_to_db(self.env) - self.threshold_db;
+        let gain = if over > 0.0 {
+            db_to_gain(-over * (1.0 - 1.0 / self.ratio))
+        } else {
+            1.0
+        };
+        x * gain * self.makeup
+    }
+
+    /// Gain reduction at the current envelope, in dB (0 or positive).
+    pub fn reduction_db(&self) -> f32 {
+        let over = gain_to_db(self.env) - self.threshold_db;
+        over.max(0.0) * (1.0 - 1.0 / self.ratio)
+    }
+}
+
+/// What the gate stage does to audio below its threshold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GateMode {
+    /// Cut it to silence.
+    Gate,
+    /// Turn it down by a ratio, no further than a set range.
+    Expander,
+}
+
+impl GateMode {
+    pub const ALL: [GateMode; 2] = [GateMode::Gate, GateMode::Expander];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GateMode::Gate => "GATE",
+            GateMode::Expander => "EXPAND",
+        }
+    }
+}
+
+/// What a key held or pressed does to the gate, for a host who decides
+/// when a guest's mic is heard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GateTrigger {
+    Off,
+    /// Each press forces the gate open, or hands it back.
+    ForceOpen,
+    /// Each press shuts the mic, or hands it back.
+    ForceShut,
+    /// The mic is heard only while the key is held.
+    Momentary,
+}
+
+impl GateTrigger {
+    pub const ALL: [GateTrigger; 4] = [
+        GateTrigger::Off,
+        GateTrigger::ForceOpen,
+        GateTrigger::ForceShut,
+        GateTrigger::Momentary,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GateTrigger::Off => "OFF",
+            GateTrigger::ForceOpen => "FORCE OPEN",
+            GateTrigger::ForceShut => "FORCE SHUT",
+            GateTrigger::Momentary => "MOMENTARY",
+        }
+    }
+
+    /// The gate as forced by a key that is `held` and has been pressed
+    /// an odd number of times (`latched`); `None` leaves it alone.
+    pub fn forced(self, held: bool, latched: bool) -> Option<bool> {
+        match self {
+            GateTrigger::Off => None,
+            GateTrigger::ForceOpen => latched.then_some(true),
+            GateTrigger::ForceShut => latched.then_some(false),
+            GateTrigger::Momentary => Some(held),
+        }
+    }
+}
+
+/// Downward expander: below the threshold, each dB the level falls takes
+/// `ratio` dB off the output, but never more than `range_db` in all. Room
+/// tone and breaths get quieter instead of dropping out.
+pub struct Expander {
+    threshold_db: f32,
+    ratio: f32,
+    range_db: f32,
+    attack: f32,
+    release: f32,
+    env: f32,
+}
+
+impl Expander {
+    const ATTACK_MS: f32 = 1.0;
+    /// Slow enough to ride over the gaps between syllables.
+    const RELEASE_MS: f32 = 150.0;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let coef = |ms: f32| (-1.0 / (ms * 0.001 * sample_rate)).exp();
+        Self {
+            threshold_db: -36.0,
+            ratio: 2.0,
+            range_db: 20.0,
+            attack: coef(Self::ATTACK_MS),
+            release: coef(Self::RELEASE_MS),
+            env: 0.0,
+        }
+    }
+
+    pub fn set(&mut self, threshold_db: f32, ratio: f32, range_db: f32) {
+        self.threshold_db = threshold_db;
+        self.ratio = ratio.max(1.0);
+        self.range_db = range_db.max(0.0);
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let level = x.abs();
+        let coef = if level > self.env {
+            self.attack
+        } else {
+            self.release
+        };
+        self.env = flush_denormal(coef * self.env + (1.0 - coef) * level);
+        x * db_to_gain(-self.reduction_db())
+    }
+
+    /// Gain reduction at the current envelope, in dB (0 or positive).
+    pub fn reduction_db(&self) -> f32 {
+        let under = self.threshold_db - gain_to_db(self.env);
+        (under * (self.ratio - 1.0)).clamp(0.0, self.range_db)
+    }
+}
+
+/// Mains hum remover: narrow notches on the mains frequency and its first
+/// few overtones, the part of hum a 100 Hz high-pass leaves in place.
+pub struct HumFilter {
+    sample_rate: f32,
+    notches: [Biquad; 1 + Self::MAX_OVERTONES],
+    active: usize,
+    /// (mains Hz, overtones) the notches are tuned to.
+    tuned: (u32, usize),
+}
+
+impl HumFilter {
+    pub const MAX_OVERTONES: usize = 4;
+    /// Narrow enough to leave the voice alone: 5 Hz wide at 50 Hz.
+    const Q: f32 = 10.0;
+
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            notches: std::array::from_fn(|_| Biquad::notch(sample_rate, 50.0, Self::Q)),
+            active: 0,
+            tuned: (0, 0),
+        }
+    }
+
+    /// Notch `mains_hz` (50 or 60) and `overtones` multiples above it.
+    /// Notches keep their state, so switching is click-free.
+    pub fn set(&mut self, mains_hz: u32, overtones: usize) {
+        let overtones = overtones.min(Self::MAX_OVERTONES);
+        if (mains_hz, overtones) == self.tuned {
+            return;
+        }
+        self.tuned = (mains_hz, overtones);
+        self.active = 0;
+        for k in 1..=1 + overtones {
+            let freq = (mains_hz as usize * k) as f32;
+            if freq >= self.sample_rate * 0.45 {
+                break;
+            }
+            self.notches[k - 1].retune(Biquad::notch(self.sample_rate, freq, Self::Q));
+            self.active = k;
+        }
+    }
+
+    /// Run the active notches over a block, one at a time.
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for notch in &mut self.notches[..self.active] {
+            notch.process_block(block);
+        }
+    }
+}
+
+/// Automatic gain control: slowly rides the gain so speech sits at a
+/// target RMS level, e.g. when moving toward or away from the mic. Gain
+/// only moves while there's signal above the floor, so pauses don't get
+/// pumped up, and it falls faster than it rises.
+pub struct Agc {
+    target: f32,
+    max_gain: f32,
+    power: f32,
+    power_coef: f32,
+    rise: f32,
+    fall: f32,
+    gain: f32,
+}
+
+impl Agc {
+    const WINDOW_MS: f32 = 300.0;
+    const RISE_MS: f32 = 3000.0;
+    const FALL_MS: f32 = 500.0;
+    /// RMS below this is a pause, not quiet speech.
+    const FLOOR_DB: f32 = -55.0;
+
+    pub fn new(sample_rate: f32, target_db: f32, max_gain_db: f32) -> Self {
+        let coef = |ms: f32| 1.0 - (-1.0 / (ms * 0.001 * sample_rate)).exp();
+        let mut agc = Self {
+            target: 1.0,
+            max_gain: 1.0,
+            power: 0.0,
+            power_coef: coef(Self::WINDOW_MS),
+            rise: coef(Self::RISE_MS),
+            fall: coef(Self::FALL_MS),
+            gain: 1.0,
+        };
+        agc.set(target_db, max_gain_db);
+        agc
+    }
+
+    /// Target RMS in dBFS and the most gain (and cut) it may apply.
+    pub fn set(&mut self, target_db: f32, max_gain_db: f32) {
+        self.target = db_to_gain(target_db);
+        self.max_gain = db_to_gain(max_gain_db.max(0.0));
+        self.gain = self.gain.clamp(1.0 / self.max_gain, self.max_gain);
+    }
+
+    pub fn gain_db(&self) -> f32 {
+        gain_to_db(self.gain)
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.power = flush_denormal(self.power + (x * x - self.power) * self.power_coef);
+        let rms = self.power.sqrt();
+        if rms > db_to_gain(Self::FLOOR_DB) {
+            let wanted = (self.target / rms).clamp(1.0 / self.max_gain, self.max_gain);
+            let coef = if wanted < self.gain {
+                self.fall
+            } else {
+                self.rise
+            };
+            self.gain += (wanted - self.gain) * coef;
+        }
+        x * self.gain
+    }
+}
+
+/// Lookahead brickwall limiter: the output never goes above the ceiling.
+///
+/// Each sample's required gain is held for the lookahead window and
+/// smoothed with a box filter of the same length, with the signal delayed
+/// to match, so gain is already down by the time a peak arrives and
+/// there are no steps in it. Recovery is exponential.
+pub struct Limiter {
+    ceiling: f32,
+    release: f32,
+    /// Delayed input and required gains, both `lookahead` long.
+    delay: Vec<f32>,
+    required: Vec<f32>,
+    /// Held-and-released gains and their running sum (the box filter).
+    held: Vec<f32>,
+    held_sum: f32,
+    env: f32,
+    pos: usize,
+    gain: f32,
+}
+
+impl Limiter {
+    const LOOKAHEAD_MS: f32 = 1.0;
+    const RELEASE_MS: f32 = 60.0;
+
+    pub fn new(sample_rate: f32, ceiling_db: f32) -> Self {
+        let len = (Self::LOOKAHEAD_MS * 0.001 * sample_rate).round().max(1.0) as usize;
+        let mut limiter = Self {
+            ceiling: 1.0,
+            release: 1.0 - (-1.0 / (Self::RELEASE_MS * 0.001 * sample_rate)).exp(),
+            delay: vec![0.0; len],
+            required: vec![1.0; len],
+            held: vec![1.0; len],
+            held_sum: len as f32,
+            env: 1.0,
+            pos: 0,
+            gain: 1.0,
+        };
+        limiter.set_ceiling(ceiling_db);
+        limiter
+    }
+
+    pub fn set_ceiling(&mut self, ceiling_db: f32) {
+        self.ceiling = db_to_gain(ceiling_db.min(0.0));
+    }
+
+    /// How many samples late the output is: the lookahead.
+    pub fn latency(&self) -> usize {
+        self.delay.len()
+    }
+
+    /// Gain reduction on the sample last output, in dB (0 or positive).
+    pub fn reduction_db(&self) -> f32 {
+        -gain_to_db(self.gain)
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let len = self.delay.len();
+        let need = if x.abs() > self.ceiling {
+            self.ceiling / x.abs()
+        } else {
+            1.0
+        };
+        self.required[self.pos] = need;
+        let hold = self.required.iter().fold(1.0f32, |m, &g| m.min(g));
+        self.env = hold.min(self.env + (1.0 - self.env) * self.release);
+        self.held_sum += self.env - self.held[self.pos];
+        self.held[self.pos] = self.env;
+        self.gain = (self.held_sum / len as f32).min(1.0);
+
+        // The oldest sample in the delay line is one window, less one, behind `x`.
+        self.delay[self.pos] = x;
+        self.pos = (self.pos + 1) % len;
+        if self.pos == 0 {
+            // Re-sum once per lap so rounding can't build up over a session.
+            self.held_sum = self.held.iter().sum();
+        }
+        let out = self.delay[self.pos] * self.gain;
+        // Float rounding in the running sum must not let a peak through.
+        out.clamp(-self.ceiling, self.ceiling)
+    }
+}
+
+/// Tube-style soft saturation: a biased tanh curve run at `OVERSAMPLE`
+/// times the sample rate, so the harmonics it adds above Nyquist are
+/// filtered out instead of folding back down as aliasing. The bias makes
+/// the curve lopsided like a triode, adding even harmonics as well as odd
+/// ones; the DC that comes with it is blocked afterwards.
+///
+/// Drive pushes the signal into the curve and is compensated for quiet
+/// audio, so it changes the colour rather than the level. Tone is a high
+/// shelf on the result, for brightening a thin mic or taking the edge off;
+/// trim sets the level.
+pub struct Saturator {
+    sample_rate: f32,
+    drive: f32,
+    tone_db: f32,
+    trim: f32,
+    tone: Biquad,
+    /// DC blocker: last input and output.
+    dc: (f32, f32),
+    dc_pole: f32,
+    /// Interpolation and decimation filters at the oversampled rate, each
+    /// two Butterworths deep: the images of a high note land only an
+    /// octave or so above the cutoff.
+    up: [Butterworth; 2],
+    down: [Butterworth; 2],
+}
+
+impl Saturator {
+    pub const OVERSAMPLE: usize = 4;
+    /// Offset into the curve: gentle, the 2nd harmonic stays below the 3rd.
+    const BIAS: f32 = 0.25;
+    const TONE_HZ: f32 = 3000.0;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let rate = sample_rate * Self::OVERSAMPLE as f32;
+        let cutoff = (0.45 * sample_rate).min(20_000.0);
+        let filter = || Butterworth::lowpass(rate, cutoff, Butterworth::MAX_ORDER);
+        let mut saturator = Self {
+            sample_rate,
+            drive: 1.0,
+            tone_db: 0.0,
+            trim: 1.0,
+            tone: Biquad::high_shelf(sample_rate, Self::TONE_HZ, 0.0, FRAC_1_SQRT_2),
+            dc: (0.0, 0.0),
+            dc_pole: (-2.0 * PI * 10.0 / sample_rate).exp(),
+            up: [filter(), filter()],
+            down: [filter(), filter()],
+        };
+        saturator.set(6.0, 0.0, 0.0);
+        saturator
+    }
+
+    /// Drive into the curve, high-shelf tone and output trim, all in dB.
+    pub fn set(&mut self, drive_db: f32, tone_db: f32, trim_db: f32) {
+        self.drive = db_to_gain(drive_db.max(0.0));
+        self.trim = db_to_gain(trim_db);
+        if tone_db != self.tone_db {
+            self.tone_db = tone_db;
+            self.tone.retune(Biquad::high_shelf(
+                self.sample_rate,
+                Self::TONE_HZ,
+                tone_db,
+                FRAC_1_SQRT_2,
+            ));
+        }
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let rest = Self::BIAS.tanh();
+        // Slope of the curve at rest, so quiet audio comes out unchanged.
+        let slope = self.drive * (1.0 - rest * rest);
+        let mut y = 0.0;
+        for i in 0..Self::OVERSAMPLE {
+            // Zero-stuffed, scaled back up for the energy the zeros take out.
+            let stuffed = if i == 0 {
+                x * Self::OVERSAMPLE as f32
+            } else {
+                0.0
+            };
+            let up = self.up.iter_mut().fold(stuffed, |x, f| f.process(x));
+            let shaped = ((up * self.drive + Self::BIAS).tanh() - rest) / slope;
+            y = self.down.iter_mut().fold(shaped, |x, f| f.process(x));
+        }
+        let (last_in, last_out) = self.dc;
+        let blocked = y - last_in + self.dc_pole * last_out;
+        self.dc = (y, blocked);
+        self.tone.process(blocked) * self.trim
+    }
+}
+
+/// What the far end of a call roughly hears.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallSim {
+    Off,
+    Voip,
+    Phone,
+}
+
+impl CallSim {
+    pub const ALL: [CallSim; 3] = [CallSim::Off, CallSim::Voip, CallSim::Phone];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CallSim::Off => "OFF",
+            CallSim::Voip => "VOIP",
+            CallSim::Phone => "PHONE",
+        }
+    }
+}
+
+/// Band-limit, compress and degrade the signal the way a voice call would:
+/// codec bandwidth, call-app AGC-style compression, then sample-and-hold at
+/// the codec rate plus mu-law quantisation for the "low bitrate" grit.
+pub struct TelephoneSim {
+    sample_rate: f32,
+    hp: Biquad,
+    lp: Biquad,
+    comp: Compressor,
+    hold_step: f32,
+    hold_count: f32,
+    held: f32,
+    levels: f32,
+}
+
+impl TelephoneSim {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut sim = Self {
+            sample_rate,
+            hp: Biquad::highpass(sample_rate, 80.0, 0.707),
+            lp: Biquad::lowpass(sample_rate, 7000.0, 0.707),
+            comp: Compressor::new(sample_rate),
+            hold_step: 1.0,
+            hold_count: 0.0,
+            held: 0.0,
+            levels: 2048.0,
+        };
+        sim.set_mode(CallSim::Voip);
+        sim
+    }
+
+    pub fn set_mode(&mut self, mode: CallSim) {
+        // (low cut, high cut, codec rate, bits)
+        let (lo, hi, rate, bits) = match mode {
+            CallSim::Phone => (300.0, 3400.0, 8000.0, 8),
+            CallSim::Off | CallSim::Voip => (80.0, 7000.0, 16000.0, 12),
+        };
+        self.hp = Biquad::highpass(self.sample_rate, lo, 0.707);
+        self.lp = Biquad::lowpass(self.sample_rate, hi, 0.707);
+        self.hold_step = (self.sample_rate / rate).max(1.0);
+        self.hold_count = 0.0;
+        self.levels = (1u32 << (bits - 1)) as f32;
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let band = self.lp.process(self.hp.process(x));
+        let y = self.comp.process(band);
+
+        self.hold_count += 1.0;
+        if self.hold_count >= self.hold_step {
+            self.hold_count -= self.hold_step;
+            self.held = mu_law_quantize(y, self.levels);
+        }
+        self.held
+    }
+}
+
+fn mu_law_quantize(x: f32, levels: f32) -> f32 {
+    const MU: f32 = 255.0;
+    let x = x.clamp(-1.0, 1.0);
+    let compressed = x.signum() * (1.0 + MU * x.abs()).ln() / (1.0 + MU).ln();
+    let q = (compressed * levels).round() / levels;
+    q.signum() * ((1.0 + MU).powf(q.abs()) - 1.0) / MU
+}
+
+/// Reverb character: a small room, or a bright, dense plate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReverbKind {
+    Room,
+    Plate,
+}
+
+impl ReverbKind {
+    pub const ALL: [ReverbKind; 2] = [ReverbKind::Room, ReverbKind::Plate];
+
+    pub fn from_u32(v: u32) -> Self {
+        match v {
+            1 => ReverbKind::Plate,
+            _ => ReverbKind::Room,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReverbKind::Room => "ROOM",
+            ReverbKind::Plate => "PLATE",
+        }
+    }
+
+    /// (comb delays ms, allpass delays ms, comb damping)
+    fn tuning(self) -> ([f32; 4], [f32; 2], f32) {
+        match self {
+            ReverbKind::Room => ([25.3, 26.9, 29.0, 30.7], [12.6, 10.0], 0.4),
+            ReverbKind::Plate => ([17.1, 19.3, 21.7, 23.9], [5.1, 3.7], 0.1),
+        }
+    }
+}
+
+/// Feedback delay line with a one-pole low-pass in the loop, so highs die
+/// away faster than lows.
+struct Comb {
+    buf: Vec<f32>,
+    len: usize,
+    pos: usize,
+    feedback: f32,
+    damp: f32,
+    filtered: f32,
+}
+
+impl Comb {
+    fn process(&mut self, x: f32) -> f32 {
+        let out = self.buf[self.pos];
+        self.filtered = flush_denormal(out * (1.0 - self.damp) + self.filtered * self.damp);
+        self.buf[self.pos] = flush_denormal(x + self.filtered * self.feedback);
+        self.pos = (self.pos + 1) % self.len;
+        out
+    }
+}
+
+/// Schroeder allpass: smears the comb echoes into a smooth tail.
+struct Allpass {
+    buf: Vec<f32>,
+    len: usize,
+    pos: usize,
+}
+
+impl Allpass {
+    fn process(&mut self, x: f32) -> f32 {
+        let delayed = self.buf[self.pos];
+        self.buf[self.pos] = flush_denormal(x + delayed * 0.5);
+        self.pos = (self.pos + 1) % self.len;
+        delayed - x
+    }
+}
+
+/// Lightweight Schroeder/Freeverb-style reverb for the monitor mix: four
+/// damped combs in parallel into two allpasses in series. Buffers are
+/// sized for the longest tuning up front, so switching kind or decay
+/// never allocates.
+pub struct Reverb {
+    sample_rate: f32,
+    kind: ReverbKind,
+    decay_s: f32,
+    mix: f32,
+    combs: [Comb; 4],
+    allpasses: [Allpass; 2],
+}
+
+impl Reverb {
+    pub fn new(sample_rate: f32, kind: ReverbKind, decay_s: f32, mix: f32) -> Self {
+        let line = |ms: f32| vec![0.0; (ms * 0.001 * sample_rate).ceil() as usize + 1];
+        let (room, plate) = (ReverbKind::Room.tuning(), ReverbKind::Plate.tuning());
+        let comb = |i: usize| Comb {
+            buf: line(room.0[i].max(plate.0[i])),
+            len: 1,
+            pos: 0,
+            feedback: 0.0,
+            damp: 0.0,
+            filtered: 0.0,
+        };
+        let allpass = |i: usize| Allpass {
+            buf: line(room.1[i].max(plate.1[i])),
+            len: 1,
+            pos: 0,
+        };
+        let mut reverb = Self {
+            sample_rate,
+            kind,
+            decay_s,
+            mix: 0.0,
+            combs: [comb(0), comb(1), comb(2), comb(3)],
+            allpasses: [allpass(0), allpass(1)],
+        };
+        reverb.tune();
+        reverb.set(kind, decay_s, mix);
+        reverb
+    }
+
+    /// Algorithm, RT60 decay time in seconds, and wet/dry mix (0 = dry).
+    /// A new kind starts from silence; decay and mix carry the tail on.
+    pub fn set(&mut self, kind: ReverbKind, decay_s: f32, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+        if kind != self.kind || decay_s != self.decay_s {
+            let new_kind = kind != self.kind;
+            self.kind = kind;
+            self.decay_s = decay_s;
+            self.tune();
+            if new_kind {
+                self.reset();
+            }
+        }
+    }
+
+    fn tune(&mut self) {
+        let (comb_ms, allpass_ms, damp) = self.kind.tuning();
+        let samples = |ms: f32| ((ms * 0.001 * self.sample_rate) as usize).max(1);
+        let decay_s = self.decay_s.max(0.05);
+        for (comb, &ms) in self.combs.iter_mut().zip(&comb_ms) {
+            comb.len = samples(ms);
+            comb.pos %= comb.len;
+            comb.damp = damp;
+            // -60 dB after `decay_s`, one pass through the loop at a time.
+            comb.feedback = db_to_gain(-60.0 * ms * 0.001 / decay_s);
+        }
+        for (allpass, &ms) in self.allpasses.iter_mut().zip(&allpass_ms) {
+            allpass.len = samples(ms);
+            allpass.pos %= allpass.len;
+        }
+    }
+
+    /// Drop the tail, e.g. when the stage is switched back on.
+    pub fn reset(&mut self) {
+        for comb in &mut self.combs {
+            comb.buf.fill(0.0);
+            comb.filtered = 0.0;
+        }
+        for allpass in &mut self.allpasses {
+            allpass.buf.fill(0.0);
+        }
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let wet = self.combs.iter_mut().map(|c| c.process(x)).sum::<f32>() * 0.25;
+        let wet = self.allpasses.iter_mut().fold(wet, |y, a| a.process(y));
+        x + (wet - x) * self.mix
+    }
+}
+
+/// Feedback delay for slapback echoes and speech-jamming (delayed auditory
+/// feedback). The line is sized for `MAX_MS` up front, and a new time
+/// glides in like a tape delay instead of clicking.
+pub struct Echo {
+    buf: Vec<f32>,
+    write: usize,
+    sample_rate: f32,
+    /// Current and requested delay in samples.
+    delay: f32,
+    target: f32,
+    /// Per-sample step of the glide towards `target`.
+    glide: f32,
+    feedback: f32,
+    mix: f32,
+}
+
+impl Echo {
+    pub const MAX_MS: f32 = 1000.0;
+    /// Kept below 1 so the repeats always die away.
+    const MAX_FEEDBACK: f32 = 0.95;
+    const GLIDE_MS: f32 = 50.0;
+
+    pub fn new(sample_rate: f32, time_ms: f32, feedback: f32, mix: f32) -> Self {
+        let mut echo = Self {
+            buf: vec![0.0; (Self::MAX_MS * 0.001 * sample_rate) as usize + 2],
+            write: 0,
+            sample_rate,
+            delay: 1.0,
+            target: 1.0,
+            glide: 1.0 - (-1.0 / (Self::GLIDE_MS * 0.001 * sample_rate)).exp(),
+            feedback: 0.0,
+            mix: 0.0,
+        };
+        echo.set(time_ms, feedback, mix);
+        echo.delay = echo.target;
+        echo
+    }
+
+    /// Delay time in ms, how much of each repeat comes round again, and
+    /// wet/dry mix (0 = dry, 1 = only the echo).
+    pub fn set(&mut self, time_ms: f32, feedback: f32, mix: f32) {
+        let max = (self.buf.len() - 2) as f32;
+        self.target = (time_ms * 0.001 * self.sample_rate).clamp(1.0, max);
+        self.feedback = feedback.clamp(0.0, Self::MAX_FEEDBACK);
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// Drop the repeats, e.g. when the stage is switched back on.
+    pub fn reset(&mut self) {
+        self.buf.fill(0.0);
+        self.delay = self.target;
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.delay += (self.target - self.delay) * self.glide;
+        let len = self.buf.len();
+        let pos = self.write as f32 + len as f32 - self.delay;
+        let whole = pos as usize;
+        let frac = pos - whole as f32;
+        let a = self.buf[whole % len];
+        let b = self.buf[(whole + 1) % len];
+        let delayed = a + (b - a) * frac;
+        self.buf[self.write] = flush_denormal(x + delayed * self.feedback);
+        self.write = (self.write + 1) % len;
+        x + (delayed - x) * self.mix
+    }
+}
+
+/// Where the input chain gets its signal from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Mic,
+    SpeechNoise,
+    Tone,
+    /// A looped audio file, played by `player::FilePlayer`.
+    File,
+}
+
+impl Source {
+    pub const ALL: [Source; 4] = [Source::Mic, Source::SpeechNoise, Source::Tone, Source::File];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Source::Mic => "MIC",
+            Source::SpeechNoise => "SPEECH NOISE",
+            Source::Tone => "TONE 1k",
+            Source::File => "FILE",
+        }
+    }
+}
+
+/// Test signals for setting up downstream apps without talking.
+///
+/// Speech noise is pink noise band-limited to the voice range, modulated at
+/// a syllable-like 4 Hz and broken into ~2 s phrases, so gates and
+/// compressors downstream open and close the way they would on speech.
+/// Both signals sit around -20 dBFS RMS.
+pub struct SignalGenerator {
+    sample_rate: f32,
+    rng: u32,
+    pink: [f32; 7],
+    hp: Biquad,
+    lp: Biquad,
+    n: u64,
+    env: f32,
+}
+
+impl SignalGenerator {
+    const PHRASE_SECS: f32 = 2.5;
+    const SPEAKING_SECS: f32 = 1.8;
+    const SYLLABLE_HZ: f32 = 4.0;
+    const TONE_HZ: f32 = 1000.0;
+    const TONE_AMP: f32 = 0.14;
+    const NOISE_GAIN: f32 = 1.4;
+
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            rng: 0x1234_5678,
+            pink: [0.0; 7],
+            hp: Biquad::highpass(sample_rate, 150.0, 0.707),
+            lp: Biquad::lowpass(sample_rate, 4000.0, 0.707),
+            n: 0,
+            env: 0.0,
+        }
+    }
+
+    fn white(&mut self) -> f32 {
+        // xorshift32
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    /// Paul Kellett's refined pink noise filter.
+    fn pink(&mut self) -> f32 {
+        let w = self.white();
+        let b = &mut self.pink;
+        b[0] = 0.99886 * b[0] + w * 0.0555179;
+        b[1] = 0.99332 * b[1] + w * 0.0750759;
+        b[2] = 0.969 * b[2] + w * 0.153852;
+        b[3] = 0.8665 * b[3] + w * 0.3104856;
+        b[4] = 0.55 * b[4] + w * 0.5329522;
+        b[5] = -0.7616 * b[5] - w * 0.0168980;
+        let out = b.iter().sum::<f32>() + w * 0.5362;
+        b[6] = w * 0.115926;
+        out * 0.11
+    }
+
+    pub fn fill(&mut self, source: Source, block: &mut [f32]) {
+        for s in block.iter_mut() {
+            let t = self.n as f32 / self.sample_rate;
+            *s = match source {
+                Source::Mic | Source::File => *s,
+                Source::Tone => Self::TONE_AMP * (2.0 * PI * Self::TONE_HZ * t).sin(),
+                Source::SpeechNoise => {
+                    let speaking = t % Self::PHRASE_SECS < Self::SPEAKING_SECS;
+                    let syllable = 0.5 - 0.5 * (2.0 * PI * Self::SYLLABLE_HZ * t).cos();
+                    let target = if speaking { syllable } else { 0.0 };
+                    // ~5 ms smoothing so phrase edges don't click
+                    self.env += (target - self.env) * (200.0 / self.sample_rate);
+                    let noise = self.pink();
+                    self.lp.process(self.hp.process(noise)) * self.env * Self::NOISE_GAIN
+                }
+            };
+            // Wrap once a minute of phrases so `t` keeps its f32 precision.
+            self.n = (self.n + 1) % (self.sample_rate * Self::PHRASE_SECS * 24.0) as u64;
+        }
+    }
+}
+
+/// What a keyed sidechain does to the main signal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SidechainMode {
+    /// Turn the main signal down while the sidechain is active.
+    Duck,
+    /// Mute the main signal unless the sidechain is active.
+    Unmute,
+}
+
+impl SidechainMode {
+    pub const ALL: [SidechainMode; 2] = [SidechainMode::Duck, SidechainMode::Unmute];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SidechainMode::Duck => "DUCK",
+            SidechainMode::Unmute => "UNMUTE",
+        }
+    }
+}
+
+/// Smoothed gain on the main signal, driven block by block by whether the
+/// sidechain key is above threshold. Engages fast, holds through short
+/// gaps between words, then releases slowly.
+pub struct SidechainGain {
+    engage: f32,
+    release: f32,
+    hold: usize,
+    hold_left: usize,
+    gain: f32,
+}
+
+impl SidechainGain {
+    pub const DUCK_DB: f32 = -18.0;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let coef = |ms: f32| 1.0 - (-1.0 / (ms * 0.001 * sample_rate)).exp();
+        Self {
+            engage: coef(10.0),
+            release: coef(250.0),
+            hold: (0.3 * sample_rate) as usize,
+            hold_left: 0,
+            gain: 1.0,
+        }
+    }
+
+    pub fn process(&mut self, block: &mut [f32], keyed: bool, mode: SidechainMode) {
+        self.hold_left = if keyed {
+            self.hold
+        } else {
+            self.hold_left.saturating_sub(block.len())
+        };
+        let active = self.hold_left > 0;
+        let target = match (mode, active) {
+            (SidechainMode::Duck, true) => db_to_gain(Self::DUCK_DB),
+            (SidechainMode::Duck, false) => 1.0,
+            (SidechainMode::Unmute, true) => 1.0,
+            (SidechainMode::Unmute, false) => 0.0,
+        };
+        let coef = if active { self.engage } else { self.release };
+        for s in block.iter_mut() {
+            self.gain += (target - self.gain) * coef;
+            *s *= self.gain;
+        }
+    }
+}
+
+/// Audible cues mixed into the monitor outputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alert {
+    Clip = 1,
+    HourMark = 2,
+    StopWarning = 3,
+    InputLost = 4,
+}
+
+impl Alert {
+    pub fn from_u32(v: u32) -> Option<Self> {
+        match v {
+            1 => Some(Alert::Clip),
+            2 => Some(Alert::HourMark),
+            3 => Some(Alert::StopWarning),
+            4 => Some(Alert::InputLost),
+            _ => None,
+        }
+    }
+
+    /// (frequency Hz, length ms) per note; 0 Hz is a gap.
+    fn notes(self) -> &'static [(f32, f32)] {
+        match self {
+            Alert::Clip => &[(1760.0, 60.0), (0.0, 40.0), (1760.0, 60.0)],
+            Alert::HourMark => &[(880.0, 150.0), (1320.0, 220.0)],
+            Alert::StopWarning => &[
+                (1320.0, 150.0),
+                (0.0, 60.0),
+                (880.0, 150.0),
+                (0.0, 60.0),
+                (660.0, 300.0),
+            ],
+            Alert::InputLost => &[(440.0, 200.0), (0.0, 80.0), (330.0, 400.0)],
+        }
+    }
+}
+
+/// Plays one alert at a time as short sine notes with raised-cosine edges,
+/// so cues never click. Silent when idle.
+pub struct AlertTone {
+    sample_rate: f32,
+    notes: &'static [(f32, f32)],
+    note: usize,
+    pos: usize,
+    phase: f32,
+}
+
+impl AlertTone {
+    const LEVEL: f32 = 0.25;
+    const EDGE_MS: f32 = 5.0;
+
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            notes: &[],
+            note: 0,
+            pos: 0,
+            phase: 0.0,
+        }
+    }
+
+    pub fn start(&mut self, alert: Alert) {
+        self.notes = alert.notes();
+        self.note = 0;
+        self.pos = 0;
+        self.phase = 0.0;
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let Some(&(freq, ms)) = self.notes.get(self.note) else {
+            return 0.0;
+        };
+        let len = (ms * 0.001 * self.sample_rate) as usize;
+        let edge = (Self::EDGE_MS * 0.001 * self.sample_rate).max(1.0);
+        let from_edge = self.pos.min(len - self.pos) as f32;
+        let env = if from_edge < edge {
+            0.5 - 0.5 * (PI * from_edge / edge).cos()
+        } else {
+            1.0
+        };
+        let y = (2.0 * PI * self.phase).sin() * env * Self::LEVEL;
+
+        self.phase = (self.phase + freq / self.sample_rate).fract();
+        self.pos += 1;
+        if self.pos >= len {
+            self.note += 1;
+            self.pos = 0;
+            self.phase = 0.0;
+        }
+        if freq == 0.0 {
+            0.0
+        } else {
+            y
+        }
+    }
+}
+
+/// Floor for loudness readings; BS.1770's absolute gate.
+pub const LUFS_FLOOR: f32 = -70.0;
+
+/// Short-term loudness (BS.1770 K-weighting, 3 s window) of a mono
+/// signal, updated every 100 ms.
+pub struct LoudnessMeter {
+    shelf: Biquad,
+    rlb: Biquad,
+    block_len: usize,
+    block_pos: usize,
+    block_sum: f64,
+    /// Mean square of the last 30 100 ms blocks.
+    blocks: [f64; 30],
+    block_idx: usize,
+    short_term: f32,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        // K-weighting as specified by BS.1770 (coefficients derived for any
+        // rate, as libebur128 does): a +4 dB shelf for head diffraction,
+        // then the RLB high-pass.
+        let k = (PI * 1681.974 / sample_rate).tan();
+        let q = 0.707_175_3;
+        let vh = 10f32.powf(3.999_844 / 20.0);
+        let vb = vh.powf(0.499_666_8);
+        let shelf = Biquad::from_coeffs(
+            vh + vb * k / q + k * k,
+            2.0 * (k * k - vh),
+            vh - vb * k / q + k * k,
+            1.0 + k / q + k * k,
+            2.0 * (k * k - 1.0),
+            1.0 - k / q + k * k,
+        );
+        let k = (PI * 38.135_47 / sample_rate).tan();
+        let q = 0.500_327;
+        // The spec leaves the numerator unnormalized (unity gain at Nyquist).
+        let a0 = 1.0 + k / q + k * k;
+        let rlb = Biquad::from_coeffs(
+            a0,
+            -2.0 * a0,
+            a0,
+            a0,
+            2.0 * (k * k - 1.0),
+            1.0 - k / q + k * k,
+        );
+
+        Self {
+            shelf,
+            rlb,
+            block_len: (sample_rate / 10.0) as usize,
+            block_pos: 0,
+            block_sum: 0.0,
+            blocks: [0.0; 30],
+            block_idx: 0,
+            short_term: LUFS_FLOOR,
+        }
+    }
+
+    pub fn process(&mut self, x: f32) {
+        let k = self.rlb.process(self.shelf.process(x)) as f64;
+        self.block_sum += k * k;
+        self.block_pos += 1;
+        if self.block_pos < self.block_len {
+            return;
+        }
+
+        self.blocks[self.block_idx] = self.block_sum / self.block_len as f64;
+        self.block_idx = (self.block_idx + 1) % self.blocks.len();
+        self.block_pos = 0;
+        self.block_sum = 0.0;
+
+        let mean = self.blocks.iter().sum::<f64>() / self.blocks.len() as f64;
+        self.short_term = ((-0.691 + 10.0 * mean.log10()) as f32).max(LUFS_FLOOR);
+    }
+
+    pub fn short_term_lufs(&self) -> f32 {
+        self.short_term
+    }
+}
+
+/// Recognizes sustained pitched content (singing, held instrument notes)
+/// from how periodic the signal is, so the gate can stay open through a
+/// decaying note. Speech is periodic too, but only for a vowel at a time,
+/// hence the onset delay.
+///
+/// Runs the normalized autocorrelation (McLeod's NSDF) on a ~12 kHz
+/// decimated copy every `hop`, which keeps it cheap enough for the audio
+/// thread.
+pub struct TonalDetector {
+    decimation: usize,
+    acc: f32,
+    acc_len: usize,
+    /// Decimated history, oldest first once linearized into `frame`.
+    ring: Vec<f32>,
+    ring_pos: usize,
+    frame: Vec<f32>,
+    min_lag: usize,
+    max_lag: usize,
+    hop: usize,
+    since_hop: usize,
+    /// Consecutive decimated samples that were (not) periodic.
+    periodic_for: usize,
+    aperiodic_for: usize,
+    onset: usize,
+    release: usize,
+    tonal: bool,
+}
+
+impl TonalDetector {
+    const RATE: f32 = 12_000.0;
+    const WINDOW_MS: f32 = 40.0;
+    const HOP_MS: f32 = 20.0;
+    const MIN_HZ: f32 = 60.0;
+    const MAX_HZ: f32 = 1000.0;
+    /// NSDF peak that counts as pitched; noise and fricatives sit well below.
+    const CLARITY: f32 = 0.85;
+    const ONSET_MS: f32 = 250.0;
+    const RELEASE_MS: f32 = 400.0;
+    /// Below about -80 dBFS RMS nothing is tonal.
+    const FLOOR: f32 = 1e-8;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let decimation = (sample_rate / Self::RATE).round().max(1.0) as usize;
+        let rate = sample_rate / decimation as f32;
+        let ms = |ms: f32| (ms / 1000.0 * rate) as usize;
+        let window = ms(Self::WINDOW_MS);
+        Self {
+            decimation,
+            acc: 0.0,
+            acc_len: 0,
+            ring: vec![0.0; window],
+            ring_pos: 0,
+            frame: vec![0.0; window],
+            min_lag: (rate / Self::MAX_HZ) as usize,
+            max_lag: ((rate / Self::MIN_HZ) as usize).min(window / 2),
+            hop: ms(Self::HOP_MS),
+            since_hop: 0,
+            periodic_for: 0,
+            aperiodic_for: 0,
+            onset: ms(Self::ONSET_MS),
+            release: ms(Self::RELEASE_MS),
+            tonal: false,
+        }
+    }
+
+    pub fn process(&mut self, block: &[f32]) {
+        for &x in block {
+            // Box-filter decimation; aliasing doesn't matter for periodicity.
+            self.acc += x;
+            self.acc_len += 1;
+            if self.acc_len < self.decimation {
+                continue;
+            }
+            self.ring[self.ring_pos] = self.acc / self.decimation as f32;
+            self.ring_pos = (self.ring_pos + 1) % self.ring.len();
+            self.acc = 0.0;
+            self.acc_len = 0;
+
+            self.since_hop += 1;
+            if self.since_hop >= self.hop {
+                self.since_hop = 0;
+                self.analyze();
+            }
+        }
+    }
+
+    fn analyze(&mut self) {
+        let (newer, older) = self.ring.split_at(self.ring_pos);
+        self.frame[..older.len()].copy_from_slice(older);
+        self.frame[older.len()..].copy_from_slice(newer);
+        let x = &self.frame;
+
+        let energy: f32 = x.iter().map(|s| s * s).sum();
+        let clarity = if energy < Self::FLOOR * x.len() as f32 {
+            0.0
+        } else {
+            (self.min_lag..=self.max_lag)
+                .map(|lag| {
+                    let (mut r, mut m) = (0.0f32, 0.0f32);
+                    for (a, b) in x.iter().zip(&x[lag..]) {
+                        r += a * b;
+                        m += a * a + b * b;
+                    }
+                    if m > 0.0 {
+                        2.0 * r / m
+                    } else {
+                        0.0
+                    }
+                })
+                .fold(0.0, f32::max)
+        };
+
+        if clarity >= Self::CLARITY {
+            self.periodic_for += self.hop;
+            self.aperiodic_for = 0;
+        } else {
+            self.aperiodic_for += self.hop;
+            self.periodic_for = 0;
+        }
+        if self.periodic_for >= self.onset {
+            self.tonal = true;
+        } else if self.aperiodic_for >= self.release {
+            self.tonal = false;
+        }
+    }
+
+    pub fn is_tonal(&self) -> bool {
+        self.tonal
+    }
+}
+
+/// Sample-rate converter between the session and an output: to another
+/// rate for one that can't run at the session's, e.g. a 48 kHz-only
+/// Bluetooth or HDMI sink behind a 44.1 kHz mic, and by a few hundred ppm
+/// either way to follow the output's clock (see `set_speed`).
+///
+/// Windowed-sinc interpolation: each output frame is a Blackman-windowed
+/// sinc centred on its position between input frames, from a table of
+/// `PHASES` fractional offsets (interpolated between neighbours). When
+/// converting down, the sinc is widened to cut below the new Nyquist so
+/// nothing aliases. Latency is half the kernel, in input frames.
+pub struct Resampler {
+    /// Input frames per output frame, as converted and as nudged.
+    nominal: f64,
+    step: f64,
+    /// Where the next output falls past the centre of `history`, in input frames.
+    phase: f64,
+    channels: usize,
+    taps: usize,
+    /// The last `taps` input frames, interleaved, oldest first.
+    history: Vec<f32>,
+    /// `PHASES + 1` rows of `taps` coefficients.
+    table: Vec<f32>,
+    frame: Vec<f32>,
+}
+
+impl Resampler {
+    /// Enough to keep a 20 kHz band clean of aliases across a rate change.
+    const TAPS: usize = 64;
+    /// Near 1:1 nothing folds, so a short kernel (under 0.2 ms) will do.
+    const DRIFT_TAPS: usize = 16;
+    const PHASES: usize = 128;
+    /// Furthest `set_speed` strays from 1.
+    pub const MAX_SPEED_CHANGE: f64 = 0.002;
+
+    pub fn new(from_rate: u32, to_rate: u32, channels: usize) -> Self {
+        Self::with_taps(from_rate as f64 / to_rate as f64, channels, Self::TAPS)
+    }
+
+    /// 1:1 until `set_speed` says otherwise.
+    pub fn drift(channels: usize) -> Self {
+        Self::with_taps(1.0, channels, Self::DRIFT_TAPS)
+    }
+
+    fn with_taps(step: f64, channels: usize, taps: usize) -> Self {
+        let half = (taps / 2) as f64;
+        // A little under the lower Nyquist, so the window's skirt falls above it.
+        let cutoff = 0.9 * step.recip().min(1.0);
+        let mut table = Vec::with_capacity((Self::PHASES + 1) * taps);
+        for p in 0..=Self::PHASES {
+            let frac = p as f64 / Self::PHASES as f64;
+            let row: Vec<f64> = (0..taps)
+                .map(|k| {
+                    let t = k as f64 - (half - 1.0) - frac;
+                    let sinc = if t == 0.0 {
+                        1.0
+                    } else {
+                        (std::f64::consts::PI * cutoff * t).sin()
+                            / (std::f64::consts::PI * cutoff * t)
+                    };
+                    let w = std::f64::consts::PI * t / half;
+                    let window = (0.42 + 0.5 * w.cos() + 0.08 * (2.0 * w).cos()).max(0.0);
+                    sinc * window
+                })
+                .collect();
+            // Unity gain at DC on every phase, so there's no ripple at the phase rate.
+            let sum: f64 = row.iter().sum();
+            table.extend(row.iter().map(|c| (c / sum) as f32));
+        }
+        Self {
+            nominal: step,
+            step,
+            phase: 0.0,
+            channels,
+            taps,
+            history: vec![0.0; taps * channels],
+            table,
+            frame: vec![0.0; channels],
+        }
+    }
+
+    /// Play the input `speed` times faster than converted, within
+    /// `MAX_SPEED_CHANGE`: above 1 gives fewer output frames.
+    pub fn set_speed(&mut self, speed: f64) {
+        let change = (speed - 1.0).clamp(-Self::MAX_SPEED_CHANGE, Self::MAX_SPEED_CHANGE);
+        self.step = self.nominal * (1.0 + change);
+    }
+
+    /// Take one interleaved input frame and hand `emit` the output frames
+    /// now due: none, one, or (converting up) sometimes two.
+    pub fn process(&mut self, input: &[f32], mut emit: impl FnMut(&[f32])) {
+        let (ch, taps) = (self.channels, self.taps);
+        self.history.copy_within(ch.., 0);
+        let newest = self.history.len() - ch;
+        self.history[newest..].copy_from_slice(&input[..ch]);
+        while self.phase < 1.0 {
+            let pos = self.phase * Self::PHASES as f64;
+            let row = pos as usize;
+            let mix = (pos - row as f64) as f32;
+            let (a, b) = (
+                &self.table[row * taps..(row + 1) * taps],
+                &self.table[(row + 1) * taps..(row + 2) * taps],
+            );
+            self.frame.fill(0.0);
+            for (k, frame) in self.history.chunks_exact(ch).enumerate() {
+                let c = a[k] + (b[k] - a[k]) * mix;
+                for (out, &x) in self.frame.iter_mut().zip(frame) {
+                    *out += c * x;
+                }
+            }
+            emit(&self.frame);
+            self.phase += self.step;
+        }
+        self.phase -= 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! Golden-response tests: every stage is fed an impulse and a log sweep
+    //! and the output is compared to `testdata/golden/<name>.txt`.
+    //! Regenerate after an intentional sound change with
+    //! `VIBETONE_BLESS=1 cargo test`. The noise gate lives in the
+    //! `audio-gate` crate and isn't covered here.
+
+    use super::*;
+    use std::path::PathBuf;
+
+    const SR: f32 = 48000.0;
+    const LEN: usize = 1024;
+    /// Loose enough for reordered float math (SIMD, block processing),
+    /// tight enough to catch any audible change.
+    const TOLERANCE: f32 = 1e-4;
+
+    fn impulse() -> Vec<f32> {
+        let mut x = vec![0.0; LEN];
+        x[0] = 1.0;
+        x
+    }
+
+    /// Exponential sine sweep 20 Hz → 20 kHz at -6 dBFS.
+    fn sweep() -> Vec<f32> {
+        let (f0, f1) = (20.0f32, 20000.0f32);
+        let dur = LEN as f32 / SR;
+        let k = (f1 / f0).ln();
+        (0..LEN)
+            .map(|i| {
+                let t = i as f32 / SR;
+                let phase = 2.0 * PI * f0 * dur / k * ((t / dur * k).exp() - 1.0);
+                0.5 * phase.sin()
+            })
+            .collect()
+    }
+
+    fn run(input: &[f32], mut stage: impl FnMut(f32) -> f32) -> Vec<f32> {
+        input.iter().map(|&x| stage(x)).collect()
+    }
+
+    fn check_golden(name: &str, actual: &[f32]) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata/golden")
+            .join(format!("{name}.txt"));
+
+        if std::env::var_os("VIBETONE_BLESS").is_some() {
+            let text: String = actual.iter().map(|v| format!("{v:.9e}\n")).collect();
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, text).unwrap();
+            return;
+        }
+
+        let text = std::fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("missing {}; run with VIBETONE_BLESS=1", path.display()));
+        let expected: Vec<f32> = text.lines().map(|l| l.parse().unwrap()).collect();
+        assert_eq!(actual.len(), expected.len(), "{name}: length changed");
+        for (i, (a, e)) in actual.iter().zip(&expected).enumerate() {
+            assert!(
+                (a - e).abs() <= TOLERANCE * (1.0 + e.abs()),
+                "{name}[{i}]: got {a}, golden {e}"
+            );
+        }
+    }
+
+    fn check_stage(name: &str, make: impl Fn() -> Box<dyn FnMut(f32) -> f32>) {
+        check_golden(&format!("{name}_impulse"), &run(&impulse(), make()));
+        check_golden(&format!("{name}_sweep"), &run(&sweep(), make()));
+    }
+
+    #[test]
+    fn highpass_100hz() {
+        check_stage("highpass_100hz", || {
+            let mut f = Butterworth::highpass(SR, 100.0, 4);
+            Box::new(move |x| f.process(x))
+        });
+    }
+
+    #[test]
+    fn lowpass_8khz() {
+        check_stage("lowpass_8khz", || {
+            let mut f = Butterworth::lowpass(SR, 8000.0, 4);
+            Box::new(move |x| f.process(x))
+        });
+    }
+
+    #[test]
+    fn pitch_shift_down_3() {
+        check_stage("pitch_shift_down_3", || {
+            let mut p = PitchShifter::new(SR, 40.0);
+            p.set_semitones(-3.0);
+            Box::new(move |x| p.process(x))
+        });
+    }
+
+    #[test]
+    fn psola_shifts_a_note_by_the_interval() {
+        // Harmonics falling off like a sung vowel: PSOLA moves the pitch
+        // of pulse-like sources, not of bare sines.
+        let voice = |i: usize| -> f32 {
+            let t = i as f32 / SR;
+            (1..=10)
+                .map(|k| (2.0 * PI * 220.0 * k as f32 * t).sin() / k as f32)
+                .sum::<f32>()
+                * 0.2
+        };
+        // Frequency from the strongest autocorrelation lag, 50 Hz to 1 kHz.
+        let freq_after = |semitones: f32| {
+            let mut p = PsolaShifter::new(SR);
+            p.set_semitones(semitones);
+            let out: Vec<f32> = (0..SR as usize / 2).map(|i| p.process(voice(i))).collect();
+            let half = &out[out.len() / 2..];
+            let corr =
+                |lag: usize| -> f32 { half.iter().zip(&half[lag..]).map(|(a, b)| a * b).sum() };
+            let lags = (SR / 1000.0) as usize..(SR / 50.0) as usize;
+            let best = lags.max_by(|&a, &b| corr(a).total_cmp(&corr(b))).unwrap();
+            SR / best as f32
+        };
+        for semitones in [-12.0, -5.0, 4.0, 12.0] {
+            let want = 220.0 * 2f32.powf(semitones / 12.0);
+            let got = freq_after(semitones);
+            assert!(
+                (got / want - 1.0).abs() < 0.02,
+                "{semitones:+} st: {got} Hz, want {want}"
+            );
+        }
+    }
+
+    #[test]
+    fn voice_changer_moves_pitch_and_formant_apart() {
+        // A 150 Hz voice with one formant around 800 Hz.
+        let voice = |i: usize| -> f32 {
+            let t = i as f32 / SR;
+            (1..=20)
+                .map(|k| {
+                    let f = 150.0 * k as f32;
+                    (-((f - 800.0) / 300.0).powi(2)).exp() * (2.0 * PI * f * t).sin()
+                })
+                .sum::<f32>()
+                * 0.1
+        };
+        // Fundamental from the strongest autocorrelation lag, and the
+        // formant as the power-weighted mean frequency.
+        let measure = |pitch: f32, formant: f32| {
+            let mut v = VoiceChanger::new(SR);
+            v.set(pitch, formant);
+            let out: Vec<f32> = (0..SR as usize).map(|i| v.process(voice(i))).collect();
+            let half = &out[out.len() / 2..];
+            let corr =
+                |lag: usize| -> f32 { half.iter().zip(&half[lag..]).map(|(a, b)| a * b).sum() };
+            let lags = (SR / 1000.0) as usize..(SR / 50.0) as usize;
+            let best = lags.max_by(|&a, &b| corr(a).total_cmp(&corr(b))).unwrap();
+            let power = |freq: f32| {
+                let (mut re, mut im) = (0.0f32, 0.0f32);
+                for (i, &x) in half.iter().enumerate() {
+                    let phase = 2.0 * PI * freq * i as f32 / SR;
+                    re += x * phase.cos();
+                    im += x * phase.sin();
+                }
+                re * re + im * im
+            };
+            let (mut weighted, mut total) = (0.0, 0.0);
+            for freq in (100..4000).step_by(10).map(|f| f as f32) {
+                let p = power(freq);
+                weighted += freq * p;
+                total += p;
+            }
+            (SR / best as f32, weighted / total)
+        };
+        let (f0, formant) = measure(0.0, 0.0);
+        for (pitch, shift) in [(0.0, 5.0), (7.0, 0.0), (-5.0, -3.0)] {
+            let (got_f0, got_formant) = measure(pitch, shift);
+            let (want_f0, want_formant) = (
+                f0 * 2f32.powf(pitch / 12.0),
+                formant * 2f32.powf(shift / 12.0),
+            );
+            assert!(
+                (got_f0 / want_f0 - 1.0).abs() < 0.03,
+                "{pitch:+}/{shift:+} st: {got_f0} Hz, want {want_f0}"
+            );
+            assert!(
+                (got_formant / want_formant - 1.0).abs() < 0.1,
+                "{pitch:+}/{shift:+} st: formant {got_formant} Hz, want {want_formant}"
+            );
+        }
+    }
+
+    #[test]
+    fn compressor_default() {
+        check_stage("compressor_default", || {
+            let mut c = Compressor::new(SR);
+            Box::new(move |x| c.process(x))
+        });
+    }
+
+    #[test]
+    fn saturation_12db() {
+        check_stage("saturation_12db", || {
+            let mut s = Saturator::new(SR);
+            s.set(12.0, 0.0, 0.0);
+            Box::new(move |x| s.process(x))
+        });
+    }
+
+    #[test]
+    fn saturation_is_clean_when_quiet_and_does_not_alias() {
+        // Level of `freq` in `signal`, by correlating one second of it.
+        let level_db = |signal: &[f32], rate: f32, freq: f32| {
+            let (mut re, mut im) = (0.0f64, 0.0f64);
+            for (i, &x) in signal.iter().enumerate() {
+                let phase = 2.0 * std::f64::consts::PI * freq as f64 * i as f64 / rate as f64;
+                re += x as f64 * phase.cos();
+                im += x as f64 * phase.sin();
+            }
+            gain_to_db((2.0 * (re * re + im * im).sqrt() / signal.len() as f64) as f32)
+        };
+        let sine = |rate: f32, freq: f32, amplitude: f32| -> Vec<f32> {
+            (0..rate as usize)
+                .map(|i| amplitude * (2.0 * PI * freq * i as f32 / rate).sin())
+                .collect()
+        };
+
+        // Far below the knee it's just the trim.
+        let mut saturator = Saturator::new(SR);
+        saturator.set(12.0, 0.0, -6.0);
+        let quiet = run(&sine(SR, 1000.0, 0.001), |x| saturator.process(x));
+        assert!((level_db(&quiet[4800..], SR, 1000.0) - (-66.0)).abs() < 0.1);
+        // Tone is a shelf well above the voice's body.
+        let mut saturator = Saturator::new(SR);
+        saturator.set(12.0, 6.0, 0.0);
+        let bright = run(&sine(SR, 10_000.0, 0.001), |x| saturator.process(x));
+        assert!((level_db(&bright[4800..], SR, 10_000.0) - (-54.0)).abs() < 0.5);
+
+        // Driven, the lopsided curve adds even harmonics under the odd ones.
+        let mut saturator = Saturator::new(SR);
+        saturator.set(12.0, 0.0, 0.0);
+        let warm = run(&sine(SR, 200.0, 0.5), |x| saturator.process(x));
+        let [first, second, third] = [200.0, 400.0, 600.0].map(|f| level_db(&warm[4800..], SR, f));
+        assert!(
+            second > first - 40.0 && second < third,
+            "2nd {second}, 3rd {third} dB"
+        );
+
+        // Driven hard at 44.1 kHz, 15 kHz makes harmonics at 30, 45 and
+        // 75 kHz, which would fold back down to 14.1 kHz, 900 Hz and 13.2 kHz.
+        let rate = 44_100.0;
+        let mut saturator = Saturator::new(rate);
+        saturator.set(18.0, 0.0, 0.0);
+        let hot = run(&sine(rate, 15_000.0, 0.5), |x| saturator.process(x));
+        let hot = &hot[4410..];
+        let fundamental = level_db(hot, rate, 15_000.0);
+        for alias in [900.0, 13_200.0, 14_100.0] {
+            let level = level_db(hot, rate, alias);
+            assert!(
+                level < fundamental - 60.0,
+                "alias at {alias} Hz: {level} dB"
+            );
+        }
+    }
+
+    #[test]
+    fn limiter_minus_1db() {
+        check_stage("limiter_minus_1db", || {
+            let mut l = Limiter::new(SR, -1.0);
+            Box::new(move |x| l.process(x))
+        });
+    }
+
+    #[test]
+    fn limiter_holds_ceiling_and_passes_quiet_audio() {
+        let ceiling = db_to_gain(-1.0);
+        let mut limiter = Limiter::new(SR, -1.0);
+        let mut noise = SignalGenerator::new(SR);
+        let mut block = vec![0.0; SR as usize];
+        noise.fill(Source::SpeechNoise, &mut block);
+        for (i, x) in block.iter().enumerate() {
+            let y = limiter.process(x * 8.0);
+            assert!(y.abs() <= ceiling, "sample {i}: {y} over the ceiling");
+        }
+
+        let mut limiter = Limiter::new(SR, -1.0);
+        // One lookahead window, less the sample the box filter includes.
+        let delay = (Limiter::LOOKAHEAD_MS * 0.001 * SR) as usize - 1;
+        let quiet: Vec<f32> = block.iter().map(|x| x * 0.1).collect();
+        let out = run(&quiet, |x| limiter.process(x));
+        assert_eq!(&out[delay..], &quiet[..quiet.len() - delay]);
+    }
+
+    #[test]
+    fn expander_turns_down_by_ratio_within_range() {
+        // Steady level (dBFS) in, gain (dB) once the envelope has settled.
+        let settled_gain = |ratio: f32, range: f32, level_db: f32| {
+            let mut expander = Expander::new(SR);
+            expander.set(-36.0, ratio, range);
+            let x = db_to_gain(level_db);
+            let y = (0..SR as usize).fold(0.0, |_, _| expander.process(x));
+            gain_to_db(y / x)
+        };
+        assert!(
+            settled_gain(2.0, 40.0, -20.0).abs() < 0.01,
+            "above threshold"
+        );
+        // 10 dB under at 1:2 comes out 20 dB under.
+        assert!((settled_gain(2.0, 40.0, -46.0) + 10.0).abs() < 0.1);
+        assert!((settled_gain(4.0, 40.0, -46.0) + 30.0).abs() < 0.1);
+        assert!(
+            (settled_gain(4.0, 12.0, -46.0) + 12.0).abs() < 0.1,
+            "held to the range"
+        );
+        assert!(
+            (settled_gain(4.0, 12.0, -90.0) + 12.0).abs() < 0.1,
+            "never a hard mute"
+        );
+    }
+
+    #[test]
+    fn block_loops_match_per_sample_and_decay_to_zero() {
+        let input = sweep();
+        let mut per_sample = Butterworth::highpass(SR, 100.0, 8);
+        let expected = run(&input, |x| per_sample.process(x));
+        let mut blocked = Butterworth::highpass(SR, 100.0, 8);
+        let mut out = input.clone();
+        // An odd block size carries the state across uneven boundaries.
+        for block in out.chunks_mut(13) {
+            blocked.process_block(block);
+        }
+        assert!(out.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 1e-6));
+
+        // Ringing out into silence ends at zero, not in denormals.
+        for _ in 0..(10.0 * SR) as usize / 64 {
+            blocked.process_block(&mut [0.0; 64]);
+        }
+        assert!(blocked.sections.iter().all(|s| s.z1 == 0.0 && s.z2 == 0.0));
+
+        let dry = impulse();
+        let mut wet = vec![0.5; LEN - 3];
+        mix(&mut wet, &dry[..LEN - 3], 0.25);
+        scale(&mut wet, 2.0);
+        assert_eq!((wet[0], wet[1], wet[LEN - 4]), (1.75, 0.25, 0.25));
+    }
+
+    #[test]
+    fn feedback_rings_out_to_zero_and_flush_to_zero_holds_for_its_scope() {
+        let mut echo = Echo::new(SR, 100.0, 0.5, 1.0);
+        let mut reverb = Reverb::new(SR, ReverbKind::Plate, 2.5, 1.0);
+        echo.process(1.0);
+        reverb.process(1.0);
+        for _ in 0..(30.0 * SR) as usize {
+            echo.process(0.0);
+            reverb.process(0.0);
+        }
+        assert!(echo.buf.iter().all(|&s| s == 0.0));
+        assert!(reverb
+            .allpasses
+            .iter()
+            .all(|a| a.buf.iter().all(|&s| s == 0.0)));
+
+        let tiny = std::hint::black_box(f32::MIN_POSITIVE);
+        {
+            let _ftz = FlushToZero::new();
+            let flushed = std::hint::black_box(tiny * 0.5);
+            if cfg!(any(target_arch = "x86_64", target_arch = "aarch64")) {
+                assert_eq!(flushed, 0.0);
+            }
+        }
+        assert!(
+            std::hint::black_box(tiny * 0.5) > 0.0,
+            "mode restored on drop"
+        );
+    }
+
+    #[test]
+    fn smoother_glides_without_steps_and_settles() {
+        let mut volume = Smoother::new(SR, 20.0);
+        assert_eq!(volume.next(1.0), 1.0, "the first value isn't ramped");
+        // Full volume to silence: no sample-to-sample step near the jump,
+        // one time constant in it's most of the way there, then it lands
+        // exactly.
+        let ramp: Vec<f32> = (0..SR as usize).map(|_| volume.next(0.0)).collect();
+        assert!(ramp.windows(2).all(|w| w[0] - w[1] < 0.002), "stepped");
+        assert!((ramp[(0.02 * SR) as usize] - (-1.0f32).exp()).abs() < 0.01);
+        assert_eq!(ramp.last(), Some(&0.0));
+
+        // A block at a time follows the same curve.
+        let (mut per_sample, mut per_block) = (Smoother::new(SR, 20.0), Smoother::new(SR, 20.0));
+        per_sample.next(-36.0);
+        per_block.next(-36.0);
+        let by_sample = (0..256).fold(0.0, |_, _| per_sample.next(-20.0));
+        let by_block = (0..4).fold(0.0, |_, _| per_block.block(-20.0, 64));
+        assert!(
+            (by_sample - by_block).abs() < 1e-3,
+            "{by_sample} vs {by_block}"
+        );
+    }
+
+    #[test]
+    fn agc_reaches_target_within_max_gain() {
+        let rms_db = |agc: &mut Agc, amplitude: f32| {
+            let mut sum = 0.0f64;
+            let len = SR as usize * 12;
+            for i in 0..len {
+                let y = agc.process(amplitude * (2.0 * PI * 220.0 * i as f32 / SR).sin());
+                if i >= len - SR as usize {
+                    sum += (y * y) as f64;
+                }
+            }
+            10.0 * (sum / SR as f64).log10() as f32
+        };
+        // A sine's RMS is 3 dB under its peak: this one is at -40 dBFS RMS.
+        let quiet = db_to_gain(-37.0);
+        let db = rms_db(&mut Agc::new(SR, -18.0, 30.0), quiet);
+        assert!((db + 18.0).abs() < 1.0, "quiet input ends at {db} dB");
+        let db = rms_db(&mut Agc::new(SR, -18.0, 12.0), quiet);
+        assert!((db + 28.0).abs() < 1.0, "capped at +12 dB, ends at {db} dB");
+        let db = rms_db(&mut Agc::new(SR, -18.0, 12.0), db_to_gain(-3.0));
+        assert!((db + 18.0).abs() < 1.0, "loud input ends at {db} dB");
+
+        let mut agc = Agc::new(SR, -18.0, 30.0);
+        rms_db(&mut agc, 0.0);
+        assert_eq!(agc.gain_db(), 0.0, "silence moved the gain");
+    }
+
+    #[test]
+    fn reverb_tail_follows_decay_time() {
+        let tail_db = |kind: ReverbKind, decay_s: f32, from_s: f32| {
+            let mut reverb = Reverb::new(SR, kind, decay_s, 1.0);
+            let (from, to) = ((from_s * SR) as usize, ((from_s + 0.1) * SR) as usize);
+            let energy: f64 = (0..to)
+                .map(|i| reverb.process(if i == 0 { 1.0 } else { 0.0 }))
+                .skip(from)
+                .map(|y| (y * y) as f64)
+                .sum();
+            10.0 * energy.log10() as f32
+        };
+        for kind in ReverbKind::ALL {
+            let early = tail_db(kind, 1.0, 0.1);
+            let late = tail_db(kind, 1.0, 1.1);
+            let fall = early - late;
+            assert!(
+                (45.0..75.0).contains(&fall),
+                "{kind:?}: fell {fall} dB in 1 s"
+            );
+            assert!(
+                tail_db(kind, 2.0, 1.1) > late + 10.0,
+                "{kind:?}: longer decay, same tail"
+            );
+        }
+
+        let mut dry = Reverb::new(SR, ReverbKind::Room, 1.0, 0.0);
+        let input = sweep();
+        assert_eq!(run(&input, |x| dry.process(x)), input);
+    }
+
+    #[test]
+    fn echo_repeats_at_the_delay_time() {
+        let mut echo = Echo::new(SR, 100.0, 0.5, 0.5);
+        let out: Vec<f32> = (0..SR as usize / 2)
+            .map(|i| echo.process(if i == 0 { 1.0 } else { 0.0 }))
+            .collect();
+        let step = (0.1 * SR) as usize;
+        for (n, want) in [(0, 0.5), (1, 0.5), (2, 0.25), (3, 0.125)] {
+            assert!(
+                (out[n * step] - want).abs() < 1e-6,
+                "repeat {n}: {}",
+                out[n * step]
+            );
+        }
+        let stray = out
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i % step != 0)
+            .fold(0.0f32, |m, (_, y)| m.max(y.abs()));
+        assert!(stray < 1e-6, "{stray} between the repeats");
+
+        // A new time glides in: a low tone stays smooth, where a switch
+        // straight to the new tap would jump by up to its amplitude.
+        let tone: Vec<f32> = (0..SR as usize / 2)
+            .map(|i| 0.5 * (2.0 * PI * 200.0 * i as f32 / SR).sin())
+            .collect();
+        let mut out = run(&tone, |x| echo.process(x));
+        echo.set(300.0, 0.5, 0.5);
+        out.extend(run(&tone, |x| echo.process(x)));
+        let jump = out
+            .windows(2)
+            .fold(0.0f32, |m, w| m.max((w[1] - w[0]).abs()));
+        assert!(jump < 0.1, "jumped by {jump}");
+
+        let mut dry = Echo::new(SR, 100.0, 0.5, 0.0);
+        let input = sweep();
+        assert_eq!(run(&input, |x| dry.process(x)), input);
+    }
+
+    #[test]
+    fn robot_speaks_on_the_oscillator_pitch() {
+        let level = |x: &[f32], hz: f32| {
+            let (re, im) = x
+                .iter()
+                .enumerate()
+                .fold((0.0f32, 0.0f32), |(re, im), (i, &v)| {
+                    let w = 2.0 * PI * hz * i as f32 / SR;
+                    (re + v * w.cos(), im + v * w.sin())
+                });
+            2.0 * re.hypot(im) / x.len() as f32
+        };
+        let rms = |x: &[f32]| (x.iter().map(|v| v * v).sum::<f32>() / x.len() as f32).sqrt();
+
+        // The ring modulator moves a 1 kHz tone 100 Hz either way.
+        let mut ring = Robot::new(SR, RobotMode::Ring, 100.0);
+        let tone: Vec<f32> = (0..SR as usize / 10)
+            .map(|i| 0.5 * (2.0 * PI * 1000.0 * i as f32 / SR).sin())
+            .collect();
+        let out = run(&tone, |x| ring.process(x));
+        assert!(
+            level(&out, 1000.0) < 1e-3,
+            "1 kHz left: {}",
+            level(&out, 1000.0)
+        );
+        for hz in [900.0, 1100.0] {
+            assert!(
+                (level(&out, hz) - 0.25).abs() < 0.01,
+                "{hz} Hz: {}",
+                level(&out, hz)
+            );
+        }
+
+        // The vocoder says a 220 Hz vowel on its own 130 Hz, at about the
+        // vowel's level, and stays quiet in the pauses.
+        let voice = |i: usize| -> f32 {
+            let t = i as f32 / SR;
+            (1..=10)
+                .map(|k| (2.0 * PI * 220.0 * k as f32 * t).sin() / k as f32)
+                .sum::<f32>()
+                * 0.2
+        };
+        let mut vocoder = Robot::new(SR, RobotMode::Vocoder, 130.0);
+        let input: Vec<f32> = (0..SR as usize / 2).map(voice).collect();
+        let out = run(&input, |x| vocoder.process(x));
+        let half = &out[out.len() / 2..];
+        let corr = |lag: usize| -> f32 { half.iter().zip(&half[lag..]).map(|(a, b)| a * b).sum() };
+        let lags = (SR / 1000.0) as usize..(SR / 50.0) as usize;
+        let best = lags.max_by(|&a, &b| corr(a).total_cmp(&corr(b))).unwrap();
+        let pitch = SR / best as f32;
+        assert!((pitch / 130.0 - 1.0).abs() < 0.02, "{pitch} Hz");
+        let gain_db = gain_to_db(rms(half) / rms(&input[input.len() / 2..]));
+        assert!(gain_db.abs() < 3.0, "{gain_db} dB");
+        let pause = run(&vec![0.0; SR as usize / 10], |x| vocoder.process(x));
+        assert!(
+            rms(&pause[pause.len() / 2..]) < 1e-3,
+            "{}",
+            rms(&pause[pause.len() / 2..])
+        );
+    }
+
+    #[test]
+    fn resampler_keeps_the_tone_and_drops_what_would_alias() {
+        let tone = |freq: f32, sr: u32, n: usize| -> Vec<f32> {
+            (0..n)
+                .map(|i| 0.5 * (2.0 * PI * freq * i as f32 / sr as f32).sin())
+                .collect()
+        };
+        let convert = |input: &[f32], from: u32, to: u32| -> Vec<f32> {
+            let mut resampler = Resampler::new(from, to, 1);
+            let mut out = Vec::new();
+            for &x in input {
+                resampler.process(&[x], |frame| out.push(frame[0]));
+            }
+            out
+        };
+        let rms = |x: &[f32]| (x.iter().map(|v| v * v).sum::<f32>() / x.len() as f32).sqrt();
+
+        for (from, to) in [(44100, 48000), (48000, 44100), (96000, 48000)] {
+            let out = convert(&tone(1000.0, from, from as usize), from, to);
+            assert!(
+                (out.len() as i64 - to as i64).abs() <= 1,
+                "{from}→{to}: {} frames",
+                out.len()
+            );
+            // The same 1 kHz at the new rate, past the filter's delay.
+            let settled = &out[100..];
+            let level_db = gain_to_db(rms(settled) / (0.5 / 2f32.sqrt()));
+            assert!(level_db.abs() < 0.05, "{from}→{to}: {level_db} dB");
+            let crossings = settled
+                .windows(2)
+                .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+                .count() as f32;
+            let freq = crossings * to as f32 / settled.len() as f32;
+            assert!((freq - 1000.0).abs() < 5.0, "{from}→{to}: {freq} Hz");
+        }
+
+        // 23 kHz fits at 48 kHz but not at 44.1 kHz, where it would fold to 21.1 kHz.
+        let out = convert(&tone(23000.0, 48000, 48000), 48000, 44100);
+        let level_db = gain_to_db(rms(&out[100..]) / (0.5 / 2f32.sqrt()));
+        assert!(level_db < -40.0, "aliasing at {level_db} dB");
+
+        // Channels stay apart.
+        let mut resampler = Resampler::new(44100, 48000, 2);
+        let mut out = Vec::new();
+        for _ in 0..1000 {
+            resampler.process(&[0.5, -0.25], |frame| out.extend_from_slice(frame));
+        }
+        assert!(out[out.len() - 2..]
+            .iter()
+            .zip([0.5, -0.25])
+            .all(|(a, e)| (a - e).abs() < 1e-4));
+    }
+
+    #[test]
+    fn call_sim_voip() {
+        check_stage("call_sim_voip", || {
+            let mut sim = TelephoneSim::new(SR);
+            sim.set_mode(CallSim::Voip);
+            Box::new(move |x| sim.process(x))
+        });
+    }
+
+    #[test]
+    fn call_sim_phone() {
+        check_stage("call_sim_phone", || {
+            let mut sim = TelephoneSim::new(SR);
+            sim.set_mode(CallSim::Phone);
+            Box::new(move |x| sim.process(x))
+        });
+    }
+
+    /// BS.1770 calibration: a 997 Hz sine at -20 dBFS reads -23.0 LUFS.
+    #[test]
+    fn loudness_meter_calibration() {
+        let mut meter = LoudnessMeter::new(SR);
+        for i in 0..(SR as usize * 4) {
+            meter.process(0.1 * (2.0 * PI * 997.0 * i as f32 / SR).sin());
+        }
+        assert!((meter.short_term_lufs() - -23.0).abs() < 0.05);
+    }
+
+    /// Steady-state gain of `f` for a sine at `freq`, in dB.
+    fn gain_db_at(sr: f32, freq: f32, mut f: impl FnMut(f32) -> f32) -> f32 {
+        let len = sr as usize;
+        let settle = len / 2;
+        let (mut in_sq, mut out_sq) = (0.0f64, 0.0f64);
+        for i in 0..len {
+            let x = (2.0 * PI * freq * i as f32 / sr).sin();
+            let y = f(x);
+            if i >= settle {
+                in_sq += (x * x) as f64;
+                out_sq += (y * y) as f64;
+            }
+        }
+        10.0 * (out_sq / in_sq).log10() as f32
+    }
+
+    #[test]
+    fn butterworth_corners_and_slopes_across_rates() {
+        for sr in [44_100.0, 48_000.0, 96_000.0] {
+            for order in Butterworth::ORDERS {
+                let mut hp = Butterworth::highpass(sr, 100.0, order);
+                let hp_db = gain_db_at(sr, 100.0, |x| hp.process(x));
+                assert!(
+                    (hp_db + 3.01).abs() < 0.05,
+                    "order {order} highpass at {sr} Hz: {hp_db} dB"
+                );
+                // Two octaves down: 6 dB/oct per order, until f32 rounding
+                // sets a floor around -90 dB.
+                let mut hp = Butterworth::highpass(sr, 100.0, order);
+                let stop_db = gain_db_at(sr, 25.0, |x| hp.process(x));
+                assert!(
+                    stop_db < (-11.5 * order as f32).max(-80.0),
+                    "order {order} highpass at 25 Hz: {stop_db} dB"
+                );
+
+                let mut lp = Butterworth::lowpass(sr, 8000.0, order);
+                let lp_db = gain_db_at(sr, 8000.0, |x| lp.process(x));
+                assert!(
+                    (lp_db + 3.01).abs() < 0.05,
+                    "order {order} lowpass at {sr} Hz: {lp_db} dB"
+                );
+                let mut lp = Butterworth::lowpass(sr, 8000.0, order);
+                let pass_db = gain_db_at(sr, 1000.0, |x| lp.process(x));
+                assert!(
+                    pass_db.abs() < 0.05,
+                    "order {order} lowpass at 1 kHz: {pass_db} dB"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tonal_detector_holds_notes_not_noise() {
+        for sr in [44_100.0, 48_000.0, 96_000.0] {
+            let second = sr as usize;
+
+            let mut det = TonalDetector::new(sr);
+            let note: Vec<f32> = (0..second)
+                .map(|i| 0.1 * (2.0 * PI * 220.0 * i as f32 / sr).sin())
+                .collect();
+            det.process(&note[..second / 10]);
+            assert!(!det.is_tonal(), "tonal after 100 ms at {sr} Hz");
+            det.process(&note[second / 10..]);
+            assert!(det.is_tonal(), "sustained note not tonal at {sr} Hz");
+
+            let mut det = TonalDetector::new(sr);
+            let mut noise = SignalGenerator::new(sr);
+            let mut block = vec![0.0; second];
+            noise.fill(Source::SpeechNoise, &mut block);
+            det.process(&block);
+            assert!(!det.is_tonal(), "speech noise tonal at {sr} Hz");
+        }
+    }
+
+    #[test]
+    fn hum_filter_notches_mains_and_overtones() {
+        let sr = 48_000.0;
+        for (mains, overtones) in [(50, 2), (60, 4)] {
+            let notched = 1..=1 + overtones;
+            for k in 1..=6 {
+                let mut hum = HumFilter::new(sr);
+                hum.set(mains, overtones);
+                let freq = (mains as usize * k) as f32;
+                let db = gain_db_at(sr, freq, |x| {
+                    let mut sample = [x];
+                    hum.process_block(&mut sample);
+                    sample[0]
+                });
+                if notched.contains(&k) {
+                    assert!(db < -30.0, "{freq} Hz only down {db} dB");
+                } else {
+                    assert!(db > -1.0, "{freq} Hz cut by {db} dB");
+                }
+            }
+            let mut hum = HumFilter::new(sr);
+            hum.set(mains, overtones);
+            let db = gain_db_at(sr, 1000.0, |x| {
+                let mut sample = [x];
+                hum.process_block(&mut sample);
+                sample[0]
+            });
+            assert!(db.abs() < 0.1, "1 kHz: {db} dB");
+        }
+    }
+
+    #[test]
+    fn eq_sections_hit_their_gain() {
+        let sr = 48_000.0;
+        let mut bell = Biquad::peaking(sr, 1000.0, 6.0, 1.0);
+        let db = gain_db_at(sr, 1000.0, |x| bell.process(x));
+        assert!((db - 6.0).abs() < 0.05, "peaking: {db} dB");
+
+        let mut low = Biquad::low_shelf(sr, 200.0, -4.0, 0.7);
+        let db = gain_db_at(sr, 20.0, |x| low.process(x));
+        assert!((db + 4.0).abs() < 0.1, "low shelf: {db} dB");
+
+        let mut high = Biquad::high_shelf(sr, 4000.0, 3.0, 0.7);
+        let db = gain_db_at(sr, 16000.0, |x| high.process(x));
+        assert!((db - 3.0).abs() < 0.1, "high shelf: {db} dB");
+    }
+}
@@ -0,0 +1,211 @@
+//! Parametric EQ: a preamp plus peaking and shelf bands. Used for
+//! per-headphone correction, imported from AutoEq's `ParametricEQ.txt`.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::{db_to_gain, Biquad};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BandKind {
+    Peak,
+    LowShelf,
+    HighShelf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EqBand {
+    pub kind: BandKind,
+    pub freq: f32,
+    pub gain_db: f32,
+    pub q: f32,
+}
+
+impl EqBand {
+    pub fn biquad(&self, sample_rate: f32) -> Biquad {
+        match self.kind {
+            BandKind::Peak => Biquad::peaking(sample_rate, self.freq, self.gain_db, self.q),
+            BandKind::LowShelf => Biquad::low_shelf(sample_rate, self.freq, self.gain_db, self.q),
+            BandKind::HighShelf => Biquad::high_shelf(sample_rate, self.freq, self.gain_db, self.q),
+        }
+    }
+}
+
+/// Bands of the mic EQ stage in the input chain: low shelf, two bells,
+/// high shelf, all flat to start with.
+pub const CHAIN_BANDS: [EqBand; 4] = [
+    EqBand {
+        kind: BandKind::LowShelf,
+        freq: 120.0,
+        gain_db: 0.0,
+        q: 0.71,
+    },
+    EqBand {
+        kind: BandKind::Peak,
+        freq: 400.0,
+        gain_db: 0.0,
+        q: 1.0,
+    },
+    EqBand {
+        kind: BandKind::Peak,
+        freq: 3000.0,
+        gain_db: 0.0,
+        q: 1.0,
+    },
+    EqBand {
+        kind: BandKind::HighShelf,
+        freq: 8000.0,
+        gain_db: 0.0,
+        q: 0.71,
+    },
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqProfile {
+    /// Usually negative, so boosts can't clip.
+    pub preamp_db: f32,
+    pub bands: Vec<EqBand>,
+}
+
+impl EqProfile {
+    /// Parse AutoEq's parametric format:
+    ///
+    /// ```text
+    /// Preamp: -6.2 dB
+    /// Filter 1: ON LSC Fc 105 Hz Gain 5.5 dB Q 0.70
+    /// Filter 2: ON PK Fc 2600 Hz Gain -3.1 dB Q 2.00
+    /// ```
+    ///
+    /// Filters marked OFF are skipped; shelves without a Q get 0.71.
+    pub fn parse_autoeq(text: &str) -> Result<Self> {
+        let mut profile = Self::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let value_after = |key: &str| -> Result<Option<f32>> {
+                match words.iter().position(|w| w.eq_ignore_ascii_case(key)) {
+                    Some(i) => {
+                        let word = words.get(i + 1).copied().unwrap_or_default();
+                        let v = word
+                            .parse()
+                            .with_context(|| format!("line {}: bad {key} value {word:?}", n + 1))?;
+                        Ok(Some(v))
+                    }
+                    None => Ok(None),
+                }
+            };
+
+            if let Some(rest) = line.strip_prefix("Preamp:") {
+                let db = rest.split_whitespace().next().unwrap_or_default();
+                profile.preamp_db = db
+                    .parse()
+                    .with_context(|| format!("line {}: bad preamp {db:?}", n + 1))?;
+            } else if line.starts_with("Filter") {
+                if !words.contains(&"ON") {
+                    continue;
+                }
+                let kind = match words.iter().find_map(|w| match *w {
+                    "PK" | "PEQ" => Some(BandKind::Peak),
+                    "LSC" | "LS" => Some(BandKind::LowShelf),
+                    "HSC" | "HS" => Some(BandKind::HighShelf),
+                    _ => None,
+                }) {
+                    Some(kind) => kind,
+                    None => bail!("line {}: unsupported filter type", n + 1),
+                };
+                let Some(freq) = value_after("Fc")? else {
+                    bail!("line {}: no Fc", n + 1);
+                };
+                profile.bands.push(EqBand {
+                    kind,
+                    freq,
+                    gain_db: value_after("Gain")?.unwrap_or(0.0),
+                    q: value_after("Q")?.unwrap_or(0.71),
+                });
+            }
+        }
+        if profile.bands.is_empty() {
+            bail!("no filters found");
+        }
+        Ok(profile)
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} bands, preamp {:+.1} dB",
+            self.bands.len(),
+            self.preamp_db
+        )
+    }
+}
+
+/// An `EqProfile` running at one sample rate. Bands at or above Nyquist
+/// are left out.
+pub struct Equalizer {
+    preamp: f32,
+    sections: Vec<Biquad>,
+}
+
+impl Equalizer {
+    pub fn new(sample_rate: f32, profile: &EqProfile) -> Self {
+        let sections = profile
+            .bands
+            .iter()
+            .filter(|b| b.freq > 0.0 && b.freq < sample_rate * 0.49 && b.q > 0.0)
+            .map(|b| b.biquad(sample_rate))
+            .collect();
+        Self {
+            preamp: db_to_gain(profile.preamp_db),
+            sections,
+        }
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.sections
+            .iter_mut()
+            .fold(x * self.preamp, |y, section| section.process(y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_autoeq_parametric() {
+        let text = "Preamp: -6.2 dB\n\
+                    Filter 1: ON LSC Fc 105 Hz Gain 5.5 dB Q 0.70\n\
+                    Filter 2: ON PK Fc 2600 Hz Gain -3.1 dB Q 2.00\n\
+                    Filter 3: OFF PK Fc 5000 Hz Gain 2.0 dB Q 1.00\n\
+                    Filter 4: ON HSC Fc 10000 Hz Gain -2.2 dB\n";
+        let eq = EqProfile::parse_autoeq(text).unwrap();
+        assert_eq!(eq.preamp_db, -6.2);
+        assert_eq!(
+            eq.bands,
+            vec![
+                EqBand {
+                    kind: BandKind::LowShelf,
+                    freq: 105.0,
+                    gain_db: 5.5,
+                    q: 0.70
+                },
+                EqBand {
+                    kind: BandKind::Peak,
+                    freq: 2600.0,
+                    gain_db: -3.1,
+                    q: 2.0
+                },
+                EqBand {
+                    kind: BandKind::HighShelf,
+                    freq: 10000.0,
+                    gain_db: -2.2,
+                    q: 0.71
+                },
+            ]
+        );
+
+        assert!(EqProfile::parse_autoeq("Preamp: -1 dB\n").is_err());
+        assert!(EqProfile::parse_autoeq("Filter 1: ON XX Fc 100 Hz").is_err());
+    }
+}
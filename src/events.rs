@@ -0,0 +1,103 @@
+//! Events from the audio threads to the GUI: stream errors, clipping and
+//! the gate opening and closing. The meters only show the latest state,
+//! read a few dozen times a second; these are the moments in between.
+//!
+//! Each sending callback gets a queue of its own, so every queue has a
+//! single producer and stays lock-free. A full queue drops the event
+//! rather than make a callback wait.
+
+use std::fmt;
+
+use cpal::StreamError;
+use ringbuf::{
+    traits::{Consumer, Producer, Split},
+    HeapCons, HeapProd, HeapRb,
+};
+
+/// Events a queue holds until the GUI drains it.
+const QUEUE_LEN: usize = 64;
+
+/// Which stream an error came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamRole {
+    Input,
+    Sidechain,
+    /// An output by the order they were opened in: 0 is OUT.
+    Output(usize),
+}
+
+impl fmt::Display for StreamRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamRole::Input => f.write_str("Input"),
+            StreamRole::Sidechain => f.write_str("Sidechain"),
+            StreamRole::Output(0) => f.write_str("Output"),
+            StreamRole::Output(slot) => write!(f, "Output {}", slot + 1),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Event {
+    /// A stream's error callback fired. The error is moved on as cpal
+    /// handed it over, so sending it doesn't allocate.
+    StreamError(StreamRole, StreamError),
+    /// The output started clipping, at this block peak in dBFS.
+    Clip(f32),
+    /// The gate opened (`true`) or shut fully.
+    Gate(bool),
+}
+
+/// One callback's end of a queue.
+pub struct EventSender(HeapProd<Event>);
+
+impl EventSender {
+    pub fn send(&mut self, event: Event) {
+        let _ = self.0.try_push(event);
+    }
+}
+
+/// The GUI's end of every queue.
+#[derive(Default)]
+pub struct EventReceiver(Vec<HeapCons<Event>>);
+
+impl EventReceiver {
+    /// A new queue into this receiver, for one callback.
+    pub fn sender(&mut self) -> EventSender {
+        let (producer, consumer) = HeapRb::new(QUEUE_LEN).split();
+        self.0.push(consumer);
+        EventSender(producer)
+    }
+
+    /// Everything sent since the last drain, queue by queue.
+    pub fn drain(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.0.iter_mut().flat_map(|queue| queue.pop_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queues_drain_in_order_and_drop_when_full() {
+        let mut receiver = EventReceiver::default();
+        let (mut input, mut output) = (receiver.sender(), receiver.sender());
+        output.send(Event::StreamError(
+            StreamRole::Output(0),
+            StreamError::DeviceNotAvailable,
+        ));
+        for n in 0..QUEUE_LEN + 10 {
+            input.send(Event::Gate(n % 2 == 0));
+        }
+        let events: Vec<Event> = receiver.drain().collect();
+        assert_eq!(events.len(), QUEUE_LEN + 1);
+        assert!(matches!(events[0], Event::Gate(true)));
+        assert!(matches!(events[QUEUE_LEN - 1], Event::Gate(false)));
+        assert!(matches!(
+            events[QUEUE_LEN],
+            Event::StreamError(StreamRole::Output(0), StreamError::DeviceNotAvailable)
+        ));
+        assert_eq!(receiver.drain().count(), 0);
+    }
+}
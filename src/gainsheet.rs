@@ -0,0 +1,651 @@
+//! Gain sheet: the gain structure of the whole signal path as a plain-text
+//! report, stage by stage in the order they run, for documenting a chain
+//! or for tracking down where the level goes.
+//!
+//! Fixed gains (compressor makeup, volume, headphone EQ preamp) add up to
+//! a net figure. Gains that move with the signal (AGC, compression, the
+//! limiter) are shown as their range, plus what they were doing at the
+//! moment of export while the engine runs.
+
+use std::fmt::Write;
+
+use crate::audio::{Controls, Stage, PRIVACY_SHIFTS};
+use crate::bus::{BusMix, Tap, CUE_OUTPUT};
+use crate::dsp::{
+    db_to_gain, gain_to_db, CallSim, GateMode, RobotMode, SidechainGain, SidechainMode, Source,
+};
+use crate::eq::{EqBand, EqProfile};
+use crate::meter::MeterFrame;
+
+/// One output the chain feeds.
+pub struct SheetOutput<'a> {
+    pub name: &'a str,
+    pub eq: Option<&'a EqProfile>,
+}
+
+/// One line of the sheet: stage, state, gain and notes.
+struct Row {
+    stage: String,
+    state: String,
+    gain: String,
+    note: String,
+}
+
+impl Row {
+    fn new(
+        stage: &str,
+        state: impl Into<String>,
+        gain: impl Into<String>,
+        note: impl Into<String>,
+    ) -> Self {
+        Self {
+            stage: stage.to_string(),
+            state: state.into(),
+            gain: gain.into(),
+            note: note.into(),
+        }
+    }
+
+    fn off(stage: Stage) -> Self {
+        Self::new(stage.label(), "off", "", "")
+    }
+}
+
+fn db(value: f32) -> String {
+    format!("{value:+.1} dB")
+}
+
+/// The sheet for the chain as `controls` set it up. `live` is the latest
+/// meter frame while the engine runs; without it only the settings are
+/// reported.
+pub fn render(
+    controls: &Controls,
+    input: &str,
+    sidechain: Option<&str>,
+    outputs: &[SheetOutput],
+    live: Option<&MeterFrame>,
+) -> String {
+    let mut rows = Vec::new();
+    // Broadband gain that doesn't depend on the signal, in dB.
+    let mut fixed_db = 0.0;
+
+    let source = controls.source;
+    rows.push(match source {
+        Source::Mic => Row::new(
+            Stage::Source.label(),
+            source.label(),
+            db(0.0),
+            format!("{input}, taken as captured (set its level in the system mixer)"),
+        ),
+        Source::File => Row::new(
+            Stage::Source.label(),
+            source.label(),
+            "",
+            "replaces the input, at the file's own level",
+        ),
+        _ => Row::new(
+            Stage::Source.label(),
+            source.label(),
+            "",
+            "replaces the input, around -20 dBFS RMS",
+        ),
+    });
+
+    for &stage in controls.effect_chain.stages() {
+        let Some((mut row, mut stage_db)) = chain_row(controls, stage) else {
+            rows.push(Row::off(stage));
+            continue;
+        };
+        // A blend scales a broadband gain by the stage's mix.
+        let mix = controls.stage_mix[stage as usize].clamp(0.0, 1.0);
+        if stage.blendable() && mix < 1.0 {
+            if stage_db != 0.0 {
+                stage_db = gain_to_db(1.0 + (db_to_gain(stage_db) - 1.0) * mix);
+                row.gain = db(stage_db);
+            }
+            if !row.note.is_empty() {
+                row.note.push_str("; ");
+            }
+            let _ = write!(row.note, "{:.0}% wet", mix * 100.0);
+        }
+        fixed_db += stage_db;
+        rows.push(row);
+    }
+
+    let wet = controls.dry_wet.clamp(0.0, 1.0);
+    rows.push(Row::new(
+        "DRY/WET",
+        format!("{:.0}% wet", wet * 100.0),
+        "",
+        if wet < 1.0 {
+            "blends the chain above with the unprocessed input"
+        } else {
+            ""
+        },
+    ));
+
+    let mode = controls.sidechain_mode;
+    rows.push(match (sidechain, mode) {
+        (None, _) => Row::off(Stage::Sidechain),
+        (Some(key), SidechainMode::Duck) => Row::new(
+            Stage::Sidechain.label(),
+            mode.label(),
+            format!("0 to {}", db(SidechainGain::DUCK_DB)),
+            format!("down while {key} is active"),
+        ),
+        (Some(key), SidechainMode::Unmute) => Row::new(
+            Stage::Sidechain.label(),
+            mode.label(),
+            "",
+            format!("muted unless {key} is active"),
+        ),
+    });
+
+    let volume_db = gain_to_db(controls.volume);
+    // What the VOICE tap carries, ahead of VOLUME.
+    let voice_db = fixed_db;
+    fixed_db += volume_db;
+    rows.push(Row::new(
+        "VOLUME",
+        format!("{:.0}%", controls.volume * 100.0),
+        db(volume_db),
+        "",
+    ));
+
+    if controls.limiter_enabled {
+        let ceiling = controls.limiter_ceiling;
+        let mut note = format!("only takes gain off, above {ceiling:.1} dBFS");
+        if let Some(frame) = live {
+            let _ = write!(note, "; now {}", db(-frame.limiter_reduction_db));
+        }
+        rows.push(Row::new(Stage::Limiter.label(), "on", "", note));
+    } else {
+        rows.push(Row::off(Stage::Limiter));
+    }
+
+    let mut sheet = String::from("VIBETONE GAIN SHEET\n\n");
+    let width = |f: fn(&Row) -> &String| rows.iter().map(|r| f(r).len()).max().unwrap_or(0);
+    let (w_stage, w_state, w_gain) = (width(|r| &r.stage), width(|r| &r.state), width(|r| &r.gain));
+    for row in &rows {
+        let line = format!(
+            "{:w_stage$}  {:w_state$}  {:>w_gain$}  {}",
+            row.stage, row.state, row.gain, row.note
+        );
+        sheet.push_str(line.trim_end());
+        sheet.push('\n');
+    }
+
+    sheet.push_str("\nOUTPUTS\n");
+    for (i, output) in outputs.iter().enumerate() {
+        let (eq, preamp_db) = match output.eq {
+            Some(eq) => (format!("headphone EQ, {}", eq.summary()), eq.preamp_db),
+            None => ("no headphone EQ".to_string(), 0.0),
+        };
+        match controls.cue.as_ref().filter(|_| i == CUE_OUTPUT) {
+            Some(cue) => {
+                let paths = cue_paths(cue, voice_db)
+                    .map(|(tap, gain_db)| {
+                        format!("{} via {}", db(gain_db + preamp_db), tap.label())
+                    })
+                    .collect::<Vec<_>>();
+                let net = if paths.is_empty() {
+                    "none, no sends up".to_string()
+                } else {
+                    paths.join(", ")
+                };
+                let _ = writeln!(
+                    sheet,
+                    "{}: CUE bus, {eq}; net fixed gain {net}",
+                    output.name
+                );
+            }
+            None => {
+                let _ = writeln!(
+                    sheet,
+                    "{}: {eq}; net fixed gain {}",
+                    output.name,
+                    db(fixed_db + preamp_db)
+                );
+            }
+        }
+    }
+
+    if let Some(cue) = &controls.cue {
+        match outputs.get(CUE_OUTPUT) {
+            Some(output) => {
+                let _ = writeln!(sheet, "\nCUE on {}", output.name);
+            }
+            None => sheet.push_str("\nCUE (no OUT 2 selected, so not heard)\n"),
+        }
+        for (tap, &send) in Tap::ALL.iter().zip(&cue.sends) {
+            let from = match tap {
+                Tap::Voice => format!("the processed voice, {} fixed before VOLUME", db(voice_db)),
+                Tap::Dry => "the input before any stage".to_string(),
+            };
+            if send > 0.0 {
+                let _ = writeln!(
+                    sheet,
+                    "{} send {:.0}% ({}), from {from}",
+                    tap.label(),
+                    send * 100.0,
+                    db(gain_to_db(send))
+                );
+            } else {
+                let _ = writeln!(sheet, "{} send off", tap.label());
+            }
+        }
+        match &cue.eq {
+            Some(bands) => {
+                let _ = writeln!(sheet, "EQ by frequency: {}", band_list(bands));
+            }
+            None => sheet.push_str("EQ off\n"),
+        }
+        let _ = writeln!(
+            sheet,
+            "VOLUME {:.0}% ({})\nLIMITER only takes gain off, above {:.1} dBFS",
+            cue.volume * 100.0,
+            db(gain_to_db(cue.volume)),
+            cue.ceiling_db
+        );
+    }
+
+    let _ = writeln!(
+        sheet,
+        "\nNet fixed gain is the input-to-output change outside the AGC, \
+         compressor and limiter, EQ bands and sidechain."
+    );
+    if let Some(frame) = live {
+        let moving = frame.agc_gain_db - frame.gain_reduction_db - frame.limiter_reduction_db;
+        let _ = writeln!(
+            sheet,
+            "At export: AGC {}, compressor {}, limiter {}, so {} on top \
+             (output peak {:.1} dBFS, {:.1} LUFS short-term).",
+            db(frame.agc_gain_db),
+            db(-frame.gain_reduction_db),
+            db(-frame.limiter_reduction_db),
+            db(moving),
+            frame.peak_db,
+            frame.short_term_lufs
+        );
+    }
+    sheet
+}
+
+/// Fixed gain from the input to a CUE bus's output along each tap it
+/// sends from, given `voice_db` up to the VOICE tap.
+fn cue_paths(cue: &BusMix, voice_db: f32) -> impl Iterator<Item = (Tap, f32)> + '_ {
+    Tap::ALL
+        .into_iter()
+        .zip(cue.sends)
+        .filter(|&(_, send)| send > 0.0)
+        .map(move |(tap, send)| {
+            let tap_db = match tap {
+                Tap::Voice => voice_db,
+                Tap::Dry => 0.0,
+            };
+            (tap, tap_db + gain_to_db(send) + gain_to_db(cue.volume))
+        })
+}
+
+fn band_list(bands: &[EqBand]) -> String {
+    bands
+        .iter()
+        .map(|b| format!("{:.0} Hz {}", b.freq, db(b.gain_db)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// One line per stage of the `EffectChain` that's on, in the order they
+/// run, for annotating a snapshot.
+pub fn active_stages(controls: &Controls) -> Vec<String> {
+    controls
+        .effect_chain
+        .stages()
+        .iter()
+        .filter_map(|&stage| chain_row(controls, stage))
+        .map(|(row, _)| {
+            format!("{} {} {}", row.stage, row.state, row.note)
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+}
+
+/// The row for a stage of the `EffectChain` and its fixed gain in dB, or
+/// `None` while the stage is off.
+fn chain_row(controls: &Controls, stage: Stage) -> Option<(Row, f32)> {
+    let label = stage.label();
+    let row = match stage {
+        Stage::Filters => {
+            let hp = controls.highpass_enabled;
+            let lp = controls.lowpass_enabled;
+            if !hp && !lp {
+                return None;
+            }
+            let which = match (hp, lp) {
+                (true, true) => "HP + LP",
+                (true, false) => "HP",
+                _ => "LP",
+            };
+            let order = controls.filter_order;
+            Row::new(
+                label,
+                which,
+                db(0.0),
+                format!("in the passband; order {order}"),
+            )
+        }
+        Stage::Hum => {
+            let mains = controls.hum_mains_hz;
+            if mains == 0 {
+                return None;
+            }
+            let overtones = controls.hum_overtones;
+            Row::new(
+                label,
+                format!("{mains} Hz"),
+                db(0.0),
+                format!("off the notches; {overtones} overtones"),
+            )
+        }
+        Stage::Eq => {
+            if !controls.eq_enabled {
+                return None;
+            }
+            let gains: Vec<f32> = controls.eq_bands.iter().map(|b| b.gain_db).collect();
+            let bands = band_list(&controls.eq_bands);
+            let lo = gains.iter().copied().fold(0.0f32, f32::min);
+            let hi = gains.iter().copied().fold(0.0f32, f32::max);
+            let gain = if lo == hi {
+                db(0.0)
+            } else {
+                format!("{lo:+.1} to {hi:+.1} dB")
+            };
+            Row::new(label, "on", gain, format!("by frequency: {bands}"))
+        }
+        Stage::Denoise => {
+            if !controls.denoise {
+                return None;
+            }
+            Row::new(
+                label,
+                "on",
+                db(0.0),
+                "on speech; noise between words goes down",
+            )
+        }
+        Stage::Gate => {
+            if !controls.noise_gate_enabled {
+                return None;
+            }
+            let threshold = controls.noise_gate_threshold;
+            match controls.gate_mode {
+                GateMode::Gate => Row::new(
+                    label,
+                    "on",
+                    db(0.0),
+                    format!("while open; closes below {threshold:.1} dBFS"),
+                ),
+                GateMode::Expander => Row::new(
+                    label,
+                    GateMode::Expander.label(),
+                    format!("0 to {}", db(-controls.expander_range_db)),
+                    format!("1:{:.1} below {threshold:.1} dBFS", controls.expander_ratio),
+                ),
+            }
+        }
+        Stage::Agc => {
+            if !controls.agc_enabled {
+                return None;
+            }
+            let max = controls.agc_max_gain_db;
+            Row::new(
+                label,
+                "on",
+                format!("0 to {}", db(max)),
+                format!("rides toward {:.1} dBFS RMS", controls.agc_target_db),
+            )
+        }
+        Stage::Compressor => {
+            if !controls.comp_enabled {
+                return None;
+            }
+            let makeup = controls.comp_makeup_db;
+            let row = Row::new(
+                label,
+                "on",
+                db(makeup),
+                format!(
+                    "makeup; {:.1}:1 above {:.1} dBFS takes more off",
+                    controls.comp_ratio, controls.comp_threshold
+                ),
+            );
+            return Some((row, makeup));
+        }
+        Stage::Saturation => {
+            if !controls.saturation_enabled {
+                return None;
+            }
+            let trim = controls.saturation_trim_db;
+            let row = Row::new(
+                label,
+                "on",
+                db(trim),
+                format!(
+                    "trim; {} drive into the curve, only peaks get softer; tone {} above 3 kHz",
+                    db(controls.saturation_drive_db),
+                    db(controls.saturation_tone_db)
+                ),
+            );
+            return Some((row, trim));
+        }
+        Stage::Voice => {
+            if !controls.voice_enabled {
+                return None;
+            }
+            Row::new(
+                label,
+                "on",
+                db(0.0),
+                format!(
+                    "pitch {:+.1}, formants {:+.1} semitones",
+                    controls.voice_pitch_semitones, controls.voice_formant_semitones
+                ),
+            )
+        }
+        Stage::Robot => {
+            if !controls.robot_enabled {
+                return None;
+            }
+            let mode = controls.robot_mode;
+            let pitch = controls.robot_pitch_hz;
+            Row::new(
+                label,
+                "on",
+                db(0.0),
+                match mode {
+                    RobotMode::Ring => format!("ring modulated at {pitch:.0} Hz"),
+                    RobotMode::Vocoder => format!("vocoded onto a {pitch:.0} Hz buzz"),
+                },
+            )
+        }
+        Stage::Privacy => {
+            if !controls.privacy_voice {
+                return None;
+            }
+            let (pitch, formant) = PRIVACY_SHIFTS;
+            Row::new(
+                label,
+                "on",
+                db(0.0),
+                format!("pitch {pitch:+.1}, formants {formant:+.1} semitones"),
+            )
+        }
+        Stage::Harmony => {
+            if !controls.harmony_enabled {
+                return None;
+            }
+            Row::new(
+                label,
+                "on",
+                "",
+                format!(
+                    "{:+.1} semitones, {:.0}% of the voice",
+                    controls.harmony_semitones,
+                    controls.harmony_mix * 100.0
+                ),
+            )
+        }
+        Stage::CallSim => {
+            let sim = controls.call_sim;
+            if sim == CallSim::Off {
+                return None;
+            }
+            Row::new(
+                label,
+                sim.label(),
+                "",
+                "band-limited and compressed like a call",
+            )
+        }
+        Stage::Script => {
+            #[cfg(feature = "scripting")]
+            if controls.script_enabled {
+                return Some((Row::new(label, "on", "", "whatever the script does"), 0.0));
+            }
+            return None;
+        }
+        Stage::Echo => {
+            if !controls.echo_enabled {
+                return None;
+            }
+            Row::new(
+                label,
+                "on",
+                "",
+                format!(
+                    "{:.0} ms, {:.0}% feedback, {:.0}% wet",
+                    controls.echo_time_ms,
+                    controls.echo_feedback * 100.0,
+                    controls.echo_mix * 100.0
+                ),
+            )
+        }
+        Stage::Reverb => {
+            if !controls.reverb_enabled {
+                return None;
+            }
+            let kind = controls.reverb_kind;
+            Row::new(
+                label,
+                kind.label(),
+                "",
+                format!(
+                    "{:.1} s, {:.0}% wet",
+                    controls.reverb_decay_s,
+                    controls.reverb_mix * 100.0
+                ),
+            )
+        }
+        Stage::Source | Stage::Sidechain | Stage::Limiter | Stage::HeadphoneEq => {
+            unreachable!("{label} is not in the effect chain")
+        }
+    };
+    Some((row, 0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sheet_adds_up_the_fixed_gains() {
+        let mut controls = Controls {
+            volume: 0.5,
+            comp_enabled: true,
+            comp_makeup_db: 12.0,
+            limiter_enabled: true,
+            limiter_ceiling: -1.0,
+            ..Controls::default()
+        };
+        let eq = EqProfile {
+            preamp_db: -3.0,
+            bands: Vec::new(),
+        };
+        let outputs = [
+            SheetOutput {
+                name: "Speakers",
+                eq: None,
+            },
+            SheetOutput {
+                name: "Headphones",
+                eq: Some(&eq),
+            },
+        ];
+        let sheet = render(&controls, "USB Mic", None, &outputs, None);
+        assert!(sheet.contains("USB Mic, taken as captured"), "{sheet}");
+        assert!(sheet.contains("above -1.0 dBFS"), "{sheet}");
+        // +12 makeup, -6 volume.
+        assert!(
+            sheet.contains("Speakers: no headphone EQ; net fixed gain +6.0 dB"),
+            "{sheet}"
+        );
+        assert!(sheet.contains("net fixed gain +3.0 dB"), "{sheet}");
+        assert!(!sheet.contains("At export"), "{sheet}");
+
+        // Half wet makeup: +12 dB blended 50/50 is about +7.9 dB.
+        controls.stage_mix[Stage::Compressor as usize] = 0.5;
+        let frame = MeterFrame {
+            agc_gain_db: 0.0,
+            gain_reduction_db: 4.0,
+            limiter_reduction_db: 1.0,
+            ..MeterFrame::default()
+        };
+        let sheet = render(&controls, "USB Mic", None, &outputs[..1], Some(&frame));
+        assert!(sheet.contains("+7.9 dB  makeup"), "{sheet}");
+        assert!(sheet.contains("50% wet"), "{sheet}");
+        assert!(sheet.contains("so -5.0 dB on top"), "{sheet}");
+    }
+
+    #[test]
+    fn sheet_follows_the_cue_bus_to_out_2() {
+        let controls = Controls {
+            volume: 0.5,
+            comp_enabled: true,
+            comp_makeup_db: 12.0,
+            cue: Some(BusMix {
+                sends: [1.0, 0.5],
+                volume: 0.5,
+                eq: None,
+                ceiling_db: -3.0,
+            }),
+            ..Controls::default()
+        };
+        let outputs = [
+            SheetOutput {
+                name: "Speakers",
+                eq: None,
+            },
+            SheetOutput {
+                name: "Guest",
+                eq: None,
+            },
+        ];
+        let sheet = render(&controls, "USB Mic", None, &outputs, None);
+        assert!(
+            sheet.contains("Speakers: no headphone EQ; net fixed gain +6.0 dB"),
+            "{sheet}"
+        );
+        // +12 makeup then the CUE volume, not the main one; the dry send is
+        // 6 dB down as well.
+        assert!(
+            sheet.contains("Guest: CUE bus, no headphone EQ; net fixed gain +6.0 dB via VOICE, -12.0 dB via DRY"),
+            "{sheet}"
+        );
+        assert!(sheet.contains("CUE on Guest"), "{sheet}");
+        assert!(sheet.contains("above -3.0 dBFS"), "{sheet}");
+
+        let sheet = render(&controls, "USB Mic", None, &outputs[..1], None);
+        assert!(sheet.contains("no OUT 2 selected"), "{sheet}");
+    }
+}
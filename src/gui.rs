@@ -1,16 +1,137 @@
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::Result;
 use cpal::traits::StreamTrait;
+use cpal::StreamError;
 use eframe::egui;
 
-use crate::audio::{AudioEngine, AudioParams};
+use crate::audio::{
+    AudioEngine, AudioParams, ChannelMode, Controls, EffectChain, InputLoss, InputTarget,
+    OutputTarget, Stage, AGC_MAX_GAIN_DB, AGC_TARGET_DB, COMP_DEFAULTS, ECHO_FEEDBACK, ECHO_MIX,
+    ECHO_TIME_MS, EXPANDER_RANGE_DB, EXPANDER_RATIO, FILTER_ORDER, GATE_MIN_OPEN_MS, HARMONY_MIX,
+    HARMONY_SEMITONES, HIGHPASS_HZ, HUM_OVERTONES, LIMITER_CEILING_DB, LOWPASS_HZ, MAX_OUTPUTS,
+    MAX_OUTPUT_DELAY_MS, PRIVACY_SHIFTS, REVERB_DECAY_S, REVERB_MIX, ROBOT_PITCH_HZ,
+    SATURATION_DRIVE_DB, SATURATION_TONE_DB, SATURATION_TRIM_DB,
+};
+use crate::beam::{self, Steering};
+use crate::bus::{BusMix, Tap};
+use crate::cli::LaunchOptions;
+use crate::denoise;
 use crate::device;
+use crate::dsp::{
+    db_to_gain, gain_to_db, Alert, Butterworth, CallSim, Echo, GateMode, GateTrigger, HumFilter,
+    ReverbKind, RobotMode, SidechainMode, Source, VoicePreset,
+};
+use crate::eq::{EqBand, EqProfile, CHAIN_BANDS};
+use crate::events::{Event, StreamRole};
+use crate::gainsheet::{self, SheetOutput};
+use crate::headset::Headset;
+use crate::idle::IdleMonitor;
+use crate::loopguard;
+use crate::meter::MeterFrame;
+use crate::onair::{self, AirState, OnAir};
+use crate::preflight;
+use crate::preset::{self, Preset, Sound, UseCase};
+use crate::room::{self, Report};
+use crate::schedule::{self, Schedule, Scheduler};
+use crate::settings::{Profiles, Settings, UI_SCALES};
+use crate::whatsnew::{self, Announcement, Feature};
 
 struct DeviceEntry {
     name: String,
     device: cpal::Device,
+    /// Matches the user's blocklist: listed last and never picked by default.
+    blocked: bool,
+}
+
+/// The device picks, as indices into the input and output lists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Picks {
+    input: usize,
+    output: usize,
+    aux: Option<usize>,
+    backup: Option<usize>,
+    sidechain: Option<usize>,
+}
+
+impl Picks {
+    /// Clear the extra picks that landed on a main device: OUT 2 or the
+    /// backup on OUT, the sidechain on IN.
+    fn dedup(&mut self) {
+        if self.aux == Some(self.output) {
+            self.aux = None;
+        }
+        if self.backup == Some(self.output) {
+            self.backup = None;
+        }
+        if self.sidechain == Some(self.input) {
+            self.sidechain = None;
+        }
+    }
+}
+
+/// Name and blocked flag of each device in `list`: what the selection
+/// logic goes by.
+fn listing(list: &[DeviceEntry]) -> Vec<(&str, bool)> {
+    list.iter().map(|e| (e.name.as_str(), e.blocked)).collect()
+}
+
+/// The best-ranked unblocked input/output pair (see `device::pair_score`).
+/// Ties go to the earlier devices, i.e. the OS order.
+fn suggest_pair(inputs: &[(&str, bool)], outputs: &[(&str, bool)]) -> Option<(usize, usize)> {
+    let unblocked = |list: &[(&str, bool)]| {
+        list.iter()
+            .enumerate()
+            .filter(|(_, (_, blocked))| !blocked)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>()
+    };
+    let usable = unblocked(outputs);
+    unblocked(inputs)
+        .into_iter()
+        .flat_map(|i| {
+            usable
+                .iter()
+                .map(move |&o| (device::pair_score(inputs[i].0, outputs[o].0), i, o))
+        })
+        .max_by_key(|&(score, i, o)| (score, std::cmp::Reverse((i, o))))
+        .map(|(_, i, o)| (i, o))
+}
+
+/// `current` with the devices `settings` remembers picked again where
+/// they're still in the lists. With nothing remembered (first run or a
+/// new profile), starts from the suggested pair instead.
+fn restore_picks(
+    current: Picks,
+    inputs: &[(&str, bool)],
+    outputs: &[(&str, bool)],
+    settings: &Settings,
+) -> Picks {
+    let find = |list: &[(&str, bool)], name: &Option<String>| {
+        name.as_deref()
+            .and_then(|n| list.iter().position(|&(e, _)| e == n))
+    };
+    let mut picks = current;
+    if settings.input_device.is_none() && settings.output_device.is_none() {
+        if let Some((i, o)) = suggest_pair(inputs, outputs) {
+            (picks.input, picks.output) = (i, o);
+        }
+    }
+    if let Some(i) = find(inputs, &settings.input_device) {
+        picks.input = i;
+    }
+    if let Some(i) = find(outputs, &settings.output_device) {
+        picks.output = i;
+    }
+    picks.aux = find(outputs, &settings.aux_output_device);
+    picks.backup = find(outputs, &settings.backup_output_device);
+    picks.sidechain = find(inputs, &settings.sidechain_device);
+    picks.dedup();
+    picks
 }
 
 const ALL_BUFFER_SIZES: &[u32] = &[16, 32, 64, 128, 256, 512, 1024];
@@ -28,6 +149,48 @@ const TEXT_BRIGHT: egui::Color32 = egui::Color32::from_rgb(230, 230, 245);
 
 const LOGO: &str = "> vibetone_";
 
+/// Pending parameter syncs shorter than this are not worth flagging.
+const SYNC_WARN: Duration = Duration::from_millis(50);
+
+/// A dropout this recent is flagged in DIAGNOSTICS.
+const DROPOUT_RECENT: Duration = Duration::from_secs(10);
+
+/// Callback events DIAGNOSTICS keeps.
+const EVENT_LOG_LEN: usize = 8;
+
+/// How often a running session re-checks device channel counts.
+const FORMAT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the loudness history is sampled.
+const LOUDNESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Window size in points at 100% UI scale.
+const WINDOW_SIZE: egui::Vec2 = egui::vec2(420.0, 950.0);
+
+/// Repaint rate while running, for the output meter.
+const METER_REFRESH: Duration = Duration::from_millis(50);
+
+/// Bottom of the output meter scale.
+const METER_FLOOR_DB: f32 = -60.0;
+
+/// Loudness graph range, in LUFS.
+const LOUDNESS_RANGE: std::ops::RangeInclusive<f32> = -50.0..=0.0;
+
+/// How far the monitor volume drops while the user is away.
+const IDLE_DROP_DB: f32 = -20.0;
+
+/// Silence this long counts as a break and resets the speaking time.
+const BREAK_PAUSE: Duration = Duration::from_secs(5 * 60);
+/// How long and how far a break reminder ducks the monitor.
+const BREAK_DUCK: Duration = Duration::from_secs(3);
+const BREAK_DUCK_DB: f32 = -12.0;
+
+/// How often a running session checks the room again.
+const ROOM_RECHECK: Duration = Duration::from_secs(5 * 60);
+
+/// Extra time allowed past the fade length before a stop is forced.
+const FADE_GRACE: Duration = Duration::from_millis(500);
+
 fn setup_style(ctx: &egui::Context) {
     let mut style = (*ctx.style()).clone();
 
@@ -93,51 +256,794 @@ struct VibetoneApp {
     outputs: Vec<DeviceEntry>,
     selected_input: usize,
     selected_output: usize,
+    /// Optional second output (e.g. Bluetooth headphones) with its own latency.
+    selected_aux_output: Option<usize>,
+    /// Output to fail over to if OUT errors or disappears while running.
+    selected_backup_output: Option<usize>,
+    /// Why OUT failed, until the failover to the backup runs.
+    failover: Option<String>,
+    /// Extra input used only to key ducking/unmuting of the main signal.
+    selected_sidechain: Option<usize>,
+    /// (input, output) names of the current pair and the one before it,
+    /// for SWAP.
+    current_pair: Option<(String, String)>,
+    previous_pair: Option<(String, String)>,
+    sidechain_mode: SidechainMode,
+    sidechain_threshold: f32,
     buffer_size: u32,
+    /// OUT's own buffer size, for a DAC that only takes larger ones;
+    /// `None` runs it at `buffer_size`.
+    out_buffer_size: Option<u32>,
     sample_rate: u32,
+    /// RATE on AUTO: follow the OS mixer's rate for the selected devices
+    /// until a rate is picked by hand. The (input, output) it was last
+    /// read for.
+    rate_auto: bool,
+    rate_auto_for: Option<(usize, usize)>,
+    channel_mode: ChannelMode,
+    /// STEREO: process the mid, pass the sides.
+    mid_side: bool,
+    /// CUE MIX: a mix of its own for OUT 2 (see `bus`), with its sends,
+    /// volume and EQ.
+    cue_enabled: bool,
+    cue_sends: [f32; Tap::ALL.len()],
+    cue_volume: f32,
+    cue_ceiling: f32,
+    cue_eq_enabled: bool,
+    cue_eq_bands: [EqBand; CHAIN_BANDS.len()],
+    /// Start/stop ramp length.
+    fade_ms: u32,
     volume: f32,
+    /// Processed share of the signal; the rest is the raw input.
+    dry_wet: f32,
     noise_gate: bool,
     noise_gate_threshold: f32,
+    /// RNNoise ahead of the gate (48 kHz only).
+    denoise: bool,
+    /// Gate music mode: hold longer and open `gate_music_db` lower on held notes.
+    gate_music: bool,
+    gate_music_db: f32,
+    /// Shortest time the gate stays open once it opens.
+    gate_min_open_ms: f32,
+    /// What the gate does below its threshold, and the expander's shape.
+    gate_mode: GateMode,
+    expander_ratio: f32,
+    expander_range_db: f32,
+    gate_trigger: GateTrigger,
+    /// The trigger key is down, and has been pressed an odd number of
+    /// times since the mode was picked.
+    trigger_held: bool,
+    trigger_latched: bool,
+    /// The next key pressed becomes the trigger key.
+    learning_trigger: bool,
+    /// AGC after the gate: target speech level and gain limit.
+    agc: bool,
+    agc_target_db: f32,
+    agc_max_gain_db: f32,
+    /// Compressor after the AGC.
+    comp: bool,
+    comp_threshold: f32,
+    comp_ratio: f32,
+    comp_attack_ms: f32,
+    comp_release_ms: f32,
+    comp_makeup_db: f32,
+    /// Oversampled tube-style saturation: drive, tone and output trim in dB.
+    saturation: bool,
+    saturation_drive_db: f32,
+    saturation_tone_db: f32,
+    saturation_trim_db: f32,
+    /// Brickwall limiter ahead of the outputs.
+    limiter: bool,
+    limiter_ceiling: f32,
+    /// Feedback delay: time in ms, feedback and wet/dry mix.
+    echo: bool,
+    echo_time_ms: f32,
+    echo_feedback: f32,
+    echo_mix: f32,
+    /// Monitor reverb: algorithm, RT60 in seconds and wet/dry mix.
+    reverb: bool,
+    reverb_kind: ReverbKind,
+    reverb_decay_s: f32,
+    reverb_mix: f32,
     config_warning: Option<String>,
-    voice_filter: bool,
+    /// Pre-start hints for the current devices/buffer/rate, and the
+    /// (input, output, buffer, rate) they were worked out for.
+    preflight: Vec<String>,
+    preflight_for: Option<(usize, usize, u32, u32)>,
+    highpass: bool,
+    lowpass: bool,
+    /// Butterworth order of both filters.
+    filter_order: u32,
+    /// Order of the reorderable stages.
+    effect_chain: EffectChain,
+    /// Wet/dry per stage, indexed by `Stage as usize`.
+    stage_mix: [f32; Stage::ALL.len()],
+    /// Mains hum notch at `hum_mains_hz` (50 or 60) and its overtones.
+    hum: bool,
+    hum_mains_hz: u32,
+    hum_overtones: u32,
+    /// Mic EQ after the filters.
+    eq_enabled: bool,
+    eq_bands: [EqBand; CHAIN_BANDS.len()],
+    /// Voice changer: pitch and formant shifts in semitones.
+    voice: bool,
+    voice_pitch_semitones: f32,
+    voice_formant_semitones: f32,
+    /// Robot voice: ring modulator or vocoder, and its oscillator pitch.
+    robot: bool,
+    robot_mode: RobotMode,
+    robot_pitch_hz: f32,
+    privacy_voice: bool,
+    /// Pitch-shifted copy of the voice: interval and blend.
+    harmony: bool,
+    harmony_semitones: f32,
+    harmony_mix: f32,
+    call_sim: CallSim,
+    source: Source,
+    #[cfg(feature = "scripting")]
+    script_enabled: bool,
     engine: Option<AudioEngine>,
     params_handle: Option<Arc<AudioParams>>,
     status: String,
+    /// Zoom factor last applied, and the window size (in points) to
+    /// restore once it has taken effect.
+    ui_scale_applied: f32,
+    resize_to: Option<egui::Vec2>,
+    /// Running with the outputs muted; also how START starts.
+    standby: bool,
     error: Option<String>,
+    sync_pending_since: Option<Instant>,
+    /// Set while the outputs ramp down after STOP; the engine is dropped
+    /// once they're silent.
+    stopping_since: Option<Instant>,
+    /// Output glitch recoveries already reported for this session.
+    xrun_recoveries_seen: u32,
+    /// Underruns and overruns this session, and when either last went up.
+    xruns_seen: (u32, u32),
+    last_dropout: Option<Instant>,
+    /// Stream errors and clips the callbacks reported, glitch recoveries,
+    /// crashed stages, loopback and the input coming and going, oldest
+    /// first; and whether
+    /// the gate is open and since when.
+    event_log: VecDeque<String>,
+    gate_since: Option<(bool, Instant)>,
+    /// What the outputs do when the input fails.
+    input_loss: InputLoss,
+    /// Probe for an output feeding back into the input at START.
+    loop_guard: bool,
+    /// When this session's loopback probe started; `None` once it's done.
+    probe_started: Option<Instant>,
+    /// BEAM mode's null, kept across restarts, and the "point at noise"
+    /// calibration: when it started listening and how the last one went.
+    beam_steering: Steering,
+    beam_started: Option<Instant>,
+    beam_status: Option<Result<String, String>>,
+    /// Room check: when the current one started listening, when the last
+    /// one did, and its result.
+    room_started: Option<Instant>,
+    room_checked_at: Option<Instant>,
+    room_report: Option<Report>,
+    /// Settings drawn over the window while a SNAPSHOT is being taken.
+    snapshot_caption: Option<Vec<String>>,
+    /// `input_lost` as last reported.
+    input_lost_seen: bool,
+    /// `faulted_stages` bits already reported.
+    stage_faults_seen: u32,
+    /// Latest meter frame from the audio thread, read once per repaint.
+    meter: MeterFrame,
+    /// Vibe mode: logo and separators pulse with the output level.
+    pulse_enabled: bool,
+    /// Smoothed 0..1 level driving the pulse.
+    pulse: f32,
+    /// Short-term LUFS over the session as (seconds since start, LUFS).
+    loudness_history: Vec<(f32, f32)>,
+    session_start: Instant,
+    last_loudness_sample: Instant,
+    /// Audible cues (clipping, each hour of the session) in the outputs.
+    alerts: bool,
+    alert_volume: f32,
+    /// Session hours already announced.
+    hours_alerted: u64,
+    /// Stop the session after `auto_stop_minutes`, with a warning a minute before.
+    auto_stop: bool,
+    auto_stop_minutes: u32,
+    /// When the one-minute warning went out this session.
+    stop_warned_at: Option<Instant>,
+    idle_monitor: IdleMonitor,
+    /// Lower the monitor volume after `idle_minutes` without user input.
+    idle_enabled: bool,
+    idle_minutes: u32,
+    /// Mute instead of dropping by `IDLE_DROP_DB`.
+    idle_mute: bool,
+    /// Currently lowered because the user is away.
+    idle_away: bool,
+    /// Remind (and briefly duck) after `break_minutes` of speaking time
+    /// without a `BREAK_PAUSE`.
+    break_enabled: bool,
+    break_minutes: u32,
+    speaking_since_break: Duration,
+    /// `speaking_samples` already counted, and when speech was last seen.
+    speaking_samples_seen: u64,
+    last_spoke: Instant,
+    break_duck_until: Option<Instant>,
+    last_format_check: Instant,
+    /// Informational message (not an error), e.g. after an automatic rebuild.
+    notice: Option<String>,
+    vibe_rng: u64,
+    /// What the VIBE button last picked, shown next to it.
+    vibe_summary: Option<String>,
+    /// Controls from before the first roll, for one-click revert.
+    vibe_revert: Option<Controls>,
+    /// PRESETS browser: search text, tag filter, and the name and tags
+    /// for SAVE.
+    preset_query: String,
+    preset_tag: Option<UseCase>,
+    new_preset_name: String,
+    new_preset_tags: Vec<UseCase>,
+    profiles: Profiles,
+    /// Announcements the WHAT'S NEW panel shows, newest first; empty once
+    /// dismissed.
+    whats_new: Vec<&'static Announcement>,
+    /// Name typed into the profile switcher's "new" field.
+    new_profile_name: String,
+    blocklist_text: String,
+    /// Publishes LIVE/MUTED/OFFLINE for an on-air light; `None` while off.
+    on_air: Option<OnAir>,
+    /// What `on_air` last published.
+    on_air_sent: Option<AirState>,
+    /// Why the profile's broker setting can't be used.
+    on_air_error: Option<String>,
+    /// Starts and stops at the profile's scheduled times; `None` while off.
+    scheduler: Option<Scheduler>,
+    /// Why the profile's schedule can't be used.
+    schedule_error: Option<String>,
+    /// Mute button and LED of a USB headset; `None` while off.
+    headset: Option<Headset>,
+    /// What the headset's mute LED was last set to.
+    headset_led: Option<bool>,
+    /// File typed into HEADPHONE EQ for IMPORT.
+    eq_path: String,
     style_init: bool,
 }
 
 impl VibetoneApp {
-    fn new() -> Self {
+    fn new(profiles: Profiles) -> Self {
         let host = device::host();
         let inputs: Vec<DeviceEntry> = device::input_device_list(&host)
             .unwrap_or_default()
             .into_iter()
-            .map(|(_, name, device)| DeviceEntry { name, device })
+            .map(|(_, name, device)| DeviceEntry {
+                name,
+                device,
+                blocked: false,
+            })
             .collect();
         let outputs: Vec<DeviceEntry> = device::output_device_list(&host)
             .unwrap_or_default()
             .into_iter()
-            .map(|(_, name, device)| DeviceEntry { name, device })
+            .map(|(_, name, device)| DeviceEntry {
+                name,
+                device,
+                blocked: false,
+            })
             .collect();
+        Self::with_devices(profiles, inputs, outputs)
+    }
 
-        Self {
+    /// The app over a given device list, without touching the audio host.
+    fn with_devices(
+        profiles: Profiles,
+        inputs: Vec<DeviceEntry>,
+        outputs: Vec<DeviceEntry>,
+    ) -> Self {
+        let mut app = Self {
             inputs,
             outputs,
             selected_input: 0,
             selected_output: 0,
+            selected_aux_output: None,
+            selected_backup_output: None,
+            failover: None,
+            selected_sidechain: None,
+            current_pair: None,
+            previous_pair: None,
+            sidechain_mode: SidechainMode::Duck,
+            sidechain_threshold: -40.0,
             buffer_size: 64,
+            out_buffer_size: None,
             sample_rate: 48000,
+            rate_auto: true,
+            rate_auto_for: None,
+            channel_mode: ChannelMode::Mix,
+            mid_side: false,
+            cue_enabled: false,
+            cue_sends: BusMix::default().sends,
+            cue_volume: 1.0,
+            cue_ceiling: LIMITER_CEILING_DB,
+            cue_eq_enabled: false,
+            cue_eq_bands: CHAIN_BANDS,
+            fade_ms: 50,
             volume: 1.0,
+            dry_wet: 1.0,
             noise_gate: false,
             noise_gate_threshold: -36.0,
+            denoise: false,
+            gate_music: false,
+            gate_music_db: 10.0,
+            gate_min_open_ms: GATE_MIN_OPEN_MS,
+            gate_mode: GateMode::Gate,
+            expander_ratio: EXPANDER_RATIO,
+            expander_range_db: EXPANDER_RANGE_DB,
+            gate_trigger: GateTrigger::Off,
+            trigger_held: false,
+            trigger_latched: false,
+            learning_trigger: false,
+            agc: false,
+            agc_target_db: AGC_TARGET_DB,
+            agc_max_gain_db: AGC_MAX_GAIN_DB,
+            comp: false,
+            comp_threshold: COMP_DEFAULTS.0,
+            comp_ratio: COMP_DEFAULTS.1,
+            comp_attack_ms: COMP_DEFAULTS.2,
+            comp_release_ms: COMP_DEFAULTS.3,
+            comp_makeup_db: COMP_DEFAULTS.4,
+            saturation: false,
+            saturation_drive_db: SATURATION_DRIVE_DB,
+            saturation_tone_db: SATURATION_TONE_DB,
+            saturation_trim_db: SATURATION_TRIM_DB,
+            limiter: true,
+            limiter_ceiling: LIMITER_CEILING_DB,
+            echo: false,
+            echo_time_ms: ECHO_TIME_MS,
+            echo_feedback: ECHO_FEEDBACK,
+            echo_mix: ECHO_MIX,
+            reverb: false,
+            reverb_kind: ReverbKind::Room,
+            reverb_decay_s: REVERB_DECAY_S,
+            reverb_mix: REVERB_MIX,
             config_warning: None,
-            voice_filter: true,
+            preflight: Vec::new(),
+            preflight_for: None,
+            highpass: true,
+            lowpass: true,
+            filter_order: FILTER_ORDER,
+            effect_chain: EffectChain::default(),
+            stage_mix: [1.0; Stage::ALL.len()],
+            hum: false,
+            hum_mains_hz: 50,
+            hum_overtones: HUM_OVERTONES,
+            eq_enabled: false,
+            eq_bands: CHAIN_BANDS,
+            voice: false,
+            voice_pitch_semitones: VoicePreset::Deep.shifts().0,
+            voice_formant_semitones: VoicePreset::Deep.shifts().1,
+            robot: false,
+            robot_mode: RobotMode::Vocoder,
+            robot_pitch_hz: ROBOT_PITCH_HZ,
+            privacy_voice: false,
+            harmony: false,
+            harmony_semitones: HARMONY_SEMITONES,
+            harmony_mix: HARMONY_MIX,
+            call_sim: CallSim::Off,
+            source: Source::Mic,
+            #[cfg(feature = "scripting")]
+            script_enabled: false,
             engine: None,
             params_handle: None,
             status: "OFFLINE".into(),
+            standby: false,
+            ui_scale_applied: 1.0,
+            resize_to: None,
             error: None,
+            sync_pending_since: None,
+            stopping_since: None,
+            xrun_recoveries_seen: 0,
+            xruns_seen: (0, 0),
+            last_dropout: None,
+            event_log: VecDeque::with_capacity(EVENT_LOG_LEN),
+            gate_since: None,
+            stage_faults_seen: 0,
+            input_loss: InputLoss::Silence,
+            loop_guard: true,
+            probe_started: None,
+            beam_steering: Steering::OFF,
+            beam_started: None,
+            beam_status: None,
+            room_started: None,
+            room_checked_at: None,
+            room_report: None,
+            snapshot_caption: None,
+            input_lost_seen: false,
+            meter: MeterFrame::default(),
+            pulse_enabled: false,
+            pulse: 0.0,
+            loudness_history: Vec::new(),
+            session_start: Instant::now(),
+            last_loudness_sample: Instant::now(),
+            alerts: false,
+            alert_volume: 0.5,
+            hours_alerted: 0,
+            auto_stop: false,
+            auto_stop_minutes: 60,
+            stop_warned_at: None,
+            idle_monitor: IdleMonitor::new(),
+            idle_enabled: false,
+            idle_minutes: 10,
+            idle_mute: false,
+            idle_away: false,
+            break_enabled: false,
+            break_minutes: 45,
+            speaking_since_break: Duration::ZERO,
+            speaking_samples_seen: 0,
+            last_spoke: Instant::now(),
+            break_duck_until: None,
+            last_format_check: Instant::now(),
+            notice: None,
+            vibe_rng: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |d| d.as_nanos() as u64 | 1),
+            vibe_summary: None,
+            vibe_revert: None,
+            preset_query: String::new(),
+            preset_tag: None,
+            new_preset_name: String::new(),
+            new_preset_tags: Vec::new(),
+            whats_new: whatsnew::unseen(profiles.whats_new_seen()),
+            profiles,
+            new_profile_name: String::new(),
+            blocklist_text: String::new(),
+            on_air: None,
+            on_air_sent: None,
+            on_air_error: None,
+            scheduler: None,
+            schedule_error: None,
+            headset: None,
+            headset_led: None,
+            eq_path: String::new(),
             style_init: false,
+        };
+        app.apply_profile();
+        app
+    }
+
+    /// Load the active profile's blocklist and device picks into the UI.
+    fn apply_profile(&mut self) {
+        self.blocklist_text = self.profiles.current().device_blocklist.join("\n");
+        self.apply_blocklist();
+        self.apply_on_air();
+        self.apply_schedule();
+        self.apply_headset();
+
+        let picks = restore_picks(
+            self.picks(),
+            &listing(&self.inputs),
+            &listing(&self.outputs),
+            self.profiles.current(),
+        );
+        self.set_picks(picks);
+    }
+
+    fn picks(&self) -> Picks {
+        Picks {
+            input: self.selected_input,
+            output: self.selected_output,
+            aux: self.selected_aux_output,
+            backup: self.selected_backup_output,
+            sidechain: self.selected_sidechain,
+        }
+    }
+
+    fn set_picks(&mut self, picks: Picks) {
+        self.selected_input = picks.input;
+        self.selected_output = picks.output;
+        self.selected_aux_output = picks.aux;
+        self.selected_backup_output = picks.backup;
+        self.selected_sidechain = picks.sidechain;
+    }
+
+    /// Clear OUT 2, the backup or the sidechain where they've landed on
+    /// the main device.
+    fn dedup_picks(&mut self) {
+        let mut picks = self.picks();
+        picks.dedup();
+        self.set_picks(picks);
+    }
+
+    /// Apply command-line overrides on top of the profile's picks.
+    fn apply_launch(&mut self, options: &LaunchOptions) {
+        let mut problems = Vec::new();
+        let find = |list: &[DeviceEntry], name: &str| {
+            let lower = name.to_lowercase();
+            list.iter().position(|e| e.name == name).or_else(|| {
+                list.iter()
+                    .position(|e| e.name.to_lowercase().contains(&lower))
+            })
+        };
+        if let Some(name) = &options.input {
+            match find(&self.inputs, name) {
+                Some(i) => self.selected_input = i,
+                None => problems.push(format!("no input matching \"{name}\"")),
+            }
+        }
+        if let Some(name) = &options.output {
+            match find(&self.outputs, name) {
+                Some(i) => self.selected_output = i,
+                None => problems.push(format!("no output matching \"{name}\"")),
+            }
+        }
+        if let Some(name) = &options.aux_output {
+            match find(&self.outputs, name) {
+                Some(i) if i != self.selected_output => self.selected_aux_output = Some(i),
+                Some(_) => problems.push("OUT 2 can't be the main output".into()),
+                None => problems.push(format!("no output matching \"{name}\"")),
+            }
+        }
+        match options.rate {
+            Some(r) if ALL_SAMPLE_RATES.contains(&r) => {
+                self.sample_rate = r;
+                self.rate_auto = false;
+            }
+            Some(r) => problems.push(format!("unsupported rate {r}")),
+            None => {}
+        }
+        match options.buffer {
+            Some(b) if ALL_BUFFER_SIZES.contains(&b) => self.buffer_size = b,
+            Some(b) => problems.push(format!("unsupported buffer size {b}")),
+            None => {}
+        }
+        match options.buffer_out {
+            Some(b) if ALL_BUFFER_SIZES.contains(&b) => self.out_buffer_size = Some(b),
+            Some(b) => problems.push(format!("unsupported output buffer size {b}")),
+            None => {}
+        }
+        if let Some(v) = options.volume {
+            self.volume = v as f32 / 100.0;
+        }
+        if let Some(db) = options.gate {
+            self.noise_gate = true;
+            self.noise_gate_threshold = db;
+        }
+        if !problems.is_empty() {
+            self.error = Some(format!("Command line: {}", problems.join(", ")));
+        }
+    }
+
+    /// The command line that starts vibetone the way it's set up now.
+    fn launch_command(&self) -> String {
+        let name = |list: &[DeviceEntry], i: usize| list.get(i).map(|e| e.name.clone());
+        LaunchOptions {
+            profile: Some(self.profiles.active().to_string()),
+            input: name(&self.inputs, self.selected_input),
+            output: name(&self.outputs, self.selected_output),
+            aux_output: self
+                .selected_aux_output
+                .and_then(|i| name(&self.outputs, i)),
+            rate: (!self.rate_auto).then_some(self.sample_rate),
+            buffer: Some(self.buffer_size),
+            buffer_out: self.out_buffer_size,
+            volume: Some((self.volume * 100.0).round() as u32),
+            gate: self.noise_gate.then_some(self.noise_gate_threshold),
+        }
+        .to_command()
+    }
+
+    /// The buffer size OUT is opened with.
+    fn output_buffer(&self) -> u32 {
+        self.out_buffer_size.unwrap_or(self.buffer_size)
+    }
+
+    /// On AUTO, take the OS mixer's rate when the devices change, so
+    /// forcing 48000 on a 44.1 kHz device doesn't add a hidden resample.
+    /// Where it can't be matched, PREFLIGHT says so.
+    fn follow_mixer_rate(&mut self) {
+        let pair = (self.selected_input, self.selected_output);
+        if !self.rate_auto || self.rate_auto_for == Some(pair) {
+            return;
+        }
+        self.rate_auto_for = Some(pair);
+        let input = self
+            .inputs
+            .get(pair.0)
+            .and_then(|e| device::input_mixer_rate(&e.device));
+        let output = self
+            .outputs
+            .get(pair.1)
+            .and_then(|e| device::output_mixer_rate(&e.device));
+        if let Some(rate) = device::mixer_session_rate(input, output, ALL_SAMPLE_RATES) {
+            self.sample_rate = rate;
+        }
+    }
+
+    /// Re-run the preflight checks when the devices, buffer or rate change.
+    fn update_preflight(&mut self) {
+        let key = (
+            self.selected_input,
+            self.selected_output,
+            self.buffer_size.min(self.output_buffer()),
+            self.sample_rate,
+        );
+        if self.preflight_for == Some(key) {
+            return;
+        }
+        self.preflight_for = Some(key);
+        self.preflight = match (
+            self.inputs.get(self.selected_input),
+            self.outputs.get(self.selected_output),
+        ) {
+            (Some(input), Some(output)) => preflight::check(
+                &input.device,
+                &output.device,
+                &output.name,
+                self.buffer_size.min(self.output_buffer()),
+                self.sample_rate,
+            ),
+            _ => Vec::new(),
+        };
+    }
+
+    /// Load `eq_path` as an AutoEq profile for the output named `device`.
+    fn import_eq(&mut self, device: &str) {
+        let result = std::fs::read_to_string(self.eq_path.trim())
+            .map_err(anyhow::Error::from)
+            .and_then(|text| EqProfile::parse_autoeq(&text));
+        match result {
+            Ok(eq) => {
+                self.notice = Some(format!("EQ for {device}: {}", eq.summary()));
+                self.profiles
+                    .current_mut()
+                    .output_eq
+                    .insert(device.to_string(), eq);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(format!("EQ import: {e:#}")),
+        }
+    }
+
+    /// Store the current device picks in the active profile.
+    fn remember_devices(&mut self) {
+        let name = |list: &[DeviceEntry], i: usize| list.get(i).map(|e| e.name.clone());
+        let input = name(&self.inputs, self.selected_input);
+        let output = name(&self.outputs, self.selected_output);
+        let aux = self
+            .selected_aux_output
+            .and_then(|i| name(&self.outputs, i));
+        let backup = self
+            .selected_backup_output
+            .and_then(|i| name(&self.outputs, i));
+        let sidechain = self.selected_sidechain.and_then(|i| name(&self.inputs, i));
+        let settings = self.profiles.current_mut();
+        settings.input_device = input;
+        settings.output_device = output;
+        settings.aux_output_device = aux;
+        settings.backup_output_device = backup;
+        settings.sidechain_device = sidechain;
+    }
+
+    fn switch_profile(&mut self, name: &str) {
+        self.remember_devices();
+        self.profiles.switch(name);
+        self.apply_profile();
+    }
+
+    /// Re-flag blocked devices and move them to the end of each list,
+    /// keeping the current selections pointing at the same devices.
+    fn apply_blocklist(&mut self) {
+        let blocklist = &self.profiles.current().device_blocklist;
+        let in_name = self.inputs.get(self.selected_input).map(|e| e.name.clone());
+        let out_name = self
+            .outputs
+            .get(self.selected_output)
+            .map(|e| e.name.clone());
+        let aux_name = self
+            .selected_aux_output
+            .and_then(|i| self.outputs.get(i))
+            .map(|e| e.name.clone());
+        let backup_name = self
+            .selected_backup_output
+            .and_then(|i| self.outputs.get(i))
+            .map(|e| e.name.clone());
+        let sidechain_name = self
+            .selected_sidechain
+            .and_then(|i| self.inputs.get(i))
+            .map(|e| e.name.clone());
+
+        for list in [&mut self.inputs, &mut self.outputs] {
+            for e in list.iter_mut() {
+                e.blocked = device::is_blocked(&e.name, blocklist);
+            }
+            list.sort_by_key(|e| e.blocked);
+        }
+
+        let find = |list: &[DeviceEntry], name: Option<String>| {
+            name.and_then(|n| list.iter().position(|e| e.name == n))
+        };
+        self.selected_input = find(&self.inputs, in_name).unwrap_or(0);
+        self.selected_output = find(&self.outputs, out_name).unwrap_or(0);
+        self.selected_aux_output = find(&self.outputs, aux_name);
+        self.selected_backup_output = find(&self.outputs, backup_name);
+        self.selected_sidechain = find(&self.inputs, sidechain_name);
+    }
+
+    fn selected_pair(&self) -> Option<(String, String)> {
+        let input = self.inputs.get(self.selected_input)?;
+        let output = self.outputs.get(self.selected_output)?;
+        Some((input.name.clone(), output.name.clone()))
+    }
+
+    /// Remember the previous in/out pair whenever the selection changes.
+    fn track_pair(&mut self) {
+        self.follow_pair(self.selected_pair());
+    }
+
+    /// `track_pair` with the selected pair's names given.
+    fn follow_pair(&mut self, pair: Option<(String, String)>) {
+        if pair != self.current_pair {
+            if self.current_pair.is_some() {
+                self.previous_pair = self.current_pair.take();
+            }
+            self.current_pair = pair;
+        }
+    }
+
+    /// Where SWAP goes: back to the previous pair if both devices are still
+    /// there, otherwise to the first other device whose input and output
+    /// belong together (same hardware).
+    fn swap_target(&self) -> Option<(usize, usize)> {
+        let position = |list: &[DeviceEntry], name: &str| list.iter().position(|e| e.name == name);
+        if let Some((in_name, out_name)) = &self.previous_pair {
+            if let (Some(i), Some(o)) = (
+                position(&self.inputs, in_name),
+                position(&self.outputs, out_name),
+            ) {
+                return Some((i, o));
+            }
+        }
+
+        let current = device::hardware_key(&self.inputs.get(self.selected_input)?.name);
+        self.inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| !e.blocked && device::hardware_key(&e.name) != current)
+            .find_map(|(i, input)| {
+                let key = device::hardware_key(&input.name);
+                self.outputs
+                    .iter()
+                    .position(|o| !o.blocked && device::hardware_key(&o.name) == key)
+                    .map(|o| (i, o))
+            })
+    }
+
+    /// Switch input and output together, checking the new pair first and
+    /// restarting the engine on it if it's running.
+    fn swap_devices(&mut self) {
+        let Some((i, o)) = self.swap_target() else {
+            self.error = Some("No other input/output pair to swap to".into());
+            return;
+        };
+        if let Err(e) = device::validate_config(
+            &self.inputs[i].device,
+            &self.outputs[o].device,
+            self.buffer_size,
+            self.output_buffer(),
+            self.sample_rate,
+        ) {
+            self.error = Some(format!("Can't swap: {e}"));
+            return;
+        }
+
+        self.error = None;
+        self.selected_input = i;
+        self.selected_output = o;
+        self.dedup_picks();
+        if self.is_running() {
+            self.stop_now();
+            self.start();
+        }
+        if self.error.is_none() {
+            self.notice = Some(format!(
+                "Swapped to {} / {}",
+                self.inputs[i].name, self.outputs[o].name
+            ));
         }
     }
 
@@ -147,15 +1053,13 @@ impl VibetoneApp {
 
     fn start(&mut self) {
         self.error = None;
+        self.notice = None;
         if self.inputs.is_empty() || self.outputs.is_empty() {
             self.error = Some("No audio devices available".into());
             return;
         }
 
-        let input = &self.inputs[self.selected_input].device;
-        let output = &self.outputs[self.selected_output].device;
-
-        let (in_ch, out_ch) = match device::negotiate_config(input, output) {
+        let (in_ch, out_chs) = match self.negotiate_channels() {
             Ok(v) => v,
             Err(e) => {
                 self.error = Some(format!("{e}"));
@@ -163,14 +1067,58 @@ impl VibetoneApp {
             }
         };
 
+        let input = InputTarget {
+            device: &self.inputs[self.selected_input].device,
+            channels: in_ch,
+            mode: self.channel_mode,
+        };
+        let sidechain = match self.selected_sidechain {
+            Some(i) => {
+                let device = &self.inputs[i].device;
+                match device::input_channels(device) {
+                    Ok(channels) => Some(InputTarget {
+                        device,
+                        channels,
+                        mode: ChannelMode::Mix,
+                    }),
+                    Err(e) => {
+                        self.error = Some(format!("Sidechain: {e}"));
+                        return;
+                    }
+                }
+            }
+            None => None,
+        };
+        let targets: Vec<OutputTarget> = self
+            .output_indices()
+            .into_iter()
+            .zip(out_chs)
+            .enumerate()
+            .map(|(n, (i, channels))| OutputTarget {
+                device: &self.outputs[i].device,
+                channels,
+                // Only the primary output is held to a chosen buffer size.
+                buffer_size: (n == 0).then_some(self.output_buffer()),
+                sample_rate: device::output_rate(&self.outputs[i].device, self.sample_rate),
+                eq: self.profiles.current().output_eq.get(&self.outputs[i].name),
+            })
+            .collect();
+        let resampled: Vec<String> = self
+            .output_indices()
+            .into_iter()
+            .zip(&targets)
+            .filter(|(_, t)| t.sample_rate != self.sample_rate)
+            .map(|(i, t)| format!("{} at {} Hz", self.outputs[i].name, t.sample_rate))
+            .collect();
+
         let (engine, params) = match AudioEngine::build(
             input,
-            output,
+            &targets,
+            sidechain,
             self.sample_rate,
             self.buffer_size,
-            in_ch,
-            out_ch,
             self.volume,
+            self.fade_ms,
         ) {
             Ok(v) => v,
             Err(e) => {
@@ -179,324 +1127,3835 @@ impl VibetoneApp {
             }
         };
 
+        // Before the streams play, so the first block already runs on the
+        // controls and a standby start never leaks audio.
+        params.publish(self.controls());
+
         if let Err(e) = engine.input_stream.play() {
             self.error = Some(format!("Input stream: {e}"));
             return;
         }
-        if let Err(e) = engine.output_stream.play() {
-            self.error = Some(format!("Output stream: {e}"));
-            return;
+        for stream in &engine.output_streams {
+            if let Err(e) = stream.play() {
+                self.error = Some(format!("Output stream: {e}"));
+                return;
+            }
+        }
+        if let Some(stream) = &engine.sidechain_stream {
+            if let Err(e) = stream.play() {
+                self.error = Some(format!("Sidechain stream: {e}"));
+                return;
+            }
+        }
+
+        if self.source == Source::File {
+            if let Some(path) = self.source_file() {
+                crate::player::spawn_loader(Arc::downgrade(&params.file), path, self.sample_rate);
+            }
+        }
+
+        #[cfg(feature = "scripting")]
+        {
+            *params.script.path.lock().unwrap() = self.script_path();
+            crate::script::spawn_watcher(Arc::downgrade(&params.script));
         }
 
+        if self.loop_guard {
+            params.loop_guard.start();
+            self.probe_started = Some(Instant::now());
+        }
+        // The first periodic room check waits out the loopback probe.
+        self.room_started = None;
+        self.room_checked_at = Some(Instant::now());
+
+        let engine_default_buffer: Vec<String> = engine
+            .default_buffer
+            .iter()
+            .map(|&role| {
+                let size = if role == StreamRole::Input {
+                    self.buffer_size
+                } else {
+                    self.output_buffer()
+                };
+                format!("{role} refused a fixed {size}-frame buffer")
+            })
+            .collect();
         self.params_handle = Some(params);
         self.engine = Some(engine);
-        self.status = "LIVE".into();
-    }
-
-    fn stop(&mut self) {
-        self.engine = None;
-        self.params_handle = None;
-        self.status = "OFFLINE".into();
+        self.xrun_recoveries_seen = 0;
+        self.xruns_seen = (0, 0);
+        self.last_dropout = None;
+        self.gate_since = None;
+        self.stage_faults_seen = 0;
+        self.input_lost_seen = false;
+        self.loudness_history.clear();
+        self.session_start = Instant::now();
+        self.hours_alerted = 0;
+        self.stop_warned_at = None;
+        self.speaking_samples_seen = 0;
+        self.remember_devices();
+        self.status = self.live_status();
+        if !resampled.is_empty() {
+            self.notice = Some(format!(
+                "Resampling {} Hz for {}",
+                self.sample_rate,
+                resampled.join(", ")
+            ));
+        }
+        if !engine_default_buffer.is_empty() {
+            self.notice = Some(format!(
+                "{}; running at the driver's own size",
+                engine_default_buffer.join(", ")
+            ));
+        }
+        if in_ch < 2 {
+            match self.channel_mode {
+                ChannelMode::Mix => {}
+                ChannelMode::Beam => {
+                    self.notice =
+                        Some("Input is mono: BEAM needs two mics, so it plays mic 1".into())
+                }
+                mode => {
+                    self.notice = Some(format!(
+                        "Input is mono: {} plays it in both ears",
+                        mode.label()
+                    ))
+                }
+            }
+        }
     }
 
-    fn sync_params(&self) {
-        let Some(p) = &self.params_handle else {
-            return;
-        };
-        p.volume.store(self.volume);
-        p.noise_gate_enabled
-            .store(self.noise_gate, Ordering::Relaxed);
-        p.noise_gate_threshold.store(self.noise_gate_threshold);
-        p.highpass_enabled
-            .store(self.voice_filter, Ordering::Relaxed);
-        p.lowpass_enabled
-            .store(self.voice_filter, Ordering::Relaxed);
+    /// START/STOP.
+    fn toggle_running(&mut self) {
+        if self.is_running() {
+            self.stop();
+        } else {
+            self.start();
+        }
     }
 
-    fn section_label(ui: &mut egui::Ui, text: &str) {
-        ui.label(
-            egui::RichText::new(text)
-                .color(DIM)
-                .size(10.0)
-                .strong(),
-        );
+    fn toggle_standby(&mut self) {
+        self.standby = !self.standby;
+        if self.is_running() && self.stopping_since.is_none() {
+            self.status = self.live_status();
+        }
     }
 
-    fn neon_separator(ui: &mut egui::Ui, color: egui::Color32) {
-        let available = ui.available_width();
-        let (rect, _) = ui.allocate_exact_size(
-            egui::vec2(available, 1.0),
-            egui::Sense::hover(),
-        );
-        ui.painter().line_segment(
-            [rect.left_center(), rect.right_center()],
-            egui::Stroke::new(0.5, color),
-        );
+    /// Per-frame bookkeeping after the UI has run: push parameters to the
+    /// audio thread, finish a fade-out, and react to what it reported.
+    fn tick(&mut self) {
+        self.check_schedule();
+        self.sync_params();
+        if self.stopping_since.is_some() {
+            self.finish_stop();
+        }
+        if self.is_running() {
+            self.check_loopback();
+            self.check_beam();
+            self.check_room();
+            self.check_format_change();
+            self.check_events();
+            self.check_xrun_recoveries();
+            self.check_dropouts();
+            self.check_stage_faults();
+            self.check_input_loss();
+            self.sample_loudness();
+            self.check_hour_mark();
+            self.check_auto_stop();
+            self.check_break();
+            self.check_idle();
+        }
+        self.update_on_air();
+        self.check_headset();
     }
-}
 
-impl eframe::App for VibetoneApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if !self.style_init {
-            setup_style(ctx);
-            self.style_init = true;
+    /// Status line while running.
+    fn live_status(&self) -> String {
+        match (self.standby, self.channel_mode) {
+            (true, _) => "STANDBY".into(),
+            (false, ChannelMode::Mix) => "LIVE".into(),
+            (false, ChannelMode::Stereo) => "LIVE STEREO".into(),
+            (false, ChannelMode::Beam) => "LIVE BEAM".into(),
+            (false, ChannelMode::Split) => "LIVE SPLIT".into(),
         }
+    }
 
-        let running = self.is_running();
-        let accent = if running { CYAN } else { MAGENTA };
+    /// Primary output index, then the secondary one if selected.
+    fn output_indices(&self) -> Vec<usize> {
+        std::iter::once(self.selected_output)
+            .chain(self.selected_aux_output)
+            .collect()
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.add_space(4.0);
+    /// Current default channel counts: input, then one per output.
+    fn negotiate_channels(&self) -> Result<(u16, Vec<u16>)> {
+        let input = &self.inputs[self.selected_input].device;
+        let mut in_ch = 0;
+        let mut out_chs = Vec::new();
+        for i in self.output_indices() {
+            let (ic, oc) = device::negotiate_config(input, &self.outputs[i].device)?;
+            in_ch = ic;
+            out_chs.push(oc);
+        }
+        Ok((in_ch, out_chs))
+    }
 
-            // ── Logo ──
-            ui.vertical_centered(|ui| {
-                ui.label(
-                    egui::RichText::new(LOGO)
-                        .monospace()
-                        .color(accent)
-                        .size(28.0)
-                        .strong(),
-                );
-                ui.label(
-                    egui::RichText::new("hear yourself vibe")
-                        .color(DIM)
-                        .size(11.0),
-                );
-            });
+    fn read_meter(&mut self) {
+        if let Some(engine) = &mut self.engine {
+            self.meter = *engine.meter();
+        }
+    }
 
-            ui.add_space(6.0);
-            Self::neon_separator(ui, accent);
-            ui.add_space(4.0);
+    /// Follow the output peak for vibe mode. Only runs at the meter's
+    /// repaint rate, so it never asks for extra frames.
+    fn update_pulse(&mut self) {
+        let target = if self.pulse_enabled && self.is_running() {
+            ((self.meter.peak_db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        // Fast up, slower down, like a glow rather than a strobe.
+        let rate = if target > self.pulse { 0.6 } else { 0.2 };
+        self.pulse += (target - self.pulse) * rate;
+    }
 
-            // ── Routing ──
-            Self::section_label(ui, "ROUTING");
-            ui.add_space(2.0);
-
-            ui.add_enabled_ui(!running, |ui| {
-                egui::Grid::new("routing")
-                    .num_columns(2)
-                    .spacing([10.0, 4.0])
-                    .show(ui, |ui| {
-                        ui.label(egui::RichText::new("IN").color(CYAN).strong().size(11.0));
-                        let in_name = if self.inputs.is_empty() {
-                            "No devices".into()
-                        } else {
-                            self.inputs[self.selected_input].name.clone()
-                        };
-                        egui::ComboBox::from_id_salt("in")
-                            .selected_text(egui::RichText::new(&in_name).color(TEXT_BRIGHT))
-                            .width(310.0)
-                            .show_ui(ui, |ui| {
-                                for (i, e) in self.inputs.iter().enumerate() {
-                                    ui.selectable_value(&mut self.selected_input, i, &e.name);
-                                }
-                            });
-                        ui.end_row();
+    fn sample_loudness(&mut self) {
+        if self.last_loudness_sample.elapsed() < LOUDNESS_INTERVAL {
+            return;
+        }
+        self.last_loudness_sample = Instant::now();
+        let t = self.session_start.elapsed().as_secs_f32();
+        self.loudness_history.push((t, self.meter.short_term_lufs));
+    }
 
-                        ui.label(egui::RichText::new("OUT").color(MAGENTA).strong().size(11.0));
-                        let out_name = if self.outputs.is_empty() {
-                            "No devices".into()
-                        } else {
-                            self.outputs[self.selected_output].name.clone()
-                        };
-                        egui::ComboBox::from_id_salt("out")
-                            .selected_text(egui::RichText::new(&out_name).color(TEXT_BRIGHT))
-                            .width(310.0)
-                            .show_ui(ui, |ui| {
-                                for (i, e) in self.outputs.iter().enumerate() {
-                                    ui.selectable_value(&mut self.selected_output, i, &e.name);
-                                }
-                            });
-                        ui.end_row();
-                    });
+    /// Write the loudness history to a CSV file in the working directory.
+    fn export_loudness_csv(&self) -> Result<std::path::PathBuf> {
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let path = std::env::current_dir()?.join(format!("vibetone-loudness-{stamp}.csv"));
+        let mut csv = String::from("seconds,short_term_lufs\n");
+        for (t, lufs) in &self.loudness_history {
+            csv.push_str(&format!("{t:.2},{lufs:.1}\n"));
+        }
+        std::fs::write(&path, csv)?;
+        Ok(path)
+    }
 
-                ui.add_space(2.0);
+    /// The gain structure of the chain as set up now, with what the
+    /// dynamics are doing while running.
+    fn gain_sheet(&self) -> String {
+        fn name(list: &[DeviceEntry], i: usize) -> &str {
+            list.get(i).map_or("", |e| e.name.as_str())
+        }
+        let eq = &self.profiles.current().output_eq;
+        let outputs: Vec<SheetOutput> = self
+            .output_indices()
+            .into_iter()
+            .map(|i| SheetOutput {
+                name: name(&self.outputs, i),
+                eq: eq.get(name(&self.outputs, i)),
+            })
+            .collect();
+        gainsheet::render(
+            &self.controls(),
+            name(&self.inputs, self.selected_input),
+            self.selected_sidechain.map(|i| name(&self.inputs, i)),
+            &outputs,
+            self.is_running().then_some(&self.meter),
+        )
+    }
 
-                ui.horizontal(|ui| {
-                    ui.add_space(2.0);
-                    ui.label(egui::RichText::new("BUF").color(DIM).size(10.0));
-                    egui::ComboBox::from_id_salt("buf")
-                        .selected_text(
-                            egui::RichText::new(format!("{}", self.buffer_size)).color(TEXT_BRIGHT),
-                        )
-                        .width(70.0)
-                        .show_ui(ui, |ui| {
-                            for &s in ALL_BUFFER_SIZES {
-                                ui.selectable_value(&mut self.buffer_size, s, format!("{s}"));
-                            }
-                        });
+    /// What a snapshot is annotated with: the input, the levels and every
+    /// stage that's on.
+    fn snapshot_caption(&self) -> Vec<String> {
+        if !self.is_running() {
+            return Vec::new();
+        }
+        let input = self
+            .inputs
+            .get(self.selected_input)
+            .map_or("", |e| e.name.as_str());
+        let mut caption = vec![
+            format!(
+                "VIBETONE  {}  {input} @ {} Hz",
+                chrono::Local::now().format("%Y-%m-%d %H:%M"),
+                self.sample_rate
+            ),
+            format!(
+                "peak {:.1} dBFS, {:.1} LUFS short-term",
+                self.meter.peak_db, self.meter.short_term_lufs
+            ),
+        ];
+        caption.extend(gainsheet::active_stages(&self.controls()));
+        caption
+    }
 
-                    ui.label(egui::RichText::new("RATE").color(DIM).size(10.0));
-                    egui::ComboBox::from_id_salt("rate")
-                        .selected_text(
-                            egui::RichText::new(format!("{} Hz", self.sample_rate))
-                                .color(TEXT_BRIGHT),
-                        )
-                        .width(90.0)
-                        .show_ui(ui, |ui| {
-                            for &r in ALL_SAMPLE_RATES {
-                                ui.selectable_value(&mut self.sample_rate, r, format!("{r} Hz"));
-                            }
-                        });
+    /// Save the screenshot a SNAPSHOT asked for, once it arrives, as a PNG
+    /// in the working directory.
+    fn save_snapshot(&mut self, ctx: &egui::Context) {
+        if self.snapshot_caption.is_none() {
+            return;
+        }
+        let shot = ctx.input(|i| {
+            i.raw.events.iter().find_map(|e| match e {
+                egui::Event::Screenshot { image, .. } => Some(Arc::clone(image)),
+                _ => None,
+            })
+        });
+        let Some(shot) = shot else {
+            ctx.request_repaint();
+            return;
+        };
+        self.snapshot_caption = None;
+        let save = || -> Result<std::path::PathBuf> {
+            let stamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let path = std::env::current_dir()?.join(format!("vibetone-snapshot-{stamp}.png"));
+            let [w, h] = shot.size;
+            let image = image::RgbaImage::from_raw(w as u32, h as u32, shot.as_raw().to_vec())
+                .ok_or_else(|| anyhow::anyhow!("bad screenshot size"))?;
+            image.save(&path)?;
+            Ok(path)
+        };
+        match save() {
+            Ok(path) => self.notice = Some(format!("Saved {}", path.display())),
+            Err(e) => self.error = Some(format!("Snapshot: {e}")),
+        }
+    }
 
-                    let ms = self.buffer_size as f64 / self.sample_rate as f64 * 1000.0;
-                    ui.label(
-                        egui::RichText::new(format!("{ms:.1}ms"))
-                            .color(accent)
-                            .size(10.0),
-                    );
-                });
-            });
+    /// Write the gain sheet to a text file in the working directory.
+    fn export_gain_sheet(&self) -> Result<std::path::PathBuf> {
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let path = std::env::current_dir()?.join(format!("vibetone-gains-{stamp}.txt"));
+        std::fs::write(&path, self.gain_sheet())?;
+        Ok(path)
+    }
 
-            // Validate config against current devices
-            self.config_warning = if !self.inputs.is_empty() && !self.outputs.is_empty() {
-                device::validate_config(
-                    &self.inputs[self.selected_input].device,
-                    &self.outputs[self.selected_output].device,
-                    self.buffer_size,
-                    self.sample_rate,
-                )
-                .err()
-            } else {
-                None
-            };
+    /// Chime once per full hour of the session.
+    fn check_hour_mark(&mut self) {
+        let Some(params) = &self.params_handle else {
+            return;
+        };
+        let hours = self.session_start.elapsed().as_secs() / 3600;
+        if hours > self.hours_alerted {
+            self.hours_alerted = hours;
+            params.trigger_alert(Alert::HourMark);
+        }
+    }
 
-            if let Some(warning) = &self.config_warning {
-                ui.add_space(2.0);
-                ui.label(
-                    egui::RichText::new(warning.as_str())
-                        .color(egui::Color32::from_rgb(255, 200, 50))
-                        .size(10.0),
-                );
+    /// Warn a minute before the auto-stop time, then stop (with the usual fade).
+    fn check_auto_stop(&mut self) {
+        if !self.auto_stop || self.stopping_since.is_some() {
+            return;
+        }
+        let limit = Duration::from_secs(self.auto_stop_minutes as u64 * 60);
+        let elapsed = self.session_start.elapsed();
+        if elapsed >= limit {
+            self.stop();
+            self.notice = Some(format!("Auto-stopped after {} min", self.auto_stop_minutes));
+        } else if limit - elapsed <= Duration::from_secs(60) && self.stop_warned_at.is_none() {
+            self.stop_warned_at = Some(Instant::now());
+            if let Some(params) = &self.params_handle {
+                params.trigger_alert(Alert::StopWarning);
             }
-
-            ui.add_space(4.0);
-            Self::neon_separator(ui, DIM);
+            self.notice = Some("Auto-stop in 1 minute".into());
+        }
+    }
+
+    /// Track speaking time from the audio thread's counter and remind the
+    /// user to take a break once enough of it piles up.
+    fn check_break(&mut self) {
+        let Some(params) = &self.params_handle else {
+            return;
+        };
+        let samples = params.speaking_samples.load(Ordering::Relaxed);
+        let new = samples.saturating_sub(self.speaking_samples_seen);
+        self.speaking_samples_seen = samples;
+        if !self.break_enabled {
+            // Talk while BREAK is off doesn't count toward the next one.
+            self.speaking_since_break = Duration::ZERO;
+        } else if new > 0 {
+            let secs = new as f64 / self.sample_rate as f64;
+            self.speaking_since_break += Duration::from_secs_f64(secs);
+            self.last_spoke = Instant::now();
+        } else if self.last_spoke.elapsed() >= BREAK_PAUSE {
+            self.speaking_since_break = Duration::ZERO;
+        }
+
+        if self.break_duck_until.is_some_and(|t| Instant::now() >= t) {
+            self.break_duck_until = None;
+        }
+        let limit = Duration::from_secs(self.break_minutes as u64 * 60);
+        if self.break_enabled && self.speaking_since_break >= limit {
+            self.speaking_since_break = Duration::ZERO;
+            self.break_duck_until = Some(Instant::now() + BREAK_DUCK);
+            self.notice = Some(format!(
+                "Break time: {} min of talking. Rest your voice for a bit",
+                self.break_minutes
+            ));
+        }
+    }
+
+    /// Lower the volume once the user has been away long enough, and bring
+    /// it back on the first input.
+    fn check_idle(&mut self) {
+        let away = self.idle_enabled
+            && self
+                .idle_monitor
+                .idle_time()
+                .is_some_and(|t| t.as_secs() >= self.idle_minutes as u64 * 60);
+        if away == self.idle_away {
+            return;
+        }
+        self.idle_away = away;
+        self.notice = Some(if away {
+            "Idle: monitor volume lowered".into()
+        } else {
+            "Welcome back: monitor volume restored".into()
+        });
+    }
+
+    fn effective_volume(&self) -> f32 {
+        let volume = match (self.idle_away, self.idle_mute) {
+            (false, _) => self.volume,
+            (true, true) => 0.0,
+            (true, false) => self.volume * db_to_gain(IDLE_DROP_DB),
+        };
+        if self.break_duck_until.is_some() {
+            volume * db_to_gain(BREAK_DUCK_DB)
+        } else {
+            volume
+        }
+    }
+
+    /// Log output glitch recoveries done by the audio thread.
+    fn check_xrun_recoveries(&mut self) {
+        let Some(params) = &self.params_handle else {
+            return;
+        };
+        let total = params.xrun_recoveries.load(Ordering::Relaxed);
+        if total == self.xrun_recoveries_seen {
+            return;
+        }
+        self.xrun_recoveries_seen = total;
+        self.log_event(&format!(
+            "output underrun burst: ring flushed and re-primed ({total} this session)"
+        ));
+        self.notice = Some(format!("Audio glitch recovered ({total} this session)"));
+    }
+
+    /// Take in what the callbacks reported since the last frame.
+    fn check_events(&mut self) {
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+        let events: Vec<Event> = engine.events().collect();
+        for event in events {
+            self.handle_event(event);
+        }
+        if let Some(reason) = self.failover.take() {
+            self.fail_over(&reason);
+        }
+    }
+
+    /// Restart with the backup output as OUT, after OUT failed with `reason`.
+    /// The backup is used up: a second failure is shown, not failed over.
+    fn fail_over(&mut self, reason: &str) {
+        let Some(backup) = self.selected_backup_output.take() else {
+            return;
+        };
+        let failed = self.outputs[self.selected_output].name.clone();
+        let name = self.outputs[backup].name.clone();
+        self.selected_output = backup;
+        self.dedup_picks();
+        self.stop_now();
+        self.start();
+        if self.is_running() {
+            self.notice = Some(format!(
+                "{failed} failed ({reason}): OUT moved to the backup, {name}"
+            ));
+        } else if let Some(e) = self.error.take() {
+            self.error = Some(format!(
+                "{failed} failed ({reason}), and so did the backup: {e}"
+            ));
+        }
+    }
+
+    /// Log a callback event; a stream error nothing else reports is also
+    /// shown as the error.
+    fn handle_event(&mut self, event: Event) {
+        let line = match event {
+            Event::Gate(open) => {
+                self.gate_since = Some((open, Instant::now()));
+                return;
+            }
+            Event::Clip(peak_db) => format!("clipped, peak {peak_db:+.1} dBFS"),
+            Event::StreamError(role, err) => {
+                let line = format!("{role} error: {err}");
+                // An invalidated stream is rebuilt, and a lost input
+                // has its own policy and message.
+                let handled = matches!(err, StreamError::StreamInvalidated)
+                    || (role == StreamRole::Input
+                        && matches!(err, StreamError::DeviceNotAvailable));
+                if !handled
+                    && role == StreamRole::Output(0)
+                    && self.selected_backup_output.is_some()
+                {
+                    self.failover = Some(err.to_string());
+                } else if !handled {
+                    self.error = Some(line.clone());
+                }
+                line
+            }
+        };
+        self.log_event(&line);
+    }
+
+    /// Add a timestamped line to the session's event log, dropping the
+    /// oldest once it's full.
+    fn log_event(&mut self, line: &str) {
+        if self.event_log.len() == EVENT_LOG_LEN {
+            self.event_log.pop_front();
+        }
+        self.event_log.push_back(format!(
+            "{} {line}",
+            chrono::Local::now().format("%H:%M:%S")
+        ));
+    }
+
+    /// Note when the outputs last dropped audio, from the callbacks' counts.
+    fn check_dropouts(&mut self) {
+        let Some(params) = &self.params_handle else {
+            return;
+        };
+        let xruns = (
+            params.underruns.load(Ordering::Relaxed),
+            params.overruns.load(Ordering::Relaxed),
+        );
+        if xruns != self.xruns_seen {
+            self.xruns_seen = xruns;
+            self.last_dropout = Some(Instant::now());
+        }
+    }
+
+    /// Follow the gate's trigger key, or learn a new one.
+    fn trigger_keys(&mut self, ctx: &egui::Context) {
+        // Keys typed into a text field aren't presses; letting go still
+        // counts, so a held key can't stick.
+        let typing = ctx.wants_keyboard_input();
+        let keys: Vec<(egui::Key, bool)> = ctx.input(|i| {
+            i.events
+                .iter()
+                .filter_map(|e| match *e {
+                    egui::Event::Key {
+                        key,
+                        pressed,
+                        repeat: false,
+                        ..
+                    } if !(typing && pressed) => Some((key, pressed)),
+                    _ => None,
+                })
+                .collect()
+        });
+        for (key, pressed) in keys {
+            self.trigger_key(key, pressed);
+        }
+    }
+
+    fn trigger_key(&mut self, key: egui::Key, pressed: bool) {
+        if self.learning_trigger {
+            if pressed {
+                self.learning_trigger = false;
+                self.profiles.current_mut().gate_trigger_key = key.name().into();
+            }
+            return;
+        }
+        if key.name() != self.profiles.current().gate_trigger_key {
+            return;
+        }
+        self.trigger_held = pressed;
+        if pressed {
+            self.trigger_latched = !self.trigger_latched;
+        }
+    }
+
+    /// Switch on a feature from the WHAT'S NEW panel, or back off.
+    fn set_feature(&mut self, feature: Feature, on: bool) {
+        match feature {
+            Feature::MidSide => self.mid_side = on,
+            Feature::LoopGuard => self.loop_guard = on,
+            Feature::HeadsetButton => {
+                self.profiles.current_mut().headset_button = on;
+                self.apply_headset();
+            }
+        }
+    }
+
+    fn feature_on(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::MidSide => self.mid_side,
+            Feature::LoopGuard => self.loop_guard,
+            Feature::HeadsetButton => self.profiles.current().headset_button,
+        }
+    }
+
+    /// Close WHAT'S NEW for good: everything in it has been seen.
+    fn dismiss_whats_new(&mut self) {
+        self.whats_new.clear();
+        self.profiles.set_whats_new_seen(whatsnew::latest());
+    }
+
+    /// The announcements not seen yet, each item with a toggle for the
+    /// feature it's about, if it has one.
+    fn whats_new_window(&mut self, ctx: &egui::Context) {
+        if self.whats_new.is_empty() {
+            return;
+        }
+        let mut open = true;
+        let mut dismissed = false;
+        egui::Window::new(egui::RichText::new("WHAT'S NEW").color(CYAN).strong())
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                for announcement in self.whats_new.clone() {
+                    ui.label(
+                        egui::RichText::new(announcement.title)
+                            .color(TEXT_BRIGHT)
+                            .strong()
+                            .size(12.0),
+                    );
+                    for item in announcement.items {
+                        ui.horizontal(|ui| {
+                            match item.feature {
+                                Some(feature) => {
+                                    let mut on = self.feature_on(feature);
+                                    if ui
+                                        .checkbox(&mut on, "")
+                                        .on_hover_text("switch it on here")
+                                        .changed()
+                                    {
+                                        self.set_feature(feature, on);
+                                    }
+                                }
+                                None => {
+                                    ui.label(egui::RichText::new("·").color(DIM));
+                                }
+                            }
+                            ui.label(egui::RichText::new(item.text).color(TEXT).size(10.0));
+                        });
+                    }
+                    ui.add_space(6.0);
+                }
+                ui.vertical_centered(|ui| {
+                    dismissed = ui.button("GOT IT").clicked();
+                });
+            });
+        if dismissed || !open {
+            self.dismiss_whats_new();
+        }
+    }
+
+    /// Zoom to the profile's UI scale and resize the window with it. The
+    /// new zoom only takes effect next frame, so the resize waits for it;
+    /// the size in points stays the same, which scales the window.
+    fn apply_ui_scale(&mut self, ctx: &egui::Context) {
+        if let Some(size) = self.resize_to {
+            if ctx.zoom_factor() == self.ui_scale_applied {
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+                self.resize_to = None;
+            }
+            return;
+        }
+        let (min, max) = (UI_SCALES[0], UI_SCALES[UI_SCALES.len() - 1]);
+        let scale = self.profiles.current().ui_scale.clamp(min, max);
+        if scale != self.ui_scale_applied {
+            let size = ctx.input(|i| i.viewport().inner_rect.map(|r| r.size()));
+            self.resize_to = Some(size.unwrap_or(WINDOW_SIZE));
+            ctx.set_zoom_factor(scale);
+            self.ui_scale_applied = scale;
+            ctx.request_repaint();
+        }
+    }
+
+    /// Report stages the audio thread has bypassed after a panic.
+    fn check_stage_faults(&mut self) {
+        let Some(params) = &self.params_handle else {
+            return;
+        };
+        let faults = params.faulted_stages.load(Ordering::Relaxed);
+        if faults == self.stage_faults_seen {
+            return;
+        }
+        self.stage_faults_seen = faults;
+        let stages: Vec<&str> = Stage::ALL
+            .iter()
+            .filter(|s| faults & s.bit() != 0)
+            .map(|s| s.label())
+            .collect();
+        let stages = stages.join(", ");
+        self.log_event(&format!(
+            "audio stage panicked, bypassed until restart: {stages}"
+        ));
+        self.error = Some(format!(
+            "{stages} crashed and is bypassed (restart to retry)"
+        ));
+    }
+
+    /// Act on the input failing or stalling: the outputs already hold
+    /// silence (or cue) on their own; STOP also tears the engine down.
+    /// Once the loopback probe has played, stop at once if the input
+    /// heard it: that output would feed the input into runaway feedback.
+    fn check_loopback(&mut self) {
+        let (Some(params), Some(started)) = (&self.params_handle, self.probe_started) else {
+            return;
+        };
+        if started.elapsed() < Duration::from_millis(loopguard::PROBE_MS) {
+            return;
+        }
+        self.probe_started = None;
+        if params.loop_guard.finish() {
+            let output = self.outputs[self.selected_output].name.clone();
+            self.log_event(&format!("loopback: {output} feeds back into the input"));
+            self.stop_now();
+            self.error = Some(format!(
+                "{output} feeds straight back into the input: stopped before it could \
+                 build up (pick another output or input, or turn off LOOP GUARD)"
+            ));
+        }
+    }
+
+    /// Aim BEAM's null once calibration has listened long enough.
+    fn check_beam(&mut self) {
+        let (Some(params), Some(started)) = (&self.params_handle, self.beam_started) else {
+            return;
+        };
+        if started.elapsed() < Duration::from_millis(beam::CALIBRATE_MS) {
+            return;
+        }
+        self.beam_started = None;
+        self.beam_status = Some(match params.beam.finish(self.sample_rate) {
+            Ok(steering) => {
+                self.beam_steering = steering;
+                let ms = steering.lag as f32 * 1000.0 / self.sample_rate as f32;
+                Ok(format!(
+                    "noise nulled: mic 2 {ms:+.2} ms, ×{:.2}",
+                    steering.gain
+                ))
+            }
+            Err(e) => Err(format!("{e:#}")),
+        });
+    }
+
+    fn start_room_check(&mut self) {
+        if let Some(params) = &self.params_handle {
+            params.room.start();
+            self.room_started = Some(Instant::now());
+            self.room_checked_at = self.room_started;
+        }
+    }
+
+    /// Finish a room check once it has listened long enough, and start the
+    /// periodic one when due. A check without a clap keeps the last reverb.
+    fn check_room(&mut self) {
+        let Some(params) = &self.params_handle else {
+            return;
+        };
+        let Some(started) = self.room_started else {
+            if self
+                .room_checked_at
+                .is_some_and(|t| t.elapsed() >= ROOM_RECHECK)
+            {
+                self.start_room_check();
+            }
+            return;
+        };
+        if started.elapsed() < Duration::from_millis(room::LISTEN_MS) {
+            return;
+        }
+        self.room_started = None;
+        if let Some(mut report) = params.room.finish(self.sample_rate) {
+            if let Some(last) = &self.room_report {
+                report.rt60 = report.rt60.or(last.rt60);
+            }
+            self.room_report = Some(report);
+        }
+    }
+
+    fn check_input_loss(&mut self) {
+        let Some(params) = &self.params_handle else {
+            return;
+        };
+        let lost = params.input_lost.load(Ordering::Relaxed);
+        if lost == self.input_lost_seen {
+            return;
+        }
+        self.input_lost_seen = lost;
+        if !lost {
+            self.log_event("input resumed");
+            self.error = None;
+            self.notice = Some("Input is back".into());
+            return;
+        }
+        self.log_event(&format!("input lost, policy {}", self.input_loss.label()));
+        self.error = Some(match self.input_loss {
+            InputLoss::Stop => {
+                self.stop();
+                "Input device lost: monitoring stopped".into()
+            }
+            InputLoss::Silence => "Input device lost: outputs held silent".into(),
+            InputLoss::Alert => "Input device lost: cue repeats until it's back".into(),
+        });
+    }
+
+    /// Rebuild the streams if a device renegotiated its channel count
+    /// mid-session (reported by the driver or seen by polling), so
+    /// monitoring carries on instead of playing garbled audio.
+    fn check_format_change(&mut self) {
+        if self.last_format_check.elapsed() < FORMAT_CHECK_INTERVAL {
+            return;
+        }
+        self.last_format_check = Instant::now();
+
+        if self.stopping_since.is_some() {
+            return;
+        }
+        let (Some(engine), Some(params)) = (&self.engine, &self.params_handle) else {
+            return;
+        };
+        let invalidated = params.stream_invalidated.swap(false, Ordering::Relaxed);
+        let running = (engine.in_channels, engine.out_channels.clone());
+        // Backends that can't query a device while it's open just skip polling.
+        let current = self.negotiate_channels().ok();
+        let changed = current.as_ref().is_some_and(|c| *c != running);
+        if !invalidated && !changed {
+            return;
+        }
+
+        self.stop_now();
+        self.start();
+        if self.is_running() {
+            let channels = |(i, o): &(u16, Vec<u16>)| {
+                let outs: Vec<String> = o.iter().map(|c| c.to_string()).collect();
+                format!("{i} in / {} out", outs.join("+"))
+            };
+            self.notice = Some(match current {
+                Some(now) if now != running => format!(
+                    "Device format changed ({} → {}), streams rebuilt",
+                    channels(&running),
+                    channels(&now)
+                ),
+                _ => "Device format changed, streams rebuilt".into(),
+            });
+        }
+    }
+
+    /// (Re)start the on-air publisher for the profile's broker and topic.
+    /// A publisher that's already connected there is kept.
+    fn apply_on_air(&mut self) {
+        let settings = self.profiles.current();
+        match onair::Target::parse(&settings.on_air_broker, &settings.on_air_topic) {
+            Ok(target) => {
+                self.on_air_error = None;
+                if self.on_air.as_ref().map(OnAir::target) != target.as_ref() {
+                    self.on_air = target.map(OnAir::spawn);
+                    self.on_air_sent = None;
+                }
+            }
+            Err(e) => {
+                self.on_air = None;
+                self.on_air_error = Some(format!("{e:#}"));
+            }
+        }
+    }
+
+    /// (Re)start the scheduler for the profile's schedule. One already
+    /// running for the same schedule is kept.
+    fn apply_schedule(&mut self) {
+        match Schedule::parse(&self.profiles.current().schedule) {
+            Ok(schedule) => {
+                self.schedule_error = None;
+                if self.scheduler.as_ref().map(Scheduler::schedule) != schedule.as_ref() {
+                    self.scheduler = schedule.map(Scheduler::spawn);
+                }
+            }
+            Err(e) => {
+                self.scheduler = None;
+                self.schedule_error = Some(format!("{e:#}"));
+            }
+        }
+    }
+
+    /// Start or stop when the scheduler says so. Either can still be done
+    /// by hand in between.
+    fn check_schedule(&mut self) {
+        let Some(action) = self
+            .scheduler
+            .as_ref()
+            .and_then(|s| s.actions.try_iter().last())
+        else {
+            return;
+        };
+        match action {
+            schedule::Action::Start if !self.is_running() => {
+                self.start();
+                if self.is_running() {
+                    self.notice = Some("Scheduled start".into());
+                }
+            }
+            schedule::Action::Stop if self.is_running() && self.stopping_since.is_none() => {
+                self.stop();
+                self.notice = Some("Scheduled stop".into());
+            }
+            _ => {}
+        }
+    }
+
+    /// Start or stop listening to the headset as the profile says.
+    fn apply_headset(&mut self) {
+        let wanted = self.profiles.current().headset_button;
+        if wanted != self.headset.is_some() {
+            self.headset = wanted.then(Headset::spawn);
+            self.headset_led = None;
+        }
+    }
+
+    /// Toggle STANDBY on each press of the headset's mute button, and keep
+    /// its mute LED in step with STANDBY however that was set.
+    fn check_headset(&mut self) {
+        let Some(presses) = self.headset.as_ref().map(|h| h.presses.try_iter().count()) else {
+            return;
+        };
+        if presses % 2 == 1 {
+            self.toggle_standby();
+            self.notice = Some(
+                if self.standby {
+                    "Headset: muted"
+                } else {
+                    "Headset: unmuted"
+                }
+                .into(),
+            );
+        }
+        if let Some(headset) = &self.headset {
+            if self.headset_led != Some(self.standby) {
+                headset.set_muted(self.standby);
+                self.headset_led = Some(self.standby);
+            }
+        }
+    }
+
+    fn air_state(&self) -> AirState {
+        match (self.is_running(), self.standby) {
+            (false, _) => AirState::Offline,
+            (true, true) => AirState::Muted,
+            (true, false) => AirState::Live,
+        }
+    }
+
+    /// Publish the on-air state when it changes.
+    fn update_on_air(&mut self) {
+        let state = self.air_state();
+        if let Some(on_air) = &self.on_air {
+            if self.on_air_sent != Some(state) {
+                on_air.publish(state);
+                self.on_air_sent = Some(state);
+            }
+        }
+    }
+
+    /// The `hum_mains_hz` param: 0 while the notch is off.
+    fn hum_mains(&self) -> u32 {
+        if self.hum {
+            self.hum_mains_hz
+        } else {
+            0
+        }
+    }
+
+    fn source_file(&self) -> Option<std::path::PathBuf> {
+        let path = self.profiles.current().source_file.trim();
+        (!path.is_empty()).then(|| path.into())
+    }
+
+    /// (Re)load the SRC FILE clip into the running engine.
+    fn load_source_file(&mut self) {
+        let (Some(params), Some(path)) = (&self.params_handle, self.source_file()) else {
+            return;
+        };
+        crate::player::spawn_loader(Arc::downgrade(&params.file), path, self.sample_rate);
+    }
+
+    #[cfg(feature = "scripting")]
+    fn script_path(&self) -> Option<std::path::PathBuf> {
+        let path = self.profiles.current().script_path.trim();
+        (!path.is_empty()).then(|| path.into())
+    }
+
+    /// Fade the outputs out, then drop the engine from `finish_stop`.
+    /// Pressing STOP again mid-fade stops immediately.
+    fn stop(&mut self) {
+        match &self.params_handle {
+            Some(params) if self.fade_ms > 0 && self.stopping_since.is_none() => {
+                params.fade_out();
+                self.stopping_since = Some(Instant::now());
+                self.status = "FADING".into();
+            }
+            _ => self.stop_now(),
+        }
+    }
+
+    fn finish_stop(&mut self) {
+        let Some(since) = self.stopping_since else {
+            return;
+        };
+        let faded = self
+            .params_handle
+            .as_deref()
+            .is_none_or(AudioParams::faded_out);
+        // Don't hang on a device that stopped calling back mid-fade.
+        let timeout = Duration::from_millis(self.fade_ms as u64) + FADE_GRACE;
+        if faded || since.elapsed() > timeout {
+            self.stop_now();
+        }
+    }
+
+    fn stop_now(&mut self) {
+        self.engine = None;
+        self.params_handle = None;
+        self.sync_pending_since = None;
+        self.stopping_since = None;
+        self.probe_started = None;
+        self.beam_started = None;
+        self.status = "OFFLINE".into();
+    }
+
+    /// The current settings of the stages a preset covers.
+    fn sound(&self) -> Sound {
+        Sound {
+            highpass: self.highpass,
+            lowpass: self.lowpass,
+            denoise: self.denoise,
+            noise_gate: self.noise_gate,
+            noise_gate_threshold: self.noise_gate_threshold,
+            agc: self.agc,
+            agc_target_db: self.agc_target_db,
+            agc_max_gain_db: self.agc_max_gain_db,
+            comp: self.comp,
+            comp_settings: (
+                self.comp_threshold,
+                self.comp_ratio,
+                self.comp_attack_ms,
+                self.comp_release_ms,
+                self.comp_makeup_db,
+            ),
+            saturation: self.saturation,
+            saturation_settings: (
+                self.saturation_drive_db,
+                self.saturation_tone_db,
+                self.saturation_trim_db,
+            ),
+            eq_enabled: self.eq_enabled,
+            eq_bands: self.eq_bands,
+            reverb: self.reverb,
+            reverb_kind: self.reverb_kind as u32,
+            reverb_decay_s: self.reverb_decay_s,
+            reverb_mix: self.reverb_mix,
+            limiter: self.limiter,
+            limiter_ceiling: self.limiter_ceiling,
+        }
+    }
+
+    fn apply_sound(&mut self, sound: &Sound) {
+        self.highpass = sound.highpass;
+        self.lowpass = sound.lowpass;
+        self.denoise = sound.denoise;
+        self.noise_gate = sound.noise_gate;
+        self.noise_gate_threshold = sound.noise_gate_threshold;
+        self.agc = sound.agc;
+        self.agc_target_db = sound.agc_target_db;
+        self.agc_max_gain_db = sound.agc_max_gain_db;
+        self.comp = sound.comp;
+        (
+            self.comp_threshold,
+            self.comp_ratio,
+            self.comp_attack_ms,
+            self.comp_release_ms,
+            self.comp_makeup_db,
+        ) = sound.comp_settings;
+        self.saturation = sound.saturation;
+        (
+            self.saturation_drive_db,
+            self.saturation_tone_db,
+            self.saturation_trim_db,
+        ) = sound.saturation_settings;
+        self.eq_enabled = sound.eq_enabled;
+        self.eq_bands = sound.eq_bands;
+        self.reverb = sound.reverb;
+        self.reverb_kind = ReverbKind::from_u32(sound.reverb_kind);
+        self.reverb_decay_s = sound.reverb_decay_s;
+        self.reverb_mix = sound.reverb_mix;
+        self.limiter = sound.limiter;
+        self.limiter_ceiling = sound.limiter_ceiling;
+    }
+
+    /// Keep the current sound as a user preset, replacing one of the same name.
+    fn save_preset(&mut self) {
+        let name = self.new_preset_name.trim().to_string();
+        if name.is_empty() {
+            return;
+        }
+        let preset = Preset {
+            name: name.clone(),
+            tags: self.new_preset_tags.clone(),
+            sound: self.sound(),
+        };
+        let presets = &mut self.profiles.current_mut().presets;
+        presets.retain(|p| p.name != name);
+        presets.push(preset);
+        self.new_preset_name.clear();
+        self.notice = Some(format!("Saved preset {name}"));
+    }
+
+    /// xorshift64 for VIBE.
+    fn vibe_next(&mut self) -> u64 {
+        self.vibe_rng ^= self.vibe_rng << 13;
+        self.vibe_rng ^= self.vibe_rng >> 7;
+        self.vibe_rng ^= self.vibe_rng << 17;
+        self.vibe_rng
+    }
+
+    /// Uniform in `lo..hi`.
+    fn vibe_uniform(&mut self, lo: f32, hi: f32) -> f32 {
+        lo + (hi - lo) * (self.vibe_next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Randomize the fun stages within bounds that still sound like a
+    /// voice: pitch (and now and then a harmony), slapback, reverb and the
+    /// call sim. PRIVACY is never touched, so a roll can't give the real
+    /// voice away.
+    fn roll_vibe(&mut self) {
+        if self.vibe_revert.is_none() {
+            self.vibe_revert = Some(self.controls());
+        }
+
+        // ±2..6 semitones with the formants kept: less is inaudible, more
+        // turns a voice into a cartoon.
+        let steps = 2 + self.vibe_next() % 5;
+        let sign = if self.vibe_next().is_multiple_of(3) {
+            1.0
+        } else {
+            -1.0
+        };
+        self.voice = true;
+        self.voice_pitch_semitones = sign * steps as f32;
+        self.voice_formant_semitones = 0.0;
+        // A fourth or fifth either way, or a third above.
+        const INTERVALS: [f32; 6] = [-7.0, -5.0, 3.0, 4.0, 5.0, 7.0];
+        self.harmony = self.vibe_next().is_multiple_of(3);
+        self.harmony_semitones = INTERVALS[(self.vibe_next() % INTERVALS.len() as u64) as usize];
+        self.harmony_mix = self.vibe_uniform(0.25, 0.5);
+        // Slapback: one short repeat, two at most.
+        self.echo = self.vibe_next().is_multiple_of(2);
+        self.echo_time_ms = self.vibe_uniform(70.0, 180.0);
+        self.echo_feedback = self.vibe_uniform(0.0, 0.3);
+        self.echo_mix = self.vibe_uniform(0.2, 0.45);
+        self.reverb = !self.vibe_next().is_multiple_of(3);
+        self.reverb_kind =
+            ReverbKind::ALL[(self.vibe_next() % ReverbKind::ALL.len() as u64) as usize];
+        self.reverb_decay_s = self.vibe_uniform(0.4, 2.5);
+        self.reverb_mix = self.vibe_uniform(0.1, 0.4);
+        self.call_sim = CallSim::ALL[(self.vibe_next() % CallSim::ALL.len() as u64) as usize];
+
+        let mut summary = vec![format!("PITCH {:+.0}st", self.voice_pitch_semitones)];
+        if self.harmony {
+            summary.push(format!("HARMONY {:+.0}st", self.harmony_semitones));
+        }
+        if self.echo {
+            summary.push(format!("SLAP {:.0}ms", self.echo_time_ms));
+        }
+        if self.reverb {
+            summary.push(format!(
+                "{} {:.1}s",
+                self.reverb_kind.label(),
+                self.reverb_decay_s
+            ));
+        }
+        summary.push(self.call_sim.label().to_string());
+        self.vibe_summary = Some(summary.join(" / "));
+    }
+
+    fn revert_vibe(&mut self) {
+        if let Some(c) = self.vibe_revert.take() {
+            self.voice = c.voice_enabled;
+            self.voice_pitch_semitones = c.voice_pitch_semitones;
+            self.voice_formant_semitones = c.voice_formant_semitones;
+            self.harmony = c.harmony_enabled;
+            self.harmony_semitones = c.harmony_semitones;
+            self.harmony_mix = c.harmony_mix;
+            self.echo = c.echo_enabled;
+            self.echo_time_ms = c.echo_time_ms;
+            self.echo_feedback = c.echo_feedback;
+            self.echo_mix = c.echo_mix;
+            self.reverb = c.reverb_enabled;
+            self.reverb_kind = c.reverb_kind;
+            self.reverb_decay_s = c.reverb_decay_s;
+            self.reverb_mix = c.reverb_mix;
+            self.call_sim = c.call_sim;
+        }
+        self.vibe_summary = None;
+    }
+
+    fn effective_alert_volume(&self) -> f32 {
+        // The auto-stop warning sounds even with the other alerts off.
+        let warning = self
+            .stop_warned_at
+            .is_some_and(|t| t.elapsed() < Duration::from_secs(2));
+        if self.alerts || warning {
+            self.alert_volume
+        } else {
+            0.0
+        }
+    }
+
+    fn sync_params(&mut self) {
+        let Some(p) = &self.params_handle else {
+            return;
+        };
+
+        let controls = self.controls();
+        let published = p.controls();
+        let generation = if published.controls != controls {
+            self.sync_pending_since.get_or_insert_with(Instant::now);
+            p.publish(controls)
+        } else {
+            published.generation
+        };
+
+        if p.acked_generation.load(Ordering::Acquire) == generation {
+            self.sync_pending_since = None;
+        }
+    }
+
+    /// Every control as the audio thread should run it.
+    fn controls(&self) -> Controls {
+        Controls {
+            volume: self.effective_volume(),
+            dry_wet: self.dry_wet,
+            noise_gate_enabled: self.noise_gate,
+            noise_gate_threshold: self.noise_gate_threshold,
+            denoise: self.denoise,
+            gate_music: self.gate_music,
+            gate_music_db: self.gate_music_db,
+            gate_min_open_ms: self.gate_min_open_ms,
+            gate_mode: self.gate_mode,
+            expander_ratio: self.expander_ratio,
+            expander_range_db: self.expander_range_db,
+            gate_forced: self
+                .gate_trigger
+                .forced(self.trigger_held, self.trigger_latched),
+            agc_enabled: self.agc,
+            agc_target_db: self.agc_target_db,
+            agc_max_gain_db: self.agc_max_gain_db,
+            comp_enabled: self.comp,
+            comp_threshold: self.comp_threshold,
+            comp_ratio: self.comp_ratio,
+            comp_attack_ms: self.comp_attack_ms,
+            comp_release_ms: self.comp_release_ms,
+            comp_makeup_db: self.comp_makeup_db,
+            saturation_enabled: self.saturation,
+            saturation_drive_db: self.saturation_drive_db,
+            saturation_tone_db: self.saturation_tone_db,
+            saturation_trim_db: self.saturation_trim_db,
+            limiter_enabled: self.limiter,
+            limiter_ceiling: self.limiter_ceiling,
+            echo_enabled: self.echo,
+            echo_time_ms: self.echo_time_ms,
+            echo_feedback: self.echo_feedback,
+            echo_mix: self.echo_mix,
+            reverb_enabled: self.reverb,
+            reverb_kind: self.reverb_kind,
+            reverb_decay_s: self.reverb_decay_s,
+            reverb_mix: self.reverb_mix,
+            highpass_enabled: self.highpass,
+            lowpass_enabled: self.lowpass,
+            filter_order: self.filter_order,
+            hum_mains_hz: self.hum_mains(),
+            effect_chain: self.effect_chain,
+            stage_mix: self.stage_mix,
+            hum_overtones: self.hum_overtones,
+            eq_enabled: self.eq_enabled,
+            eq_bands: self.eq_bands,
+            voice_enabled: self.voice,
+            voice_pitch_semitones: self.voice_pitch_semitones,
+            voice_formant_semitones: self.voice_formant_semitones,
+            robot_enabled: self.robot,
+            robot_mode: self.robot_mode,
+            robot_pitch_hz: self.robot_pitch_hz,
+            mid_side: self.mid_side,
+            beam_steering: self.beam_steering,
+            privacy_voice: self.privacy_voice,
+            harmony_enabled: self.harmony,
+            harmony_semitones: self.harmony_semitones,
+            harmony_mix: self.harmony_mix,
+            call_sim: self.call_sim,
+            source: self.source,
+            sidechain_mode: self.sidechain_mode,
+            sidechain_threshold: self.sidechain_threshold,
+            input_loss: self.input_loss,
+            standby: self.standby,
+            alert_volume: self.effective_alert_volume(),
+            output_delay_ms: self.output_delays(),
+            cue: self.cue_enabled.then(|| BusMix {
+                sends: self.cue_sends,
+                volume: self.cue_volume,
+                eq: self.cue_eq_enabled.then_some(self.cue_eq_bands),
+                ceiling_db: self.cue_ceiling,
+            }),
+            #[cfg(feature = "scripting")]
+            script_enabled: self.script_enabled,
+        }
+    }
+
+    /// The alignment delay kept for each selected output, in the order
+    /// they're opened.
+    fn output_delays(&self) -> [f32; MAX_OUTPUTS] {
+        let kept = &self.profiles.current().output_delay_ms;
+        let mut delays = [0.0; MAX_OUTPUTS];
+        for (delay, i) in delays.iter_mut().zip(self.output_indices()) {
+            if let Some(ms) = self.outputs.get(i).and_then(|e| kept.get(&e.name)) {
+                *delay = *ms;
+            }
+        }
+        delays
+    }
+
+    /// How long the latest parameter generation has been waiting for the
+    /// audio thread, or `None` if it has been picked up.
+    fn sync_pending(&self) -> Option<Duration> {
+        self.sync_pending_since.map(|t| t.elapsed())
+    }
+
+    /// A collapsible section of the main window. egui keeps the open state
+    /// in its memory, which eframe saves with the rest of the app state.
+    /// Frequency, gain and Q of each band of a four-band EQ.
+    fn eq_band_rows(ui: &mut egui::Ui, bands: &mut [EqBand; CHAIN_BANDS.len()]) {
+        const NAMES: [&str; CHAIN_BANDS.len()] = ["LOW", "MID", "MID", "HIGH"];
+        for (name, band) in NAMES.iter().zip(bands) {
+            ui.horizontal(|ui| {
+                ui.add_space(20.0);
+                ui.label(egui::RichText::new(*name).color(DIM).monospace().size(10.0));
+                let speed = band.freq * 0.01;
+                ui.add(
+                    egui::DragValue::new(&mut band.freq)
+                        .range(20.0..=20_000.0)
+                        .speed(speed)
+                        .suffix("Hz")
+                        .max_decimals(0),
+                );
+                ui.add(
+                    egui::Slider::new(&mut band.gain_db, -12.0..=12.0)
+                        .step_by(0.5)
+                        .suffix("dB"),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut band.q)
+                        .range(0.3..=8.0)
+                        .speed(0.01)
+                        .prefix("Q ")
+                        .max_decimals(2),
+                )
+                .on_hover_text("bandwidth: higher is narrower");
+            });
+        }
+    }
+
+    fn section(ui: &mut egui::Ui, title: &str, add: impl FnOnce(&mut egui::Ui)) {
+        egui::CollapsingHeader::new(egui::RichText::new(title).color(DIM).size(10.0).strong())
+            .id_salt(title)
+            .default_open(true)
+            .show(ui, add);
+    }
+
+    /// Peak meter for the processed signal. While the gate is fully
+    /// closed the bar turns amber and reads GATED, so a silent output can be
+    /// told apart from a broken one at a glance.
+    fn output_meter(ui: &mut egui::Ui, meter: &MeterFrame) {
+        let (peak_db, gated) = (meter.peak_db, meter.gated);
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new("OUT").color(DIM).strong().size(10.0));
+            let (rect, _) = ui.allocate_exact_size(
+                egui::vec2(ui.available_width() - 60.0, 8.0),
+                egui::Sense::hover(),
+            );
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 1.0, SURFACE);
+
+            let amber = egui::Color32::from_rgb(255, 200, 50);
+            let (fill, color, text) = if gated {
+                (1.0, amber.gamma_multiply(0.35), "GATED".to_string())
+            } else {
+                let color = if peak_db > -1.0 { MAGENTA } else { CYAN };
+                let fill =
+                    ((peak_db + METER_FLOOR_DB.abs()) / METER_FLOOR_DB.abs()).clamp(0.0, 1.0);
+                let text = if peak_db <= METER_FLOOR_DB {
+                    "-inf".to_string()
+                } else {
+                    format!("{peak_db:.0}dB")
+                };
+                (fill, color, text)
+            };
+            let mut bar = rect;
+            bar.set_width(rect.width() * fill);
+            painter.rect_filled(bar, 1.0, color);
+
+            ui.label(
+                egui::RichText::new(text)
+                    .color(if gated { amber } else { TEXT_BRIGHT })
+                    .monospace()
+                    .size(10.0),
+            );
+        });
+    }
+
+    /// The last few milliseconds of output waveform.
+    fn scope(ui: &mut egui::Ui, samples: &[f32], color: egui::Color32) {
+        let (rect, _) =
+            ui.allocate_exact_size(egui::vec2(ui.available_width(), 24.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, SURFACE);
+        let step = rect.width() / (samples.len() - 1).max(1) as f32;
+        let points: Vec<egui::Pos2> = samples
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| {
+                let y = rect.center().y - s.clamp(-1.0, 1.0) * rect.height() / 2.0;
+                egui::pos2(rect.left() + i as f32 * step, y)
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+    }
+
+    /// Peak hold and session clip count; click either to reset it.
+    fn meter_holds(ui: &mut egui::Ui, params: &AudioParams) {
+        ui.horizontal(|ui| {
+            ui.add_space(24.0);
+            let hold = params.peak_hold.load();
+            let hold_text = if hold <= METER_FLOOR_DB {
+                "PK -inf".to_string()
+            } else {
+                format!("PK {hold:.1}dB")
+            };
+            let hold_color = if hold > -1.0 { MAGENTA } else { TEXT_BRIGHT };
+            let pk = ui
+                .add(
+                    egui::Label::new(
+                        egui::RichText::new(hold_text)
+                            .color(hold_color)
+                            .monospace()
+                            .size(10.0),
+                    )
+                    .sense(egui::Sense::click()),
+                )
+                .on_hover_text("highest peak this session, click to reset");
+            if pk.clicked() {
+                params.peak_hold.store(gain_to_db(0.0));
+            }
+
+            let clips = params.clip_count.load(Ordering::Relaxed);
+            let clip_color = if clips > 0 { MAGENTA } else { DIM };
+            let clip = ui
+                .add(
+                    egui::Label::new(
+                        egui::RichText::new(format!("CLIP {clips}"))
+                            .color(clip_color)
+                            .monospace()
+                            .size(10.0),
+                    )
+                    .sense(egui::Sense::click()),
+                )
+                .on_hover_text("clipping events this session, click to reset");
+            if clip.clicked() {
+                params.clip_count.store(0, Ordering::Relaxed);
+            }
+        });
+    }
+
+    /// Session loudness timeline with dim guides at the common -23/-16 LUFS
+    /// delivery targets.
+    fn loudness_graph(ui: &mut egui::Ui, history: &[(f32, f32)], color: egui::Color32) {
+        let (rect, _) =
+            ui.allocate_exact_size(egui::vec2(ui.available_width(), 60.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, SURFACE);
+
+        let (lo, hi) = (*LOUDNESS_RANGE.start(), *LOUDNESS_RANGE.end());
+        let y = |lufs: f32| {
+            let t = (lufs.clamp(lo, hi) - lo) / (hi - lo);
+            rect.bottom() - t * rect.height()
+        };
+        for target in [-23.0, -16.0] {
+            painter.line_segment(
+                [
+                    egui::pos2(rect.left(), y(target)),
+                    egui::pos2(rect.right(), y(target)),
+                ],
+                egui::Stroke::new(0.5, DIM),
+            );
+        }
+
+        let Some(&(end, _)) = history.last() else {
+            return;
+        };
+        // One point per pixel column is plenty for a multi-hour read.
+        let step = (history.len() / rect.width().max(1.0) as usize).max(1);
+        let points: Vec<egui::Pos2> = history
+            .iter()
+            .step_by(step)
+            .map(|&(t, lufs)| {
+                let x = rect.left() + t / end.max(1.0) * rect.width();
+                egui::pos2(x, y(lufs))
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+    }
+
+    /// `glow` (0..1) thickens the line and adds a soft halo for vibe mode.
+    fn neon_separator(ui: &mut egui::Ui, color: egui::Color32, glow: f32) {
+        let available = ui.available_width();
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(available, 1.0), egui::Sense::hover());
+        let line = [rect.left_center(), rect.right_center()];
+        if glow > 0.01 {
+            let halo = egui::Stroke::new(4.0 * glow, color.gamma_multiply(0.25 * glow));
+            ui.painter().line_segment(line, halo);
+        }
+        ui.painter()
+            .line_segment(line, egui::Stroke::new(0.5 + glow, color));
+    }
+}
+
+impl eframe::App for VibetoneApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if !self.style_init {
+            setup_style(ctx);
+            self.style_init = true;
+        }
+
+        self.apply_ui_scale(ctx);
+        self.trigger_keys(ctx);
+        self.track_pair();
+        let running = self.is_running();
+        if running {
+            self.read_meter();
+            if let Some(params) = &self.params_handle {
+                params.file.free_retired();
+            }
+        }
+        self.update_pulse();
+        let accent = if running { CYAN } else { MAGENTA };
+        let glow = self.pulse;
+
+        // ── Action ── pinned to the bottom so START/STOP stays in reach
+        // however the sections above are folded or scrolled.
+        egui::TopBottomPanel::bottom("action").show(ctx, |ui| {
+            ui.add_space(6.0);
+            ui.vertical_centered(|ui| {
+                let btn_text;
+                let btn_fill;
+                let btn_stroke;
+
+                if running {
+                    btn_text = egui::RichText::new("    STOP    ")
+                        .strong()
+                        .size(16.0)
+                        .color(MAGENTA);
+                    btn_fill = egui::Color32::from_rgb(50, 10, 25);
+                    btn_stroke = egui::Stroke::new(1.5, MAGENTA);
+                } else {
+                    btn_text = egui::RichText::new("    START    ")
+                        .strong()
+                        .size(16.0)
+                        .color(CYAN);
+                    btn_fill = egui::Color32::from_rgb(8, 40, 35);
+                    btn_stroke = egui::Stroke::new(1.5, CYAN);
+                };
+
+                let btn = egui::Button::new(btn_text)
+                    .fill(btn_fill)
+                    .stroke(btn_stroke);
+
+                let can_start = !self.inputs.is_empty()
+                    && !self.outputs.is_empty()
+                    && self.config_warning.is_none();
+                let enabled = if running { true } else { can_start };
+
+                if ui.add_enabled(enabled, btn).clicked() {
+                    self.toggle_running();
+                }
+
+                let amber = egui::Color32::from_rgb(255, 200, 50);
+                let standby_text = egui::RichText::new("STANDBY")
+                    .size(10.0)
+                    .color(if self.standby { amber } else { DIM });
+                let standby = ui
+                    .add(
+                        egui::Button::new(standby_text)
+                            .small()
+                            .selected(self.standby),
+                    )
+                    .on_hover_text(
+                        "devices open and metering, outputs muted: going live is instant",
+                    );
+                if standby.clicked() {
+                    self.toggle_standby();
+                }
+
+                ui.add_space(4.0);
+
+                let (dot, status_color) = if running && self.standby {
+                    ("..", amber)
+                } else if running {
+                    (">>", CYAN)
+                } else {
+                    ("--", DIM)
+                };
+                ui.label(
+                    egui::RichText::new(format!("{dot} {} {dot}", self.status))
+                        .color(status_color)
+                        .monospace()
+                        .strong()
+                        .size(12.0),
+                );
+
+                if running {
+                    let elapsed = self.session_start.elapsed().as_secs();
+                    let mut timer = format_hms(elapsed);
+                    if self.auto_stop {
+                        let limit = self.auto_stop_minutes as u64 * 60;
+                        let left = limit.saturating_sub(elapsed);
+                        timer.push_str(&format!(" / -{}", format_hms(left)));
+                    }
+                    ui.label(
+                        egui::RichText::new(timer)
+                            .color(TEXT_BRIGHT)
+                            .monospace()
+                            .size(10.0),
+                    );
+                }
+
+                if let Some(notice) = &self.notice {
+                    ui.add_space(2.0);
+                    ui.label(egui::RichText::new(notice.as_str()).color(CYAN).size(10.0));
+                }
+
+                if let Some(err) = &self.error {
+                    ui.add_space(2.0);
+                    ui.label(
+                        egui::RichText::new(err.as_str())
+                            .color(egui::Color32::from_rgb(255, 80, 80))
+                            .size(11.0),
+                    );
+                }
+            });
             ui.add_space(4.0);
+        });
+
+        if !running {
+            self.follow_mixer_rate();
+            self.update_preflight();
+        }
+        self.whats_new_window(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.add_space(4.0);
+
+                // ── Logo ──
+                ui.vertical_centered(|ui| {
+                    let logo = egui::RichText::new(LOGO).monospace().size(28.0).strong();
+                    let response = ui.label(logo.clone().color(accent));
+                    if glow > 0.01 {
+                        // Soft halo: the logo again, faint and slightly offset.
+                        let halo = accent.gamma_multiply(0.35 * glow);
+                        let galley = egui::WidgetText::from(logo.color(halo)).into_galley(
+                            ui,
+                            Some(egui::TextWrapMode::Extend),
+                            f32::INFINITY,
+                            egui::TextStyle::Body,
+                        );
+                        for offset in [egui::vec2(-1.5, 0.0), egui::vec2(1.5, 0.0)] {
+                            ui.painter()
+                                .galley(response.rect.min + offset * glow, galley.clone(), halo);
+                        }
+                    }
+                    ui.label(
+                        egui::RichText::new("hear yourself vibe")
+                            .color(DIM)
+                            .size(11.0),
+                    );
+                    if ui
+                        .link(egui::RichText::new("what's new").color(DIM).size(10.0))
+                        .clicked()
+                    {
+                        self.whats_new = whatsnew::unseen(whatsnew::latest() - 1);
+                    }
+                });
+
+                ui.add_space(6.0);
+                Self::neon_separator(ui, accent, glow);
+                ui.add_space(4.0);
+
+                Self::section(ui, "ROUTING", |ui| {
+
+                    ui.add_enabled_ui(!running, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.add_space(2.0);
+                            ui.label(egui::RichText::new("PROFILE").color(DIM).size(10.0));
+                            let mut picked = None;
+                            egui::ComboBox::from_id_salt("profile")
+                                .selected_text(
+                                    egui::RichText::new(self.profiles.active()).color(TEXT_BRIGHT),
+                                )
+                                .width(110.0)
+                                .show_ui(ui, |ui| {
+                                    for name in self.profiles.names() {
+                                        let current = name == self.profiles.active();
+                                        if ui.selectable_label(current, name).clicked() && !current {
+                                            picked = Some(name.to_string());
+                                        }
+                                    }
+                                });
+                            if let Some(name) = picked {
+                                self.switch_profile(&name);
+                            }
+
+                            let edit = egui::TextEdit::singleline(&mut self.new_profile_name)
+                                .hint_text("new profile")
+                                .desired_width(90.0);
+                            ui.add(edit);
+                            let name = self.new_profile_name.trim().to_string();
+                            if ui
+                                .add_enabled(!name.is_empty(), egui::Button::new("ADD").small())
+                                .on_hover_text("copy this profile under a new name")
+                                .clicked()
+                            {
+                                self.remember_devices();
+                                self.profiles.duplicate(&name);
+                                self.apply_profile();
+                                self.new_profile_name.clear();
+                            }
+                            if self.profiles.names().count() > 1
+                                && ui
+                                    .small_button("DEL")
+                                    .on_hover_text("delete this profile")
+                                    .clicked()
+                            {
+                                self.profiles.remove_current();
+                                self.apply_profile();
+                            }
+                        });
+
+                        egui::Grid::new("routing")
+                            .num_columns(2)
+                            .spacing([10.0, 4.0])
+                            .show(ui, |ui| {
+                                ui.label(egui::RichText::new("IN").color(CYAN).strong().size(11.0));
+                                let in_name = if self.inputs.is_empty() {
+                                    "No devices".into()
+                                } else {
+                                    self.inputs[self.selected_input].name.clone()
+                                };
+                                egui::ComboBox::from_id_salt("in")
+                                    .selected_text(egui::RichText::new(&in_name).color(TEXT_BRIGHT))
+                                    .width(310.0)
+                                    .show_ui(ui, |ui| {
+                                        for (i, e) in self.inputs.iter().enumerate() {
+                                            ui.selectable_value(
+                                                &mut self.selected_input,
+                                                i,
+                                                device_label(e),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+
+                                ui.label(egui::RichText::new("OUT").color(MAGENTA).strong().size(11.0));
+                                let out_name = if self.outputs.is_empty() {
+                                    "No devices".into()
+                                } else {
+                                    self.outputs[self.selected_output].name.clone()
+                                };
+                                egui::ComboBox::from_id_salt("out")
+                                    .selected_text(egui::RichText::new(&out_name).color(TEXT_BRIGHT))
+                                    .width(310.0)
+                                    .show_ui(ui, |ui| {
+                                        for (i, e) in self.outputs.iter().enumerate() {
+                                            ui.selectable_value(
+                                                &mut self.selected_output,
+                                                i,
+                                                device_label(e),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+
+                                ui.label(egui::RichText::new("OUT 2").color(DIM).strong().size(11.0));
+                                let aux_name = match self.selected_aux_output {
+                                    Some(i) => self.outputs[i].name.clone(),
+                                    None => "none".into(),
+                                };
+                                egui::ComboBox::from_id_salt("out2")
+                                    .selected_text(egui::RichText::new(&aux_name).color(TEXT_BRIGHT))
+                                    .width(310.0)
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut self.selected_aux_output, None, "none");
+                                        for (i, e) in self.outputs.iter().enumerate() {
+                                            if i != self.selected_output {
+                                                ui.selectable_value(
+                                                    &mut self.selected_aux_output,
+                                                    Some(i),
+                                                    device_label(e),
+                                                );
+                                            }
+                                        }
+                                    });
+                                ui.end_row();
+
+                                ui.label(egui::RichText::new("BACKUP").color(DIM).strong().size(11.0))
+                                    .on_hover_text("takes over from OUT if it errors or disappears while running");
+                                let backup_name = match self.selected_backup_output {
+                                    Some(i) => self.outputs[i].name.clone(),
+                                    None => "none".into(),
+                                };
+                                egui::ComboBox::from_id_salt("out_backup")
+                                    .selected_text(egui::RichText::new(&backup_name).color(TEXT_BRIGHT))
+                                    .width(310.0)
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut self.selected_backup_output, None, "none");
+                                        for (i, e) in self.outputs.iter().enumerate() {
+                                            if i != self.selected_output {
+                                                ui.selectable_value(
+                                                    &mut self.selected_backup_output,
+                                                    Some(i),
+                                                    device_label(e),
+                                                );
+                                            }
+                                        }
+                                    });
+                                ui.end_row();
+                            });
+
+                        ui.add_space(2.0);
+
+                        ui.horizontal(|ui| {
+                            ui.add_space(2.0);
+                            ui.label(egui::RichText::new("BUF").color(DIM).size(10.0));
+                            egui::ComboBox::from_id_salt("buf")
+                                .selected_text(
+                                    egui::RichText::new(format!("{}", self.buffer_size)).color(TEXT_BRIGHT),
+                                )
+                                .width(70.0)
+                                .show_ui(ui, |ui| {
+                                    for &s in ALL_BUFFER_SIZES {
+                                        ui.selectable_value(&mut self.buffer_size, s, format!("{s}"));
+                                    }
+                                });
+
+                            ui.label(egui::RichText::new("OUT").color(DIM).size(10.0));
+                            egui::ComboBox::from_id_salt("out_buf")
+                                .selected_text(
+                                    egui::RichText::new(
+                                        self.out_buffer_size.map_or("SAME".to_string(), |s| s.to_string()),
+                                    )
+                                    .color(TEXT_BRIGHT),
+                                )
+                                .width(60.0)
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.out_buffer_size, None, "SAME");
+                                    for &s in ALL_BUFFER_SIZES {
+                                        ui.selectable_value(&mut self.out_buffer_size, Some(s), format!("{s}"));
+                                    }
+                                })
+                                .response
+                                .on_hover_text(
+                                    "OUT's own buffer size, for a DAC that only takes larger \
+                                     ones. The buffer between input and output bridges the two.",
+                                );
+
+                            ui.label(egui::RichText::new("RATE").color(DIM).size(10.0));
+                            egui::ComboBox::from_id_salt("rate")
+                                .selected_text(
+                                    egui::RichText::new(if self.rate_auto {
+                                        format!("AUTO {}", self.sample_rate)
+                                    } else {
+                                        format!("{} Hz", self.sample_rate)
+                                    })
+                                    .color(TEXT_BRIGHT),
+                                )
+                                .width(90.0)
+                                .show_ui(ui, |ui| {
+                                    if ui
+                                        .selectable_label(self.rate_auto, "AUTO")
+                                        .on_hover_text("the rate the OS mixer runs these devices at")
+                                        .clicked()
+                                    {
+                                        self.rate_auto = true;
+                                        self.rate_auto_for = None;
+                                    }
+                                    for &r in ALL_SAMPLE_RATES {
+                                        let picked = !self.rate_auto && self.sample_rate == r;
+                                        if ui.selectable_label(picked, format!("{r} Hz")).clicked() {
+                                            self.sample_rate = r;
+                                            self.rate_auto = false;
+                                        }
+                                    }
+                                });
+
+                            ui.label(egui::RichText::new("FADE").color(DIM).size(10.0));
+                            ui.add(
+                                egui::DragValue::new(&mut self.fade_ms)
+                                    .range(0..=2000)
+                                    .speed(5)
+                                    .suffix("ms"),
+                            )
+                            .on_hover_text("ramp output in/out on START/STOP to avoid pops");
+
+                            let ms = self.buffer_size as f64 / self.sample_rate as f64 * 1000.0;
+                            ui.label(
+                                egui::RichText::new(format!("{ms:.1}ms"))
+                                    .color(accent)
+                                    .size(10.0),
+                            );
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.add_space(2.0);
+                            ui.label(egui::RichText::new("MODE").color(DIM).size(10.0));
+                            egui::ComboBox::from_id_salt("channel_mode")
+                                .selected_text(
+                                    egui::RichText::new(self.channel_mode.label()).color(TEXT_BRIGHT),
+                                )
+                                .width(70.0)
+                                .show_ui(ui, |ui| {
+                                    for mode in ChannelMode::ALL {
+                                        ui.selectable_value(&mut self.channel_mode, mode, mode.label());
+                                    }
+                                });
+                            let help = match self.channel_mode {
+                                ChannelMode::Mix => "channels mixed, processed",
+                                ChannelMode::Stereo => "in 1 → left, in 2 → right, each processed",
+                                ChannelMode::Beam => "two mics, noise direction nulled, processed",
+                                ChannelMode::Split => "in 1 → left, in 2 → right, unprocessed",
+                            };
+                            ui.label(egui::RichText::new(help).color(DIM).size(10.0));
+                        });
+                        if self.channel_mode == ChannelMode::Stereo {
+                            ui.horizontal(|ui| {
+                                ui.add_space(20.0);
+                                ui.checkbox(&mut self.mid_side, "");
+                                ui.label(egui::RichText::new("M/S").strong().size(11.0));
+                                ui.label(
+                                    egui::RichText::new("chain on the mid (voice), sides passed as they are")
+                                        .color(DIM)
+                                        .size(10.0),
+                                );
+                            });
+                        }
+                        if self.channel_mode == ChannelMode::Beam {
+                            ui.horizontal(|ui| {
+                                ui.add_space(20.0);
+                                let listening = self.beam_started.is_some();
+                                let label = if listening { "LISTENING…" } else { "POINT AT NOISE" };
+                                let button = ui
+                                    .add_enabled(
+                                        self.is_running() && !listening,
+                                        egui::Button::new(egui::RichText::new(label).size(10.0)),
+                                    )
+                                    .on_hover_text("stay quiet with only the noise on for two seconds")
+                                    .on_disabled_hover_text("START first, with the noise on");
+                                if button.clicked() {
+                                    if let Some(params) = &self.params_handle {
+                                        params.beam.start();
+                                        self.beam_started = Some(Instant::now());
+                                    }
+                                }
+                                if ui.small_button("RESET").on_hover_text("back to mic 1 alone").clicked() {
+                                    self.beam_steering = Steering::OFF;
+                                    self.beam_status = None;
+                                }
+                            });
+                            if let Some(status) = &self.beam_status {
+                                let (text, color) = match status {
+                                    Ok(text) => (text.as_str(), DIM),
+                                    Err(e) => (e.as_str(), egui::Color32::from_rgb(255, 200, 50)),
+                                };
+                                ui.horizontal(|ui| {
+                                    ui.add_space(20.0);
+                                    ui.label(egui::RichText::new(text).color(color).size(10.0));
+                                });
+                            }
+                        }
+
+                        egui::CollapsingHeader::new(
+                            egui::RichText::new("BLOCKLIST").color(DIM).size(10.0),
+                        )
+                        .id_salt("blocklist")
+                        .show(ui, |ui| {
+                            ui.label(
+                                egui::RichText::new(
+                                    "one name fragment per line — matching devices are listed last",
+                                )
+                                .color(DIM)
+                                .size(10.0),
+                            );
+                            let edit = egui::TextEdit::multiline(&mut self.blocklist_text)
+                                .desired_rows(3)
+                                .desired_width(f32::INFINITY)
+                                .font(egui::TextStyle::Monospace);
+                            if ui.add(edit).changed() {
+                                self.profiles.current_mut().device_blocklist = self
+                                    .blocklist_text
+                                    .lines()
+                                    .map(str::trim)
+                                    .filter(|l| !l.is_empty())
+                                    .map(String::from)
+                                    .collect();
+                                self.apply_blocklist();
+                            }
+                        });
+
+                        egui::CollapsingHeader::new(
+                            egui::RichText::new("HEADPHONE EQ").color(DIM).size(10.0),
+                        )
+                        .id_salt("output_eq")
+                        .show(ui, |ui| {
+                            ui.label(
+                                egui::RichText::new(
+                                    "AutoEq ParametricEQ.txt, kept per output device",
+                                )
+                                .color(DIM)
+                                .size(10.0),
+                            );
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.eq_path)
+                                    .hint_text("path/to/ParametricEQ.txt")
+                                    .desired_width(f32::INFINITY)
+                                    .font(egui::TextStyle::Monospace),
+                            );
+                            let outputs: Vec<(&str, usize)> =
+                                std::iter::once(("OUT", self.selected_output))
+                                    .chain(self.selected_aux_output.map(|i| ("OUT 2", i)))
+                                    .collect();
+                            for (label, i) in outputs {
+                                let Some(name) = self.outputs.get(i).map(|e| e.name.clone()) else {
+                                    continue;
+                                };
+                                ui.horizontal(|ui| {
+                                    ui.label(egui::RichText::new(label).color(DIM).size(10.0));
+                                    let current = self.profiles.current().output_eq.get(&name);
+                                    let summary = current.map_or("none".into(), |eq| eq.summary());
+                                    ui.label(egui::RichText::new(summary).color(TEXT_BRIGHT).size(10.0))
+                                        .on_hover_text(&name);
+                                    if current.is_some() && ui.small_button("CLEAR").clicked() {
+                                        self.profiles.current_mut().output_eq.remove(&name);
+                                    }
+                                    if ui.small_button("IMPORT").clicked() {
+                                        self.import_eq(&name);
+                                    }
+                                });
+                            }
+                        });
+
+                        egui::CollapsingHeader::new(
+                            egui::RichText::new("ALIGN").color(DIM).size(10.0),
+                        )
+                        .id_salt("output_delay")
+                        .show(ui, |ui| {
+                            ui.label(
+                                egui::RichText::new(
+                                    "delay the nearer output to line up with the other, kept per output device",
+                                )
+                                .color(DIM)
+                                .size(10.0),
+                            );
+                            let outputs: Vec<(&str, usize)> =
+                                std::iter::once(("OUT", self.selected_output))
+                                    .chain(self.selected_aux_output.map(|i| ("OUT 2", i)))
+                                    .collect();
+                            for (label, i) in outputs {
+                                let Some(name) = self.outputs.get(i).map(|e| e.name.clone()) else {
+                                    continue;
+                                };
+                                ui.horizontal(|ui| {
+                                    ui.label(egui::RichText::new(label).color(DIM).size(10.0))
+                                        .on_hover_text(&name);
+                                    let kept = self.profiles.current().output_delay_ms.get(&name).copied();
+                                    let mut ms = kept.unwrap_or(0.0);
+                                    ui.add(
+                                        egui::Slider::new(&mut ms, 0.0..=MAX_OUTPUT_DELAY_MS)
+                                            .step_by(0.1)
+                                            .suffix(" ms"),
+                                    )
+                                    .on_hover_text(
+                                        "speakers heard together with headphones arrive late by their \
+                                         distance; delay the headphones by as much to stop the comb \
+                                         filtering",
+                                    );
+                                    // Sound covers 0.343 m per ms.
+                                    ui.label(
+                                        egui::RichText::new(format!("{:.2} m", ms * 0.343))
+                                            .color(DIM)
+                                            .size(10.0),
+                                    );
+                                    if Some(ms) != kept && (kept.is_some() || ms > 0.0) {
+                                        let delays = &mut self.profiles.current_mut().output_delay_ms;
+                                        if ms > 0.0 {
+                                            delays.insert(name, ms);
+                                        } else {
+                                            delays.remove(&name);
+                                        }
+                                    }
+                                });
+                            }
+                        });
+
+                        egui::CollapsingHeader::new(
+                            egui::RichText::new("CUE MIX").color(DIM).size(10.0),
+                        )
+                        .id_salt("cue_mix")
+                        .show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.cue_enabled, "")
+                                    .on_hover_text("OUT 2 plays this mix instead of the main one");
+                                ui.label(
+                                    egui::RichText::new(if self.selected_aux_output.is_some() {
+                                        "a mix of its own on OUT 2, for a guest or producer"
+                                    } else {
+                                        "pick an OUT 2 to hear it on"
+                                    })
+                                    .color(DIM)
+                                    .size(10.0),
+                                );
+                            });
+                            if !self.cue_enabled {
+                                return;
+                            }
+                            for (tap, send) in Tap::ALL.into_iter().zip(&mut self.cue_sends) {
+                                ui.horizontal(|ui| {
+                                    ui.label(
+                                        egui::RichText::new(tap.label())
+                                            .color(DIM)
+                                            .monospace()
+                                            .size(10.0),
+                                    );
+                                    ui.add(egui::Slider::new(send, 0.0..=1.0).fixed_decimals(2))
+                                        .on_hover_text(match tap {
+                                            Tap::Voice => "the processed voice, before VOLUME",
+                                            Tap::Dry => {
+                                                "the mic before any processing; not delayed to \
+                                                 match VOICE, so blending the two can sound phasey"
+                                            }
+                                        });
+                                });
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    egui::RichText::new("VOL  ")
+                                        .color(DIM)
+                                        .monospace()
+                                        .size(10.0),
+                                );
+                                ui.add(egui::Slider::new(&mut self.cue_volume, 0.0..=1.0).fixed_decimals(2));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    egui::RichText::new("CEIL ")
+                                        .color(DIM)
+                                        .monospace()
+                                        .size(10.0),
+                                );
+                                ui.add(egui::Slider::new(&mut self.cue_ceiling, -12.0..=0.0).fixed_decimals(1).suffix("dB"))
+                                    .on_hover_text("the CUE bus's own limiter, in dBFS");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.cue_eq_enabled, "");
+                                ui.label(egui::RichText::new("EQ").color(DIM).size(10.0));
+                                if ui.small_button("RESET").on_hover_text("default bands").clicked() {
+                                    self.cue_eq_bands = CHAIN_BANDS;
+                                }
+                            });
+                            if self.cue_eq_enabled {
+                                Self::eq_band_rows(ui, &mut self.cue_eq_bands);
+                            }
+                        });
+                    });
+
+                    // One-click swap of input and output together
+                    ui.horizontal(|ui| {
+                        ui.add_space(2.0);
+                        let target = self.swap_target();
+                        let swap = ui
+                            .add_enabled(target.is_some(), egui::Button::new("SWAP").small())
+                            .on_hover_text("switch input and output together to the other device pair");
+                        if let Some((i, o)) = target {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "-> {} / {}",
+                                    self.inputs[i].name, self.outputs[o].name
+                                ))
+                                .color(DIM)
+                                .size(10.0),
+                            );
+                        }
+                        if swap.clicked() {
+                            self.swap_devices();
+                        }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui
+                                .small_button("COPY CLI")
+                                .on_hover_text("copy a command line that starts with these settings")
+                                .clicked()
+                            {
+                                ui.ctx().copy_text(self.launch_command());
+                                self.notice = Some("Command line copied".into());
+                            }
+                        });
+                    });
+
+                    // Advanced routing: sidechain key input
+                    egui::CollapsingHeader::new(egui::RichText::new("ADVANCED").color(DIM).size(10.0))
+                        .id_salt("advanced")
+                        .show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new("SIDECHAIN").strong().size(11.0));
+                                let sc_name = match self.selected_sidechain {
+                                    Some(i) => self.inputs[i].name.clone(),
+                                    None => "none".into(),
+                                };
+                                ui.add_enabled_ui(!running, |ui| {
+                                    egui::ComboBox::from_id_salt("sidechain")
+                                        .selected_text(egui::RichText::new(&sc_name).color(TEXT_BRIGHT))
+                                        .width(250.0)
+                                        .show_ui(ui, |ui| {
+                                            ui.selectable_value(&mut self.selected_sidechain, None, "none");
+                                            for (i, e) in self.inputs.iter().enumerate() {
+                                                if i != self.selected_input {
+                                                    ui.selectable_value(
+                                                        &mut self.selected_sidechain,
+                                                        Some(i),
+                                                        device_label(e),
+                                                    );
+                                                }
+                                            }
+                                        });
+                                });
+                            });
+                            ui.horizontal(|ui| {
+                                egui::ComboBox::from_id_salt("sidechain_mode")
+                                    .selected_text(
+                                        egui::RichText::new(self.sidechain_mode.label())
+                                            .color(TEXT_BRIGHT),
+                                    )
+                                    .width(80.0)
+                                    .show_ui(ui, |ui| {
+                                        for mode in SidechainMode::ALL {
+                                            ui.selectable_value(
+                                                &mut self.sidechain_mode,
+                                                mode,
+                                                mode.label(),
+                                            );
+                                        }
+                                    });
+                                ui.add(
+                                    egui::Slider::new(&mut self.sidechain_threshold, -60.0..=-10.0)
+                                        .show_value(false),
+                                );
+                                ui.label(
+                                    egui::RichText::new(format!("{:.0}dB", self.sidechain_threshold))
+                                        .color(TEXT_BRIGHT)
+                                        .monospace()
+                                        .size(11.0),
+                                );
+                                let level = self
+                                    .params_handle
+                                    .as_ref()
+                                    .filter(|_| self.selected_sidechain.is_some())
+                                    .map(|p| p.sidechain_level.load());
+                                if let Some(level) = level {
+                                    let color = if level > self.sidechain_threshold { CYAN } else { DIM };
+                                    ui.label(egui::RichText::new("KEY").color(color).strong().size(10.0));
+                                }
+                            });
+                            ui.label(
+                                egui::RichText::new(
+                                    "DUCK lowers the main signal while the sidechain is \
+                                     active, UNMUTE only passes it then",
+                                )
+                                .color(DIM)
+                                .size(10.0),
+                            );
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new("INPUT LOSS").strong().size(11.0));
+                                egui::ComboBox::from_id_salt("input_loss")
+                                    .selected_text(
+                                        egui::RichText::new(self.input_loss.label()).color(TEXT_BRIGHT),
+                                    )
+                                    .width(80.0)
+                                    .show_ui(ui, |ui| {
+                                        for policy in InputLoss::ALL {
+                                            ui.selectable_value(
+                                                &mut self.input_loss,
+                                                policy,
+                                                policy.label(),
+                                            );
+                                        }
+                                    });
+                                ui.label(
+                                    egui::RichText::new("if the input fails mid-session")
+                                        .color(DIM)
+                                        .size(10.0),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.loop_guard, "");
+                                ui.label(egui::RichText::new("LOOP GUARD").strong().size(11.0));
+                                ui.label(
+                                    egui::RichText::new("stop at START if the output loops back into the input")
+                                        .color(DIM)
+                                        .size(10.0),
+                                )
+                                .on_hover_text("plays a quiet probe for a moment and listens for it");
+                            });
+                            ui.horizontal(|ui| {
+                                let settings = self.profiles.current_mut();
+                                let toggled = ui.checkbox(&mut settings.headset_button, "").changed();
+                                ui.label(egui::RichText::new("HEADSET MUTE").strong().size(11.0));
+                                ui.label(
+                                    egui::RichText::new("a USB headset's mute button toggles STANDBY")
+                                        .color(DIM)
+                                        .size(10.0),
+                                )
+                                .on_hover_text("its mute LED follows STANDBY where the headset has one");
+                                if toggled {
+                                    self.apply_headset();
+                                }
+                            });
+                            if let Some(headset) = &self.headset {
+                                let (text, color) = match headset.status.lock().unwrap().clone() {
+                                    Ok(name) => (name, DIM),
+                                    Err(e) => (e, egui::Color32::from_rgb(255, 200, 50)),
+                                };
+                                ui.horizontal(|ui| {
+                                    ui.add_space(20.0);
+                                    ui.label(egui::RichText::new(text).color(color).size(10.0));
+                                });
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new("UI SCALE").strong().size(11.0));
+                                let scale = &mut self.profiles.current_mut().ui_scale;
+                                egui::ComboBox::from_id_salt("ui_scale")
+                                    .selected_text(
+                                        egui::RichText::new(format!("{:.0}%", *scale * 100.0))
+                                            .color(TEXT_BRIGHT),
+                                    )
+                                    .width(80.0)
+                                    .show_ui(ui, |ui| {
+                                        for s in UI_SCALES {
+                                            ui.selectable_value(scale, s, format!("{:.0}%", s * 100.0));
+                                        }
+                                    });
+                                ui.label(
+                                    egui::RichText::new("on top of the OS display scaling")
+                                        .color(DIM)
+                                        .size(10.0),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new("ON AIR").strong().size(11.0))
+                                    .on_hover_text("publish LIVE / MUTED / OFFLINE to MQTT, e.g. for a light by the door");
+                                let settings = self.profiles.current_mut();
+                                let broker = egui::TextEdit::singleline(&mut settings.on_air_broker)
+                                    .hint_text("[user:pass@]broker[:1883]")
+                                    .desired_width(170.0)
+                                    .font(egui::TextStyle::Monospace);
+                                let topic = egui::TextEdit::singleline(&mut settings.on_air_topic)
+                                    .hint_text(onair::DEFAULT_TOPIC)
+                                    .desired_width(120.0)
+                                    .font(egui::TextStyle::Monospace);
+                                let broker = ui.add(broker);
+                                let topic = ui.add(topic);
+                                if broker.lost_focus() || topic.lost_focus() {
+                                    self.apply_on_air();
+                                }
+                            });
+                            let status = match (&self.on_air_error, &self.on_air) {
+                                (Some(e), _) => Some(Err(e.clone())),
+                                (None, Some(on_air)) => Some(
+                                    on_air
+                                        .status
+                                        .lock()
+                                        .unwrap()
+                                        .clone()
+                                        .map(|sent| format!("{}: {sent}", on_air.target().describe())),
+                                ),
+                                (None, None) => None,
+                            };
+                            if let Some(status) = status {
+                                let (text, color) = match status {
+                                    Ok(sent) => (sent, DIM),
+                                    Err(e) => (e, egui::Color32::from_rgb(255, 200, 50)),
+                                };
+                                ui.horizontal(|ui| {
+                                    ui.add_space(20.0);
+                                    ui.label(egui::RichText::new(text).color(color).size(10.0));
+                                });
+                                // Results arrive from the publisher thread.
+                                ctx.request_repaint_after(Duration::from_millis(500));
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new("SCHEDULE").strong().size(11.0))
+                                    .on_hover_text(
+                                        "start and stop by themselves at these local times; \
+                                         slots split by ; and days as mon-fri, sat,sun, daily…",
+                                    );
+                                let settings = self.profiles.current_mut();
+                                let schedule = egui::TextEdit::singleline(&mut settings.schedule)
+                                    .hint_text("mon-fri 19:00-21:30; sat 10:00-12:00")
+                                    .desired_width(294.0)
+                                    .font(egui::TextStyle::Monospace);
+                                if ui.add(schedule).lost_focus() {
+                                    self.apply_schedule();
+                                }
+                            });
+                            if let Some(e) = &self.schedule_error {
+                                ui.horizontal(|ui| {
+                                    ui.add_space(20.0);
+                                    ui.label(
+                                        egui::RichText::new(e)
+                                            .color(egui::Color32::from_rgb(255, 200, 50))
+                                            .size(10.0),
+                                    );
+                                });
+                            }
+                        });
+
+                    self.dedup_picks();
+
+                    // Validate config against current devices
+                    self.config_warning = if !self.inputs.is_empty() && !self.outputs.is_empty() {
+                        device::validate_config(
+                            &self.inputs[self.selected_input].device,
+                            &self.outputs[self.selected_output].device,
+                            self.buffer_size,
+                            self.output_buffer(),
+                            self.sample_rate,
+                        )
+                        .err()
+                    } else {
+                        None
+                    };
+
+                    if let Some(warning) = &self.config_warning {
+                        ui.add_space(2.0);
+                        ui.label(
+                            egui::RichText::new(warning.as_str())
+                                .color(egui::Color32::from_rgb(255, 200, 50))
+                                .size(10.0),
+                        );
+                    }
+                });
+
+                ui.add_space(4.0);
+                Self::neon_separator(ui, DIM, 0.0);
+                ui.add_space(4.0);
+
+                // Factory and user presets, searchable by name and use
+                Self::section(ui, "PRESETS", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.preset_query)
+                                .hint_text("search")
+                                .desired_width(110.0),
+                        );
+                        ui.selectable_value(&mut self.preset_tag, None, "ALL");
+                        for tag in UseCase::ALL {
+                            ui.selectable_value(&mut self.preset_tag, Some(tag), tag.label());
+                        }
+                    });
+                    let user = self.profiles.current().presets.clone();
+                    let listed: Vec<(Preset, bool)> = preset::factory()
+                        .into_iter()
+                        .map(|p| (p, false))
+                        .chain(user.into_iter().map(|p| (p, true)))
+                        .filter(|(p, _)| p.matches(&self.preset_query, self.preset_tag))
+                        .collect();
+                    if listed.is_empty() {
+                        ui.label(egui::RichText::new("no presets match").color(DIM).size(10.0));
+                    }
+                    for (preset, own) in listed {
+                        ui.horizontal(|ui| {
+                            if ui
+                                .small_button("LOAD")
+                                .on_hover_text("set the cleanup and tone stages; effects and routing stay")
+                                .clicked()
+                            {
+                                self.apply_sound(&preset.sound);
+                                self.notice = Some(format!("Loaded preset {}", preset.name));
+                            }
+                            ui.label(egui::RichText::new(&preset.name).color(TEXT_BRIGHT).size(11.0));
+                            let tags: Vec<&str> = preset.tags.iter().map(|t| t.label()).collect();
+                            ui.label(egui::RichText::new(tags.join(" ")).color(DIM).size(9.0));
+                            if own {
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.small_button("✕").on_hover_text("delete this preset").clicked() {
+                                        self.profiles
+                                            .current_mut()
+                                            .presets
+                                            .retain(|p| p.name != preset.name);
+                                    }
+                                });
+                            }
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.new_preset_name)
+                                .hint_text("new preset")
+                                .desired_width(110.0),
+                        );
+                        for tag in UseCase::ALL {
+                            let mut on = self.new_preset_tags.contains(&tag);
+                            if ui.toggle_value(&mut on, tag.label()).changed() {
+                                self.new_preset_tags.retain(|&t| t != tag);
+                                if on {
+                                    self.new_preset_tags.push(tag);
+                                }
+                            }
+                        }
+                        if ui
+                            .add_enabled(
+                                !self.new_preset_name.trim().is_empty(),
+                                egui::Button::new("SAVE").small(),
+                            )
+                            .on_hover_text("keep the current sound in this profile")
+                            .clicked()
+                        {
+                            self.save_preset();
+                        }
+                    });
+                });
+
+                ui.add_space(4.0);
+                Self::neon_separator(ui, DIM, 0.0);
+                ui.add_space(4.0);
+
+                Self::section(ui, "CONTROLS", |ui| {
+
+                    // Source: mic or a test signal for level setup
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new("SRC")
+                                .color(CYAN)
+                                .strong()
+                                .size(11.0),
+                        );
+                        egui::ComboBox::from_id_salt("source")
+                            .selected_text(
+                                egui::RichText::new(self.source.label()).color(TEXT_BRIGHT),
+                            )
+                            .width(110.0)
+                            .show_ui(ui, |ui| {
+                                for source in Source::ALL {
+                                    let picked = ui
+                                        .selectable_value(&mut self.source, source, source.label())
+                                        .changed();
+                                    if picked && source == Source::File {
+                                        self.load_source_file();
+                                    }
+                                }
+                            });
+                        let muted = match self.source {
+                            Source::Mic => None,
+                            Source::File => Some("mic muted, looping a file"),
+                            _ => Some("mic muted, -20dBFS test signal"),
+                        };
+                        if let Some(muted) = muted {
+                            ui.label(egui::RichText::new(muted).color(MAGENTA).size(10.0));
+                        }
+                    });
+                    if self.source == Source::File {
+                        ui.horizontal(|ui| {
+                            ui.add_space(20.0);
+                            let edit = egui::TextEdit::singleline(
+                                &mut self.profiles.current_mut().source_file,
+                            )
+                            .hint_text("path/to/voice.wav (or .flac, .mp3)")
+                            .desired_width(230.0)
+                            .font(egui::TextStyle::Monospace);
+                            let entered = ui.add(edit).lost_focus()
+                                && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                            let load = ui
+                                .add_enabled(running, egui::Button::new("LOAD").small())
+                                .on_hover_text("decode and loop it (only while running)");
+                            if entered || load.clicked() {
+                                self.load_source_file();
+                            }
+                        });
+                        let status = self
+                            .params_handle
+                            .as_ref()
+                            .and_then(|p| p.file.status.lock().unwrap().clone());
+                        if let Some(status) = status {
+                            let (text, color) = match status {
+                                Ok(loaded) => (loaded, DIM),
+                                Err(e) => (e, egui::Color32::from_rgb(255, 200, 50)),
+                            };
+                            ui.horizontal(|ui| {
+                                ui.add_space(20.0);
+                                ui.label(egui::RichText::new(text).color(color).size(10.0));
+                            });
+                        }
+                        if running {
+                            // Load results arrive from the decoder thread.
+                            ctx.request_repaint_after(Duration::from_millis(250));
+                        }
+                    }
+
+                    // Volume
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new("VOL")
+                                .color(CYAN)
+                                .strong()
+                                .size(11.0),
+                        );
+                        ui.add(egui::Slider::new(&mut self.volume, 0.0..=1.0).show_value(false));
+                        ui.label(
+                            egui::RichText::new(format!("{}%", (self.volume * 100.0) as u32))
+                                .color(TEXT_BRIGHT)
+                                .monospace()
+                                .size(11.0),
+                        );
+                    });
+
+                    // Global dry/wet: hear what the chain is doing to the voice
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new("DRY/WET").strong().size(11.0))
+                            .on_hover_text("blend the unprocessed input with the processed signal");
+                        ui.add(egui::Slider::new(&mut self.dry_wet, 0.0..=1.0).show_value(false));
+                        ui.label(
+                            egui::RichText::new(format!("{:.0}% wet", self.dry_wet * 100.0))
+                                .color(if self.dry_wet < 1.0 { CYAN } else { TEXT_BRIGHT })
+                                .monospace()
+                                .size(10.0),
+                        );
+                        if self.dry_wet < 1.0 && ui.small_button("WET").clicked() {
+                            self.dry_wet = 1.0;
+                        }
+                    });
+
+                    // Idle volume drop
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.idle_enabled, "");
+                        ui.label(egui::RichText::new("IDLE").strong().size(11.0));
+                        ui.add(
+                            egui::DragValue::new(&mut self.idle_minutes)
+                                .range(1..=240)
+                                .suffix("min"),
+                        );
+                        ui.checkbox(&mut self.idle_mute, "mute")
+                            .on_hover_text(format!("otherwise drop {IDLE_DROP_DB:.0}dB"));
+                        if self.idle_away {
+                            ui.label(egui::RichText::new("AWAY").color(MAGENTA).strong().size(10.0));
+                        }
+                    });
+
+                    // Session auto-stop
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.auto_stop, "");
+                        ui.label(egui::RichText::new("AUTO-STOP").strong().size(11.0));
+                        ui.add(
+                            egui::DragValue::new(&mut self.auto_stop_minutes)
+                                .range(1..=720)
+                                .suffix("min"),
+                        )
+                        .on_hover_text("stop the session after this long; warning tone a minute before");
+                    });
+
+                    // Break reminder
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.break_enabled, "");
+                        ui.label(egui::RichText::new("BREAK").strong().size(11.0));
+                        ui.add(
+                            egui::DragValue::new(&mut self.break_minutes)
+                                .range(5..=240)
+                                .suffix("min"),
+                        )
+                        .on_hover_text(format!(
+                            "remind (and briefly duck) after this much talking; {} min of silence resets it",
+                            BREAK_PAUSE.as_secs() / 60
+                        ));
+                        if self.break_enabled {
+                            let talked = self.speaking_since_break.as_secs();
+                            ui.label(
+                                egui::RichText::new(format!("talked {}:{:02}", talked / 60, talked % 60))
+                                    .color(DIM)
+                                    .monospace()
+                                    .size(10.0),
+                            );
+                        }
+                    });
+
+                    ui.add_space(2.0);
+
+                    // Denoiser
+                    ui.horizontal(|ui| {
+                        let available = self.sample_rate == denoise::SAMPLE_RATE;
+                        self.denoise &= available;
+                        ui.add_enabled(available, egui::Checkbox::without_text(&mut self.denoise));
+                        ui.label(egui::RichText::new("DENOISE").strong().size(11.0));
+                        let hint = if available {
+                            "RNNoise: fans, keyboards, hum (+10ms)"
+                        } else {
+                            "RNNoise needs 48 kHz"
+                        };
+                        ui.label(egui::RichText::new(hint).color(DIM).size(10.0));
+                    });
+
+                    // Noise gate
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.noise_gate, "");
+                        ui.label(egui::RichText::new("GATE").strong().size(11.0));
+                        if self.noise_gate {
+                            egui::ComboBox::from_id_salt("gate_mode")
+                                .selected_text(
+                                    egui::RichText::new(self.gate_mode.label()).color(TEXT_BRIGHT),
+                                )
+                                .width(64.0)
+                                .show_ui(ui, |ui| {
+                                    for mode in GateMode::ALL {
+                                        ui.selectable_value(&mut self.gate_mode, mode, mode.label());
+                                    }
+                                })
+                                .response
+                                .on_hover_text("GATE mutes below the threshold, EXPAND only turns it down");
+                            ui.add(
+                                egui::Slider::new(&mut self.noise_gate_threshold, -60.0..=-10.0)
+                                    .show_value(false),
+                            );
+                            ui.label(
+                                egui::RichText::new(format!("{:.0}dB", self.noise_gate_threshold))
+                                    .color(TEXT_BRIGHT)
+                                    .monospace()
+                                    .size(11.0),
+                            );
+                        }
+                    });
+                    // External trigger: overrides the gate, on or off.
+                    ui.horizontal(|ui| {
+                        ui.add_space(20.0);
+                        ui.label(egui::RichText::new("TRIGGER").strong().size(10.0))
+                            .on_hover_text(
+                                "a key, or a footswitch that types one, holds the gate open or shuts the mic \
+                                 (while this window has focus)",
+                            );
+                        let before = self.gate_trigger;
+                        egui::ComboBox::from_id_salt("gate_trigger")
+                            .selected_text(
+                                egui::RichText::new(self.gate_trigger.label()).color(TEXT_BRIGHT),
+                            )
+                            .width(90.0)
+                            .show_ui(ui, |ui| {
+                                for mode in GateTrigger::ALL {
+                                    ui.selectable_value(&mut self.gate_trigger, mode, mode.label());
+                                }
+                            });
+                        if self.gate_trigger != before {
+                            self.trigger_latched = false;
+                        }
+                        if self.gate_trigger != GateTrigger::Off {
+                            let key = if self.learning_trigger {
+                                "press a key…".to_string()
+                            } else {
+                                self.profiles.current().gate_trigger_key.clone()
+                            };
+                            if ui
+                                .small_button(key)
+                                .on_hover_text("click, then press the key to use")
+                                .clicked()
+                            {
+                                self.learning_trigger = true;
+                            }
+                            match self.gate_trigger.forced(self.trigger_held, self.trigger_latched) {
+                                Some(true) => {
+                                    ui.label(egui::RichText::new("OPEN").color(CYAN).strong().size(10.0));
+                                }
+                                Some(false) => {
+                                    ui.label(egui::RichText::new("SHUT").color(MAGENTA).strong().size(10.0));
+                                }
+                                None => {}
+                            }
+                        }
+                    });
+                    if self.noise_gate && self.gate_mode == GateMode::Expander {
+                        ui.horizontal(|ui| {
+                            ui.add_space(20.0);
+                            ui.label(egui::RichText::new("RATIO").strong().size(10.0))
+                                .on_hover_text("dB taken off for each dB under the threshold");
+                            ui.add(
+                                egui::Slider::new(&mut self.expander_ratio, 1.0..=8.0)
+                                    .step_by(0.5)
+                                    .show_value(false),
+                            );
+                            ui.label(
+                                egui::RichText::new(format!("1:{:.1}", self.expander_ratio))
+                                    .color(TEXT_BRIGHT)
+                                    .monospace()
+                                    .size(10.0),
+                            );
+                        });
+                        ui.horizontal(|ui| {
+                            ui.add_space(20.0);
+                            ui.label(egui::RichText::new("RANGE").strong().size(10.0))
+                                .on_hover_text("most it ever turns down; quiet passages stay audible");
+                            ui.add(
+                                egui::Slider::new(&mut self.expander_range_db, 3.0..=60.0)
+                                    .step_by(1.0)
+                                    .show_value(false),
+                            );
+                            ui.label(
+                                egui::RichText::new(format!("-{:.0}dB", self.expander_range_db))
+                                    .color(TEXT_BRIGHT)
+                                    .monospace()
+                                    .size(10.0),
+                            );
+                        });
+                    }
+                    if self.noise_gate && self.gate_mode == GateMode::Gate {
+                        ui.horizontal(|ui| {
+                            ui.add_space(20.0);
+                            ui.label(egui::RichText::new("MIN OPEN").strong().size(10.0))
+                                .on_hover_text("once open, stay open at least this long (stops flutter)");
+                            ui.add(
+                                egui::Slider::new(&mut self.gate_min_open_ms, 0.0..=200.0)
+                                    .step_by(5.0)
+                                    .show_value(false),
+                            );
+                            ui.label(
+                                egui::RichText::new(format!("{:.0}ms", self.gate_min_open_ms))
+                                    .color(TEXT_BRIGHT)
+                                    .monospace()
+                                    .size(10.0),
+                            );
+                        });
+                    }
+                    if self.noise_gate {
+                        ui.horizontal(|ui| {
+                            ui.add_space(20.0);
+                            ui.checkbox(&mut self.gate_music, "");
+                            ui.label(egui::RichText::new("MUSIC").strong().size(10.0))
+                                .on_hover_text("hold the gate open through sustained notes");
+                            if self.gate_music {
+                                ui.add(
+                                    egui::Slider::new(&mut self.gate_music_db, 0.0..=20.0)
+                                        .show_value(false),
+                                );
+                                ui.label(
+                                    egui::RichText::new(format!("-{:.0}dB", self.gate_music_db))
+                                        .color(TEXT_BRIGHT)
+                                        .monospace()
+                                        .size(10.0),
+                                )
+                                .on_hover_text("how much lower the gate opens while a note is held");
+                                let tonal = self
+                                    .params_handle
+                                    .as_ref()
+                                    .is_some_and(|p| p.tonal.load(Ordering::Relaxed));
+                                if tonal {
+                                    ui.label(egui::RichText::new("NOTE").color(CYAN).strong().size(10.0));
+                                }
+                            }
+                        });
+                    }
+
+                    // AGC
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.agc, "");
+                        ui.label(egui::RichText::new("AGC").strong().size(11.0));
+                        if self.agc {
+                            let gain = self.meter.agc_gain_db;
+                            ui.label(
+                                egui::RichText::new(format!("{gain:+.1}dB"))
+                                    .color(if gain.abs() >= 0.1 { CYAN } else { DIM })
+                                    .monospace()
+                                    .size(10.0),
+                            )
+                            .on_hover_text("gain the AGC is applying right now");
+                        } else {
+                            ui.label(
+                                egui::RichText::new("keep speech level as you move around")
+                                    .color(DIM)
+                                    .size(10.0),
+                            );
+                        }
+                    });
+                    if self.agc {
+                        for (name, value, range, hover) in [
+                            ("TARGET", &mut self.agc_target_db, -30.0..=-6.0, "speech level to aim for (RMS)"),
+                            ("MAX", &mut self.agc_max_gain_db, 0.0..=24.0, "most it may boost or cut"),
+                        ] {
+                            ui.horizontal(|ui| {
+                                ui.add_space(20.0);
+                                ui.label(
+                                    egui::RichText::new(format!("{name:<7}"))
+                                        .color(DIM)
+                                        .monospace()
+                                        .size(10.0),
+                                )
+                                .on_hover_text(hover);
+                                ui.add(egui::Slider::new(value, range).show_value(false));
+                                ui.label(
+                                    egui::RichText::new(format!("{:.0}dB", *value))
+                                        .color(TEXT_BRIGHT)
+                                        .monospace()
+                                        .size(10.0),
+                                );
+                            });
+                        }
+                    }
+
+                    // Compressor
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.comp, "");
+                        ui.label(egui::RichText::new("COMP").strong().size(11.0));
+                        if self.comp {
+                            let reduction = self.meter.gain_reduction_db;
+                            ui.label(
+                                egui::RichText::new(format!("GR -{reduction:.1}dB"))
+                                    .color(if reduction >= 0.1 { CYAN } else { DIM })
+                                    .monospace()
+                                    .size(10.0),
+                            )
+                            .on_hover_text("gain reduction right now");
+                            if ui.small_button("RESET").on_hover_text("default settings").clicked() {
+                                (
+                                    self.comp_threshold,
+                                    self.comp_ratio,
+                                    self.comp_attack_ms,
+                                    self.comp_release_ms,
+                                    self.comp_makeup_db,
+                                ) = COMP_DEFAULTS;
+                            }
+                        } else {
+                            ui.label(
+                                egui::RichText::new("tame peaks, lift quiet speech")
+                                    .color(DIM)
+                                    .size(10.0),
+                            );
+                        }
+                    });
+                    if self.comp {
+                        // (name, value, range, readout, log scale): times and ratio
+                        // feel linear on a log scale.
+                        type Row<'a> = (&'a str, &'a mut f32, RangeInclusive<f32>, fn(f32) -> String, bool);
+                        let rows: [Row; 5] = [
+                            ("THRESH", &mut self.comp_threshold, -50.0..=0.0, |v| format!("{v:.0}dB"), false),
+                            ("RATIO", &mut self.comp_ratio, 1.0..=20.0, |v| format!("{v:.1}:1"), true),
+                            ("ATTACK", &mut self.comp_attack_ms, 0.1..=100.0, |v| format!("{v:.1}ms"), true),
+                            ("RELEASE", &mut self.comp_release_ms, 10.0..=1000.0, |v| format!("{v:.0}ms"), true),
+                            ("MAKEUP", &mut self.comp_makeup_db, 0.0..=24.0, |v| format!("+{v:.0}dB"), false),
+                        ];
+                        for (name, value, range, readout, log) in rows {
+                            ui.horizontal(|ui| {
+                                ui.add_space(20.0);
+                                ui.label(
+                                    egui::RichText::new(format!("{name:<7}"))
+                                        .color(DIM)
+                                        .monospace()
+                                        .size(10.0),
+                                );
+                                ui.add(
+                                    egui::Slider::new(value, range)
+                                        .logarithmic(log)
+                                        .show_value(false),
+                                );
+                                let text = readout(*value);
+                                ui.label(
+                                    egui::RichText::new(text)
+                                        .color(TEXT_BRIGHT)
+                                        .monospace()
+                                        .size(10.0),
+                                );
+                            });
+                        }
+                    }
+
+                    // Saturation
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.saturation, "");
+                        ui.label(egui::RichText::new("SATURATE").strong().size(11.0));
+                        if !self.saturation {
+                            ui.label(
+                                egui::RichText::new("tube-style warmth: even harmonics on the peaks")
+                                    .color(DIM)
+                                    .size(10.0),
+                            );
+                        }
+                    });
+                    if self.saturation {
+                        let rows: [(&str, &mut f32, RangeInclusive<f32>, &str); 3] = [
+                            ("DRIVE", &mut self.saturation_drive_db, 0.0..=24.0, "how hard the voice hits the curve"),
+                            ("TONE", &mut self.saturation_tone_db, -6.0..=6.0, "high shelf after the curve: up for a thin mic's air, down to soften"),
+                            ("TRIM", &mut self.saturation_trim_db, -12.0..=6.0, "output level after the curve"),
+                        ];
+                        for (name, value, range, hint) in rows {
+                            ui.horizontal(|ui| {
+                                ui.add_space(20.0);
+                                ui.label(
+                                    egui::RichText::new(format!("{name:<7}"))
+                                        .color(DIM)
+                                        .monospace()
+                                        .size(10.0),
+                                )
+                                .on_hover_text(hint);
+                                ui.add(egui::Slider::new(value, range).step_by(0.5).show_value(false));
+                                ui.label(
+                                    egui::RichText::new(format!("{:+.1}dB", *value))
+                                        .color(TEXT_BRIGHT)
+                                        .monospace()
+                                        .size(10.0),
+                                );
+                            });
+                        }
+                    }
+
+                    // Limiter
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.limiter, "");
+                        ui.label(egui::RichText::new("LIMIT").strong().size(11.0));
+                        if self.limiter {
+                            ui.add(
+                                egui::Slider::new(&mut self.limiter_ceiling, -12.0..=0.0)
+                                    .step_by(0.5)
+                                    .show_value(false),
+                            );
+                            ui.label(
+                                egui::RichText::new(format!("{:.1}dB", self.limiter_ceiling))
+                                    .color(TEXT_BRIGHT)
+                                    .monospace()
+                                    .size(11.0),
+                            )
+                            .on_hover_text("ceiling: output never goes above this");
+                            let reduction = self.meter.limiter_reduction_db;
+                            ui.label(
+                                egui::RichText::new(format!("GR -{reduction:.1}dB"))
+                                    .color(if reduction >= 0.1 { MAGENTA } else { DIM })
+                                    .monospace()
+                                    .size(10.0),
+                            );
+                        } else {
+                            ui.label(
+                                egui::RichText::new("off: boosts can clip")
+                                    .color(DIM)
+                                    .size(10.0),
+                            );
+                        }
+                    });
+
+                    // Filters: independent HP/LP, VOICE toggles both
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.highpass, "");
+                        ui.label(egui::RichText::new("HP").strong().size(11.0));
+                        ui.label(
+                            egui::RichText::new(format!("{HIGHPASS_HZ:.0}Hz"))
+                                .color(DIM)
+                                .size(10.0),
+                        );
+                        ui.checkbox(&mut self.lowpass, "");
+                        ui.label(egui::RichText::new("LP").strong().size(11.0));
+                        ui.label(
+                            egui::RichText::new(format!("{:.0}kHz", LOWPASS_HZ / 1000.0))
+                                .color(DIM)
+                                .size(10.0),
+                        );
+                        let voice = self.highpass && self.lowpass;
+                        let text = egui::RichText::new("VOICE")
+                            .size(10.0)
+                            .color(if voice { CYAN } else { DIM });
+                        if ui
+                            .add(egui::Button::new(text).small())
+                            .on_hover_text("both filters: a voice-band preset")
+                            .clicked()
+                        {
+                            self.highpass = !voice;
+                            self.lowpass = !voice;
+                        }
+                        if self.highpass || self.lowpass {
+                            let slope = |order: u32| format!("{} dB/oct", order * 6);
+                            egui::ComboBox::from_id_salt("filter_order")
+                                .selected_text(
+                                    egui::RichText::new(slope(self.filter_order)).color(TEXT_BRIGHT),
+                                )
+                                .width(80.0)
+                                .show_ui(ui, |ui| {
+                                    for order in Butterworth::ORDERS.map(|o| o as u32) {
+                                        ui.selectable_value(&mut self.filter_order, order, slope(order));
+                                    }
+                                })
+                                .response
+                                .on_hover_text("Butterworth slope of both filters");
+                        }
+                    });
+
+                    // Mains hum: notches on 50/60 Hz and its overtones
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.hum, "");
+                        ui.label(egui::RichText::new("HUM").strong().size(11.0));
+                        if self.hum {
+                            egui::ComboBox::from_id_salt("hum_mains")
+                                .selected_text(
+                                    egui::RichText::new(format!("{} Hz", self.hum_mains_hz))
+                                        .color(TEXT_BRIGHT),
+                                )
+                                .width(60.0)
+                                .show_ui(ui, |ui| {
+                                    for hz in [50, 60] {
+                                        ui.selectable_value(&mut self.hum_mains_hz, hz, format!("{hz} Hz"));
+                                    }
+                                })
+                                .response
+                                .on_hover_text("mains frequency where you are");
+                            ui.add(
+                                egui::Slider::new(&mut self.hum_overtones, 0..=HumFilter::MAX_OVERTONES as u32)
+                                    .show_value(false),
+                            )
+                            .on_hover_text("overtones notched above the mains frequency");
+                            ui.label(
+                                egui::RichText::new(format!("+{}", self.hum_overtones))
+                                    .color(TEXT_BRIGHT)
+                                    .monospace()
+                                    .size(10.0),
+                            );
+                        } else {
+                            ui.label(
+                                egui::RichText::new("hum the high-pass leaves behind")
+                                    .color(DIM)
+                                    .size(10.0),
+                            );
+                        }
+                    });
+
+                    // Mic EQ: four bands after the filters
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.eq_enabled, "");
+                        ui.label(egui::RichText::new("EQ").strong().size(11.0));
+                        let flat = self.eq_bands.iter().all(|b| b.gain_db == 0.0);
+                        ui.label(
+                            egui::RichText::new(if flat { "flat" } else { "shaped" })
+                                .color(DIM)
+                                .size(10.0),
+                        );
+                        if !flat && ui.small_button("FLAT").clicked() {
+                            for band in &mut self.eq_bands {
+                                band.gain_db = 0.0;
+                            }
+                        }
+                        if ui.small_button("RESET").on_hover_text("default bands").clicked() {
+                            self.eq_bands = CHAIN_BANDS;
+                        }
+                    });
+                    if self.eq_enabled {
+                        Self::eq_band_rows(ui, &mut self.eq_bands);
+                    }
 
-            // ── Controls ──
-            Self::section_label(ui, "CONTROLS");
-            ui.add_space(2.0);
+                    // Voice changer
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.voice, "");
+                        ui.label(egui::RichText::new("VOICE").strong().size(11.0));
+                        if self.voice {
+                            let shifts = (self.voice_pitch_semitones, self.voice_formant_semitones);
+                            for preset in VoicePreset::ALL {
+                                if ui.selectable_label(shifts == preset.shifts(), preset.label()).clicked() {
+                                    (self.voice_pitch_semitones, self.voice_formant_semitones) =
+                                        preset.shifts();
+                                }
+                            }
+                        } else {
+                            ui.label(
+                                egui::RichText::new("character voices: pitch and formants apart")
+                                    .color(DIM)
+                                    .size(10.0),
+                            );
+                        }
+                    });
+                    if self.voice {
+                        let rows: [(&str, &mut f32, RangeInclusive<f32>, &str); 2] = [
+                            ("PITCH", &mut self.voice_pitch_semitones, -12.0..=12.0, "how high the voice sings"),
+                            (
+                                "FORMANT",
+                                &mut self.voice_formant_semitones,
+                                -6.0..=6.0,
+                                "how big the speaker sounds: down is larger, up is smaller",
+                            ),
+                        ];
+                        for (name, value, range, hint) in rows {
+                            ui.horizontal(|ui| {
+                                ui.add_space(20.0);
+                                ui.label(
+                                    egui::RichText::new(format!("{name:<7}"))
+                                        .color(DIM)
+                                        .monospace()
+                                        .size(10.0),
+                                )
+                                .on_hover_text(hint);
+                                ui.add(egui::Slider::new(value, range).step_by(0.5).show_value(false));
+                                ui.label(
+                                    egui::RichText::new(format!("{:+.1}st", *value))
+                                        .color(TEXT_BRIGHT)
+                                        .monospace()
+                                        .size(10.0),
+                                );
+                            });
+                        }
+                    }
 
-            // Volume
-            ui.horizontal(|ui| {
-                ui.label(
-                    egui::RichText::new("VOL")
-                        .color(CYAN)
-                        .strong()
-                        .size(11.0),
-                );
-                ui.add(egui::Slider::new(&mut self.volume, 0.0..=1.0).show_value(false));
-                ui.label(
-                    egui::RichText::new(format!("{}%", (self.volume * 100.0) as u32))
-                        .color(TEXT_BRIGHT)
-                        .monospace()
-                        .size(11.0),
-                );
-            });
+                    // Robot voice
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.robot, "");
+                        ui.label(egui::RichText::new("ROBOT").strong().size(11.0));
+                        if self.robot {
+                            for mode in RobotMode::ALL {
+                                ui.selectable_value(&mut self.robot_mode, mode, mode.label());
+                            }
+                        } else {
+                            ui.label(
+                                egui::RichText::new("ring modulator or vocoder on a steady buzz")
+                                    .color(DIM)
+                                    .size(10.0),
+                            );
+                        }
+                    });
+                    if self.robot {
+                        ui.horizontal(|ui| {
+                            ui.add_space(20.0);
+                            ui.label(
+                                egui::RichText::new(format!("{:<7}", "PITCH"))
+                                    .color(DIM)
+                                    .monospace()
+                                    .size(10.0),
+                            )
+                            .on_hover_text(match self.robot_mode {
+                                RobotMode::Ring => "wobble rate: low is a slow warble, high a metallic clang",
+                                RobotMode::Vocoder => "the one note the robot speaks on",
+                            });
+                            ui.add(
+                                egui::Slider::new(&mut self.robot_pitch_hz, 30.0..=300.0)
+                                    .logarithmic(true)
+                                    .show_value(false),
+                            );
+                            ui.label(
+                                egui::RichText::new(format!("{:.0}Hz", self.robot_pitch_hz))
+                                    .color(TEXT_BRIGHT)
+                                    .monospace()
+                                    .size(10.0),
+                            );
+                        });
+                    }
 
-            ui.add_space(2.0);
+                    // Privacy voice
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.privacy_voice, "");
+                        ui.label(egui::RichText::new("PRIVACY").strong().size(11.0));
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "voice disguise: pitch {:+.0}st, formants {:+.0}st",
+                                PRIVACY_SHIFTS.0, PRIVACY_SHIFTS.1
+                            ))
+                                .color(DIM)
+                                .size(10.0),
+                        );
+                    });
 
-            // Noise gate
-            ui.horizontal(|ui| {
-                ui.checkbox(&mut self.noise_gate, "");
-                ui.label(egui::RichText::new("GATE").strong().size(11.0));
-                if self.noise_gate {
-                    ui.add(
-                        egui::Slider::new(&mut self.noise_gate_threshold, -60.0..=-10.0)
-                            .show_value(false),
-                    );
-                    ui.label(
-                        egui::RichText::new(format!("{:.0}dB", self.noise_gate_threshold))
-                            .color(TEXT_BRIGHT)
-                            .monospace()
-                            .size(11.0),
-                    );
-                }
-            });
+                    // Harmony
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.harmony, "");
+                        ui.label(egui::RichText::new("HARMONY").strong().size(11.0));
+                        if self.harmony {
+                            ui.add(
+                                egui::Slider::new(&mut self.harmony_semitones, -12.0..=12.0)
+                                    .step_by(1.0)
+                                    .show_value(false),
+                            )
+                            .on_hover_text("interval in semitones: 4 is a major third, 7 a fifth");
+                            ui.label(
+                                egui::RichText::new(format!("{:+.0}st", self.harmony_semitones))
+                                    .color(TEXT_BRIGHT)
+                                    .monospace()
+                                    .size(10.0),
+                            );
+                        } else {
+                            ui.label(
+                                egui::RichText::new("sing against a shifted copy of yourself")
+                                    .color(DIM)
+                                    .size(10.0),
+                            );
+                        }
+                    });
+                    if self.harmony {
+                        ui.horizontal(|ui| {
+                            ui.add_space(20.0);
+                            ui.label(
+                                egui::RichText::new(format!("{:<7}", "MIX"))
+                                    .color(DIM)
+                                    .monospace()
+                                    .size(10.0),
+                            )
+                            .on_hover_text("0: just your voice, 100%: just the shifted one");
+                            ui.add(egui::Slider::new(&mut self.harmony_mix, 0.0..=1.0).show_value(false));
+                            ui.label(
+                                egui::RichText::new(format!("{:.0}%", self.harmony_mix * 100.0))
+                                    .color(TEXT_BRIGHT)
+                                    .monospace()
+                                    .size(10.0),
+                            );
+                        });
+                    }
 
-            // Voice filter
-            ui.horizontal(|ui| {
-                ui.checkbox(&mut self.voice_filter, "");
-                ui.label(egui::RichText::new("FILTER").strong().size(11.0));
-                ui.label(
-                    egui::RichText::new("100Hz-8kHz")
-                        .color(DIM)
-                        .size(10.0),
-                );
-            });
+                    // Call simulation
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new("CALL SIM").strong().size(11.0));
+                        egui::ComboBox::from_id_salt("call_sim")
+                            .selected_text(
+                                egui::RichText::new(self.call_sim.label()).color(TEXT_BRIGHT),
+                            )
+                            .width(80.0)
+                            .show_ui(ui, |ui| {
+                                for mode in CallSim::ALL {
+                                    ui.selectable_value(&mut self.call_sim, mode, mode.label());
+                                }
+                            });
+                        ui.label(
+                            egui::RichText::new("hear what listeners hear")
+                                .color(DIM)
+                                .size(10.0),
+                        );
+                    });
 
-            ui.add_space(4.0);
-            Self::neon_separator(ui, accent);
-            ui.add_space(6.0);
+                    // Echo
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.echo, "");
+                        ui.label(egui::RichText::new("ECHO").strong().size(11.0));
+                        if !self.echo {
+                            ui.label(
+                                egui::RichText::new("slapback, or delayed feedback at 100% wet")
+                                    .color(DIM)
+                                    .size(10.0),
+                            );
+                        }
+                    });
+                    if self.echo {
+                        type Row<'a> = (&'a str, &'a mut f32, RangeInclusive<f32>, fn(f32) -> String, &'a str);
+                        let rows: [Row; 3] = [
+                            ("TIME", &mut self.echo_time_ms, 20.0..=Echo::MAX_MS, |v| format!("{v:.0}ms"), "delay between repeats"),
+                            ("REPEATS", &mut self.echo_feedback, 0.0..=0.9, |v| format!("{:.0}%", v * 100.0), "how much of each repeat comes back"),
+                            ("MIX", &mut self.echo_mix, 0.0..=1.0, |v| format!("{:.0}%", v * 100.0), "wet/dry"),
+                        ];
+                        for (name, value, range, format, hover) in rows {
+                            ui.horizontal(|ui| {
+                                ui.add_space(20.0);
+                                ui.label(
+                                    egui::RichText::new(format!("{name:<7}"))
+                                        .color(DIM)
+                                        .monospace()
+                                        .size(10.0),
+                                )
+                                .on_hover_text(hover);
+                                ui.add(egui::Slider::new(value, range).show_value(false));
+                                ui.label(
+                                    egui::RichText::new(format(*value))
+                                        .color(TEXT_BRIGHT)
+                                        .monospace()
+                                        .size(10.0),
+                                );
+                            });
+                        }
+                    }
 
-            // ── Action ──
-            ui.vertical_centered(|ui| {
-                let btn_text;
-                let btn_fill;
-                let btn_stroke;
+                    // Reverb
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.reverb, "");
+                        ui.label(egui::RichText::new("REVERB").strong().size(11.0));
+                        if self.reverb {
+                            egui::ComboBox::from_id_salt("reverb_kind")
+                                .selected_text(
+                                    egui::RichText::new(self.reverb_kind.label()).color(TEXT_BRIGHT),
+                                )
+                                .width(80.0)
+                                .show_ui(ui, |ui| {
+                                    for kind in ReverbKind::ALL {
+                                        ui.selectable_value(&mut self.reverb_kind, kind, kind.label());
+                                    }
+                                });
+                        } else {
+                            ui.label(
+                                egui::RichText::new("a touch of space to sing into")
+                                    .color(DIM)
+                                    .size(10.0),
+                            );
+                        }
+                    });
+                    if self.reverb {
+                        type Row<'a> = (&'a str, &'a mut f32, RangeInclusive<f32>, fn(f32) -> String, &'a str);
+                        let rows: [Row; 2] = [
+                            ("MIX", &mut self.reverb_mix, 0.0..=1.0, |v| format!("{:.0}%", v * 100.0), "wet/dry"),
+                            ("DECAY", &mut self.reverb_decay_s, 0.2..=5.0, |v| format!("{v:.1}s"), "time to die away"),
+                        ];
+                        for (name, value, range, format, hover) in rows {
+                            ui.horizontal(|ui| {
+                                ui.add_space(20.0);
+                                ui.label(
+                                    egui::RichText::new(format!("{name:<7}"))
+                                        .color(DIM)
+                                        .monospace()
+                                        .size(10.0),
+                                )
+                                .on_hover_text(hover);
+                                ui.add(egui::Slider::new(value, range).show_value(false));
+                                ui.label(
+                                    egui::RichText::new(format(*value))
+                                        .color(TEXT_BRIGHT)
+                                        .monospace()
+                                        .size(10.0),
+                                );
+                            });
+                        }
+                    }
 
-                if running {
-                    btn_text = egui::RichText::new("    STOP    ")
-                        .strong()
-                        .size(16.0)
-                        .color(MAGENTA);
-                    btn_fill = egui::Color32::from_rgb(50, 10, 25);
-                    btn_stroke = egui::Stroke::new(1.5, MAGENTA);
-                } else {
-                    btn_text = egui::RichText::new("    START    ")
-                        .strong()
-                        .size(16.0)
-                        .color(CYAN);
-                    btn_fill = egui::Color32::from_rgb(8, 40, 35);
-                    btn_stroke = egui::Stroke::new(1.5, CYAN);
-                };
+                    // Alert cues
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.alerts, "");
+                        ui.label(egui::RichText::new("ALERTS").strong().size(11.0));
+                        if self.alerts {
+                            ui.add(egui::Slider::new(&mut self.alert_volume, 0.0..=1.0).show_value(false));
+                        } else {
+                            ui.label(
+                                egui::RichText::new("beep on clipping + every hour")
+                                    .color(DIM)
+                                    .size(10.0),
+                            );
+                        }
+                    });
 
-                let btn = egui::Button::new(btn_text)
-                    .fill(btn_fill)
-                    .stroke(btn_stroke);
+                    // Vibe randomizer
+                    ui.horizontal(|ui| {
+                        let vibe = egui::Button::new(
+                            egui::RichText::new("VIBE").strong().size(11.0).color(MAGENTA),
+                        )
+                        .stroke(egui::Stroke::new(1.0, MAGENTA));
+                        if ui.add(vibe).on_hover_text("randomize pitch, harmony, slapback, reverb and call sim").clicked() {
+                            self.roll_vibe();
+                        }
+                        if let Some(summary) = &self.vibe_summary {
+                            ui.label(
+                                egui::RichText::new(summary.as_str())
+                                    .color(TEXT_BRIGHT)
+                                    .monospace()
+                                    .size(10.0),
+                            );
+                        }
+                        if self.vibe_revert.is_some() && ui.small_button("REVERT").clicked() {
+                            self.revert_vibe();
+                        }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.checkbox(&mut self.pulse_enabled, "pulse")
+                                .on_hover_text("vibe mode: logo and separators pulse with your level");
+                        });
+                    });
 
-                let can_start = !self.inputs.is_empty()
-                    && !self.outputs.is_empty()
-                    && self.config_warning.is_none();
-                let enabled = if running { true } else { can_start };
+                    // Scripted stage (experimental)
+                    #[cfg(feature = "scripting")]
+                    {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.script_enabled, "");
+                            ui.label(egui::RichText::new("SCRIPT").strong().size(11.0));
+                            let edit = egui::TextEdit::singleline(&mut self.profiles.current_mut().script_path)
+                                .hint_text("path/to/stage.rhai")
+                                .desired_width(230.0)
+                                .font(egui::TextStyle::Monospace);
+                            if ui.add(edit).changed() {
+                                if let Some(p) = &self.params_handle {
+                                    *p.script.path.lock().unwrap() = self.script_path();
+                                }
+                            }
+                        });
+                        let script_error = self
+                            .params_handle
+                            .as_ref()
+                            .and_then(|p| p.script.error.lock().unwrap().clone());
+                        if let Some(err) = script_error {
+                            ui.label(
+                                egui::RichText::new(err)
+                                    .color(egui::Color32::from_rgb(255, 200, 50))
+                                    .size(10.0),
+                            );
+                        }
+                        if running && self.script_enabled {
+                            // Reloads and errors arrive from the watcher thread.
+                            ctx.request_repaint_after(Duration::from_millis(250));
+                        }
+                    }
+                });
 
-                if ui.add_enabled(enabled, btn).clicked() {
+                ui.add_space(4.0);
+                Self::neon_separator(ui, DIM, 0.0);
+                ui.add_space(4.0);
+
+                // Stage order (drag to reorder) and wet/dry per stage
+                Self::section(ui, "CHAIN", |ui| {
+                    let mut moved = None;
+                    for (i, &stage) in self.effect_chain.stages().iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            let handle = ui
+                                .dnd_drag_source(egui::Id::new(("chain", stage as u32)), i, |ui| {
+                                    ui.label(
+                                        egui::RichText::new(format!("≡ {:<9}", stage.label()))
+                                            .color(DIM)
+                                            .monospace()
+                                            .size(10.0),
+                                    )
+                                })
+                                .response
+                                .on_hover_text("drag to move this stage");
+                            if let Some(from) = handle.dnd_hover_payload::<usize>() {
+                                let rect = handle.rect;
+                                let y = if *from < i { rect.bottom() } else { rect.top() };
+                                ui.painter()
+                                    .hline(rect.x_range(), y, egui::Stroke::new(1.0, CYAN));
+                            }
+                            if let Some(from) = handle.dnd_release_payload::<usize>() {
+                                moved = Some((*from, i));
+                            }
+                            if stage.blendable() {
+                                let mix = &mut self.stage_mix[stage as usize];
+                                ui.add(egui::Slider::new(mix, 0.0..=1.0).show_value(false))
+                                    .on_hover_text("dry ← → processed");
+                                ui.label(
+                                    egui::RichText::new(format!("{:>3.0}%", *mix * 100.0))
+                                        .color(if *mix < 1.0 { CYAN } else { TEXT_BRIGHT })
+                                        .monospace()
+                                        .size(10.0),
+                                );
+                            } else {
+                                ui.label(egui::RichText::new("no blend").color(DIM).size(10.0))
+                                    .on_hover_text("delays the signal or has a mix of its own");
+                            }
+                        });
+                    }
+                    if let Some((from, to)) = moved {
+                        self.effect_chain.move_stage(from, to);
+                    }
+                    ui.label(
+                        egui::RichText::new("then SIDECHAIN, VOL and LIMIT, always last")
+                            .color(DIM)
+                            .size(10.0),
+                    );
+                    ui.horizontal(|ui| {
+                        if self.stage_mix.iter().any(|&m| m < 1.0)
+                            && ui.small_button("ALL 100%").on_hover_text("fully processed").clicked()
+                        {
+                            self.stage_mix = [1.0; Stage::ALL.len()];
+                        }
+                        if self.effect_chain != EffectChain::default()
+                            && ui.small_button("DEFAULT ORDER").clicked()
+                        {
+                            self.effect_chain = EffectChain::default();
+                        }
+                    });
+                });
+
+                ui.add_space(4.0);
+                Self::neon_separator(ui, DIM, 0.0);
+                ui.add_space(4.0);
+
+                Self::section(ui, "METERS", |ui| {
                     if running {
-                        self.stop();
+                        Self::output_meter(ui, &self.meter);
+                        if let Some(p) = &self.params_handle {
+                            Self::meter_holds(ui, p);
+                        }
+                        Self::scope(ui, &self.meter.scope, accent);
+                        let taking = self.snapshot_caption.is_some();
+                        if ui
+                            .add_enabled(!taking, egui::Button::new("SNAPSHOT").small())
+                            .on_hover_text("save the window as a PNG, with the settings written on it, to share")
+                            .clicked()
+                        {
+                            self.snapshot_caption = Some(self.snapshot_caption());
+                            ui.ctx().send_viewport_cmd(egui::ViewportCommand::Screenshot(egui::UserData::default()));
+                        }
                     } else {
-                        self.start();
+                        ui.label(egui::RichText::new("START to see levels").color(DIM).size(10.0));
                     }
-                }
 
-                ui.add_space(4.0);
+                    // Loudness history
+                    let lufs = self.loudness_history.last().map(|&(_, l)| l);
+                    let header = match lufs {
+                        Some(l) => format!("LOUDNESS  {l:.1} LUFS"),
+                        None => "LOUDNESS".into(),
+                    };
+                    egui::CollapsingHeader::new(egui::RichText::new(header).color(DIM).size(10.0))
+                        .id_salt("loudness")
+                        .show(ui, |ui| {
+                            Self::loudness_graph(ui, &self.loudness_history, accent);
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    egui::RichText::new("short-term, 3s window  --  guides at -23 / -16")
+                                        .color(DIM)
+                                        .size(10.0),
+                                );
+                                let can_export = !self.loudness_history.is_empty();
+                                if ui
+                                    .add_enabled(can_export, egui::Button::new("CSV").small())
+                                    .clicked()
+                                {
+                                    match self.export_loudness_csv() {
+                                        Ok(path) => {
+                                            self.notice = Some(format!("Saved {}", path.display()))
+                                        }
+                                        Err(e) => self.error = Some(format!("CSV export: {e}")),
+                                    }
+                                }
+                            });
+                        });
+                });
 
-                let (dot, status_color) = if running {
-                    (">>", CYAN)
-                } else {
-                    ("--", DIM)
-                };
-                ui.label(
-                    egui::RichText::new(format!("{dot} {} {dot}", self.status))
-                        .color(status_color)
-                        .monospace()
-                        .strong()
-                        .size(12.0),
-                );
+                ui.add_space(4.0);
+                Self::neon_separator(ui, DIM, 0.0);
+                ui.add_space(4.0);
 
-                if let Some(err) = &self.error {
-                    ui.add_space(2.0);
-                    ui.label(
-                        egui::RichText::new(err.as_str())
-                            .color(egui::Color32::from_rgb(255, 80, 80))
-                            .size(11.0),
-                    );
-                }
+                Self::section(ui, "DIAGNOSTICS", |ui| {
+                    if !running && !self.preflight.is_empty() {
+                        egui::CollapsingHeader::new(
+                            egui::RichText::new(format!("PREFLIGHT ({})", self.preflight.len()))
+                                .color(egui::Color32::from_rgb(255, 200, 50))
+                                .size(10.0),
+                        )
+                        .id_salt("preflight")
+                        .show(ui, |ui| {
+                            for hint in &self.preflight {
+                                let hint = egui::RichText::new(format!("! {hint}"));
+                                ui.label(hint.color(DIM).size(10.0));
+                            }
+                        });
+                    }
+                    if running {
+                        let (sync_text, sync_color) = match self.sync_pending() {
+                            Some(d) if d >= SYNC_WARN => (
+                                format!("sync {}ms", d.as_millis()),
+                                egui::Color32::from_rgb(255, 200, 50),
+                            ),
+                            _ => ("sync ok".to_string(), DIM),
+                        };
+                        ui.label(
+                            egui::RichText::new(sync_text)
+                                .color(sync_color)
+                                .monospace()
+                                .size(9.0),
+                        )
+                        .on_hover_text(
+                            "Time since the last control change without the audio \
+                             thread picking it up. Stays \"ok\" if sliders only feel \
+                             slow because the GUI is lagging.",
+                        );
+                        if let Some(latency) = self.params_handle.as_ref().and_then(|p| p.latency_ms()) {
+                            ui.label(
+                                egui::RichText::new(format!("latency ~{latency:.0}ms"))
+                                    .color(DIM)
+                                    .monospace()
+                                    .size(9.0),
+                            )
+                            .on_hover_text(
+                                "Input to OUT, from the buffer sizes the drivers actually \
+                                 run at: an input period, what OUT buffers, and an OUT \
+                                 period. The devices' own converters add a little more.",
+                            );
+                        }
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "glitches recovered {}",
+                                self.xrun_recoveries_seen
+                            ))
+                            .color(DIM)
+                            .monospace()
+                            .size(9.0),
+                        );
+                        let (underruns, overruns) = self.xruns_seen;
+                        let (dropout_text, dropout_color) = match self.last_dropout {
+                            Some(at) => (
+                                format!("last dropout {} s ago", at.elapsed().as_secs()),
+                                if at.elapsed() < DROPOUT_RECENT {
+                                    egui::Color32::from_rgb(255, 200, 50)
+                                } else {
+                                    DIM
+                                },
+                            ),
+                            None => ("no dropouts".to_string(), DIM),
+                        };
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "underruns {underruns}  overruns {overruns}  {dropout_text}"
+                            ))
+                            .color(dropout_color)
+                            .monospace()
+                            .size(9.0),
+                        )
+                        .on_hover_text(
+                            "Underrun: an output ran out of audio. Overrun: an output's \
+                             buffer was full and audio was dropped. Either is a click; if \
+                             they keep coming, raise the buffer size.",
+                        );
+                        if let (true, Some((open, since))) = (self.noise_gate, self.gate_since) {
+                            let state = if open { "open" } else { "shut" };
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "gate {state} {} s",
+                                    since.elapsed().as_secs()
+                                ))
+                                .color(DIM)
+                                .monospace()
+                                .size(9.0),
+                            );
+                        }
+                    } else if self.preflight.is_empty() && self.event_log.is_empty() {
+                        ui.label(egui::RichText::new("nothing to report").color(DIM).size(10.0));
+                    }
+                    for line in &self.event_log {
+                        ui.label(egui::RichText::new(line).color(DIM).monospace().size(9.0));
+                    }
+                    ui.horizontal(|ui| {
+                        let listening = self.room_started.is_some();
+                        let label = if listening { "LISTENING…" } else { "ROOM CHECK" };
+                        let check = ui
+                            .add_enabled(
+                                running && !listening,
+                                egui::Button::new(egui::RichText::new(label).size(10.0)),
+                            )
+                            .on_hover_text(format!(
+                                "listen for {} s: stay quiet and clap once; repeats every {} min",
+                                room::LISTEN_MS / 1000,
+                                ROOM_RECHECK.as_secs() / 60
+                            ))
+                            .on_disabled_hover_text("START first");
+                        if check.clicked() {
+                            self.start_room_check();
+                        }
+                        if let Some(report) = &self.room_report {
+                            let color = match report.grade() {
+                                'A' | 'B' => CYAN,
+                                'C' => TEXT_BRIGHT,
+                                _ => egui::Color32::from_rgb(255, 200, 50),
+                            };
+                            ui.label(
+                                egui::RichText::new(report.grade().to_string())
+                                    .color(color)
+                                    .strong()
+                                    .size(14.0),
+                            );
+                            let reverb = report
+                                .rt60
+                                .map_or("reverb ?".into(), |t| format!("reverb {t:.1}s"));
+                            let hum = report
+                                .hum
+                                .map_or("no hum".into(), |h| format!("hum {} Hz", h.mains_hz));
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "floor {:.0} dBFS / {hum} / {reverb}",
+                                    report.noise_db
+                                ))
+                                .color(DIM)
+                                .monospace()
+                                .size(9.0),
+                            );
+                        }
+                    });
+                    if let Some(report) = &self.room_report {
+                        for tip in report.suggestions() {
+                            ui.label(egui::RichText::new(format!("> {tip}")).color(DIM).size(10.0));
+                        }
+                    }
+                    if ui
+                        .small_button("GAIN SHEET")
+                        .on_hover_text("save the gain of every stage, input to output, as a text file")
+                        .clicked()
+                    {
+                        match self.export_gain_sheet() {
+                            Ok(path) => self.notice = Some(format!("Saved {}", path.display())),
+                            Err(e) => self.error = Some(format!("Gain sheet export: {e}")),
+                        }
+                    }
+                });
+                ui.add_space(4.0);
             });
         });
 
-        self.sync_params();
+        // A SNAPSHOT's caption, over START/STOP until the screenshot is in.
+        if let Some(caption) = &self.snapshot_caption {
+            egui::Area::new(egui::Id::new("snapshot_caption"))
+                .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -8.0])
+                .order(egui::Order::Foreground)
+                .show(ctx, |ui| {
+                    egui::Frame::new()
+                        .fill(egui::Color32::from_black_alpha(230))
+                        .inner_margin(6.0)
+                        .show(ui, |ui| {
+                            for line in caption {
+                                ui.label(
+                                    egui::RichText::new(line.as_str())
+                                        .color(TEXT_BRIGHT)
+                                        .monospace()
+                                        .size(9.0),
+                                );
+                            }
+                        });
+                });
+        }
+        self.save_snapshot(ctx);
+
+        let stopping = self.stopping_since.is_some();
+        self.tick();
+        if stopping {
+            ctx.request_repaint_after(Duration::from_millis(16));
+        }
+        if self.is_running() {
+            ctx.request_repaint_after(METER_REFRESH);
+        }
+        if self.sync_pending_since.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(16));
+        }
+        if self.pulse > 0.01 {
+            // Let the glow settle after STOP.
+            ctx.request_repaint_after(METER_REFRESH);
+        }
+        if self.scheduler.is_some() || self.headset.is_some() {
+            // Scheduled starts and stops, and headset presses while
+            // stopped, are picked up in `tick`.
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
     }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.remember_devices();
+        self.profiles.save(storage);
+    }
+}
+
+fn device_label(entry: &DeviceEntry) -> egui::RichText {
+    let color = if entry.blocked { DIM } else { TEXT };
+    egui::RichText::new(&entry.name).color(color)
+}
+
+/// `h:mm:ss`.
+fn format_hms(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
 }
 
 fn load_icon() -> egui::IconData {
@@ -527,17 +4986,507 @@ fn load_icon() -> egui::IconData {
     }
 }
 
-pub fn run() -> Result<()> {
+pub fn run(launch: LaunchOptions) -> Result<()> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([420.0, 440.0])
+            .with_inner_size(WINDOW_SIZE)
+            .with_min_inner_size([320.0, 200.0])
             .with_icon(load_icon()),
         ..Default::default()
     };
     eframe::run_native(
         "Vibetone",
         options,
-        Box::new(|_cc| Ok(Box::new(VibetoneApp::new()))),
+        Box::new(move |cc| {
+            let profiles = Profiles::load(cc.storage, launch.profile.as_deref());
+            let mut app = VibetoneApp::new(profiles);
+            app.apply_launch(&launch);
+            Ok(Box::new(app))
+        }),
     )
     .map_err(|e| anyhow::anyhow!("{e}"))
 }
+
+#[cfg(test)]
+mod tests {
+    //! Lifecycle and parameter flows, driven without an audio host: the app
+    //! gets an empty device list, and where a session is needed the shared
+    //! `AudioParams` are attached directly in place of a built engine.
+
+    use super::*;
+
+    fn app() -> VibetoneApp {
+        VibetoneApp::with_devices(Profiles::default(), Vec::new(), Vec::new())
+    }
+
+    fn attach(app: &mut VibetoneApp) -> Arc<AudioParams> {
+        let params = Arc::new(AudioParams::new(1.0));
+        app.params_handle = Some(Arc::clone(&params));
+        params
+    }
+
+    #[test]
+    fn break_counts_talk_only_while_enabled() {
+        let mut app = app();
+        let params = attach(&mut app);
+        let rate = app.sample_rate as u64;
+        params.speaking_samples.store(600 * rate, Ordering::Relaxed);
+        app.check_break();
+        assert_eq!(app.speaking_since_break, Duration::ZERO);
+
+        app.break_enabled = true;
+        app.check_break();
+        assert_eq!(app.speaking_since_break, Duration::ZERO);
+        params
+            .speaking_samples
+            .fetch_add(60 * rate, Ordering::Relaxed);
+        app.check_break();
+        assert_eq!(app.speaking_since_break.as_secs(), 60);
+    }
+
+    #[test]
+    fn vibe_rolls_the_fun_stages_and_reverts_them_but_never_privacy() {
+        let mut app = app();
+        app.privacy_voice = true;
+        let before = app.controls();
+        for _ in 0..20 {
+            app.roll_vibe();
+            assert!(app.privacy_voice, "a roll turned PRIVACY off");
+            assert!(app.voice);
+            assert!((2.0..=6.0).contains(&app.voice_pitch_semitones.abs()));
+            assert!((70.0..180.0).contains(&app.echo_time_ms));
+            assert!((0.4..2.5).contains(&app.reverb_decay_s));
+        }
+        assert!(app
+            .vibe_summary
+            .as_deref()
+            .is_some_and(|s| s.starts_with("PITCH")));
+        app.revert_vibe();
+        assert_eq!(app.controls(), before);
+        assert!(app.vibe_summary.is_none() && app.vibe_revert.is_none());
+    }
+
+    #[test]
+    fn start_without_devices_stays_offline() {
+        let mut app = app();
+        app.toggle_running();
+        assert!(!app.is_running());
+        assert_eq!(app.error.as_deref(), Some("No audio devices available"));
+        assert_eq!(app.status, "OFFLINE");
+
+        app.toggle_standby();
+        assert!(app.standby);
+        assert_eq!(
+            app.status, "OFFLINE",
+            "standby changed the status while stopped"
+        );
+        app.tick();
+        assert!(app.stopping_since.is_none());
+    }
+
+    #[test]
+    fn stop_fades_every_output_out_before_dropping_the_engine() {
+        let mut app = app();
+        app.fade_ms = 100;
+        let params = attach(&mut app);
+        params.open_outputs.store(2, Ordering::Relaxed);
+        app.stop();
+        assert!(params.fading_out.load(Ordering::Relaxed));
+        assert_eq!(app.status, "FADING");
+        app.finish_stop();
+        assert!(
+            app.params_handle.is_some(),
+            "dropped before any output faded"
+        );
+        params.faded_outputs.fetch_add(1, Ordering::Relaxed);
+        app.finish_stop();
+        assert!(
+            app.params_handle.is_some(),
+            "dropped with OUT 2 still fading"
+        );
+        params.faded_outputs.fetch_add(1, Ordering::Relaxed);
+        app.finish_stop();
+        assert!(app.params_handle.is_none() && app.stopping_since.is_none());
+        assert_eq!(app.status, "OFFLINE");
+
+        // An output that stopped calling back mid-fade is given up on once
+        // the fade and the grace have passed.
+        let params = attach(&mut app);
+        params.open_outputs.store(1, Ordering::Relaxed);
+        app.stop();
+        app.stopping_since = Instant::now().checked_sub(Duration::from_millis(90) + FADE_GRACE);
+        app.finish_stop();
+        assert!(app.params_handle.is_some(), "gave up inside the grace");
+        app.stopping_since = Instant::now().checked_sub(Duration::from_millis(110) + FADE_GRACE);
+        app.finish_stop();
+        assert!(app.params_handle.is_none());
+
+        // STOP again mid-fade stops at once, as does STOP without a fade.
+        attach(&mut app);
+        app.stop();
+        app.stop();
+        assert!(app.params_handle.is_none());
+        app.fade_ms = 0;
+        attach(&mut app);
+        app.stop();
+        assert!(app.params_handle.is_none() && app.stopping_since.is_none());
+    }
+
+    #[test]
+    fn device_picks_restore_by_name_and_never_double_up() {
+        let inputs = [
+            ("Microphone (Webcam)", false),
+            ("Microphone (USB Audio)", false),
+            ("CABLE Output", true),
+        ];
+        let outputs = [
+            ("Speakers (Realtek)", false),
+            ("Headphones (USB Audio)", false),
+            ("CABLE Input", true),
+        ];
+        // First run: the best-matched unblocked pair, never a blocked one.
+        assert_eq!(suggest_pair(&inputs, &outputs), Some((1, 1)));
+        assert_eq!(suggest_pair(&inputs[2..], &outputs), None);
+        let first = restore_picks(Picks::default(), &inputs, &outputs, &Settings::default());
+        assert_eq!((first.input, first.output), (1, 1));
+
+        // Remembered devices win, and missing ones keep the current pick.
+        let remembered = Settings {
+            input_device: Some("Microphone (Webcam)".into()),
+            output_device: Some("Gone".into()),
+            aux_output_device: Some("Speakers (Realtek)".into()),
+            backup_output_device: Some("Headphones (USB Audio)".into()),
+            sidechain_device: Some("Microphone (Webcam)".into()),
+            ..Settings::default()
+        };
+        let picks = restore_picks(first, &inputs, &outputs, &remembered);
+        assert_eq!(
+            picks,
+            Picks {
+                input: 0,
+                output: 1,
+                aux: Some(0),
+                backup: None,
+                sidechain: None,
+            },
+            "backup and sidechain landed on the main devices"
+        );
+
+        // Moving OUT onto OUT 2 clears OUT 2.
+        let mut app = app();
+        app.set_picks(Picks {
+            output: 1,
+            aux: Some(2),
+            backup: Some(1),
+            ..picks
+        });
+        app.selected_output = 2;
+        app.dedup_picks();
+        assert_eq!(
+            (app.selected_aux_output, app.selected_backup_output),
+            (None, Some(1))
+        );
+    }
+
+    #[test]
+    fn track_pair_remembers_the_last_pair_for_swap() {
+        let mut app = app();
+        let pair = |i: &str, o: &str| Some((i.to_string(), o.to_string()));
+        app.follow_pair(pair("USB Mic", "Headphones"));
+        assert_eq!(app.previous_pair, None, "nothing before the first pair");
+        app.follow_pair(pair("USB Mic", "Headphones"));
+        assert_eq!(app.previous_pair, None, "an unchanged pair isn't a switch");
+        app.follow_pair(pair("Webcam", "Speakers"));
+        assert_eq!(app.previous_pair, pair("USB Mic", "Headphones"));
+        // Devices vanishing doesn't count as a pair to go back to.
+        app.follow_pair(None);
+        app.follow_pair(pair("USB Mic", "Headphones"));
+        assert_eq!(app.previous_pair, pair("Webcam", "Speakers"));
+    }
+
+    #[test]
+    fn sync_params_pushes_changes_once_until_acked() {
+        let mut app = app();
+        let params = attach(&mut app);
+        app.reverb = true;
+        app.reverb_kind = ReverbKind::Plate;
+        app.comp_threshold = -30.0;
+        app.eq_bands[1].gain_db = 4.0;
+        app.cue_enabled = true;
+        app.cue_volume = 0.5;
+        app.sync_params();
+        let snapshot = params.controls();
+        let controls = &snapshot.controls;
+        assert!(controls.reverb_enabled);
+        assert_eq!(controls.reverb_kind, ReverbKind::Plate);
+        assert_eq!(controls.comp_threshold, -30.0);
+        assert_eq!(controls.eq_bands[1].gain_db, 4.0);
+        let cue = controls.cue.as_ref().expect("cue bus");
+        assert_eq!((cue.volume, cue.eq), (0.5, None));
+        assert_eq!(snapshot.generation, 1);
+        assert!(app.sync_pending().is_some());
+
+        // Nothing changed: no new generation, still waiting on the first.
+        app.sync_params();
+        assert_eq!(params.controls().generation, 1);
+        assert!(app.sync_pending().is_some());
+
+        params.acked_generation.store(1, Ordering::Release);
+        app.sync_params();
+        assert!(app.sync_pending().is_none());
+
+        app.volume = 0.5;
+        app.sync_params();
+        let snapshot = params.controls();
+        assert_eq!((snapshot.generation, snapshot.controls.volume), (2, 0.5));
+    }
+
+    #[test]
+    fn gain_sheet_reflects_the_controls_while_stopped() {
+        let mut app = app();
+        app.comp = true;
+        app.comp_makeup_db = 4.0;
+        let sheet = app.gain_sheet();
+        assert!(sheet.contains("makeup"), "{sheet}");
+        assert!(!sheet.contains("At export"), "{sheet}");
+    }
+
+    #[test]
+    fn presets_save_and_load_the_sound() {
+        let mut app = app();
+        assert_eq!(
+            app.sound(),
+            Sound::default(),
+            "preset defaults drifted from the GUI's"
+        );
+
+        let whisper = preset::factory()
+            .into_iter()
+            .find(|p| p.name == "Whisper")
+            .unwrap();
+        app.apply_sound(&whisper.sound);
+        assert!(!app.highpass && app.agc);
+        app.new_preset_name = " mine ".into();
+        app.new_preset_tags = vec![UseCase::Asmr];
+        app.save_preset();
+        app.apply_sound(&Sound::default());
+        app.save_preset();
+        assert_eq!(
+            app.profiles.current().presets.len(),
+            1,
+            "saved without a name"
+        );
+
+        let saved = app.profiles.current().presets[0].clone();
+        assert_eq!(
+            (saved.name.as_str(), saved.tags.as_slice()),
+            ("mine", [UseCase::Asmr].as_slice())
+        );
+        app.apply_sound(&saved.sound);
+        assert_eq!(app.sound(), whisper.sound);
+    }
+
+    #[test]
+    fn input_loss_follows_policy() {
+        let mut app = app();
+        let params = attach(&mut app);
+        params.input_lost.store(true, Ordering::Relaxed);
+        app.check_input_loss();
+        assert_eq!(
+            app.error.as_deref(),
+            Some("Input device lost: outputs held silent")
+        );
+        assert!(app.params_handle.is_some());
+
+        params.input_lost.store(false, Ordering::Relaxed);
+        app.check_input_loss();
+        assert_eq!(app.error, None);
+        assert_eq!(app.notice.as_deref(), Some("Input is back"));
+        let logged: Vec<&str> = app.event_log.iter().map(|l| &l[9..]).collect();
+        assert_eq!(logged, ["input lost, policy SILENCE", "input resumed"]);
+
+        app.input_loss = InputLoss::Stop;
+        params.input_lost.store(true, Ordering::Relaxed);
+        app.check_input_loss();
+        assert_eq!(
+            app.error.as_deref(),
+            Some("Input device lost: monitoring stopped")
+        );
+        assert_eq!(app.status, "FADING");
+        // No outputs open here, so nothing to wait for.
+        app.finish_stop();
+        assert!(app.params_handle.is_none());
+        assert_eq!(app.status, "OFFLINE");
+    }
+
+    #[test]
+    fn xrun_recoveries_are_logged_for_the_session() {
+        let mut app = app();
+        let params = attach(&mut app);
+        app.check_xrun_recoveries();
+        assert!(app.event_log.is_empty());
+
+        params.xrun_recoveries.store(2, Ordering::Relaxed);
+        app.check_xrun_recoveries();
+        app.notice = Some("something else".into());
+        app.check_xrun_recoveries();
+        assert_eq!(app.event_log.len(), 1);
+        assert!(
+            app.event_log[0].ends_with("ring flushed and re-primed (2 this session)"),
+            "{:?}",
+            app.event_log
+        );
+    }
+
+    #[test]
+    fn callback_events_are_logged_and_errors_shown() {
+        let mut app = app();
+        app.handle_event(Event::Gate(true));
+        assert!(matches!(app.gate_since, Some((true, _))));
+        assert!(app.event_log.is_empty(), "gate changes aren't logged");
+
+        app.handle_event(Event::StreamError(
+            StreamRole::Output(0),
+            StreamError::StreamInvalidated,
+        ));
+        assert_eq!(app.error, None, "a rebuild follows, nothing to show");
+        app.handle_event(Event::StreamError(
+            StreamRole::Output(0),
+            StreamError::DeviceNotAvailable,
+        ));
+        assert!(
+            app.error
+                .as_deref()
+                .is_some_and(|e| e.starts_with("Output error:")),
+            "{:?}",
+            app.error
+        );
+
+        // With a backup, OUT failing is failed over instead; OUT 2 isn't.
+        app.error = None;
+        app.selected_backup_output = Some(1);
+        app.handle_event(Event::StreamError(
+            StreamRole::Output(0),
+            StreamError::DeviceNotAvailable,
+        ));
+        assert_eq!(app.error, None);
+        assert!(
+            app.failover
+                .as_deref()
+                .is_some_and(|r| r.contains("no longer available")),
+            "{:?}",
+            app.failover
+        );
+        app.handle_event(Event::StreamError(
+            StreamRole::Output(1),
+            StreamError::DeviceNotAvailable,
+        ));
+        assert!(
+            app.error
+                .as_deref()
+                .is_some_and(|e| e.starts_with("Output 2 error:")),
+            "{:?}",
+            app.error
+        );
+
+        for _ in 0..EVENT_LOG_LEN {
+            app.handle_event(Event::Clip(0.4));
+        }
+        assert_eq!(app.event_log.len(), EVENT_LOG_LEN);
+        assert!(
+            app.event_log
+                .iter()
+                .all(|l| l.ends_with("clipped, peak +0.4 dBFS")),
+            "{:?}",
+            app.event_log
+        );
+    }
+
+    #[test]
+    fn whats_new_shows_until_dismissed_and_toggles_features() {
+        let mut app = app();
+        assert_eq!(
+            app.whats_new.len(),
+            whatsnew::CHANGELOG.len(),
+            "nothing seen yet"
+        );
+        assert_eq!(app.whats_new[0].version, whatsnew::latest(), "newest first");
+
+        app.set_feature(Feature::LoopGuard, false);
+        assert!(!app.loop_guard && !app.feature_on(Feature::LoopGuard));
+        app.set_feature(Feature::MidSide, true);
+        assert!(app.controls().mid_side);
+
+        app.dismiss_whats_new();
+        assert!(app.whats_new.is_empty());
+        let relaunched =
+            VibetoneApp::with_devices(std::mem::take(&mut app.profiles), Vec::new(), Vec::new());
+        assert!(relaunched.whats_new.is_empty(), "shown once");
+    }
+
+    #[test]
+    fn trigger_key_forces_the_gate_by_mode() {
+        let mut app = app();
+        let forced = |app: &VibetoneApp| app.controls().gate_forced;
+        app.trigger_key(egui::Key::F13, true);
+        assert_eq!(forced(&app), None, "trigger off");
+        app.trigger_key(egui::Key::F13, false);
+
+        app.gate_trigger = GateTrigger::Momentary;
+        assert_eq!(forced(&app), Some(false), "not held");
+        app.trigger_key(egui::Key::Space, true);
+        assert_eq!(forced(&app), Some(false), "another key");
+        app.trigger_key(egui::Key::F13, false);
+        app.trigger_key(egui::Key::F13, true);
+        assert_eq!(forced(&app), Some(true));
+
+        app.gate_trigger = GateTrigger::ForceShut;
+        app.trigger_latched = false;
+        app.trigger_key(egui::Key::F13, true);
+        app.trigger_key(egui::Key::F13, false);
+        assert_eq!(forced(&app), Some(false), "latched after letting go");
+        app.trigger_key(egui::Key::F13, true);
+        assert_eq!(forced(&app), None, "handed back");
+
+        app.learning_trigger = true;
+        app.trigger_key(egui::Key::B, true);
+        assert_eq!(app.profiles.current().gate_trigger_key, "B");
+        app.trigger_key(egui::Key::B, true);
+        assert_eq!(forced(&app), Some(false));
+    }
+
+    #[test]
+    fn dropouts_are_timed_from_the_counters() {
+        let mut app = app();
+        let params = attach(&mut app);
+        app.check_dropouts();
+        assert_eq!(app.last_dropout, None);
+
+        params.underruns.fetch_add(2, Ordering::Relaxed);
+        params.overruns.fetch_add(1, Ordering::Relaxed);
+        app.check_dropouts();
+        assert_eq!(app.xruns_seen, (2, 1));
+        let at = app.last_dropout.expect("dropout");
+
+        app.check_dropouts();
+        assert_eq!(app.last_dropout, Some(at), "no new dropout");
+    }
+
+    #[test]
+    fn stage_faults_are_reported_once() {
+        let mut app = app();
+        let params = attach(&mut app);
+        params
+            .faulted_stages
+            .fetch_or(Stage::Reverb.bit() | Stage::Gate.bit(), Ordering::Relaxed);
+        app.check_stage_faults();
+        assert_eq!(
+            app.error.as_deref(),
+            Some("GATE, REVERB crashed and is bypassed (restart to retry)")
+        );
+
+        app.error = None;
+        app.check_stage_faults();
+        assert_eq!(app.error, None);
+    }
+}
@@ -0,0 +1,370 @@
+//! Headset mute button: the telephony mute button on a USB headset
+//! toggles STANDBY, and the headset's mute LED follows STANDBY.
+//!
+//! Headsets announce both in their HID report descriptor: Phone Mute on
+//! the telephony page, Mute on the LED page. A worker thread finds the
+//! first headset that has the button, works out where the two fields sit
+//! in its reports, then reads presses and writes the LED. A headset that
+//! is missing or unplugged is looked for again every few seconds.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use hidapi::{HidApi, HidDevice};
+
+const TELEPHONY_PAGE: u16 = 0x0B;
+/// Usages as (page << 16 | id).
+const PHONE_MUTE: u32 = 0x000B_002F;
+const LED_MUTE: u32 = 0x0008_0009;
+const RETRY: Duration = Duration::from_secs(3);
+/// How long a read waits before the LED is looked at again.
+const POLL_MS: i32 = 100;
+
+/// A field in a report: its report ID (0 when the device numbers none),
+/// bit offset after the ID byte, and width in bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Field {
+    report: u8,
+    bit: usize,
+    bits: usize,
+}
+
+impl Field {
+    fn read(&self, data: &[u8]) -> u32 {
+        (0..self.bits.min(32)).fold(0, |value, k| {
+            let bit = self.bit + k;
+            let set = data
+                .get(bit / 8)
+                .is_some_and(|byte| byte >> (bit % 8) & 1 != 0);
+            value | (set as u32) << k
+        })
+    }
+
+    fn write(&self, data: &mut [u8], value: u32) {
+        for k in 0..self.bits.min(32) {
+            let bit = self.bit + k;
+            if let Some(byte) = data.get_mut(bit / 8) {
+                let mask = 1 << (bit % 8);
+                if value >> k & 1 != 0 {
+                    *byte |= mask;
+                } else {
+                    *byte &= !mask;
+                }
+            }
+        }
+    }
+}
+
+/// Where a headset keeps its mute button and LED.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Layout {
+    button: Option<Field>,
+    led: Option<Field>,
+    /// Length in bytes of the LED's output report, without the ID.
+    led_report_len: usize,
+    /// Whether reports start with a report ID.
+    numbered: bool,
+}
+
+impl Layout {
+    /// Walk a report descriptor, keeping the running bit offset of every
+    /// input and output report, and note where the two usages land.
+    fn parse(descriptor: &[u8]) -> Self {
+        #[derive(Clone, Copy, Default)]
+        struct Globals {
+            page: u16,
+            size: usize,
+            count: usize,
+            report: u8,
+        }
+        let mut globals = Globals::default();
+        let mut pushed = Vec::new();
+        // Local items, reset by every main item.
+        let mut usages: Vec<u32> = Vec::new();
+        let (mut usage_min, mut usage_max) = (None, None);
+        // Bits taken so far per (output, report ID).
+        let mut offsets: BTreeMap<(bool, u8), usize> = BTreeMap::new();
+        let mut layout = Layout::default();
+
+        let mut i = 0;
+        while i < descriptor.len() {
+            let prefix = descriptor[i];
+            if prefix == 0xFE {
+                // Long item: size byte, tag byte, data.
+                i += 3 + descriptor.get(i + 1).copied().unwrap_or(0) as usize;
+                continue;
+            }
+            let len = [0, 1, 2, 4][(prefix & 3) as usize];
+            let Some(data) = descriptor.get(i + 1..i + 1 + len) else {
+                break;
+            };
+            let value = data.iter().rev().fold(0u32, |v, &b| v << 8 | b as u32);
+            i += 1 + len;
+            match prefix & 0xFC {
+                0x04 => globals.page = value as u16,
+                0x74 => globals.size = value as usize,
+                0x84 => {
+                    globals.report = value as u8;
+                    layout.numbered = true;
+                }
+                0x94 => globals.count = value as usize,
+                0xA4 => pushed.push(globals),
+                0xB4 => globals = pushed.pop().unwrap_or_default(),
+                0x08 => usages.push(value),
+                0x18 => usage_min = Some(value),
+                0x28 => usage_max = Some(value),
+                // Input and Output.
+                tag @ (0x80 | 0x90) => {
+                    let output = tag == 0x90;
+                    // Short usages take the page current at the main item.
+                    let full = |usage: u32| {
+                        if usage > 0xFFFF {
+                            usage
+                        } else {
+                            (globals.page as u32) << 16 | usage
+                        }
+                    };
+                    let offset = offsets.entry((output, globals.report)).or_default();
+                    // Constant fields are padding.
+                    if value & 1 == 0 {
+                        for n in 0..globals.count {
+                            let usage = match usages.get(n).or(usages.last()) {
+                                Some(&usage) => Some(usage),
+                                None => usage_min
+                                    .map(|min| min + n as u32)
+                                    .filter(|&u| usage_max.is_none_or(|max| u <= max)),
+                            };
+                            let field = Field {
+                                report: globals.report,
+                                bit: *offset + n * globals.size,
+                                bits: globals.size,
+                            };
+                            match (output, usage.map(full)) {
+                                (false, Some(PHONE_MUTE)) => {
+                                    layout.button.get_or_insert(field);
+                                }
+                                (true, Some(LED_MUTE)) => {
+                                    layout.led.get_or_insert(field);
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    *offset += globals.size * globals.count;
+                    usages.clear();
+                    (usage_min, usage_max) = (None, None);
+                }
+                // Feature, Collection, End Collection.
+                0xB0 | 0xA0 | 0xC0 => {
+                    usages.clear();
+                    (usage_min, usage_max) = (None, None);
+                }
+                _ => {}
+            }
+        }
+        if let Some(led) = layout.led {
+            layout.led_report_len = offsets[&(true, led.report)].div_ceil(8);
+        }
+        layout
+    }
+}
+
+/// GUI side of the headset thread, which stops once this is dropped.
+pub struct Headset {
+    /// One message per press of the mute button.
+    pub presses: Receiver<()>,
+    muted: Sender<bool>,
+    /// The headset in use, or why there is none; for the GUI.
+    pub status: Arc<Mutex<Result<String, String>>>,
+}
+
+impl Headset {
+    pub fn spawn() -> Self {
+        let (press_tx, presses) = mpsc::channel();
+        let (muted, muted_rx) = mpsc::channel();
+        let status = Arc::new(Mutex::new(Ok("looking for a headset".to_string())));
+        let worker_status = Arc::clone(&status);
+        std::thread::spawn(move || run(press_tx, muted_rx, worker_status));
+        Self {
+            presses,
+            muted,
+            status,
+        }
+    }
+
+    /// Light the headset's mute LED, or turn it off.
+    pub fn set_muted(&self, muted: bool) {
+        // The worker only stops when this handle is dropped.
+        let _ = self.muted.send(muted);
+    }
+}
+
+fn run(presses: Sender<()>, muted: Receiver<bool>, status: Arc<Mutex<Result<String, String>>>) {
+    let set_status = |s: Result<String, String>| *status.lock().unwrap() = s;
+    let mut led = false;
+    loop {
+        let served = open().and_then(|(device, layout, name)| {
+            let lit = if layout.led.is_some() {
+                "mute LED follows"
+            } else {
+                "no mute LED"
+            };
+            set_status(Ok(format!("{name} ({lit})")));
+            serve(&device, &layout, &presses, &muted, &mut led)
+        });
+        match served {
+            // The GUI let go.
+            Ok(()) => return,
+            Err(e) => set_status(Err(format!("{e:#}"))),
+        }
+        // Keep up with the LED state until it's time to look again.
+        let retry_at = Instant::now() + RETRY;
+        while let Some(left) = retry_at.checked_duration_since(Instant::now()) {
+            match muted.recv_timeout(left) {
+                Ok(m) => led = m,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+/// The first headset whose mute button can be found, with its layout and
+/// a name for the GUI.
+fn open() -> Result<(HidDevice, Layout, String)> {
+    let api = HidApi::new().context("can't list HID devices")?;
+    let mut found = None;
+    for info in api
+        .device_list()
+        .filter(|d| d.usage_page() == TELEPHONY_PAGE)
+    {
+        let name = info.product_string().unwrap_or("headset").to_string();
+        let Ok(device) = api.open_path(info.path()) else {
+            found.get_or_insert_with(|| format!("can't open {name}"));
+            continue;
+        };
+        let mut descriptor = [0u8; 4096];
+        let len = device.get_report_descriptor(&mut descriptor).unwrap_or(0);
+        let layout = Layout::parse(&descriptor[..len]);
+        if layout.button.is_some() {
+            return Ok((device, layout, name));
+        }
+        found.get_or_insert_with(|| format!("{name} has no mute button"));
+    }
+    anyhow::bail!(found.unwrap_or_else(|| "no headset with a mute button".into()))
+}
+
+/// Pass presses up and the LED state down until the headset goes away
+/// (an error) or the GUI does (`Ok`).
+fn serve(
+    device: &HidDevice,
+    layout: &Layout,
+    presses: &Sender<()>,
+    muted: &Receiver<bool>,
+    led: &mut bool,
+) -> Result<()> {
+    let Some(button) = layout.button else {
+        return Ok(());
+    };
+    let mut lit = None;
+    let mut held = false;
+    let mut buf = [0u8; 256];
+    loop {
+        loop {
+            match muted.try_recv() {
+                Ok(m) => *led = m,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+        if let Some(field) = layout.led.filter(|_| lit != Some(*led)) {
+            let mut report = vec![0u8; 1 + layout.led_report_len];
+            report[0] = field.report;
+            field.write(&mut report[1..], *led as u32);
+            // Some headsets only take the LED from the app that owns the
+            // call; the button works regardless.
+            let _ = device.write(&report);
+            lit = Some(*led);
+        }
+
+        let n = device
+            .read_timeout(&mut buf, POLL_MS)
+            .context("headset unplugged")?;
+        let report = &buf[..n];
+        let (id, data) = match report.split_first() {
+            Some((&id, data)) if layout.numbered => (id, data),
+            _ => (0, report),
+        };
+        if n == 0 || id != button.report {
+            continue;
+        }
+        // The button reads 1 while held: a press is the rising edge.
+        let down = button.read(data) != 0;
+        if down && !held && presses.send(()).is_err() {
+            return Ok(());
+        }
+        held = down;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_mute_button_and_led() {
+        // Trimmed from a USB headset: report 1 holds hook switch, padding
+        // and the mute button; report 2 the off-hook, mute and ring LEDs.
+        #[rustfmt::skip]
+        let descriptor = [
+            0x05, 0x0B, 0x09, 0x05, 0xA1, 0x01,       // Telephony, Headset, Collection
+            0x85, 0x01, 0x15, 0x00, 0x25, 0x01,       //   Report 1, 0..1
+            0x75, 0x01, 0x95, 0x01, 0x09, 0x20,       //   1 bit Hook Switch
+            0x81, 0x02,
+            0x95, 0x02, 0x81, 0x03,                   //   2 bits padding
+            0x95, 0x01, 0x09, 0x2F, 0x81, 0x06,       //   1 bit Phone Mute (relative)
+            0x95, 0x04, 0x81, 0x03,                   //   4 bits padding
+            0x85, 0x02, 0x05, 0x08,                   //   Report 2, LEDs
+            0x95, 0x03, 0x09, 0x17, 0x09, 0x09,       //   Off-Hook, Mute, Ring
+            0x09, 0x18, 0x91, 0x02,
+            0x95, 0x0D, 0x91, 0x03,                   //   13 bits padding
+            0xC0,
+        ];
+        let layout = Layout::parse(&descriptor);
+        assert_eq!(
+            layout.button,
+            Some(Field {
+                report: 1,
+                bit: 3,
+                bits: 1
+            })
+        );
+        assert_eq!(
+            layout.led,
+            Some(Field {
+                report: 2,
+                bit: 1,
+                bits: 1
+            })
+        );
+        assert_eq!(layout.led_report_len, 2);
+        assert!(layout.numbered);
+
+        let button = layout.button.unwrap();
+        assert_eq!(button.read(&[0b0000_1000]), 1);
+        assert_eq!(button.read(&[0b1111_0111]), 0);
+        let mut report = [0u8; 2];
+        layout.led.unwrap().write(&mut report, 1);
+        assert_eq!(report, [0b10, 0]);
+
+        // A keyboard has neither.
+        let keyboard = [
+            0x05, 0x01, 0x09, 0x06, 0xA1, 0x01, 0x05, 0x07, 0x19, 0xE0, 0x29, 0xE7, 0x75, 0x01,
+            0x95, 0x08, 0x81, 0x02, 0xC0,
+        ];
+        assert_eq!(Layout::parse(&keyboard), Layout::default());
+    }
+}
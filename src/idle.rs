@@ -0,0 +1,148 @@
+//! How long the user has been away from keyboard and mouse, system-wide.
+//!
+//! Backed by GetLastInputInfo on Windows, CoreGraphics on macOS and the
+//! X11 screensaver extension on Linux. Wayland sessions have no portable
+//! equivalent, so `idle_time` returns `None` there (as it does wherever
+//! the query fails) and idle handling simply never kicks in.
+
+pub use imp::IdleMonitor;
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::ptr;
+    use std::time::Duration;
+
+    use x11_dl::{xlib, xss};
+
+    pub struct IdleMonitor {
+        x11: Option<(xlib::Xlib, xss::Xss, *mut xlib::Display)>,
+    }
+
+    impl IdleMonitor {
+        pub fn new() -> Self {
+            // XWayland only sees X clients' input, so it would report the
+            // user idle while they're busy in native Wayland apps.
+            if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                return Self { x11: None };
+            }
+            let x11 = (|| {
+                let xlib = xlib::Xlib::open().ok()?;
+                let xss = xss::Xss::open().ok()?;
+                let display = unsafe { (xlib.XOpenDisplay)(ptr::null()) };
+                (!display.is_null()).then_some((xlib, xss, display))
+            })();
+            Self { x11 }
+        }
+
+        pub fn idle_time(&self) -> Option<Duration> {
+            let (xlib, xss, display) = self.x11.as_ref()?;
+            unsafe {
+                let info = (xss.XScreenSaverAllocInfo)();
+                if info.is_null() {
+                    return None;
+                }
+                let root = (xlib.XDefaultRootWindow)(*display);
+                let ok = (xss.XScreenSaverQueryInfo)(*display, root, info) != 0;
+                // c_ulong is only 32 bits on some targets.
+                #[allow(clippy::useless_conversion)]
+                let idle = u64::from((*info).idle);
+                (xlib.XFree)(info.cast());
+                ok.then(|| Duration::from_millis(idle))
+            }
+        }
+    }
+
+    impl Drop for IdleMonitor {
+        fn drop(&mut self) {
+            if let Some((xlib, _, display)) = &self.x11 {
+                unsafe { (xlib.XCloseDisplay)(*display) };
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::time::Duration;
+
+    #[repr(C)]
+    struct LastInputInfo {
+        cb_size: u32,
+        dw_time: u32,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetLastInputInfo(plii: *mut LastInputInfo) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetTickCount() -> u32;
+    }
+
+    pub struct IdleMonitor;
+
+    impl IdleMonitor {
+        pub fn new() -> Self {
+            Self
+        }
+
+        pub fn idle_time(&self) -> Option<Duration> {
+            let mut info = LastInputInfo {
+                cb_size: std::mem::size_of::<LastInputInfo>() as u32,
+                dw_time: 0,
+            };
+            if unsafe { GetLastInputInfo(&mut info) } == 0 {
+                return None;
+            }
+            let now = unsafe { GetTickCount() };
+            Some(Duration::from_millis(now.wrapping_sub(info.dw_time) as u64))
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::time::Duration;
+
+    const COMBINED_SESSION_STATE: i32 = 0;
+    const ANY_INPUT_EVENT_TYPE: u32 = !0;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventSourceSecondsSinceLastEventType(state: i32, event_type: u32) -> f64;
+    }
+
+    pub struct IdleMonitor;
+
+    impl IdleMonitor {
+        pub fn new() -> Self {
+            Self
+        }
+
+        pub fn idle_time(&self) -> Option<Duration> {
+            let secs = unsafe {
+                CGEventSourceSecondsSinceLastEventType(COMBINED_SESSION_STATE, ANY_INPUT_EVENT_TYPE)
+            };
+            Duration::try_from_secs_f64(secs).ok()
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+mod imp {
+    use std::time::Duration;
+
+    pub struct IdleMonitor;
+
+    impl IdleMonitor {
+        pub fn new() -> Self {
+            Self
+        }
+
+        pub fn idle_time(&self) -> Option<Duration> {
+            None
+        }
+    }
+}
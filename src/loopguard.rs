@@ -0,0 +1,185 @@
+//! Loopback guard: catches an output that flows straight back into the
+//! input (a virtual cable patched onto itself, a loopback capture picked
+//! as the mic), which would otherwise build up into runaway feedback.
+//!
+//! For the first moments of a session every output plays a quiet
+//! maximum-length sequence under the audio, and the input callback keeps
+//! the raw input it sees meanwhile. An MLS only correlates with itself at
+//! one lag, so a copy of it in the input stands far above mic noise.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::dsp::db_to_gain;
+
+/// One period of the probe: a 12-bit MLS.
+const MLS_LEN: usize = 4095;
+/// Below the noise floor of most rooms and mics.
+const PROBE_DB: f32 = -54.0;
+/// How long the probe plays: the round trip plus one whole period.
+pub const PROBE_MS: u64 = 600;
+/// Enough capture for `PROBE_MS` at the highest rate offered.
+const CAPTURE_LEN: usize = 96_000 * PROBE_MS as usize / 1000;
+/// Correlation peak, in standard deviations of the other lags, that
+/// counts as a loop. Noise alone tops out around 4.
+const DETECT_SIGMAS: f32 = 10.0;
+
+/// The probe period as ±1 samples (Fibonacci LFSR, x¹² + x¹¹ + x¹⁰ + x⁴ + 1).
+fn mls() -> Vec<f32> {
+    let mut state = 1u16;
+    (0..MLS_LEN)
+        .map(|_| {
+            let bit = (state ^ (state >> 1) ^ (state >> 2) ^ (state >> 8)) & 1;
+            state = (state >> 1) | (bit << 11);
+            if state & 1 == 1 {
+                1.0
+            } else {
+                -1.0
+            }
+        })
+        .collect()
+}
+
+/// Shared between the GUI, the input callback and the outputs.
+pub struct LoopGuard {
+    /// Set while the probe plays and the input is captured.
+    probing: AtomicBool,
+    /// Raw mono input seen while probing. The input callback only
+    /// `try_lock`s it and never grows it past its capacity.
+    capture: Mutex<Vec<f32>>,
+}
+
+impl Default for LoopGuard {
+    fn default() -> Self {
+        Self {
+            probing: AtomicBool::new(false),
+            capture: Mutex::new(Vec::with_capacity(CAPTURE_LEN)),
+        }
+    }
+}
+
+impl LoopGuard {
+    pub fn start(&self) {
+        self.capture.lock().unwrap().clear();
+        self.probing.store(true, Ordering::Relaxed);
+    }
+
+    pub fn probing(&self) -> bool {
+        self.probing.load(Ordering::Relaxed)
+    }
+
+    /// Input side: keep `data` (interleaved, `channels` wide) as mono.
+    pub fn capture(&self, data: &[f32], channels: usize) {
+        if !self.probing() {
+            return;
+        }
+        if let Ok(mut capture) = self.capture.try_lock() {
+            let room = capture.capacity() - capture.len();
+            capture.extend(
+                data.chunks_exact(channels)
+                    .take(room)
+                    .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+            );
+        }
+    }
+
+    /// End the probe and say whether the input heard it.
+    pub fn finish(&self) -> bool {
+        self.probing.store(false, Ordering::Relaxed);
+        detect(&self.capture.lock().unwrap(), &mls())
+    }
+}
+
+/// Output side: the probe, looped, while the guard is probing.
+pub struct ProbeTone {
+    seq: Vec<f32>,
+    pos: usize,
+    level: f32,
+}
+
+impl ProbeTone {
+    pub fn new() -> Self {
+        Self {
+            seq: mls(),
+            pos: 0,
+            level: db_to_gain(PROBE_DB),
+        }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let s = self.seq[self.pos] * self.level;
+        self.pos = (self.pos + 1) % self.seq.len();
+        s
+    }
+}
+
+/// Whether the last period of `captured` holds a copy of `seq`, at any
+/// delay and level.
+fn detect(captured: &[f32], seq: &[f32]) -> bool {
+    let n = seq.len();
+    let Some(start) = captured.len().checked_sub(n) else {
+        return false;
+    };
+    let window = &captured[start..];
+    // Circular cross-correlation, one lag per rotation of the sequence.
+    let doubled: Vec<f32> = seq.iter().chain(seq).copied().collect();
+    let corr: Vec<f32> = (0..n)
+        .map(|lag| {
+            window
+                .iter()
+                .zip(&doubled[lag..lag + n])
+                .map(|(x, s)| x * s)
+                .sum()
+        })
+        .collect();
+    let (peak_lag, peak) =
+        corr.iter()
+            .map(|c| c.abs())
+            .enumerate()
+            .fold(
+                (0, 0.0f32),
+                |best, (i, c)| if c > best.1 { (i, c) } else { best },
+            );
+    let rest = corr
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != peak_lag)
+        .map(|(_, c)| c * c)
+        .sum::<f32>();
+    let sigma = (rest / (n - 1) as f32).sqrt();
+    peak > 0.0 && peak > DETECT_SIGMAS * sigma
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hears_a_looped_probe_but_not_noise() {
+        let mut rng = 0x2545_f491_4f6c_dd1du64;
+        let mut noise = move || {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            (rng >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        };
+        let seq = mls();
+        // The probe back 12 ms late at half level, under room noise 7 dB louder.
+        let level = db_to_gain(PROBE_DB);
+        let mut probe = ProbeTone::new();
+        let delay = 576;
+        let played: Vec<f32> = (0..2 * MLS_LEN).map(|_| probe.next_sample()).collect();
+        let looped: Vec<f32> = (0..2 * MLS_LEN)
+            .map(|i| {
+                let echo = i.checked_sub(delay).map_or(0.0, |j| played[j] * 0.5);
+                echo + noise() * level * 4.0
+            })
+            .collect();
+        assert!(detect(&looped, &seq));
+
+        let room: Vec<f32> = (0..2 * MLS_LEN).map(|_| noise() * 0.1).collect();
+        assert!(!detect(&room, &seq));
+        assert!(!detect(&[0.0; 2 * MLS_LEN], &seq), "silence");
+        assert!(!detect(&played[..100], &seq), "too short to tell");
+    }
+}
@@ -1,9 +1,36 @@
 mod audio;
+mod beam;
+mod bus;
+mod cli;
+mod denoise;
 mod device;
+mod dsp;
+mod eq;
+mod events;
+mod gainsheet;
 mod gui;
+mod headset;
+mod idle;
+mod loopguard;
+mod meter;
+mod onair;
+mod player;
+mod preflight;
+mod preset;
+mod probe;
+mod room;
+mod schedule;
+#[cfg(feature = "scripting")]
+mod script;
+mod settings;
+mod whatsnew;
 
 use anyhow::Result;
 
 fn main() -> Result<()> {
-    gui::run()
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "probe") {
+        return probe::run(args.iter().any(|a| a == "--json"));
+    }
+    gui::run(cli::LaunchOptions::parse(&args)?)
 }
@@ -0,0 +1,170 @@
+//! Meter and scope data from the audio thread to the GUI.
+//!
+//! A triple buffer: the writer always has a slot of its own to fill, the
+//! reader always has one to look at, and the third holds the newest
+//! finished frame. Neither side allocates, locks or waits, so a GUI that
+//! drops frames can't stall the audio thread, and the GUI always sees the
+//! most recent complete frame, never a half-written one.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// Samples of output waveform carried in each frame.
+pub const SCOPE_LEN: usize = 256;
+
+/// One snapshot of the processed signal.
+#[derive(Clone, Copy)]
+pub struct MeterFrame {
+    /// Decaying peak in dBFS.
+    pub peak_db: f32,
+    pub short_term_lufs: f32,
+    /// The gate is enabled and fully closed: output is silent because of
+    /// the gate, not because the device or routing is broken.
+    pub gated: bool,
+    /// Gain the AGC is applying in dB; 0 when it's off.
+    pub agc_gain_db: f32,
+    /// Compressor gain reduction in dB; 0 when it's off.
+    pub gain_reduction_db: f32,
+    /// Limiter gain reduction in dB; 0 when it's off or idle.
+    pub limiter_reduction_db: f32,
+    /// Most recent output samples, oldest first.
+    pub scope: [f32; SCOPE_LEN],
+}
+
+impl Default for MeterFrame {
+    fn default() -> Self {
+        Self {
+            peak_db: crate::dsp::gain_to_db(0.0),
+            short_term_lufs: crate::dsp::LUFS_FLOOR,
+            gated: false,
+            agc_gain_db: 0.0,
+            gain_reduction_db: 0.0,
+            limiter_reduction_db: 0.0,
+            scope: [0.0; SCOPE_LEN],
+        }
+    }
+}
+
+/// Low bits: index of the middle slot. `FRESH`: it was published after
+/// the reader's last swap.
+const INDEX: u8 = 0b11;
+const FRESH: u8 = 0b100;
+
+struct Shared<T> {
+    slots: [UnsafeCell<T>; 3],
+    middle: AtomicU8,
+}
+
+// Each slot is only ever touched through the one handle that currently
+// owns its index; ownership moves with the atomic swap on `middle`.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+pub struct TripleWriter<T> {
+    shared: Arc<Shared<T>>,
+    back: u8,
+}
+
+pub struct TripleReader<T> {
+    shared: Arc<Shared<T>>,
+    front: u8,
+}
+
+pub fn triple_buffer<T: Default>() -> (TripleWriter<T>, TripleReader<T>) {
+    let shared = Arc::new(Shared {
+        slots: Default::default(),
+        middle: AtomicU8::new(1),
+    });
+    (
+        TripleWriter {
+            shared: Arc::clone(&shared),
+            back: 0,
+        },
+        TripleReader { shared, front: 2 },
+    )
+}
+
+impl<T> TripleWriter<T> {
+    /// The slot being filled. It holds whatever frame last passed through
+    /// it, so overwrite every field.
+    pub fn back(&mut self) -> &mut T {
+        // SAFETY: `back` is owned by this writer until `publish` hands it over.
+        unsafe { &mut *self.shared.slots[self.back as usize].get() }
+    }
+
+    /// Make the back slot the newest frame.
+    pub fn publish(&mut self) {
+        let old = self.shared.middle.swap(self.back | FRESH, Ordering::AcqRel);
+        self.back = old & INDEX;
+    }
+}
+
+impl<T> TripleReader<T> {
+    /// The newest published frame (or the last one read, if nothing new
+    /// has been published since).
+    pub fn read(&mut self) -> &T {
+        if self.shared.middle.load(Ordering::Relaxed) & FRESH != 0 {
+            let old = self.shared.middle.swap(self.front, Ordering::AcqRel);
+            self.front = old & INDEX;
+        }
+        // SAFETY: `front` is owned by this reader until its next swap.
+        unsafe { &*self.shared.slots[self.front as usize].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_default_until_published() {
+        let (mut writer, mut reader) = triple_buffer::<u32>();
+        assert_eq!(*reader.read(), 0);
+        *writer.back() = 7;
+        assert_eq!(*reader.read(), 0);
+        writer.publish();
+        assert_eq!(*reader.read(), 7);
+    }
+
+    #[test]
+    fn newest_frame_wins() {
+        let (mut writer, mut reader) = triple_buffer::<u32>();
+        for v in 1..=5 {
+            *writer.back() = v;
+            writer.publish();
+        }
+        assert_eq!(*reader.read(), 5);
+        // Nothing new: the same frame again, not an older one.
+        assert_eq!(*reader.read(), 5);
+        *writer.back() = 6;
+        writer.publish();
+        assert_eq!(*reader.read(), 6);
+    }
+
+    /// Frames are filled with one value throughout, so a torn read shows up
+    /// as mixed values. Reads must also never go back in time, and once the
+    /// writer stops the reader must see its very last frame.
+    #[test]
+    fn concurrent_reads_are_whole_ordered_and_current() {
+        const FRAMES: u64 = 200_000;
+        let (mut writer, mut reader) = triple_buffer::<[u64; 32]>();
+
+        let producer = std::thread::spawn(move || {
+            for v in 1..=FRAMES {
+                writer.back().fill(v);
+                writer.publish();
+            }
+        });
+
+        let mut last = 0;
+        while !producer.is_finished() {
+            let frame = reader.read();
+            let v = frame[0];
+            assert!(frame.iter().all(|&x| x == v), "torn frame: {frame:?}");
+            assert!(v >= last, "went back from {last} to {v}");
+            last = v;
+        }
+        producer.join().unwrap();
+        assert_eq!(reader.read()[0], FRAMES);
+    }
+}
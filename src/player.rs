@@ -0,0 +1,250 @@
+//! Audio files as an input source (SRC FILE): a recording of your own voice
+//! to tune the gate and EQ against. The file is decoded to mono at the
+//! engine's rate on a worker thread, then looped by the audio thread in
+//! place of the mic.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+
+use anyhow::{Context, Result};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Only this much of a file is loaded; the loop is whatever fits.
+const MAX_SECS: u32 = 600;
+
+/// Shared between the GUI, the loader thread and the audio thread.
+#[derive(Default)]
+pub struct FileSlot {
+    /// What's loaded, or why the last load failed; shown in the GUI.
+    pub status: Mutex<Option<Result<String, String>>>,
+    /// Freshly decoded clip waiting for the audio thread.
+    pending: Mutex<Option<Vec<f32>>>,
+    /// The clip it replaced, handed back so it's freed off the audio
+    /// thread; see `free_retired`.
+    retired: Mutex<Option<Vec<f32>>>,
+}
+
+impl FileSlot {
+    /// Free the clip the audio thread last swapped out, if any. Called by
+    /// the GUI each frame and by the loader before it hands over a clip.
+    pub fn free_retired(&self) {
+        let retired = self.retired.lock().unwrap().take();
+        drop(retired);
+    }
+}
+
+/// Decode `path` off the audio thread and hand it to the slot. Nothing
+/// happens if the engine (and with it the slot) is gone by then.
+pub fn spawn_loader(slot: Weak<FileSlot>, path: PathBuf, sample_rate: u32) {
+    if let Some(slot) = slot.upgrade() {
+        *slot.status.lock().unwrap() = Some(Ok(format!("loading {}", display_name(&path))));
+    }
+    std::thread::spawn(move || {
+        let decoded = decode(&path, sample_rate);
+        let Some(slot) = slot.upgrade() else {
+            return;
+        };
+        let name = display_name(&path);
+        let status = match decoded {
+            Ok(clip) => {
+                let secs = clip.len() as f32 / sample_rate as f32;
+                slot.free_retired();
+                *slot.pending.lock().unwrap() = Some(clip);
+                Ok(format!("{name} ({secs:.0}s, looped)"))
+            }
+            Err(e) => Err(format!("{name}: {e:#}")),
+        };
+        *slot.status.lock().unwrap() = Some(status);
+    });
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |n| n.to_string_lossy().into_owned(),
+    )
+}
+
+/// The first `MAX_SECS` of the file's first audio track, as mono at
+/// `sample_rate`.
+fn decode(path: &Path, sample_rate: u32) -> Result<Vec<f32>> {
+    let file = std::fs::File::open(path)?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .context("not a supported audio file")?
+        .format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .context("no audio track")?;
+    let track_id = track.id;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut samples = Vec::new();
+    let (mut channels, mut rate) = (1, sample_rate);
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt packet: skip it, like a player would.
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let spec = *decoded.spec();
+        (channels, rate) = (spec.channels.count().max(1), spec.rate);
+        let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buf.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buf.samples());
+        if samples.len() >= (MAX_SECS * rate) as usize * channels {
+            break;
+        }
+    }
+    samples.truncate((MAX_SECS * rate) as usize * channels);
+    if samples.is_empty() {
+        anyhow::bail!("no audio in file");
+    }
+    Ok(to_mono_at(&samples, channels, rate, sample_rate))
+}
+
+/// Mix interleaved audio down to mono and resample it from `from` to `to`
+/// Hz by linear interpolation (plenty for a level and tone reference).
+fn to_mono_at(interleaved: &[f32], channels: usize, from: u32, to: u32) -> Vec<f32> {
+    let mono: Vec<f32> = interleaved
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    if from == to || mono.is_empty() {
+        return mono;
+    }
+    let step = from as f64 / to as f64;
+    let len = (mono.len() as f64 / step) as usize;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let j = pos as usize;
+            let frac = (pos - j as f64) as f32;
+            let a = mono[j];
+            let b = mono.get(j + 1).copied().unwrap_or(a);
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+/// Audio-thread side: loops the latest clip picked up from the slot.
+pub struct FilePlayer {
+    slot: Arc<FileSlot>,
+    clip: Vec<f32>,
+    pos: usize,
+}
+
+impl FilePlayer {
+    pub fn new(slot: Arc<FileSlot>) -> Self {
+        Self {
+            slot,
+            clip: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Replace `block` with the next stretch of the clip; silence until
+    /// one has loaded.
+    pub fn fill(&mut self, block: &mut [f32]) {
+        // Pick up a new clip without ever blocking the audio thread, and
+        // without freeing the old one here: it goes back through
+        // `retired`, and waits while the last one is still there.
+        if let (Ok(mut pending), Ok(mut retired)) =
+            (self.slot.pending.try_lock(), self.slot.retired.try_lock())
+        {
+            if retired.is_none() {
+                if let Some(clip) = pending.take() {
+                    let old = std::mem::replace(&mut self.clip, clip);
+                    // Dropping a never-filled Vec frees nothing.
+                    if old.capacity() > 0 {
+                        *retired = Some(old);
+                    }
+                    self.pos = 0;
+                }
+            }
+        }
+        if self.clip.is_empty() {
+            block.fill(0.0);
+            return;
+        }
+        for s in block.iter_mut() {
+            *s = self.clip[self.pos];
+            self.pos = (self.pos + 1) % self.clip.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixes_down_and_resamples() {
+        let stereo = [1.0, 0.0, 0.5, 0.5, 0.0, 1.0, 1.0, 1.0];
+        assert_eq!(to_mono_at(&stereo, 2, 48_000, 48_000), [0.5, 0.5, 0.5, 1.0]);
+
+        let ramp: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        assert_eq!(
+            to_mono_at(&ramp, 1, 24_000, 48_000)[..5],
+            [0.0, 0.5, 1.0, 1.5, 2.0]
+        );
+        assert_eq!(to_mono_at(&ramp, 1, 48_000, 24_000), [0.0, 2.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn player_loops_the_newest_clip() {
+        let slot = Arc::new(FileSlot::default());
+        let mut player = FilePlayer::new(Arc::clone(&slot));
+        let mut block = [1.0; 4];
+        player.fill(&mut block);
+        assert_eq!(block, [0.0; 4], "no clip yet");
+
+        *slot.pending.lock().unwrap() = Some(vec![0.1, 0.2, 0.3]);
+        player.fill(&mut block);
+        assert_eq!(block, [0.1, 0.2, 0.3, 0.1]);
+        player.fill(&mut block);
+        assert_eq!(block, [0.2, 0.3, 0.1, 0.2]);
+
+        // The replaced clip comes back to be freed elsewhere, and the next
+        // one waits until it has been.
+        *slot.pending.lock().unwrap() = Some(vec![0.5]);
+        player.fill(&mut block);
+        assert_eq!(block, [0.5; 4]);
+        assert_eq!(*slot.retired.lock().unwrap(), Some(vec![0.1, 0.2, 0.3]));
+        *slot.pending.lock().unwrap() = Some(vec![0.7]);
+        player.fill(&mut block);
+        assert_eq!(block, [0.5; 4], "held until the retired clip is freed");
+        slot.free_retired();
+        player.fill(&mut block);
+        assert_eq!(block, [0.7; 4]);
+    }
+}
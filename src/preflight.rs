@@ -0,0 +1,210 @@
+//! Pre-start checks for low-latency monitoring: OS power settings and
+//! device setups that are known to cause xruns or resampling. Each hint is
+//! one sentence for the PREFLIGHT list; none of them block START.
+
+use std::sync::OnceLock;
+
+use cpal::Device;
+
+use crate::device;
+
+/// Buffers at or below this many frames need the machine awake and fast.
+const SMALL_BUFFER: u32 = 64;
+
+/// Windows' High performance and Ultimate Performance plans. Matched by
+/// GUID, as the names are localized.
+#[cfg(target_os = "windows")]
+const FAST_POWER_PLANS: [&str; 2] = [
+    "8c5e7fda-e8bf-4a96-9a85-a6e23a8c635c",
+    "e9a42b02-d5df-448d-aa00-03f14749eb61",
+];
+
+/// OS settings that matter for small buffers. `None` where a platform
+/// doesn't have the setting or it couldn't be read.
+#[derive(Default)]
+struct OsState {
+    /// Windows power plan name, when it isn't one of `FAST_POWER_PLANS`.
+    slow_power_plan: Option<String>,
+    /// Windows USB selective suspend, on AC power.
+    usb_selective_suspend: Option<bool>,
+    /// Linux USB audio devices the kernel suspends when idle.
+    usb_autosuspend: Vec<String>,
+    /// Linux cpufreq governor of CPU 0.
+    cpu_governor: Option<String>,
+    /// macOS Low Power Mode.
+    low_power_mode: Option<bool>,
+}
+
+/// Read once per run: the commands behind it take a moment on Windows.
+fn os_state() -> &'static OsState {
+    static STATE: OnceLock<OsState> = OnceLock::new();
+    STATE.get_or_init(read_os_state)
+}
+
+#[cfg(target_os = "windows")]
+fn read_os_state() -> OsState {
+    let powercfg = |args: &[&str]| {
+        std::process::Command::new("powercfg")
+            .args(args)
+            .output()
+            .ok()
+            .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+    };
+    // "Power Scheme GUID: 381b4222-...  (Balanced)", in the user's
+    // language apart from the GUID.
+    let slow_power_plan = powercfg(&["/getactivescheme"]).and_then(|out| {
+        let guid = out
+            .split_whitespace()
+            .find(|w| w.len() == 36 && w.chars().all(|c| c == '-' || c.is_ascii_hexdigit()))?
+            .to_lowercase();
+        if FAST_POWER_PLANS.contains(&guid.as_str()) {
+            return None;
+        }
+        let name = match (out.rfind('('), out.rfind(')')) {
+            (Some(open), Some(close)) if open < close => out[open + 1..close].trim().to_string(),
+            _ => guid,
+        };
+        Some(name)
+    });
+    // USB settings / USB selective suspend setting. The index lines are
+    // localized too, but always AC then DC, and nothing else has a "0x".
+    let usb_selective_suspend = powercfg(&[
+        "/query",
+        "SCHEME_CURRENT",
+        "2a737441-1930-4402-8d77-b2bebba308a3",
+        "48e6b7a6-50f5-4782-a5d4-53bb8f07e226",
+    ])
+    .and_then(|out| {
+        let ac = out.lines().find_map(|l| l.rsplit_once("0x"))?.1;
+        u32::from_str_radix(ac.trim(), 16).ok().map(|v| v != 0)
+    });
+    OsState {
+        slow_power_plan,
+        usb_selective_suspend,
+        ..Default::default()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_os_state() -> OsState {
+    use std::path::Path;
+
+    let read = |path: &Path| {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|s| s.trim().to_string())
+    };
+    // Each sound card's `device` is its USB interface when it has one, and
+    // the interface's parent the USB device, whose `power/control` says
+    // whether it may suspend: "auto" lets it, "on" keeps it awake. The
+    // usbcore default delay alone doesn't say; udev rules and TLP set
+    // devices either way.
+    let usb_autosuspend = std::fs::read_dir("/sys/class/sound")
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|card| card.file_name().to_string_lossy().starts_with("card"))
+        .filter_map(|card| {
+            let interface = std::fs::canonicalize(card.path().join("device")).ok()?;
+            let usb = interface.parent()?;
+            if !usb.join("idVendor").exists() || read(&usb.join("power/control"))? != "auto" {
+                return None;
+            }
+            read(&usb.join("product")).or_else(|| read(&card.path().join("id")))
+        })
+        .collect();
+    OsState {
+        usb_autosuspend,
+        cpu_governor: read(Path::new(
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+        )),
+        ..Default::default()
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn read_os_state() -> OsState {
+    let low_power_mode = std::process::Command::new("pmset")
+        .arg("-g")
+        .output()
+        .ok()
+        .and_then(|o| {
+            let out = String::from_utf8_lossy(&o.stdout).into_owned();
+            let line = out
+                .lines()
+                .find(|l| l.trim_start().starts_with("lowpowermode"))?;
+            Some(line.split_whitespace().nth(1)? == "1")
+        });
+    OsState {
+        low_power_mode,
+        ..Default::default()
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+fn read_os_state() -> OsState {
+    OsState::default()
+}
+
+fn os_hints(buffer_size: u32, hints: &mut Vec<String>) {
+    let os = os_state();
+    let small = buffer_size <= SMALL_BUFFER;
+    if let Some(plan) = os.slow_power_plan.as_ref().filter(|_| small) {
+        hints.push(format!(
+            "Windows power plan is {plan}: expect xruns at {buffer_size} frames \
+             (High performance avoids them)"
+        ));
+    }
+    if os.usb_selective_suspend == Some(true) {
+        hints.push(
+            "USB selective suspend is on: a USB interface can drop out or click \
+             when it wakes"
+                .into(),
+        );
+    }
+    for name in &os.usb_autosuspend {
+        hints.push(format!(
+            "USB autosuspend is on for {name}: it can drop out or click when it \
+             wakes (set its power/control to on)"
+        ));
+    }
+    if small && os.cpu_governor.as_deref() == Some("powersave") {
+        hints.push(format!(
+            "CPU governor is powersave: expect xruns at {buffer_size} frames \
+             (try performance or schedutil)"
+        ));
+    }
+    if small && os.low_power_mode == Some(true) {
+        hints.push(format!(
+            "Low Power Mode is on: expect xruns at {buffer_size} frames"
+        ));
+    }
+}
+
+/// Everything worth knowing before starting with this setup.
+pub fn check(
+    input: &Device,
+    output: &Device,
+    output_name: &str,
+    buffer_size: u32,
+    sample_rate: u32,
+) -> Vec<String> {
+    let mut hints = Vec::new();
+    os_hints(buffer_size, &mut hints);
+
+    for (what, rate) in [
+        ("Input", device::input_mixer_rate(input)),
+        ("Output", device::output_mixer_rate(output)),
+    ] {
+        if let Some(rate) = rate.filter(|&r| r != sample_rate) {
+            hints.push(format!(
+                "{what} runs at {rate} Hz in the OS mixer: {sample_rate} Hz means \
+                 resampling or a format switch (set both to the same rate)"
+            ));
+        }
+    }
+    if device::is_bluetooth(output_name) {
+        hints.push("Bluetooth output adds 100+ ms whatever the buffer size".into());
+    }
+    hints
+}
@@ -0,0 +1,268 @@
+//! Presets: a named processing chain sound, tagged with what it's for.
+//!
+//! A handful of factory presets are compiled in; the user's own are kept
+//! in the profile. PRESETS lists both, narrowed by a search and a tag.
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{
+    AGC_MAX_GAIN_DB, AGC_TARGET_DB, COMP_DEFAULTS, LIMITER_CEILING_DB, REVERB_DECAY_S, REVERB_MIX,
+    SATURATION_DRIVE_DB, SATURATION_TONE_DB, SATURATION_TRIM_DB,
+};
+use crate::eq::{EqBand, CHAIN_BANDS};
+
+/// What a preset is meant for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UseCase {
+    Podcast,
+    Gaming,
+    Singing,
+    Asmr,
+}
+
+impl UseCase {
+    pub const ALL: [UseCase; 4] = [
+        UseCase::Podcast,
+        UseCase::Gaming,
+        UseCase::Singing,
+        UseCase::Asmr,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            UseCase::Podcast => "PODCAST",
+            UseCase::Gaming => "GAMING",
+            UseCase::Singing => "SINGING",
+            UseCase::Asmr => "ASMR",
+        }
+    }
+}
+
+/// The cleanup and tone stages a preset sets. The fun stages (voice,
+/// robot, call sim...) and the routing are left as they are.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sound {
+    pub highpass: bool,
+    pub lowpass: bool,
+    pub denoise: bool,
+    pub noise_gate: bool,
+    pub noise_gate_threshold: f32,
+    pub agc: bool,
+    pub agc_target_db: f32,
+    pub agc_max_gain_db: f32,
+    pub comp: bool,
+    /// (threshold dB, ratio, attack ms, release ms, makeup dB)
+    pub comp_settings: (f32, f32, f32, f32, f32),
+    pub saturation: bool,
+    /// (drive, tone, trim) in dB
+    pub saturation_settings: (f32, f32, f32),
+    pub eq_enabled: bool,
+    pub eq_bands: [EqBand; CHAIN_BANDS.len()],
+    pub reverb: bool,
+    /// `ReverbKind` as stored in `AudioParams`.
+    pub reverb_kind: u32,
+    pub reverb_decay_s: f32,
+    pub reverb_mix: f32,
+    pub limiter: bool,
+    pub limiter_ceiling: f32,
+}
+
+/// The GUI's starting values.
+impl Default for Sound {
+    fn default() -> Self {
+        Self {
+            highpass: true,
+            lowpass: true,
+            denoise: false,
+            noise_gate: false,
+            noise_gate_threshold: -36.0,
+            agc: false,
+            agc_target_db: AGC_TARGET_DB,
+            agc_max_gain_db: AGC_MAX_GAIN_DB,
+            comp: false,
+            comp_settings: COMP_DEFAULTS,
+            saturation: false,
+            saturation_settings: (SATURATION_DRIVE_DB, SATURATION_TONE_DB, SATURATION_TRIM_DB),
+            eq_enabled: false,
+            eq_bands: CHAIN_BANDS,
+            reverb: false,
+            reverb_kind: 0,
+            reverb_decay_s: REVERB_DECAY_S,
+            reverb_mix: REVERB_MIX,
+            limiter: true,
+            limiter_ceiling: LIMITER_CEILING_DB,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub tags: Vec<UseCase>,
+    pub sound: Sound,
+}
+
+impl Preset {
+    /// Whether the name holds `query` (ignoring case) and the tags hold
+    /// `tag`. A blank query or no tag matches everything.
+    pub fn matches(&self, query: &str, tag: Option<UseCase>) -> bool {
+        let query = query.trim().to_lowercase();
+        self.name.to_lowercase().contains(&query) && tag.is_none_or(|t| self.tags.contains(&t))
+    }
+}
+
+/// EQ gains in dB for the low shelf, low-mid, presence and high shelf.
+fn eq(gains: [f32; 4]) -> [EqBand; CHAIN_BANDS.len()] {
+    let mut bands = CHAIN_BANDS;
+    for (band, gain_db) in bands.iter_mut().zip(gains) {
+        band.gain_db = gain_db;
+    }
+    bands
+}
+
+/// Built-in presets, always listed ahead of the user's.
+pub fn factory() -> Vec<Preset> {
+    let preset = |name: &str, tags: &[UseCase], sound: Sound| Preset {
+        name: name.into(),
+        tags: tags.to_vec(),
+        sound,
+    };
+    vec![
+        // Even level and a clear, close voice for speech that is listened to at length.
+        preset(
+            "Broadcast voice",
+            &[UseCase::Podcast],
+            Sound {
+                denoise: true,
+                noise_gate: true,
+                noise_gate_threshold: -45.0,
+                agc: true,
+                comp: true,
+                comp_settings: (-20.0, 3.0, 5.0, 120.0, 4.0),
+                eq_enabled: true,
+                eq_bands: eq([2.0, -2.0, 3.0, 1.0]),
+                ..Sound::default()
+            },
+        ),
+        preset(
+            "Interview, two mics",
+            &[UseCase::Podcast],
+            Sound {
+                noise_gate: true,
+                noise_gate_threshold: -40.0,
+                agc: true,
+                comp: true,
+                comp_settings: (-22.0, 2.5, 8.0, 150.0, 4.0),
+                ..Sound::default()
+            },
+        ),
+        // Keyboard and fan gone, squashed hard so callouts cut through game audio.
+        preset(
+            "Team chat",
+            &[UseCase::Gaming],
+            Sound {
+                denoise: true,
+                noise_gate: true,
+                noise_gate_threshold: -34.0,
+                comp: true,
+                comp_settings: (-26.0, 5.0, 2.0, 80.0, 8.0),
+                eq_enabled: true,
+                eq_bands: eq([-3.0, -2.0, 4.0, 0.0]),
+                ..Sound::default()
+            },
+        ),
+        preset(
+            "Streamer",
+            &[UseCase::Gaming, UseCase::Podcast],
+            Sound {
+                denoise: true,
+                noise_gate: true,
+                noise_gate_threshold: -40.0,
+                agc: true,
+                comp: true,
+                saturation: true,
+                saturation_settings: (4.0, 1.0, -1.0),
+                eq_enabled: true,
+                eq_bands: eq([2.0, -1.0, 2.0, 1.0]),
+                ..Sound::default()
+            },
+        ),
+        // No gate or denoise to chop held notes; a plate to sing into.
+        preset(
+            "Vocal booth",
+            &[UseCase::Singing],
+            Sound {
+                comp: true,
+                comp_settings: (-18.0, 2.0, 15.0, 200.0, 3.0),
+                eq_enabled: true,
+                eq_bands: eq([0.0, -1.5, 1.5, 2.0]),
+                reverb: true,
+                reverb_kind: 1,
+                reverb_decay_s: 1.8,
+                reverb_mix: 0.25,
+                ..Sound::default()
+            },
+        ),
+        preset(
+            "Warm karaoke",
+            &[UseCase::Singing],
+            Sound {
+                comp: true,
+                comp_settings: (-20.0, 3.0, 10.0, 180.0, 4.0),
+                saturation: true,
+                reverb: true,
+                reverb_decay_s: 1.2,
+                reverb_mix: 0.3,
+                ..Sound::default()
+            },
+        ),
+        // Whispers and mouth sounds are the point: no filters, gate or
+        // compressor to take them away, only lifted and kept off the ceiling.
+        preset(
+            "Whisper",
+            &[UseCase::Asmr],
+            Sound {
+                highpass: false,
+                lowpass: false,
+                agc: true,
+                agc_target_db: -24.0,
+                agc_max_gain_db: 18.0,
+                eq_enabled: true,
+                eq_bands: eq([1.0, 0.0, 0.0, 3.0]),
+                limiter_ceiling: -3.0,
+                ..Sound::default()
+            },
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn searches_by_name_and_tag() {
+        let presets = factory();
+        let names = |query: &str, tag| -> Vec<&str> {
+            presets
+                .iter()
+                .filter(|p| p.matches(query, tag))
+                .map(|p| p.name.as_str())
+                .collect()
+        };
+        assert_eq!(names("", None).len(), presets.len());
+        assert_eq!(names(" VOICE", None), ["Broadcast voice"]);
+        assert_eq!(names("", Some(UseCase::Asmr)), ["Whisper"]);
+        assert_eq!(names("", Some(UseCase::Gaming)), ["Team chat", "Streamer"]);
+        assert_eq!(names("stream", Some(UseCase::Podcast)), ["Streamer"]);
+        assert!(names("stream", Some(UseCase::Singing)).is_empty());
+        for tag in UseCase::ALL {
+            assert!(
+                !names("", Some(tag)).is_empty(),
+                "no factory preset for {}",
+                tag.label()
+            );
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! `vibetone probe [--json]`: dump every host, device and supported stream
+//! config, for attaching to bug reports and for compatibility tooling.
+
+use anyhow::Result;
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::{DefaultStreamConfigError, Device, SupportedBufferSize};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Report {
+    version: &'static str,
+    os: &'static str,
+    default_host: &'static str,
+    hosts: Vec<HostReport>,
+}
+
+#[derive(Serialize)]
+struct HostReport {
+    name: &'static str,
+    /// Set when the host couldn't be opened or enumerated.
+    error: Option<String>,
+    default_input: Option<String>,
+    default_output: Option<String>,
+    devices: Vec<DeviceReport>,
+}
+
+#[derive(Serialize)]
+struct DeviceReport {
+    name: String,
+    manufacturer: Option<String>,
+    driver: Option<String>,
+    device_type: String,
+    interface: String,
+    direction: String,
+    default_input: Option<ConfigReport>,
+    default_output: Option<ConfigReport>,
+    input_configs: Vec<RangeReport>,
+    output_configs: Vec<RangeReport>,
+    /// Queries that failed for a reason other than "not an input/output".
+    errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ConfigReport {
+    channels: u16,
+    sample_rate: u32,
+    sample_format: String,
+    buffer_size: BufferReport,
+}
+
+#[derive(Serialize)]
+struct RangeReport {
+    channels: u16,
+    min_sample_rate: u32,
+    max_sample_rate: u32,
+    sample_format: String,
+    buffer_size: BufferReport,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum BufferReport {
+    Range { min: u32, max: u32 },
+    Unknown,
+}
+
+impl From<&SupportedBufferSize> for BufferReport {
+    fn from(b: &SupportedBufferSize) -> Self {
+        match *b {
+            SupportedBufferSize::Range { min, max } => BufferReport::Range { min, max },
+            SupportedBufferSize::Unknown => BufferReport::Unknown,
+        }
+    }
+}
+
+fn default_config(
+    config: Result<cpal::SupportedStreamConfig, DefaultStreamConfigError>,
+    what: &str,
+    errors: &mut Vec<String>,
+) -> Option<ConfigReport> {
+    match config {
+        Ok(c) => Some(ConfigReport {
+            channels: c.channels(),
+            sample_rate: c.sample_rate(),
+            sample_format: c.sample_format().to_string(),
+            buffer_size: c.buffer_size().into(),
+        }),
+        Err(DefaultStreamConfigError::StreamTypeNotSupported) => None,
+        Err(e) => {
+            errors.push(format!("{what}: {e}"));
+            None
+        }
+    }
+}
+
+fn config_ranges(
+    ranges: Result<
+        impl Iterator<Item = cpal::SupportedStreamConfigRange>,
+        cpal::SupportedStreamConfigsError,
+    >,
+    what: &str,
+    errors: &mut Vec<String>,
+) -> Vec<RangeReport> {
+    match ranges {
+        Ok(ranges) => ranges
+            .map(|r| RangeReport {
+                channels: r.channels(),
+                min_sample_rate: r.min_sample_rate(),
+                max_sample_rate: r.max_sample_rate(),
+                sample_format: r.sample_format().to_string(),
+                buffer_size: r.buffer_size().into(),
+            })
+            .collect(),
+        Err(e) => {
+            errors.push(format!("{what}: {e}"));
+            Vec::new()
+        }
+    }
+}
+
+fn device_report(dev: &Device) -> DeviceReport {
+    let mut errors = Vec::new();
+    let description = dev.description();
+    if let Err(e) = &description {
+        errors.push(format!("description: {e}"));
+    }
+    let description = description.ok();
+    let text =
+        |f: fn(&cpal::DeviceDescription) -> String| description.as_ref().map(f).unwrap_or_default();
+
+    DeviceReport {
+        name: text(|d| d.name().to_string()),
+        manufacturer: description
+            .as_ref()
+            .and_then(|d| d.manufacturer().map(String::from)),
+        driver: description
+            .as_ref()
+            .and_then(|d| d.driver().map(String::from)),
+        device_type: text(|d| d.device_type().to_string()),
+        interface: text(|d| d.interface_type().to_string()),
+        direction: text(|d| d.direction().to_string()),
+        default_input: default_config(dev.default_input_config(), "default input", &mut errors),
+        default_output: default_config(dev.default_output_config(), "default output", &mut errors),
+        input_configs: config_ranges(dev.supported_input_configs(), "input configs", &mut errors),
+        output_configs: config_ranges(
+            dev.supported_output_configs(),
+            "output configs",
+            &mut errors,
+        ),
+        errors,
+    }
+}
+
+fn host_report(id: cpal::HostId) -> HostReport {
+    let mut report = HostReport {
+        name: id.name(),
+        error: None,
+        default_input: None,
+        default_output: None,
+        devices: Vec::new(),
+    };
+    let host = match cpal::host_from_id(id) {
+        Ok(h) => h,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+    let name = |d: Device| d.description().ok().map(|d| d.name().to_string());
+    report.default_input = host.default_input_device().and_then(name);
+    report.default_output = host.default_output_device().and_then(name);
+    match host.devices() {
+        Ok(devices) => report.devices = devices.map(|d| device_report(&d)).collect(),
+        Err(e) => report.error = Some(e.to_string()),
+    }
+    report
+}
+
+fn print_text(report: &Report) {
+    let config = |c: &Option<ConfigReport>| match c {
+        Some(c) => format!("{}ch {} Hz {}", c.channels, c.sample_rate, c.sample_format),
+        None => "-".into(),
+    };
+    println!("vibetone {} on {}", report.version, report.os);
+    for host in &report.hosts {
+        let default = if host.name == report.default_host {
+            " (default)"
+        } else {
+            ""
+        };
+        println!("\n{}{default}", host.name);
+        if let Some(e) = &host.error {
+            println!("  error: {e}");
+        }
+        for dev in &host.devices {
+            println!(
+                "  {}  [in: {} | out: {}]",
+                dev.name,
+                config(&dev.default_input),
+                config(&dev.default_output)
+            );
+            for e in &dev.errors {
+                println!("    error: {e}");
+            }
+        }
+    }
+}
+
+pub fn run(json: bool) -> Result<()> {
+    let report = Report {
+        version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        default_host: cpal::default_host().id().name(),
+        hosts: cpal::available_hosts()
+            .into_iter()
+            .map(host_report)
+            .collect(),
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_text(&report);
+    }
+    Ok(())
+}
@@ -0,0 +1,280 @@
+//! Room check: a few seconds of raw input graded A–F, with what to do
+//! about it. Three things count against a room: the noise floor, mains
+//! hum, and reverb, timed from a clap if one was heard.
+//!
+//! The floor is a low percentile of short-term levels, so talking during
+//! the check doesn't raise it. Hum is the mains fundamental and its first
+//! overtones against the spectrum either side. Reverb is the time a clap
+//! takes to fall from 5 to 25 dB below its peak, scaled to 60 dB (T20).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::dsp::gain_to_db;
+
+/// How long a check listens.
+pub const LISTEN_MS: u64 = 5000;
+/// Enough capture for `LISTEN_MS` at the highest rate offered.
+const CAPTURE_LEN: usize = 96_000 * LISTEN_MS as usize / 1000;
+/// Short-term level window.
+const WINDOW_MS: f32 = 10.0;
+/// Share of windows quieter than the floor.
+const FLOOR_PERCENTILE: f32 = 0.1;
+/// Hum counts when it is this far above the spectrum beside it...
+const HUM_CONTRAST_DB: f32 = 20.0;
+/// ...and at least this loud.
+const HUM_MIN_DB: f32 = -70.0;
+/// Fundamental and overtones looked at.
+const HUM_HARMONICS: u32 = 3;
+/// A clap stands this far above the floor; less leaves too little decay
+/// to time.
+const CLAP_DB: f32 = 35.0;
+
+/// Shared between the GUI and the input callback.
+pub struct RoomCheck {
+    /// Set while a check listens.
+    listening: AtomicBool,
+    /// Raw mono input heard while listening. The input callback only
+    /// `try_lock`s it and never grows it past its capacity.
+    capture: Mutex<Vec<f32>>,
+}
+
+impl Default for RoomCheck {
+    fn default() -> Self {
+        Self {
+            listening: AtomicBool::new(false),
+            capture: Mutex::new(Vec::with_capacity(CAPTURE_LEN)),
+        }
+    }
+}
+
+impl RoomCheck {
+    pub fn start(&self) {
+        self.capture.lock().unwrap().clear();
+        self.listening.store(true, Ordering::Relaxed);
+    }
+
+    pub fn listening(&self) -> bool {
+        self.listening.load(Ordering::Relaxed)
+    }
+
+    /// Input side: keep `data` (interleaved, `channels` wide) as mono.
+    pub fn capture(&self, data: &[f32], channels: usize) {
+        if !self.listening() {
+            return;
+        }
+        if let Ok(mut capture) = self.capture.try_lock() {
+            let room = capture.capacity() - capture.len();
+            capture.extend(
+                data.chunks_exact(channels)
+                    .take(room)
+                    .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+            );
+        }
+    }
+
+    /// Stop listening and rate what was heard; `None` if nothing was.
+    pub fn finish(&self, sample_rate: u32) -> Option<Report> {
+        self.listening.store(false, Ordering::Relaxed);
+        analyze(&self.capture.lock().unwrap(), sample_rate as f32)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hum {
+    /// 50 or 60.
+    pub mains_hz: u32,
+    /// Level of the fundamental and overtones together, dBFS.
+    pub db: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    /// Background level with nobody talking, dBFS.
+    pub noise_db: f32,
+    pub hum: Option<Hum>,
+    /// Reverb time in seconds, if a clap was heard.
+    pub rt60: Option<f32>,
+}
+
+impl Report {
+    /// A (a quiet, dry room) to F.
+    pub fn grade(&self) -> char {
+        let noise = match self.noise_db {
+            db if db < -70.0 => 0,
+            db if db < -60.0 => 1,
+            db if db < -50.0 => 2,
+            db if db < -40.0 => 3,
+            _ => 4,
+        };
+        let reverb = match self.rt60 {
+            Some(t) if t > 0.8 => 2,
+            Some(t) if t > 0.4 => 1,
+            _ => 0,
+        };
+        let faults = noise + reverb + self.hum.is_some() as usize;
+        ['A', 'B', 'C', 'D', 'F'][faults.min(4)]
+    }
+
+    /// What to do about each fault, worst first.
+    pub fn suggestions(&self) -> Vec<String> {
+        let mut tips = Vec::new();
+        if let Some(hum) = self.hum {
+            tips.push(format!(
+                "strong {} Hz hum detected — enable HUM at {} Hz",
+                hum.mains_hz, hum.mains_hz
+            ));
+        }
+        if self.noise_db >= -50.0 {
+            tips.push(format!(
+                "noisy room ({:.0} dBFS floor) — enable DENOISE, or move away from fans and PCs",
+                self.noise_db
+            ));
+        } else if self.noise_db >= -60.0 {
+            tips.push("some background noise — the GATE will keep it out between words".into());
+        }
+        match self.rt60 {
+            Some(t) if t > 0.4 => tips.push(format!(
+                "echoey room ({t:.1} s reverb) — get closer to the mic, or add soft furnishings"
+            )),
+            Some(_) => {}
+            None => tips.push("clap once during the check to rate the reverb".into()),
+        }
+        tips
+    }
+}
+
+/// Rate `samples`; `None` if there are too few to.
+fn analyze(samples: &[f32], sample_rate: f32) -> Option<Report> {
+    let window = (WINDOW_MS * 0.001 * sample_rate) as usize;
+    if samples.len() < 50 * window {
+        return None;
+    }
+    let levels: Vec<f32> = samples
+        .chunks_exact(window)
+        .map(|w| gain_to_db((w.iter().map(|x| x * x).sum::<f32>() / window as f32).sqrt()))
+        .collect();
+    let mut sorted = levels.clone();
+    sorted.sort_by(f32::total_cmp);
+    let noise_db = sorted[(FLOOR_PERCENTILE * sorted.len() as f32) as usize];
+    Some(Report {
+        noise_db,
+        hum: hum(samples, sample_rate),
+        rt60: clap_decay(&levels, noise_db),
+    })
+}
+
+/// Level of a sine at `freq` in `samples` (Goertzel), dBFS.
+fn tone_db(samples: &[f32], sample_rate: f32, freq: f32) -> f32 {
+    // f64: over seconds of audio an f32 state drifts off the tone.
+    let coeff = 2.0 * (std::f64::consts::TAU * freq as f64 / sample_rate as f64).cos();
+    let (mut s1, mut s2) = (0.0f64, 0.0f64);
+    for &x in samples {
+        let s0 = x as f64 + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+    let power = (s1 * s1 + s2 * s2 - coeff * s1 * s2).max(0.0);
+    // Amplitude 2|X|/N, as RMS.
+    gain_to_db((2.0 * power.sqrt() / samples.len() as f64 / std::f64::consts::SQRT_2) as f32)
+}
+
+/// The louder of 50 and 60 Hz mains hum, if it stands out.
+fn hum(samples: &[f32], sample_rate: f32) -> Option<Hum> {
+    let power = |db: f32| 10f32.powf(db / 10.0);
+    [50u32, 60]
+        .into_iter()
+        .map(|mains| {
+            let (mut hum, mut beside) = (0.0, 0.0);
+            for n in 1..=HUM_HARMONICS {
+                let f = (mains * n) as f32;
+                hum += power(tone_db(samples, sample_rate, f));
+                // Between 50 and 60 Hz and their overtones, clear of both.
+                beside += power(tone_db(samples, sample_rate, f - 3.0))
+                    + power(tone_db(samples, sample_rate, f + 3.0));
+            }
+            let db = 10.0 * hum.max(1e-20).log10();
+            let beside_db = 10.0 * (beside / 2.0).max(1e-20).log10();
+            (
+                Hum {
+                    mains_hz: mains,
+                    db,
+                },
+                db - beside_db,
+            )
+        })
+        .filter(|(hum, contrast)| hum.db > HUM_MIN_DB && *contrast > HUM_CONTRAST_DB)
+        .max_by(|a, b| a.0.db.total_cmp(&b.0.db))
+        .map(|(hum, _)| hum)
+}
+
+/// T20 of the loudest moment in `levels`, if it's a clap: how long it
+/// takes to fall from 5 to 25 dB below its peak, times three.
+fn clap_decay(levels: &[f32], noise_db: f32) -> Option<f32> {
+    let (peak_at, &peak) = levels
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+    if peak - noise_db < CLAP_DB {
+        return None;
+    }
+    let after = &levels[peak_at..];
+    let start = after.iter().position(|&l| l <= peak - 5.0)?;
+    let end = after.iter().position(|&l| l <= peak - 25.0)?;
+    Some(3.0 * (end - start) as f32 * WINDOW_MS * 0.001)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::db_to_gain;
+
+    #[test]
+    fn grades_noise_hum_and_reverb() {
+        let mut rng = 0x853c_49e6_748f_ea9bu64;
+        let mut noise = move || {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            (rng >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        };
+        let sr = 48000.0;
+        let len = 5 * sr as usize;
+        // Uniform noise in ±0.5 has an RMS of 1/√12.
+        let room = |db: f32, noise: &mut dyn FnMut() -> f32| -> Vec<f32> {
+            (0..len)
+                .map(|_| noise() * db_to_gain(db) * 12f32.sqrt())
+                .collect()
+        };
+
+        let quiet = analyze(&room(-75.0, &mut noise), sr).unwrap();
+        assert!((quiet.noise_db + 75.0).abs() < 2.0, "{quiet:?}");
+        assert_eq!((quiet.hum, quiet.rt60, quiet.grade()), (None, None, 'A'));
+
+        // -68 dBFS of fan, 60 Hz hum at -62 dBFS, and a clap in a room
+        // with a 0.6 s reverb (60 dB down in 0.6 s).
+        let mut heard = room(-68.0, &mut noise);
+        for (i, x) in heard.iter_mut().enumerate() {
+            *x += db_to_gain(-62.0)
+                * 2f32.sqrt()
+                * (std::f32::consts::TAU * 60.0 * i as f32 / sr).sin();
+        }
+        let clap_at = 2 * sr as usize;
+        for (i, x) in heard[clap_at..].iter_mut().enumerate() {
+            let t = i as f32 / sr;
+            *x += 0.5 * noise() * db_to_gain(-60.0 * t / 0.6);
+        }
+        let report = analyze(&heard, sr).unwrap();
+        let hum = report.hum.unwrap_or_else(|| panic!("no hum: {report:?}"));
+        assert_eq!(hum.mains_hz, 60);
+        assert!((hum.db + 62.0).abs() < 1.0, "{report:?}");
+        let rt60 = report.rt60.expect("clap");
+        assert!((rt60 - 0.6).abs() < 0.1, "{report:?}");
+        assert_eq!(report.grade(), 'D', "{report:?}");
+        let tips = report.suggestions();
+        assert!(tips[0].starts_with("strong 60 Hz hum"), "{tips:?}");
+        assert!(tips.iter().any(|t| t.starts_with("echoey")), "{tips:?}");
+
+        assert_eq!(analyze(&heard[..1000], sr), None, "too short");
+    }
+}
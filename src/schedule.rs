@@ -0,0 +1,315 @@
+//! Scheduled sessions: start and stop at set local times on chosen
+//! weekdays, for a show that goes out at the same time every week.
+//!
+//! A timer thread follows the wall clock and hands start/stop actions to
+//! the GUI, which owns the engine. Times are local, so they follow
+//! daylight saving: a start that falls in the hour skipped in spring
+//! fires when the clocks jump past it, and one in the hour repeated in
+//! autumn fires only the first time round.
+
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, TimeDelta};
+
+const TICK: Duration = Duration::from_secs(5);
+/// After a longer gap in real time (sleep, the clock being set) events in
+/// it are dropped instead of replayed.
+const CATCH_UP: TimeDelta = TimeDelta::minutes(2);
+
+const DAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Start,
+    Stop,
+}
+
+/// One weekly slot. A stop at or before the start is on the next day.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Slot {
+    /// Bit per weekday the slot starts on, Monday lowest.
+    days: u8,
+    start: NaiveTime,
+    stop: NaiveTime,
+}
+
+/// Weekly slots, e.g. `mon-fri 19:00-21:30; sat,sun 10:00-12:00`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    slots: Vec<Slot>,
+}
+
+impl Schedule {
+    /// `None` when `text` is blank, which means no schedule. Slots are
+    /// separated by `;` or new lines; days are names, ranges (`mon-fri`),
+    /// `daily`, `weekdays` or `weekends`, joined by commas.
+    pub fn parse(text: &str) -> Result<Option<Self>> {
+        let mut slots = Vec::new();
+        for slot in text
+            .split([';', '\n'])
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        {
+            let Some((days, times)) = slot.rsplit_once(char::is_whitespace) else {
+                bail!("{slot:?}: expected days and a time range, e.g. \"mon-fri 19:00-21:30\"");
+            };
+            let Some((start, stop)) = times.split_once('-') else {
+                bail!("{slot:?}: expected a time range like 19:00-21:30");
+            };
+            let time = |t: &str| {
+                NaiveTime::parse_from_str(t, "%H:%M").with_context(|| format!("bad time {t:?}"))
+            };
+            slots.push(Slot {
+                days: parse_days(days.trim())?,
+                start: time(start)?,
+                stop: time(stop)?,
+            });
+        }
+        Ok((!slots.is_empty()).then_some(Self { slots }))
+    }
+
+    /// Every start and stop in `(from, to]`, as local wall-clock times,
+    /// oldest first.
+    fn events(&self, from: NaiveDateTime, to: NaiveDateTime) -> Vec<(NaiveDateTime, Action)> {
+        let mut events = Vec::new();
+        // A stop can belong to the slot that started the day before.
+        let mut day = from.date().pred_opt().unwrap_or(from.date());
+        while day <= to.date() {
+            let weekday = day.weekday().num_days_from_monday();
+            for slot in self.slots.iter().filter(|s| s.days & 1 << weekday != 0) {
+                let start = day.and_time(slot.start);
+                let mut stop = day.and_time(slot.stop);
+                if stop <= start {
+                    stop += TimeDelta::days(1);
+                }
+                for (at, action) in [(start, Action::Start), (stop, Action::Stop)] {
+                    if from < at && at <= to {
+                        events.push((at, action));
+                    }
+                }
+            }
+            let Some(next) = day.succ_opt() else {
+                break;
+            };
+            day = next;
+        }
+        events.sort_by_key(|&(at, _)| at);
+        events
+    }
+}
+
+fn parse_days(text: &str) -> Result<u8> {
+    let day = |name: &str| {
+        let name = name.to_ascii_lowercase();
+        DAY_NAMES
+            .iter()
+            .position(|d| name.starts_with(d))
+            .with_context(|| format!("unknown day {name:?}"))
+    };
+    let mut days = 0u8;
+    for part in text.split(',').map(str::trim) {
+        days |= match part.to_ascii_lowercase().as_str() {
+            "daily" => 0b111_1111,
+            "weekdays" => 0b001_1111,
+            "weekends" => 0b110_0000,
+            _ => match part.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (day(first)?, day(last)?);
+                    // mon-fri, or round the weekend like fri-mon.
+                    (0..7)
+                        .map(|i| (first + i) % 7)
+                        .take((last + 7 - first) % 7 + 1)
+                        .fold(0, |days, d| days | 1 << d)
+                }
+                None => 1 << day(part)?,
+            },
+        };
+    }
+    Ok(days)
+}
+
+/// Local wall-clock events between two readings of the clock, each fired
+/// once even if the clock goes back over it.
+struct Clock {
+    last: NaiveDateTime,
+    fired: Vec<NaiveDateTime>,
+}
+
+impl Clock {
+    fn new(now: NaiveDateTime) -> Self {
+        Self {
+            last: now,
+            fired: Vec::new(),
+        }
+    }
+
+    /// What's due since the last reading, `elapsed` ago in real time
+    /// (which a daylight saving change doesn't move). Only the newest
+    /// action counts: a start and stop that both passed leave it stopped.
+    fn advance(
+        &mut self,
+        schedule: &Schedule,
+        now: NaiveDateTime,
+        elapsed: TimeDelta,
+    ) -> Option<Action> {
+        let from = self.last;
+        self.last = now;
+        if now <= from || elapsed > CATCH_UP {
+            return None;
+        }
+        let mut due = None;
+        for (at, action) in schedule.events(from, now) {
+            if !self.fired.contains(&at) {
+                self.fired.push(at);
+                due = Some(action);
+            }
+        }
+        // The repeated autumn hour is the longest the clock goes back.
+        self.fired.retain(|&at| at > now - TimeDelta::days(1));
+        due
+    }
+}
+
+/// GUI side of the timer thread, which stops once this is dropped.
+pub struct Scheduler {
+    schedule: Schedule,
+    pub actions: Receiver<Action>,
+    _alive: Arc<()>,
+}
+
+impl Scheduler {
+    pub fn spawn(schedule: Schedule) -> Self {
+        let (tx, actions) = mpsc::channel();
+        let alive = Arc::new(());
+        let (worker, weak) = (schedule.clone(), Arc::downgrade(&alive));
+        std::thread::spawn(move || run(worker, weak, tx));
+        Self {
+            schedule,
+            actions,
+            _alive: alive,
+        }
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+}
+
+fn run(schedule: Schedule, alive: Weak<()>, actions: mpsc::Sender<Action>) {
+    let mut last = Local::now();
+    let mut clock = Clock::new(last.naive_local());
+    loop {
+        std::thread::sleep(TICK);
+        if alive.strong_count() == 0 {
+            return;
+        }
+        let now = Local::now();
+        let elapsed = now - last;
+        last = now;
+        if let Some(action) = clock.advance(&schedule, now.naive_local(), elapsed) {
+            if actions.send(action).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, time: &str) -> NaiveDateTime {
+        // March 2026: the 2nd is a Monday, clocks go forward on the 29th.
+        NaiveDate::from_ymd_opt(2026, 3, day)
+            .unwrap()
+            .and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+    }
+
+    #[test]
+    fn parses_days_and_times() {
+        let schedule =
+            Schedule::parse("mon-fri 19:00-21:30; Sat,sun 23:00-01:00\n fri-mon 08:00-09:00")
+                .unwrap()
+                .unwrap();
+        let days: Vec<u8> = schedule.slots.iter().map(|s| s.days).collect();
+        assert_eq!(days, [0b001_1111, 0b110_0000, 0b111_0001]);
+        assert_eq!(Schedule::parse(" ; ").unwrap(), None);
+        assert!(Schedule::parse("mon 19:00").is_err());
+        assert!(Schedule::parse("someday 19:00-20:00").is_err());
+        assert!(Schedule::parse("mon 25:00-26:00").is_err());
+    }
+
+    #[test]
+    fn fires_each_event_once_across_clock_changes() {
+        let schedule = Schedule::parse("weekdays 19:00-21:30; sun 23:30-00:30; sun 02:30-02:45")
+            .unwrap()
+            .unwrap();
+        let tick = TimeDelta::seconds(5);
+        let mut clock = Clock::new(at(2, "18:59"));
+        assert_eq!(
+            clock.advance(&schedule, at(2, "19:00"), tick),
+            Some(Action::Start)
+        );
+        assert_eq!(clock.advance(&schedule, at(2, "19:01"), tick), None);
+        assert_eq!(
+            clock.advance(&schedule, at(2, "21:31"), tick),
+            Some(Action::Stop)
+        );
+        // Saturday: nothing scheduled.
+        let mut clock = Clock::new(at(7, "18:59"));
+        assert_eq!(clock.advance(&schedule, at(7, "19:01"), tick), None);
+
+        // Past midnight, the stop belongs to Sunday's slot.
+        let mut clock = Clock::new(at(8, "23:29"));
+        assert_eq!(
+            clock.advance(&schedule, at(8, "23:30"), tick),
+            Some(Action::Start)
+        );
+        assert_eq!(
+            clock.advance(&schedule, at(9, "00:30"), tick),
+            Some(Action::Stop)
+        );
+        // And Monday's own 19:00 is still there.
+        assert_eq!(
+            clock.advance(&schedule, at(9, "19:00"), tick),
+            Some(Action::Start)
+        );
+
+        // Spring forward: 01:59 → 03:00 in one tick skips 02:30-02:45.
+        // Both pass at once, which leaves it stopped.
+        let mut clock = Clock::new(at(29, "01:59"));
+        assert_eq!(
+            clock.advance(&schedule, at(29, "03:00"), tick),
+            Some(Action::Stop)
+        );
+
+        // Fall back: 02:59 → 02:00, then through 02:30-02:45 a second time.
+        let mut clock = Clock::new(at(29, "02:29"));
+        assert_eq!(
+            clock.advance(&schedule, at(29, "02:31"), tick),
+            Some(Action::Start)
+        );
+        assert_eq!(
+            clock.advance(&schedule, at(29, "02:59"), tick),
+            Some(Action::Stop)
+        );
+        assert_eq!(clock.advance(&schedule, at(29, "02:00"), tick), None);
+        assert_eq!(clock.advance(&schedule, at(29, "02:31"), tick), None);
+        assert_eq!(clock.advance(&schedule, at(29, "02:46"), tick), None);
+
+        // Woken from sleep long after a start: not replayed.
+        let mut clock = Clock::new(at(2, "12:00"));
+        assert_eq!(
+            clock.advance(&schedule, at(2, "20:00"), TimeDelta::hours(8)),
+            None
+        );
+        assert_eq!(
+            clock.advance(&schedule, at(2, "21:30"), tick),
+            Some(Action::Stop)
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! Experimental scripted DSP stage (cargo feature `scripting`).
+//!
+//! A rhai file defines `fn process(x)`, called once per sample with the
+//! current sample and returning the new one. `this` is a map that survives
+//! between calls (for filter state etc.) and starts out holding
+//! `sample_rate`. The file is watched and hot-reloaded; compile or runtime
+//! errors bypass the stage until the file is fixed.
+//!
+//! ```text
+//! fn process(x) {
+//!     if !("lp" in this) { this.lp = 0.0; }
+//!     this.lp += 0.05 * (x - this.lp);
+//!     this.lp
+//! }
+//! ```
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime};
+
+use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST, FLOAT};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Shared between the GUI, the file watcher and the audio thread.
+#[derive(Default)]
+pub struct ScriptSlot {
+    /// File to watch. Set by the GUI, polled by the watcher.
+    pub path: Mutex<Option<PathBuf>>,
+    /// Last compile or runtime error, shown in the GUI.
+    pub error: Mutex<Option<String>>,
+    /// Freshly compiled program waiting for the audio thread.
+    pending: Mutex<Option<AST>>,
+}
+
+/// Poll the slot's file for changes and compile it off the audio thread.
+/// Exits once the engine (and with it the slot) is dropped.
+pub fn spawn_watcher(slot: Weak<ScriptSlot>) {
+    std::thread::spawn(move || {
+        let engine = Engine::new();
+        let mut loaded: Option<(PathBuf, SystemTime)> = None;
+
+        while let Some(slot) = slot.upgrade() {
+            let path = slot.path.lock().unwrap().clone();
+            if let Some(path) = path {
+                match std::fs::metadata(&path).and_then(|m| m.modified()) {
+                    Ok(modified) => {
+                        let stamp = Some((path.clone(), modified));
+                        if stamp != loaded {
+                            loaded = stamp;
+                            let compiled = std::fs::read_to_string(&path)
+                                .map_err(|e| e.to_string())
+                                .and_then(|src| engine.compile(src).map_err(|e| e.to_string()));
+                            match compiled {
+                                Ok(ast) => {
+                                    *slot.pending.lock().unwrap() = Some(ast);
+                                    *slot.error.lock().unwrap() = None;
+                                }
+                                Err(e) => *slot.error.lock().unwrap() = Some(e),
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        loaded = None;
+                        *slot.error.lock().unwrap() = Some(format!("{}: {e}", path.display()));
+                    }
+                }
+            }
+            drop(slot);
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+/// Audio-thread side: runs the latest program picked up from the slot.
+pub struct ScriptStage {
+    slot: Arc<ScriptSlot>,
+    engine: Engine,
+    scope: Scope<'static>,
+    ast: Option<AST>,
+    state: Dynamic,
+    sample_rate: f32,
+}
+
+impl ScriptStage {
+    pub fn new(slot: Arc<ScriptSlot>, sample_rate: f32) -> Self {
+        Self {
+            slot,
+            engine: Engine::new(),
+            scope: Scope::new(),
+            ast: None,
+            state: Dynamic::UNIT,
+            sample_rate,
+        }
+    }
+
+    fn fresh_state(&self) -> Dynamic {
+        let mut map = Map::new();
+        map.insert("sample_rate".into(), (self.sample_rate as FLOAT).into());
+        map.into()
+    }
+
+    pub fn process(&mut self, block: &mut [f32]) {
+        // Pick up a reloaded program without ever blocking the audio thread.
+        if let Ok(mut pending) = self.slot.pending.try_lock() {
+            if let Some(ast) = pending.take() {
+                self.ast = Some(ast);
+                self.state = self.fresh_state();
+            }
+        }
+
+        let Some(ast) = &self.ast else {
+            return;
+        };
+        let mut failure = None;
+        for s in block.iter_mut() {
+            let options = CallFnOptions::new()
+                .eval_ast(false)
+                .bind_this_ptr(&mut self.state);
+            let result = self
+                .engine
+                .call_fn_with_options::<Dynamic>(
+                    options,
+                    &mut self.scope,
+                    ast,
+                    "process",
+                    (*s as FLOAT,),
+                )
+                .map_err(|e| e.to_string())
+                .and_then(|y| {
+                    y.as_float()
+                        .or_else(|_| y.as_int().map(|i| i as FLOAT))
+                        .map_err(|t| format!("process() returned {t}, expected a number"))
+                });
+            match result {
+                Ok(y) => *s = y as f32,
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+
+        if let Some(e) = failure {
+            // Bypass until the watcher delivers a fixed program.
+            self.ast = None;
+            if let Ok(mut error) = self.slot.error.try_lock() {
+                *error = Some(e);
+            }
+        }
+    }
+}
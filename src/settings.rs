@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::device;
+use crate::eq::EqProfile;
+use crate::onair;
+use crate::preset::Preset;
+use crate::whatsnew;
+
+const PROFILES_KEY: &str = "profiles";
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// GUI scales offered, as zoom factors.
+pub const UI_SCALES: [f32; 6] = [0.75, 1.0, 1.25, 1.5, 1.75, 2.0];
+
+/// User preferences for one profile, persisted between launches through
+/// eframe's storage.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Case-insensitive name fragments for devices that are never picked by default.
+    pub device_blocklist: Vec<String>,
+    /// Device names last started with; missing devices fall back to the default pick.
+    pub input_device: Option<String>,
+    pub output_device: Option<String>,
+    pub aux_output_device: Option<String>,
+    /// Output OUT moves to if it fails mid-session.
+    pub backup_output_device: Option<String>,
+    pub sidechain_device: Option<String>,
+    /// Correction EQ per output device name.
+    pub output_eq: BTreeMap<String, EqProfile>,
+    /// Alignment delay per output device name, ms.
+    pub output_delay_ms: BTreeMap<String, f32>,
+    /// Audio file looped by SRC FILE.
+    pub source_file: String,
+    /// GUI zoom on top of the OS scaling, `UI_SCALES` range.
+    pub ui_scale: f32,
+    /// MQTT broker for the on-air status, `[user[:password]@]host[:port]`;
+    /// empty turns it off.
+    pub on_air_broker: String,
+    pub on_air_topic: String,
+    /// Weekly start/stop times, `schedule::Schedule` syntax; empty turns
+    /// it off.
+    pub schedule: String,
+    /// The mute button on a USB headset toggles STANDBY.
+    pub headset_button: bool,
+    /// `egui::Key` name of the gate's external trigger, e.g. a footswitch
+    /// that types a key.
+    pub gate_trigger_key: String,
+    /// User presets, listed after the factory ones.
+    pub presets: Vec<Preset>,
+    /// rhai file for the experimental scripted stage.
+    #[cfg(feature = "scripting")]
+    pub script_path: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            device_blocklist: device::DEFAULT_BLOCKLIST
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            input_device: None,
+            output_device: None,
+            aux_output_device: None,
+            backup_output_device: None,
+            sidechain_device: None,
+            output_eq: BTreeMap::new(),
+            output_delay_ms: BTreeMap::new(),
+            source_file: String::new(),
+            ui_scale: 1.0,
+            on_air_broker: String::new(),
+            on_air_topic: onair::DEFAULT_TOPIC.into(),
+            schedule: String::new(),
+            headset_button: false,
+            gate_trigger_key: "F13".into(),
+            presets: Vec::new(),
+            #[cfg(feature = "scripting")]
+            script_path: String::new(),
+        }
+    }
+}
+
+/// Named `Settings` sets, so a shared machine can keep one per person or
+/// use. Picked with `--profile <name>` or the switcher in the GUI.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profiles {
+    active: String,
+    profiles: BTreeMap<String, Settings>,
+    /// Newest `whatsnew` announcement shown, for every profile at once.
+    whats_new_seen: u32,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self::with_default(Settings::default())
+    }
+}
+
+impl Profiles {
+    fn with_default(settings: Settings) -> Self {
+        Self {
+            active: DEFAULT_PROFILE.into(),
+            profiles: BTreeMap::from([(DEFAULT_PROFILE.into(), settings)]),
+            whats_new_seen: 0,
+        }
+    }
+
+    /// Load the saved profiles and activate `requested`, creating it if new.
+    pub fn load(storage: Option<&dyn eframe::Storage>, requested: Option<&str>) -> Self {
+        let mut profiles = storage
+            .and_then(|s| eframe::get_value(s, PROFILES_KEY))
+            .unwrap_or_else(|| {
+                // Settings saved before profiles existed become the default profile.
+                let legacy = storage.and_then(|s| eframe::get_value(s, eframe::APP_KEY));
+                // Nothing saved at all: a fresh install, with nothing new to it.
+                let fresh = legacy.is_none();
+                let mut profiles = Self::with_default(legacy.unwrap_or_default());
+                if fresh {
+                    profiles.whats_new_seen = whatsnew::latest();
+                }
+                profiles
+            });
+        if let Some(name) = requested {
+            profiles.active = name.into();
+        }
+        profiles.switch(&profiles.active.clone());
+        profiles
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, PROFILES_KEY, self);
+    }
+
+    pub fn whats_new_seen(&self) -> u32 {
+        self.whats_new_seen
+    }
+
+    pub fn set_whats_new_seen(&mut self, version: u32) {
+        self.whats_new_seen = version;
+    }
+
+    pub fn active(&self) -> &str {
+        &self.active
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    pub fn current(&self) -> &Settings {
+        &self.profiles[&self.active]
+    }
+
+    pub fn current_mut(&mut self) -> &mut Settings {
+        self.profiles
+            .get_mut(&self.active)
+            .expect("active profile exists")
+    }
+
+    /// Make `name` active, starting it from defaults if it doesn't exist.
+    pub fn switch(&mut self, name: &str) {
+        self.profiles.entry(name.into()).or_default();
+        self.active = name.into();
+    }
+
+    /// Copy the current profile under a new name and switch to it.
+    pub fn duplicate(&mut self, name: &str) {
+        let copy = self.current().clone();
+        self.profiles.entry(name.into()).or_insert(copy);
+        self.active = name.into();
+    }
+
+    /// Delete the current profile and fall back to the first remaining one.
+    /// The last profile can't be removed.
+    pub fn remove_current(&mut self) {
+        if self.profiles.len() > 1 {
+            self.profiles.remove(&self.active);
+            self.active = self.profiles.keys().next().cloned().expect("non-empty");
+        }
+    }
+}
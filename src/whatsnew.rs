@@ -0,0 +1,97 @@
+//! What's new: the changelog, compiled in, shown once after an update.
+//!
+//! Each update worth pointing out adds an `Announcement` with the next
+//! version number. The profiles remember the newest one shown; anything
+//! newer is shown at the next launch. Items can name a `Feature` that
+//! the panel offers to switch on right there.
+
+/// A setting an announcement item can switch on from the panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    MidSide,
+    LoopGuard,
+    HeadsetButton,
+}
+
+pub struct Item {
+    pub text: &'static str,
+    pub feature: Option<Feature>,
+}
+
+pub struct Announcement {
+    /// Counts up from 1, one per announcement.
+    pub version: u32,
+    pub title: &'static str,
+    pub items: &'static [Item],
+}
+
+const fn item(text: &'static str) -> Item {
+    Item {
+        text,
+        feature: None,
+    }
+}
+
+const fn toggle(text: &'static str, feature: Feature) -> Item {
+    Item {
+        text,
+        feature: Some(feature),
+    }
+}
+
+/// Oldest first.
+pub const CHANGELOG: &[Announcement] = &[
+    Announcement {
+        version: 1,
+        title: "Stereo, presets and a room check",
+        items: &[
+            toggle("STEREO keeps left and right apart; M/S runs the chain on the mid only", Feature::MidSide),
+            item("PRESETS: tagged factory sounds for podcasts, gaming, singing and ASMR, and your own"),
+            item("ROOM CHECK grades the room's noise, hum and reverb A–F, with what to do about them"),
+            item("Outputs that can't run at the mic's rate are resampled, and kept in step with it"),
+        ],
+    },
+    Announcement {
+        version: 2,
+        title: "Staying up during a live show",
+        items: &[
+            item("BACKUP output: if OUT fails mid-session, monitoring moves to it with a notice"),
+            item("ALIGN delays an output so speakers and headphones heard together line up"),
+            item("Volume, dry/wet and thresholds glide to a new setting instead of stepping"),
+            item("DIAGNOSTICS counts dropouts and lists stream errors, clipping and gate changes"),
+            toggle("LOOP GUARD stops at START if the output loops back into the input", Feature::LoopGuard),
+            toggle("A USB headset's mute button can toggle STANDBY", Feature::HeadsetButton),
+        ],
+    },
+];
+
+/// The newest announcement's version.
+pub fn latest() -> u32 {
+    CHANGELOG.last().map_or(0, |a| a.version)
+}
+
+/// Announcements newer than `seen`, newest first.
+pub fn unseen(seen: u32) -> Vec<&'static Announcement> {
+    CHANGELOG
+        .iter()
+        .rev()
+        .take_while(|a| a.version > seen)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_count_up_and_only_newer_are_unseen() {
+        for (n, announcement) in CHANGELOG.iter().enumerate() {
+            assert_eq!(announcement.version, n as u32 + 1, "{}", announcement.title);
+            assert!(!announcement.items.is_empty(), "{}", announcement.title);
+        }
+        let versions = |seen| unseen(seen).iter().map(|a| a.version).collect::<Vec<_>>();
+        assert_eq!(versions(0), (1..=latest()).rev().collect::<Vec<_>>());
+        assert_eq!(versions(latest() - 1), [latest()]);
+        assert!(versions(latest()).is_empty());
+    }
+}